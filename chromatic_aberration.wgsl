@@ -0,0 +1,28 @@
+// Chromatic aberration: the red and blue channels are read further out and further in than
+// the green one, by an amount growing with the distance from the screen center, like the
+// color fringes of a cheap lens.
+
+struct ChromaticAberrationUniform {
+    // Texture coordinate offset at the middle of the edges
+    strength: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+};
+
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+@group(0) @binding(2)
+var<uniform> aberration: ChromaticAberrationUniform;
+
+@fragment
+fn fs_chromatic_aberration(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    // Twice the offset from the center, so that strength applies as is at the edges
+    let offset = (in.uv - 0.5) * 2.0 * aberration.strength;
+    let green = textureSample(t_input, s_input, in.uv);
+    let red = textureSample(t_input, s_input, in.uv + offset).r;
+    let blue = textureSample(t_input, s_input, in.uv - offset).b;
+    return vec4<f32>(red, green.g, blue, green.a);
+}
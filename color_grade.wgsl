@@ -0,0 +1,32 @@
+// Color grading: the tone mapped color looks itself up in a 3D LUT. LUTs are authored on
+// display encoded colors, so the lookup happens in sRGB and the result is decoded back.
+
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var t_lut: texture_3d<f32>;
+@group(0) @binding(2)
+var s_lut: sampler;
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c <= vec3<f32>(0.04045));
+}
+
+@fragment
+fn fs_color_grade(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(t_input, vec2<i32>(in.clip_position.xy), 0);
+    let encoded = linear_to_srgb(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)));
+    // 0 and 1 land on the centers of the first and last cells
+    let size = f32(textureDimensions(t_lut).x);
+    let coords = encoded * ((size - 1.0) / size) + 0.5 / size;
+    let graded = textureSample(t_lut, s_lut, coords).rgb;
+    return vec4<f32>(srgb_to_linear(graded), color.a);
+}
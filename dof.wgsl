@@ -0,0 +1,98 @@
+// Depth of field: a thin lens circle of confusion per pixel, a separable Gaussian blur
+// weighted by it, then the blurred image blended over the sharp one where out of focus.
+
+// Height of a 35 mm film frame, the focal length is relative to it
+const SENSOR_HEIGHT: f32 = 0.024;
+// Taps on each side of the center, spread over the circle of confusion
+const BLUR_TAPS: i32 = 8;
+
+struct DofUniform {
+    // Clip space back to view space, for the depth of each pixel
+    inverse_projection: mat4x4<f32>,
+    // World units
+    focal_distance: f32,
+    // Meters
+    focal_length: f32,
+    // f-number
+    aperture: f32,
+    // Circle of confusion radius in pixels stored as 1.0
+    max_radius: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> dof: DofUniform;
+// Depth for the CoC pass, the color being blurred for the other ones. Bound as an
+// unfilterable float texture: loading from `texture_depth_2d` is not portable to GL
+@group(0) @binding(1)
+var t_input: texture_2d<f32>;
+// Circle of confusion: radius / max_radius, 1 when in front of the focal plane
+@group(0) @binding(2)
+var t_coc: texture_2d<f32>;
+
+fn load_clamped(t: texture_2d<f32>, pixel: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t));
+    return textureLoad(t, clamp(pixel, vec2<i32>(0), size - 1), 0);
+}
+
+@fragment
+fn fs_coc(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_input));
+    let d = textureLoad(t_input, vec2<i32>(in.uv * size), 0).r;
+    let depth = view_depth(d, dof.inverse_projection);
+
+    // Thin lens: diameter on the sensor of the image of a point at `depth`
+    let f = dof.focal_length;
+    let s = max(dof.focal_distance, f * 1.01);
+    let diameter = f * f / (dof.aperture * (s - f)) * abs(depth - s) / depth;
+    let radius = 0.5 * diameter / SENSOR_HEIGHT * size.y;
+
+    let near = select(0.0, 1.0, depth < s);
+    return vec4<f32>(clamp(radius / dof.max_radius, 0.0, 1.0), near, 0.0, 1.0);
+}
+
+// Gaussian over the center's circle of confusion. Taps behind the focal plane also count by
+// their own CoC, so that sharp surfaces do not bleed into the blurred ones around them.
+// Taps in front always count fully: a blurred foreground spreads over its surroundings.
+fn blur(pixel: vec2<i32>, direction: vec2<i32>) -> vec4<f32> {
+    let radius = load_clamped(t_coc, pixel).r * dof.max_radius;
+    let center = load_clamped(t_input, pixel);
+    if (radius < 0.5) {
+        return center;
+    }
+
+    let sigma = radius * 0.5;
+    var sum = center.rgb;
+    var total = 1.0;
+    for (var i = 1; i <= BLUR_TAPS; i++) {
+        let offset = f32(i) / f32(BLUR_TAPS) * radius;
+        let gaussian = exp(-0.5 * offset * offset / (sigma * sigma));
+        for (var side = -1; side <= 1; side += 2) {
+            let tap = pixel + direction * i32(round(offset)) * side;
+            let tap_coc = load_clamped(t_coc, tap);
+            let far_weight = clamp(tap_coc.r * dof.max_radius / radius, 0.0, 1.0);
+            let weight = gaussian * select(far_weight, 1.0, tap_coc.g > 0.5);
+            sum += load_clamped(t_input, tap).rgb * weight;
+            total += weight;
+        }
+    }
+    return vec4<f32>(sum / total, center.a);
+}
+
+@fragment
+fn fs_blur_horizontal(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return blur(vec2<i32>(in.clip_position.xy), vec2<i32>(1, 0));
+}
+
+@fragment
+fn fs_blur_vertical(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return blur(vec2<i32>(in.clip_position.xy), vec2<i32>(0, 1));
+}
+
+// Alpha blended over the sharp image, opaque once the blur is a few pixels wide
+@fragment
+fn fs_composite(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let radius = load_clamped(t_coc, pixel).r * dof.max_radius;
+    let blurred = load_clamped(t_input, pixel);
+    return vec4<f32>(blurred.rgb, smoothstep(0.5, 2.0, radius));
+}
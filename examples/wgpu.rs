@@ -1,4 +1,4 @@
-use orengine::State;
+use orengine::{LogLevel, State, engine_log};
 use winit::{event::*, event_loop::EventLoop, window::WindowBuilder};
 
 fn main() {
@@ -11,7 +11,7 @@ fn main() {
             .expect("Failed to create window"),
     );
 
-    let mut state = match pollster::block_on(State::new(window.clone(), "drone_costum.obj")) {
+    let mut state = match pollster::block_on(State::new(window.clone(), "drone_costum.obj", 4)) {
         Ok(s) => s,
         Err(e) => {
             eprintln!("Failed to create Orengine state: {}", e);
@@ -25,7 +25,7 @@ fn main() {
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == state.window.id() => match event {
+            } if window_id == window.id() => match event {
                 WindowEvent::CloseRequested => target.exit(),
                 WindowEvent::Resized(physical_size) => state.resize(*physical_size),
                 WindowEvent::RedrawRequested => {
@@ -38,10 +38,10 @@ fn main() {
                         Err(orengine::error::OrengineError::SurfaceError(_)) => {
                             state.resize(state.size)
                         }
-                        Err(e) => eprintln!("{:?}", e),
+                        Err(e) => engine_log!(state, LogLevel::Error, "{}", e),
                     }
                 }
-                ref event if state.input(event) => {}
+                event if state.input(event) => {}
                 _ => {}
             },
             Event::DeviceEvent {
@@ -50,7 +50,7 @@ fn main() {
             } => {
                 state.handle_mouse_motion(delta);
             }
-            Event::AboutToWait => state.window.request_redraw(),
+            Event::AboutToWait => window.request_redraw(),
             _ => {}
         })
         .unwrap();
@@ -0,0 +1,84 @@
+// Automatic exposure, measured on the GPU: a histogram of the log luminance of the rendered
+// image, then a reduction to its average, eased towards over time like an eye adapting.
+
+const BIN_COUNT: u32 = 256u;
+
+struct ExposureUniform {
+    min_log_luminance: f32,
+    // max - min, in log2 units
+    log_luminance_range: f32,
+    // Weight of this frame's average in the adapted luminance, 1 - exp(-dt / lag)
+    adaptation: f32,
+    pixel_count: f32,
+    // Middle grey the average luminance is mapped to
+    key: f32,
+    // Not a vec3, which would be 16-byte aligned
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+};
+
+@group(0) @binding(0)
+var t_color: texture_2d<f32>;
+@group(0) @binding(1)
+var<storage, read_write> histogram: array<atomic<u32>, BIN_COUNT>;
+@group(0) @binding(2)
+var<storage, read_write> adapted_luminance: f32;
+@group(0) @binding(3)
+var<uniform> exposure: ExposureUniform;
+
+var<workgroup> local_bins: array<atomic<u32>, BIN_COUNT>;
+var<workgroup> weighted_bins: array<f32, BIN_COUNT>;
+
+fn luminance_bin(color: vec3<f32>) -> u32 {
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    // Bin 0 holds the (nearly) black pixels, left out of the average
+    if luminance < 0.0001 {
+        return 0u;
+    }
+    let t = (log2(luminance) - exposure.min_log_luminance) / exposure.log_luminance_range;
+    return u32(clamp(t, 0.0, 1.0) * 254.0 + 1.0);
+}
+
+// One 16x16 tile per workgroup, counted in shared memory first to limit global atomics
+@compute @workgroup_size(16, 16)
+fn cs_histogram(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    atomicStore(&local_bins[index], 0u);
+    workgroupBarrier();
+
+    let size = textureDimensions(t_color);
+    if id.x < size.x && id.y < size.y {
+        let color = textureLoad(t_color, vec2<i32>(id.xy), 0).rgb;
+        atomicAdd(&local_bins[luminance_bin(color)], 1u);
+    }
+    workgroupBarrier();
+
+    atomicAdd(&histogram[index], atomicLoad(&local_bins[index]));
+}
+
+// Single workgroup, one thread per bin. Also clears the histogram for the next frame.
+@compute @workgroup_size(256)
+fn cs_average(@builtin(local_invocation_index) index: u32) {
+    let count = atomicExchange(&histogram[index], 0u);
+    weighted_bins[index] = f32(count) * f32(index);
+    workgroupBarrier();
+
+    for (var stride = BIN_COUNT / 2u; stride > 0u; stride >>= 1u) {
+        if index < stride {
+            weighted_bins[index] += weighted_bins[index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if index == 0u {
+        // This thread read bin 0: `count` is the number of black pixels
+        let lit_pixels = max(exposure.pixel_count - f32(count), 1.0);
+        let mean_bin = max(weighted_bins[0] / lit_pixels, 1.0);
+        let log_average = (mean_bin - 1.0) / 254.0 * exposure.log_luminance_range
+            + exposure.min_log_luminance;
+        adapted_luminance = mix(adapted_luminance, exp2(log_average), exposure.adaptation);
+    }
+}
@@ -0,0 +1,26 @@
+// Shared vertex stage of every post-process pass: a single triangle covering the screen.
+// Prepended to the post-process shaders by `post_process::create_fullscreen_shader`.
+
+struct FullscreenOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // (0, 0) top-left, (1, 1) bottom-right, like texture coordinates
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> FullscreenOutput {
+    // Vertices at uv (0, 0), (2, 0), (0, 2): the visible part is exactly [0, 1]²
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    var out: FullscreenOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Distance from the camera plane of a depth buffer value (wgpu clip space, z in [0, 1]).
+// Going back through the projection works for perspective and orthographic depth alike.
+fn view_depth(d: f32, inverse_projection: mat4x4<f32>) -> f32 {
+    let view = inverse_projection * vec4<f32>(0.0, 0.0, d, 1.0);
+    return -view.z / view.w;
+}
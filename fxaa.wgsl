@@ -0,0 +1,65 @@
+// FXAA: blurs along the edges found from the local luma contrast, in a single pass
+
+const FXAA_SPAN_MAX: f32 = 8.0;
+const FXAA_REDUCE_MUL: f32 = 0.125;
+const FXAA_REDUCE_MIN: f32 = 0.0078125;
+// Contrast below which a pixel is left untouched
+const FXAA_EDGE_THRESHOLD: f32 = 0.125;
+const FXAA_EDGE_THRESHOLD_MIN: f32 = 0.0312;
+
+@group(0) @binding(0)
+var t_color: texture_2d<f32>;
+@group(0) @binding(1)
+var s_color: sampler;
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn sample_color(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(t_color, s_color, uv, 0.0).rgb;
+}
+
+@fragment
+fn fs_fxaa(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_color));
+
+    let rgb_m = sample_color(in.uv);
+    let luma_m = luma(rgb_m);
+    let luma_nw = luma(sample_color(in.uv + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample_color(in.uv + vec2<f32>(1.0, -1.0) * texel));
+    let luma_sw = luma(sample_color(in.uv + vec2<f32>(-1.0, 1.0) * texel));
+    let luma_se = luma(sample_color(in.uv + vec2<f32>(1.0, 1.0) * texel));
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+    if luma_max - luma_min < max(FXAA_EDGE_THRESHOLD_MIN, luma_max * FXAA_EDGE_THRESHOLD) {
+        return vec4<f32>(rgb_m, 1.0);
+    }
+
+    // Blur direction: perpendicular to the luma gradient (uv space, y down)
+    let gradient = vec2<f32>(
+        (luma_ne + luma_se) - (luma_nw + luma_sw),
+        (luma_sw + luma_se) - (luma_nw + luma_ne),
+    );
+    var dir = vec2<f32>(-gradient.y, gradient.x);
+
+    let dir_reduce = max(
+        (luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL,
+        FXAA_REDUCE_MIN,
+    );
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2<f32>(-FXAA_SPAN_MAX), vec2<f32>(FXAA_SPAN_MAX)) * texel;
+
+    let rgb_a = 0.5 * (sample_color(in.uv + dir * (1.0 / 3.0 - 0.5))
+        + sample_color(in.uv + dir * (2.0 / 3.0 - 0.5)));
+    let rgb_b = rgb_a * 0.5 + 0.25 * (sample_color(in.uv - dir * 0.5)
+        + sample_color(in.uv + dir * 0.5));
+
+    // The wider blur overshot past the local range: it crossed another edge
+    let luma_b = luma(rgb_b);
+    if luma_b < luma_min || luma_b > luma_max {
+        return vec4<f32>(rgb_a, 1.0);
+    }
+    return vec4<f32>(rgb_b, 1.0);
+}
@@ -0,0 +1,11 @@
+// Gamma encoding for surfaces without an sRGB format, which would otherwise present the
+// linear image as is. Approximates the sRGB transfer function with a 2.2 power.
+
+@group(0) @binding(0)
+var t_linear: texture_2d<f32>;
+
+@fragment
+fn fs_gamma(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(t_linear, vec2<i32>(in.clip_position.xy), 0);
+    return vec4<f32>(pow(max(color.rgb, vec3<f32>(0.0)), vec3<f32>(1.0 / 2.2)), color.a);
+}
@@ -0,0 +1,44 @@
+// Transform gizmo: three unlit arrows, rings or scale handles along the gizmo axes, drawn
+// over the scene without depth test. The one under the cursor, or being dragged, is
+// highlighted.
+
+struct Gizmo {
+    // Clip space from the unit space of the arrows and rings: view projection, center,
+    // rotation and scale
+    transform: mat4x4<f32>,
+    // Axis index, 3 for the center scale handle, anything above highlights none
+    highlighted: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> gizmo: Gizmo;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn vs_gizmo(@location(0) position: vec3<f32>, @location(1) axis: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = gizmo.transform * vec4<f32>(position, 1.0);
+    // X red, Y green, Z blue, the center handle grey
+    var color = vec3<f32>(0.6);
+    if axis < 3u {
+        color = vec3<f32>(0.0);
+        color[axis] = 1.0;
+    }
+    if axis == gizmo.highlighted {
+        color = vec3<f32>(1.0, 1.0, 0.0);
+    }
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_gizmo(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
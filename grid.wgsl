@@ -0,0 +1,86 @@
+// Reference grid on the Y = 0 plane: a fullscreen triangle whose fragments are moved onto
+// the plane, with their depth, and drawn as lines every `spacing` units. The X axis is red,
+// the Z axis blue, and the grid fades out toward `fade_distance` from the camera.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_pos: vec4<f32>,
+    prev_view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct Grid {
+    // Inverse of `camera.view_proj`
+    inv_view_proj: mat4x4<f32>,
+    spacing: f32,
+    fade_distance: f32,
+    _padding0: f32,
+    _padding1: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> grid: Grid;
+
+struct GridOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Homogeneous world positions on the near and far planes, divided per fragment
+    @location(0) near: vec4<f32>,
+    @location(1) far: vec4<f32>,
+};
+
+@vertex
+fn vs_grid(@builtin(vertex_index) vertex_index: u32) -> GridOutput {
+    // Covers the screen with a single triangle
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+
+    var out: GridOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.near = grid.inv_view_proj * vec4<f32>(ndc, 0.0, 1.0);
+    out.far = grid.inv_view_proj * vec4<f32>(ndc, 1.0, 1.0);
+    return out;
+}
+
+struct GridFragment {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+@fragment
+fn fs_grid(in: GridOutput) -> GridFragment {
+    let near = in.near.xyz / in.near.w;
+    let far = in.far.xyz / in.far.w;
+    // Where the view ray crosses the plane, kept only between the near and far planes. The
+    // fragments are discarded last, derivatives need every neighbour
+    let t = -near.y / (far.y - near.y);
+    let position = near + (far - near) * t;
+
+    // Lines one pixel wide whatever the distance
+    let coord = position.xz / grid.spacing;
+    let width = fwidth(coord);
+    let lines = abs(fract(coord - 0.5) - 0.5) / width;
+    let line = 1.0 - min(min(lines.x, lines.y), 1.0);
+
+    var color = vec3<f32>(0.5);
+    // The X axis runs where z = 0, the Z axis where x = 0
+    let axes = abs(position.xz) / width / grid.spacing;
+    if axes.y < 1.0 {
+        color = vec3<f32>(0.9, 0.2, 0.2);
+    } else if axes.x < 1.0 {
+        color = vec3<f32>(0.2, 0.3, 0.9);
+    }
+
+    let fade = clamp(1.0 - distance(position, camera.view_pos.xyz) / grid.fade_distance, 0.0, 1.0);
+    let alpha = line * fade * 0.6;
+    if t < 0.0 || t > 1.0 || alpha <= 0.0 {
+        discard;
+    }
+
+    let clip = camera.view_proj * vec4<f32>(position, 1.0);
+    var out: GridFragment;
+    out.color = vec4<f32>(color, alpha);
+    out.depth = clip.z / clip.w;
+    return out;
+}
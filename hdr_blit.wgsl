@@ -0,0 +1,13 @@
+// Tone mapping of the HDR scene into the render target when auto exposure is off, with the
+// curve and exposure picked in the editor. tonemap.wgsl is the adaptive version.
+
+@group(0) @binding(0)
+var t_hdr: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> settings: TonemapUniform;
+
+@fragment
+fn fs_blit(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(t_hdr, vec2<i32>(in.clip_position.xy), 0);
+    return vec4<f32>(tonemap(color.rgb, settings), color.a);
+}
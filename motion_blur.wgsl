@@ -0,0 +1,53 @@
+// Motion blur: each pixel averages the scene along its screen-space velocity, centered on
+// itself so that the trail spreads evenly between the last and the current position.
+
+struct MotionBlurUniform {
+    // Samples along the velocity, the center one included
+    taps: u32,
+    // Fraction of the frame's motion the shutter stays open for
+    strength: f32,
+    // Longest trail in pixels
+    max_length: f32,
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> blur: MotionBlurUniform;
+// Rg16Float, in texture coordinates per frame
+@group(0) @binding(1)
+var t_velocity: texture_2d<f32>;
+// The HDR scene for the blur pass, the blurred result for the copy back
+@group(0) @binding(2)
+var t_input: texture_2d<f32>;
+
+fn load_clamped(t: texture_2d<f32>, pixel: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t));
+    return textureLoad(t, clamp(pixel, vec2<i32>(0), size - 1), 0);
+}
+
+@fragment
+fn fs_motion_blur(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let size = vec2<f32>(textureDimensions(t_input));
+    var velocity = load_clamped(t_velocity, pixel).xy * size * blur.strength;
+    let trail = length(velocity);
+    if trail > blur.max_length {
+        velocity *= blur.max_length / trail;
+    }
+    if blur.taps < 2u || trail < 0.5 {
+        return load_clamped(t_input, pixel);
+    }
+
+    var sum = vec3<f32>(0.0);
+    for (var i = 0u; i < blur.taps; i++) {
+        let t = f32(i) / f32(blur.taps - 1u) - 0.5;
+        let offset = vec2<i32>(round(velocity * t));
+        sum += load_clamped(t_input, pixel + offset).rgb;
+    }
+    return vec4<f32>(sum / f32(blur.taps), 1.0);
+}
+
+@fragment
+fn fs_copy(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return load_clamped(t_input, vec2<i32>(in.clip_position.xy));
+}
@@ -0,0 +1,49 @@
+// Ink outlines: Sobel edge detection on the linearized depth buffer
+
+struct OutlineUniform {
+    // Clip space back to view space, for the depth of each tap
+    inverse_projection: mat4x4<f32>,
+    color: vec4<f32>,
+    // Relative depth gradient above which a pixel is part of an edge
+    threshold: f32,
+    // Distance in pixels between the Sobel taps
+    thickness: f32,
+    _padding0: f32,
+    _padding1: f32,
+};
+
+// Bound as an unfilterable float texture: loading from `texture_depth_2d` is not portable to GL
+@group(0) @binding(0)
+var t_depth: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> outline: OutlineUniform;
+
+fn linear_depth(pixel: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(t_depth));
+    let d = textureLoad(t_depth, clamp(pixel, vec2<i32>(0), size - 1), 0).r;
+    return view_depth(d, outline.inverse_projection);
+}
+
+@fragment
+fn fs_outline(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_depth));
+    let center = vec2<i32>(in.uv * size);
+    let step = i32(max(outline.thickness, 1.0));
+
+    var samples: array<f32, 9>;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            samples[(y + 1) * 3 + (x + 1)] = linear_depth(center + vec2<i32>(x, y) * step);
+        }
+    }
+
+    // 3x3 Sobel kernels
+    let gx = (samples[2] + 2.0 * samples[5] + samples[8]) - (samples[0] + 2.0 * samples[3] + samples[6]);
+    let gy = (samples[6] + 2.0 * samples[7] + samples[8]) - (samples[0] + 2.0 * samples[1] + samples[2]);
+
+    // Relative to the distance so far objects are not outlined more than close ones
+    let gradient = sqrt(gx * gx + gy * gy) / samples[4];
+    let edge = smoothstep(outline.threshold, outline.threshold * 1.5, gradient);
+
+    return vec4<f32>(outline.color.rgb, outline.color.a * edge);
+}
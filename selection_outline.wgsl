@@ -0,0 +1,79 @@
+// Stencil outline of the selected instances: they are first drawn into the stencil buffer
+// only, then drawn again pushed outward along their normals, in screen space so that the
+// outline keeps its thickness at any distance. That second draw only lands where the first
+// one left the stencil untouched: a rim around the silhouette.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_pos: vec4<f32>,
+    prev_view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct SelectionOutline {
+    color: vec4<f32>,
+    // Pixels
+    viewport_size: vec2<f32>,
+    // Pixels
+    thickness: f32,
+    _padding: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> outline: SelectionOutline;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(3) normal: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+};
+
+fn model_matrix(instance: InstanceInput) -> mat4x4<f32> {
+    return mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+}
+
+@vertex
+fn vs_mask(model: VertexInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    return camera.view_proj * model_matrix(instance) * vec4<f32>(model.position, 1.0);
+}
+
+@vertex
+fn vs_outline(model: VertexInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    let model_matrix = model_matrix(instance);
+    var clip = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+
+    // Same normal matrix as the scene shader, see vs_main
+    let linear_part = mat3x3<f32>(model_matrix[0].xyz, model_matrix[1].xyz, model_matrix[2].xyz);
+    let normal_matrix = mat3x3<f32>(
+        linear_part[0] / dot(linear_part[0], linear_part[0]),
+        linear_part[1] / dot(linear_part[1], linear_part[1]),
+        linear_part[2] / dot(linear_part[2], linear_part[2]),
+    );
+    let world_normal = normal_matrix * model.normal;
+    let screen_normal = (camera.view_proj * vec4<f32>(world_normal, 0.0)).xy;
+    // Normals facing the camera have no screen direction, the neighbours cover them
+    if dot(screen_normal, screen_normal) > 1e-12 {
+        // NDC spans 2 units over the viewport, and the offset is divided by w like clip.xy
+        let offset = normalize(screen_normal) * outline.thickness * 2.0 / outline.viewport_size;
+        clip = vec4<f32>(clip.xy + offset * clip.w, clip.zw);
+    }
+    return clip;
+}
+
+@fragment
+fn fs_outline() -> @location(0) vec4<f32> {
+    return outline.color;
+}
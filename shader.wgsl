@@ -2,26 +2,104 @@
 struct CameraUniform {
     view_proj: mat4x4<f32>,
     view_pos: vec4<f32>,
+    // Last frame's view_proj, for the velocity buffer
+    prev_view_proj: mat4x4<f32>,
 };
 
 // Get Bind Group 0, Binding 0
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
-struct LightUniform {
+// Must match MAX_LIGHTS in light.rs
+const MAX_LIGHTS: u32 = 16u;
+
+struct PointLight {
     position: vec3<f32>,
     color: vec3<f32>,
+    intensity: f32,
+    radius: f32,
+    // vec3 would be 16-byte aligned, so padding is spelled out
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+struct LightArray {
+    lights: array<PointLight, MAX_LIGHTS>,
+    count: u32,
 };
 
 // Group 2 for lighting
 @group(2) @binding(0)
-var<uniform> light: LightUniform;
+var<uniform> lights: LightArray;
+
+struct DirectionalLight {
+    // Direction the light travels in
+    direction: vec3<f32>,
+    color: vec3<f32>,
+    intensity: f32,
+};
+
+@group(2) @binding(1)
+var<uniform> sun: DirectionalLight;
+
+struct SpotLight {
+    position: vec3<f32>,
+    // 0 when the scene has no spot light
+    enabled: u32,
+    direction: vec3<f32>,
+    intensity: f32,
+    color: vec3<f32>,
+    // Cosines of the cone half-angles
+    cos_inner: f32,
+    cos_outer: f32,
+};
+
+@group(2) @binding(2)
+var<uniform> spot: SpotLight;
+
+struct Fog {
+    // Alpha is the opacity at its thickest, 0 disables the fog
+    color: vec4<f32>,
+    density: f32,
+    start: f32,
+    end: f32,
+    // 0 = linear, 1 = exponential, 2 = exponential squared
+    mode: u32,
+};
+
+@group(2) @binding(3)
+var<uniform> fog: Fog;
+
+// Amount of fog in [0, 1] at a view-space depth, mirrored by FogUniform::factor
+fn fog_amount(depth: f32) -> f32 {
+    var factor: f32;
+    switch fog.mode {
+        case 1u: {
+            factor = 1.0 - exp(-fog.density * depth);
+        }
+        case 2u: {
+            let d = fog.density * depth;
+            factor = 1.0 - exp(-d * d);
+        }
+        default: {
+            // An empty range fogs everything past start instead of dividing by zero
+            let range = fog.end - fog.start;
+            factor = select(f32(depth >= fog.start), (depth - fog.start) / range, range > 0.0);
+        }
+    }
+    return saturate(factor);
+}
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) color: vec3<f32>,
     @location(2) tex_coords: vec2<f32>,
     @location(3) normal: vec3<f32>,
+    // xyz along increasing U, w the handedness of the bitangent
+    @location(4) tangent: vec4<f32>,
+    // Lightmap UVs, after the instance attributes
+    @location(11) tex_coords2: vec2<f32>,
 };
 
 // A mat4 takes 4 slots (vec4)
@@ -30,6 +108,10 @@ struct InstanceInput {
     @location(6) model_matrix_1: vec4<f32>,
     @location(7) model_matrix_2: vec4<f32>,
     @location(8) model_matrix_3: vec4<f32>,
+    // Index in the scene, the buffer only holding the instances left by frustum culling
+    @location(9) scene_index: u32,
+    // Multiplies the base color
+    @location(10) tint: vec4<f32>,
 };
 
 struct VertexOutput {
@@ -38,6 +120,11 @@ struct VertexOutput {
     @location(1) color: vec3<f32>,
     @location(2) world_normal: vec3<f32>,   // Pass normal to fragment
     @location(3) world_position: vec3<f32>, // Pass position to fragment
+    @location(4) world_tangent: vec4<f32>,
+    // Distance along the view direction, for the fog
+    @location(5) view_depth: f32,
+    @location(6) tint: vec4<f32>,
+    @location(7) tex_coords2: vec2<f32>,
 };
 
 @vertex
@@ -53,6 +140,8 @@ fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
     out.color = model.color;
+    out.tint = instance.tint;
+    out.tex_coords2 = model.tex_coords2;
 
     // 1. Calculate world position
     // We assume the model matrix handles rotation/scale/translation
@@ -60,14 +149,27 @@ fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
     out.world_position = world_position.xyz;
 
     // 2. Calculate world normal
-    // If we scale the object unevenly, we would need a "Normal Matrix", 
-    // but for rotation/translation only, model_matrix is fine.
-    // .xyz is important to ignore translation for normals (vectors don't have position)
-    out.world_normal = (model_matrix * vec4<f32>(model.normal, 0.0)).xyz;
+    // Normals need the inverse transpose of the linear part to stay perpendicular under a
+    // non-uniform scale. For rotation * scale it is rotation * scale⁻¹: each column divided
+    // by its squared length (mirrors included, the sign is kept).
+    let linear_part = mat3x3<f32>(model_matrix[0].xyz, model_matrix[1].xyz, model_matrix[2].xyz);
+    let normal_matrix = mat3x3<f32>(
+        linear_part[0] / dot(linear_part[0], linear_part[0]),
+        linear_part[1] / dot(linear_part[1], linear_part[1]),
+        linear_part[2] / dot(linear_part[2], linear_part[2]),
+    );
+    out.world_normal = normal_matrix * model.normal;
+    // Tangents lie in the surface and follow the model matrix.
+    // Mirrors flip the handedness of the tangent frame
+    let mirrored = determinant(linear_part) < 0.0;
+    let handedness = select(model.tangent.w, -model.tangent.w, mirrored);
+    out.world_tangent = vec4<f32>(linear_part * model.tangent.xyz, handedness);
     
     // Order: Projection * View * Model * Position
     out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
-    
+    // With a perspective projection, w is the view-space depth
+    out.view_depth = out.clip_position.w;
+
     return out;
 }
 
@@ -77,35 +179,260 @@ var t_diffuse: texture_2d<f32>;
 @group(1) @binding(1)
 var s_diffuse: sampler;
 
+struct MaterialProperties {
+    roughness: f32,
+    metallic: f32,
+    // Non-zero when t_specular holds roughness (red) and metalness (green)
+    specular_map: u32,
+    // Multiplies the ambient occlusion map
+    ao: f32,
+    // Linear, multiplied with t_emissive
+    emissive: vec3<f32>,
+    // Multiplies the diffuse alpha
+    opacity: f32,
+    // Linear, multiplies t_diffuse
+    diffuse_color: vec3<f32>,
+};
+
+@group(1) @binding(2)
+var<uniform> material: MaterialProperties;
+
+// White when the material has no ambient occlusion map
+@group(1) @binding(3)
+var t_ambient_occlusion: texture_2d<f32>;
+
+// Tangent space, flat (0.5, 0.5, 1.0) when the material has no normal map
+@group(1) @binding(4)
+var t_normal: texture_2d<f32>;
+@group(1) @binding(5)
+var s_normal: sampler;
+
+// Neutral (0.5, 0.5) when the material has no specular map
+@group(1) @binding(6)
+var t_specular: texture_2d<f32>;
+
+const PI: f32 = 3.14159265359;
+// Share of each light's radiance added unconditionally, the minimum light everywhere
+const AMBIENT_STRENGTH: f32 = 0.1;
+
+// GGX / Trowbridge-Reitz normal distribution
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denom * denom);
+}
+
+// Smith's masking-shadowing with the Schlick-GGX approximation for direct lighting
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    let g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Cook-Torrance BRDF for a light of unit radiance coming from `light_dir`, plus its ambient
+// share. Multiply by the light's radiance. Scaled by PI so that a white light facing a white
+// diffuse surface gives white, light intensities keep the meaning they had before PBR.
+fn pbr_lighting(
+    albedo: vec3<f32>,
+    normal: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    ao: f32,
+    light_dir: vec3<f32>,
+    view_dir: vec3<f32>,
+) -> vec3<f32> {
+    let ambient = AMBIENT_STRENGTH * ao * albedo;
+    let n_dot_l = dot(normal, light_dir);
+    if (n_dot_l <= 0.0) {
+        return ambient;
+    }
+    // A perfectly smooth surface would give an infinitely small, infinitely bright highlight
+    let r = clamp(roughness, 0.04, 1.0);
+    let n_dot_v = max(dot(normal, view_dir), 0.0001);
+    let half_dir = normalize(light_dir + view_dir);
+
+    // Dielectrics reflect about 4% of the light, metals tint the reflection with their color
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let fresnel = fresnel_schlick(max(dot(half_dir, view_dir), 0.0), f0);
+    let ndf = distribution_ggx(max(dot(normal, half_dir), 0.0), r);
+    let geometry = geometry_smith(n_dot_v, n_dot_l, r);
+    let specular = ndf * geometry * fresnel / (4.0 * n_dot_v * n_dot_l);
+
+    // What is not reflected is refracted and diffused, metals have no diffuse reflection
+    let diffuse = (1.0 - fresnel) * (1.0 - metallic) * albedo / PI;
+    return PI * (diffuse + specular) * n_dot_l + ambient;
+}
+
+// White when the material has no emissive map
+@group(1) @binding(7)
+var t_emissive: texture_2d<f32>;
+
+// White when the material has no lightmap, sampled with the second UV set
+@group(1) @binding(8)
+var t_lightmap: texture_2d<f32>;
+
+// Surface normal perturbed by the normal map through the TBN frame
+fn mapped_normal(in: VertexOutput) -> vec3<f32> {
+    // Sampled before branching, implicit derivatives need uniform control flow
+    let sampled = textureSample(t_normal, s_normal, in.tex_coords).xyz * 2.0 - 1.0;
+    let n = normalize(in.world_normal);
+    if (length(in.world_tangent.xyz) < 0.0001) {
+        return n;
+    }
+    // Re-orthogonalize, interpolation skews the frame
+    let t = normalize(in.world_tangent.xyz - n * dot(n, in.world_tangent.xyz));
+    // Our V axis points down the image while normal maps have green pointing up
+    let b = -cross(n, t) * in.world_tangent.w;
+    return normalize(mat3x3<f32>(t, b, n) * sampled);
+}
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    // 1. Get base color from texture
-    let object_color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    // 1. Get base color from texture, tinted per instance
+    let object_color = textureSample(t_diffuse, s_diffuse, in.tex_coords)
+        * vec4<f32>(material.diffuse_color, 1.0) * in.tint;
     
-    // 2. Ambient light (The minimum light everywhere)
-    let ambient_strength = 0.1;
-    let ambient_color = light.color * ambient_strength;
+    let ambient_occlusion = textureSample(t_ambient_occlusion, s_diffuse, in.tex_coords).r;
+    // Back faces only reach here for double-sided materials, lit from their own side
+    let surface_normal = mapped_normal(in);
+    let normal = select(-surface_normal, surface_normal, front_facing);
+    let specular_sample = textureSample(t_specular, s_diffuse, in.tex_coords).rg;
+    let uniform_params = vec2<f32>(material.roughness, material.metallic);
+    let params = select(uniform_params, specular_sample, material.specular_map != 0u);
+    let roughness = params.x;
+    let metallic = params.y;
+    let emissive_sample = textureSample(t_emissive, s_diffuse, in.tex_coords).rgb;
+    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    let lightmap = textureSample(t_lightmap, s_diffuse, in.tex_coords2).rgb;
+    let albedo = object_color.xyz * lightmap;
+    let ao = ambient_occlusion * material.ao;
 
-    // 3. Diffuse light (Directional light)
-    let light_dir = normalize(light.position - in.world_position);
-    let normal = normalize(in.world_normal);
-    let diffuse_strength = max(dot(normal, light_dir), 0.0);
-    let diffuse_color = light.color * diffuse_strength;
+    var lit = vec3<f32>(0.0);
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i += 1u) {
+        let light = lights.lights[i];
+        let to_light = light.position - in.world_position;
 
-    // Specular highlight (Shiny spots)
-    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
-    let reflect_dir = reflect(-light_dir, normal);
+        // Smooth falloff reaching exactly zero at the light radius
+        let distance_ratio = length(to_light) / max(light.radius, 0.0001);
+        let falloff = saturate(1.0 - distance_ratio * distance_ratio);
+        let radiance = light.color * light.intensity * falloff * falloff;
+
+        let light_dir = normalize(to_light);
+        lit += radiance * pbr_lighting(albedo, normal, metallic, roughness, ao, light_dir, view_dir);
+    }
 
-    // Shininess: Higher = smaller, sharper highlight (e.g., 32.0 or 64.0)
-    let shininess = 32.0; 
-    let specular_strength = 0.5;
+    // Directional light, same direction everywhere
+    if (dot(sun.direction, sun.direction) > 0.0) {
+        let light_dir = normalize(-sun.direction);
+        let radiance = sun.color * sun.intensity;
+        lit += radiance * pbr_lighting(albedo, normal, metallic, roughness, ao, light_dir, view_dir);
+    }
 
-    // Calculate the highlight
-    let spec = pow(max(dot(view_dir, reflect_dir), 0.0), shininess);
-    let specular_color = light.color * spec * specular_strength;
+    // Spot light, soft edge between the inner and outer cones
+    if (spot.enabled != 0u && dot(spot.direction, spot.direction) > 0.0) {
+        let light_dir = normalize(spot.position - in.world_position);
+        let theta = dot(-light_dir, normalize(spot.direction));
+        let cone = smoothstep(spot.cos_outer, spot.cos_inner, theta);
+        let radiance = spot.color * spot.intensity * cone;
+        lit += radiance * pbr_lighting(albedo, normal, metallic, roughness, ao, light_dir, view_dir);
+    }
 
-    // Combine everything
-    let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
+    // Self-lit surfaces ignore the lights and their attenuation
+    lit += material.emissive * emissive_sample;
 
-    return vec4<f32>(result, object_color.a);
-}
\ No newline at end of file
+    let result = mix(lit, fog.color.rgb, fog_amount(in.view_depth) * fog.color.a);
+
+    return vec4<f32>(result, object_color.a * material.opacity);
+}
+// Debug view: each instance gets a color derived from its index in the scene, so that it
+// keeps it whatever culling does to the instance buffer. Picking reads the index in that
+// buffer instead.
+struct InstanceIdOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) @interpolate(flat) instance_index: u32,
+    @location(1) @interpolate(flat) scene_index: u32,
+};
+
+@vertex
+fn vs_instance_id(
+    model: VertexInput,
+    instance: InstanceInput,
+    @builtin(instance_index) instance_index: u32,
+) -> InstanceIdOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    var out: InstanceIdOutput;
+    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+    out.instance_index = instance_index;
+    out.scene_index = instance.scene_index;
+    return out;
+}
+
+// Integer hash (PCG) so that neighbouring indices get very different hues
+fn hash_u32(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn hsv_to_rgb(hsv: vec3<f32>) -> vec3<f32> {
+    let k = vec3<f32>(1.0, 2.0 / 3.0, 1.0 / 3.0);
+    let p = abs(fract(hsv.xxx + k) * 6.0 - 3.0);
+    return hsv.z * mix(vec3<f32>(1.0), clamp(p - 1.0, vec3<f32>(0.0), vec3<f32>(1.0)), hsv.y);
+}
+
+@fragment
+fn fs_instance_id(in: InstanceIdOutput) -> @location(0) vec4<f32> {
+    let hue = f32(hash_u32(in.scene_index) & 0xffffu) / 65536.0;
+    return vec4<f32>(hsv_to_rgb(vec3<f32>(hue, 0.75, 0.9)), 1.0);
+}
+
+// GPU picking: the index of the instance covering the pixel, 0 being the background
+@fragment
+fn fs_pick(in: InstanceIdOutput) -> @location(0) u32 {
+    return in.instance_index + 1u;
+}
+
+// Velocity buffer for the motion blur: how far the surface moved on screen since the last
+// frame. Only the camera motion is tracked, instances are drawn with their current transform
+struct VelocityOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) current: vec4<f32>,
+    @location(1) previous: vec4<f32>,
+};
+
+@vertex
+fn vs_velocity(model: VertexInput, instance: InstanceInput) -> VelocityOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VelocityOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.current = out.clip_position;
+    out.previous = camera.prev_view_proj * world_position;
+    return out;
+}
+
+// Displacement from the previous position in texture coordinates (y down)
+@fragment
+fn fs_velocity(in: VelocityOutput) -> @location(0) vec2<f32> {
+    let current = in.current.xy / in.current.w;
+    let previous = in.previous.xy / in.previous.w;
+    return (current - previous) * vec2<f32>(0.5, -0.5);
+}
@@ -0,0 +1,63 @@
+// Skybox: a unit cube centered on the camera, so that only its rotation matters, drawn on
+// the far plane behind everything else.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_pos: vec4<f32>,
+    // Last frame's view_proj, for the velocity buffer
+    prev_view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var t_skybox: texture_cube<f32>;
+@group(1) @binding(1)
+var s_skybox: sampler;
+
+struct SkyboxOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec3<f32>,
+};
+
+const CORNERS = array<vec3<f32>, 8>(
+    vec3<f32>(-1.0, -1.0, -1.0),
+    vec3<f32>(1.0, -1.0, -1.0),
+    vec3<f32>(1.0, 1.0, -1.0),
+    vec3<f32>(-1.0, 1.0, -1.0),
+    vec3<f32>(-1.0, -1.0, 1.0),
+    vec3<f32>(1.0, -1.0, 1.0),
+    vec3<f32>(1.0, 1.0, 1.0),
+    vec3<f32>(-1.0, 1.0, 1.0),
+);
+
+// Two triangles per face, drawn without culling so the winding does not matter
+const INDICES = array<u32, 36>(
+    0u, 1u, 2u, 0u, 2u, 3u, // -Z
+    4u, 6u, 5u, 4u, 7u, 6u, // +Z
+    0u, 3u, 7u, 0u, 7u, 4u, // -X
+    1u, 5u, 6u, 1u, 6u, 2u, // +X
+    0u, 4u, 5u, 0u, 5u, 1u, // -Y
+    3u, 2u, 6u, 3u, 6u, 7u, // +Y
+);
+
+@vertex
+fn vs_skybox(@builtin(vertex_index) vertex_index: u32) -> SkyboxOutput {
+    var corners = CORNERS;
+    var indices = INDICES;
+    let corner = corners[indices[vertex_index]];
+
+    var out: SkyboxOutput;
+    // Following the camera cancels its translation
+    let clip = camera.view_proj * vec4<f32>(camera.view_pos.xyz + corner, 1.0);
+    // z = w puts every fragment at depth 1.0, on the far plane
+    out.clip_position = clip.xyww;
+    out.direction = corner;
+    return out;
+}
+
+@fragment
+fn fs_skybox(in: SkyboxOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_skybox, s_skybox, in.direction);
+}
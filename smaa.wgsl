@@ -0,0 +1,157 @@
+// SMAA-style morphological anti-aliasing, in three passes:
+//   1. fs_smaa_edges: luma edge detection
+//   2. fs_smaa_weights: blending weights from the length and shape of each edge line,
+//      computed analytically instead of with SMAA's precomputed area texture
+//   3. fs_smaa_blend: neighborhood blending
+//
+// Edges are stored per pixel for its left (r) and top (g) borders. Weights follow the same
+// convention: for the top border, r = how much of the top neighbor goes into this pixel and
+// g = how much of this pixel goes into the top neighbor; b / a are the same for the left border.
+
+const EDGE_THRESHOLD: f32 = 0.1;
+const MAX_SEARCH_STEPS: i32 = 16;
+
+@group(0) @binding(0)
+var t_color: texture_2d<f32>;
+@group(0) @binding(1)
+var t_edges: texture_2d<f32>;
+@group(0) @binding(2)
+var t_weights: texture_2d<f32>;
+
+fn in_bounds(p: vec2<i32>, size: vec2<i32>) -> bool {
+    return all(p >= vec2<i32>(0)) && all(p < size);
+}
+
+fn load_color(p: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t_color));
+    return textureLoad(t_color, clamp(p, vec2<i32>(0), size - 1), 0);
+}
+
+// No edges outside of the image, which also stops the searches there
+fn load_edges(p: vec2<i32>) -> vec2<f32> {
+    let size = vec2<i32>(textureDimensions(t_edges));
+    if !in_bounds(p, size) {
+        return vec2<f32>(0.0);
+    }
+    return textureLoad(t_edges, p, 0).rg;
+}
+
+fn load_weights(p: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t_weights));
+    if !in_bounds(p, size) {
+        return vec4<f32>(0.0);
+    }
+    return textureLoad(t_weights, p, 0);
+}
+
+// Computed on roughly gamma-encoded values, where contrast matches what the eye sees
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(sqrt(color), vec3<f32>(0.299, 0.587, 0.114));
+}
+
+@fragment
+fn fs_smaa_edges(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(in.clip_position.xy);
+    let l = luma(load_color(p).rgb);
+    let l_left = luma(load_color(p + vec2<i32>(-1, 0)).rgb);
+    let l_top = luma(load_color(p + vec2<i32>(0, -1)).rgb);
+
+    let edges = step(vec2<f32>(EDGE_THRESHOLD), abs(vec2<f32>(l) - vec2<f32>(l_left, l_top)));
+    return vec4<f32>(edges, 0.0, 1.0);
+}
+
+// How many pixels the edge stored in `channel` continues past `p` along `dir`
+fn search(p: vec2<i32>, dir: vec2<i32>, channel: i32) -> i32 {
+    var count = 0;
+    for (var i = 1; i <= MAX_SEARCH_STEPS; i++) {
+        if load_edges(p + dir * i)[channel] == 0.0 {
+            break;
+        }
+        count = i;
+    }
+    return count;
+}
+
+// Height of the reconstructed silhouette at one end of an edge line, from the crossing edges
+// found there: +0.5 when it bends into the current pixel's side, -0.5 into the neighbor's side,
+// 0 without (or with an ambiguous) crossing
+fn end_height(near_side: f32, far_side: f32) -> f32 {
+    return 0.5 * (near_side - far_side);
+}
+
+// Silhouette height at `t` along a line of `len` pixels going from h0 to h1
+fn silhouette(t: f32, len: f32, h0: f32, h1: f32) -> f32 {
+    let u = t / len;
+    if h0 * h1 > 0.0 {
+        // U shape: bends the same way at both ends, meets the edge in the middle
+        return select(h1 * (2.0 * u - 1.0), h0 * (1.0 - 2.0 * u), u < 0.5);
+    }
+    return mix(h0, h1, u);
+}
+
+// Signed area between the silhouette and the edge over pixel `i` of the line
+fn edge_area(i: i32, len: i32, h0: f32, h1: f32) -> f32 {
+    let t = f32(i);
+    return 0.5 * (silhouette(t, f32(len), h0, h1) + silhouette(t + 1.0, f32(len), h0, h1));
+}
+
+@fragment
+fn fs_smaa_weights(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(in.clip_position.xy);
+    let edges = load_edges(p);
+    var weights = vec4<f32>(0.0);
+
+    if edges.g > 0.0 {
+        // Horizontal line above p, crossed by vertical edges in p's row or the row above
+        let left = search(p, vec2<i32>(-1, 0), 1);
+        let right = search(p, vec2<i32>(1, 0), 1);
+        let start = p - vec2<i32>(left, 0);
+        let end = p + vec2<i32>(right + 1, 0);
+        let h0 = end_height(load_edges(start).r, load_edges(start + vec2<i32>(0, -1)).r);
+        let h1 = end_height(load_edges(end).r, load_edges(end + vec2<i32>(0, -1)).r);
+
+        let area = edge_area(left, left + right + 1, h0, h1);
+        weights.r = max(area, 0.0);
+        weights.g = max(-area, 0.0);
+    }
+
+    if edges.r > 0.0 {
+        // Vertical line left of p, crossed by horizontal edges in p's column or the one left
+        let up = search(p, vec2<i32>(0, -1), 0);
+        let down = search(p, vec2<i32>(0, 1), 0);
+        let start = p - vec2<i32>(0, up);
+        let end = p + vec2<i32>(0, down + 1);
+        let h0 = end_height(load_edges(start).g, load_edges(start + vec2<i32>(-1, 0)).g);
+        let h1 = end_height(load_edges(end).g, load_edges(end + vec2<i32>(-1, 0)).g);
+
+        let area = edge_area(up, up + down + 1, h0, h1);
+        weights.b = max(area, 0.0);
+        weights.a = max(-area, 0.0);
+    }
+
+    return weights;
+}
+
+@fragment
+fn fs_smaa_blend(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(in.clip_position.xy);
+    let own = load_weights(p);
+
+    // Top and left come from this pixel's borders, bottom and right from the neighbors'
+    let w_top = own.r;
+    let w_left = own.b;
+    let w_bottom = load_weights(p + vec2<i32>(0, 1)).g;
+    let w_right = load_weights(p + vec2<i32>(1, 0)).a;
+
+    let color = load_color(p);
+    let total = w_top + w_left + w_bottom + w_right;
+    if total <= 0.0 {
+        return color;
+    }
+
+    let neighbors = w_top * load_color(p + vec2<i32>(0, -1))
+        + w_left * load_color(p + vec2<i32>(-1, 0))
+        + w_bottom * load_color(p + vec2<i32>(0, 1))
+        + w_right * load_color(p + vec2<i32>(1, 0));
+    return mix(color, neighbors / total, min(total, 1.0));
+}
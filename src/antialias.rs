@@ -0,0 +1,656 @@
+use crate::post_process::{
+    create_fullscreen_pipeline, create_fullscreen_shader, run_fullscreen_pass,
+    sampler_layout_entry, texture_layout_entry, uniform_layout_entry,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec2};
+use wgpu::util::DeviceExt;
+
+/// Anti-aliasing technique applied to the 3D view. Only one is active at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AntialiasingMode {
+    #[default]
+    None,
+    Msaa2x,
+    Msaa4x,
+    Msaa8x,
+    Fxaa,
+    Smaa,
+    Taa,
+}
+
+impl AntialiasingMode {
+    pub const ALL: [AntialiasingMode; 7] = [
+        AntialiasingMode::None,
+        AntialiasingMode::Msaa2x,
+        AntialiasingMode::Msaa4x,
+        AntialiasingMode::Msaa8x,
+        AntialiasingMode::Fxaa,
+        AntialiasingMode::Smaa,
+        AntialiasingMode::Taa,
+    ];
+
+    /// Samples per pixel of the scene render pass, 1 for the post-process techniques.
+    pub fn sample_count(self) -> u32 {
+        match self {
+            AntialiasingMode::Msaa2x => 2,
+            AntialiasingMode::Msaa4x => 4,
+            AntialiasingMode::Msaa8x => 8,
+            _ => 1,
+        }
+    }
+
+    /// MSAA mode with `samples` per pixel, `None` (the mode) for 1. Only powers of two up to
+    /// 8 are valid.
+    pub fn from_sample_count(samples: u32) -> Option<Self> {
+        match samples {
+            1 => Some(AntialiasingMode::None),
+            2 => Some(AntialiasingMode::Msaa2x),
+            4 => Some(AntialiasingMode::Msaa4x),
+            8 => Some(AntialiasingMode::Msaa8x),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AntialiasingMode::None => "Aucun",
+            AntialiasingMode::Msaa2x => "MSAA 2x",
+            AntialiasingMode::Msaa4x => "MSAA 4x",
+            AntialiasingMode::Msaa8x => "MSAA 8x",
+            AntialiasingMode::Fxaa => "FXAA",
+            AntialiasingMode::Smaa => "SMAA",
+            AntialiasingMode::Taa => "TAA",
+        }
+    }
+}
+
+/// Modes the adapter can render with the given formats. Sample counts other than 1 and 4
+/// need `Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES` to be enabled on the device.
+pub fn supported_antialiasing_modes(
+    adapter: &wgpu::Adapter,
+    color_format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+) -> Vec<AntialiasingMode> {
+    let adapter_specific = adapter
+        .features()
+        .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+
+    AntialiasingMode::ALL
+        .into_iter()
+        .filter(|mode| match mode.sample_count() {
+            1 | 4 => true,
+            count => {
+                adapter_specific
+                    && [color_format, depth_format].iter().all(|&format| {
+                        adapter
+                            .get_texture_format_features(format)
+                            .flags
+                            .sample_count_supported(count)
+                    })
+            }
+        })
+        .collect()
+}
+
+/// Sub-pixel offset of TAA frame `index`, in pixels within [-0.5, 0.5).
+fn taa_jitter(index: u32) -> Vec2 {
+    Vec2::new(halton(index + 1, 2), halton(index + 1, 3)) - 0.5
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// Length of the jitter sequence before it repeats
+const TAA_JITTER_PHASES: u32 = 8;
+/// Weight of the current frame in the TAA accumulation
+const TAA_BLEND: f32 = 0.1;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct TaaUniform {
+    inv_view_proj: [[f32; 4]; 4],
+    prev_view_proj: [[f32; 4]; 4],
+    blend: f32,
+    history_valid: f32,
+    _padding: [f32; 2],
+}
+
+struct PostProcessPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl PostProcessPass {
+    fn new(
+        device: &wgpu::Device,
+        label: &str,
+        shader: &wgpu::ShaderModule,
+        fragment_entry: &str,
+        format: wgpu::TextureFormat,
+        entries: &[wgpu::BindGroupLayoutEntry],
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries,
+        });
+        let pipeline = create_fullscreen_pipeline(
+            device,
+            label,
+            &[&bind_group_layout],
+            shader,
+            fragment_entry,
+            format,
+            None,
+        );
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+
+    fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        resources: &[(u32, wgpu::BindingResource)],
+    ) -> wgpu::BindGroup {
+        let entries = resources
+            .iter()
+            .map(|(binding, resource)| wgpu::BindGroupEntry {
+                binding: *binding,
+                resource: resource.clone(),
+            })
+            .collect::<Vec<_>>();
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout,
+            entries: &entries,
+        })
+    }
+}
+
+/// Size dependent resources of the active mode.
+enum Targets {
+    None,
+    Msaa {
+        color: wgpu::TextureView,
+    },
+    Fxaa {
+        scene: wgpu::TextureView,
+        bind_group: wgpu::BindGroup,
+    },
+    Smaa {
+        scene: wgpu::TextureView,
+        edges: wgpu::TextureView,
+        weights: wgpu::TextureView,
+        edges_bind_group: wgpu::BindGroup,
+        weights_bind_group: wgpu::BindGroup,
+        blend_bind_group: wgpu::BindGroup,
+    },
+    Taa {
+        scene: wgpu::TextureView,
+        history: wgpu::Texture,
+        bind_group: wgpu::BindGroup,
+    },
+}
+
+/// Owns everything mode specific: where the scene pass renders to and the passes that
+/// turn it into the anti-aliased HDR target.
+///
+/// The scene pipelines and the depth buffer must use `sample_count()` samples, and with
+/// TAA the camera uniform must be offset by `next_jitter()` every frame.
+pub struct AntialiasRenderer {
+    mode: AntialiasingMode,
+    supported_modes: Vec<AntialiasingMode>,
+    format: wgpu::TextureFormat,
+    sampler: wgpu::Sampler,
+    fxaa: PostProcessPass,
+    smaa_edges: PostProcessPass,
+    smaa_weights: PostProcessPass,
+    smaa_blend: PostProcessPass,
+    taa: PostProcessPass,
+    taa_buffer: wgpu::Buffer,
+    taa_frame: u32,
+    taa_history_valid: bool,
+    prev_view_proj: Mat4,
+    targets: Targets,
+}
+
+impl AntialiasRenderer {
+    /// Falls back to `AntialiasingMode::None` when `mode` is not in `supported_modes`.
+    /// `format` is the format of the scene and of the HDR target.
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        mode: AntialiasingMode,
+        supported_modes: Vec<AntialiasingMode>,
+        depth_view: &wgpu::TextureView,
+    ) -> Self {
+        let color = wgpu::TextureSampleType::Float { filterable: true };
+
+        let fxaa_shader =
+            create_fullscreen_shader(device, "FXAA Shader", include_str!("../fxaa.wgsl"));
+        let fxaa = PostProcessPass::new(
+            device,
+            "FXAA",
+            &fxaa_shader,
+            "fs_fxaa",
+            format,
+            &[texture_layout_entry(0, color), sampler_layout_entry(1)],
+        );
+
+        let smaa_shader =
+            create_fullscreen_shader(device, "SMAA Shader", include_str!("../smaa.wgsl"));
+        let smaa_edges = PostProcessPass::new(
+            device,
+            "SMAA Edges",
+            &smaa_shader,
+            "fs_smaa_edges",
+            wgpu::TextureFormat::Rg8Unorm,
+            &[texture_layout_entry(0, color)],
+        );
+        let smaa_weights = PostProcessPass::new(
+            device,
+            "SMAA Weights",
+            &smaa_shader,
+            "fs_smaa_weights",
+            wgpu::TextureFormat::Rgba8Unorm,
+            &[texture_layout_entry(1, color)],
+        );
+        let smaa_blend = PostProcessPass::new(
+            device,
+            "SMAA Blend",
+            &smaa_shader,
+            "fs_smaa_blend",
+            format,
+            &[
+                texture_layout_entry(0, color),
+                texture_layout_entry(2, color),
+            ],
+        );
+
+        let taa_shader =
+            create_fullscreen_shader(device, "TAA Shader", include_str!("../taa.wgsl"));
+        let taa = PostProcessPass::new(
+            device,
+            "TAA",
+            &taa_shader,
+            "fs_taa",
+            format,
+            &[
+                texture_layout_entry(0, color),
+                texture_layout_entry(1, color),
+                texture_layout_entry(2, wgpu::TextureSampleType::Float { filterable: false }),
+                sampler_layout_entry(3),
+                uniform_layout_entry(4),
+            ],
+        );
+        let taa_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("TAA Buffer"),
+            contents: bytemuck::cast_slice(&[TaaUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Antialias Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let mode = if supported_modes.contains(&mode) {
+            mode
+        } else {
+            log::warn!("Anti-aliasing mode {mode:?} is not supported, disabling it");
+            AntialiasingMode::None
+        };
+
+        let mut renderer = Self {
+            mode,
+            supported_modes,
+            format,
+            sampler,
+            fxaa,
+            smaa_edges,
+            smaa_weights,
+            smaa_blend,
+            taa,
+            taa_buffer,
+            taa_frame: 0,
+            taa_history_valid: false,
+            prev_view_proj: Mat4::IDENTITY,
+            targets: Targets::None,
+        };
+        renderer.resize(device, config, depth_view);
+        renderer
+    }
+
+    pub fn mode(&self) -> AntialiasingMode {
+        self.mode
+    }
+
+    pub fn supported_modes(&self) -> &[AntialiasingMode] {
+        &self.supported_modes
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.mode.sample_count()
+    }
+
+    /// Switches technique, effective after the next `resize` which must follow once the
+    /// depth buffer has been recreated with the new sample count.
+    /// Unsupported modes are ignored.
+    pub fn set_mode(&mut self, mode: AntialiasingMode) {
+        if self.supported_modes.contains(&mode) {
+            self.mode = mode;
+        }
+    }
+
+    /// Reallocates the intermediate textures, the depth buffer having been recreated too.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+    ) {
+        let size = (config.width, config.height);
+        let scene = || {
+            let usage =
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+            create_view(&create_target(
+                device,
+                "Antialias Scene",
+                size,
+                self.format,
+                1,
+                usage,
+            ))
+        };
+
+        self.taa_history_valid = false;
+        self.targets = match self.mode {
+            AntialiasingMode::None => Targets::None,
+            AntialiasingMode::Msaa2x | AntialiasingMode::Msaa4x | AntialiasingMode::Msaa8x => {
+                let color = create_target(
+                    device,
+                    "MSAA Color",
+                    size,
+                    self.format,
+                    self.mode.sample_count(),
+                    wgpu::TextureUsages::RENDER_ATTACHMENT,
+                );
+                Targets::Msaa {
+                    color: create_view(&color),
+                }
+            }
+            AntialiasingMode::Fxaa => {
+                let scene = scene();
+                let bind_group = self.fxaa.bind_group(
+                    device,
+                    &[
+                        (0, wgpu::BindingResource::TextureView(&scene)),
+                        (1, wgpu::BindingResource::Sampler(&self.sampler)),
+                    ],
+                );
+                Targets::Fxaa { scene, bind_group }
+            }
+            AntialiasingMode::Smaa => {
+                let scene = scene();
+                let usage =
+                    wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+                let edges = create_view(&create_target(
+                    device,
+                    "SMAA Edges",
+                    size,
+                    wgpu::TextureFormat::Rg8Unorm,
+                    1,
+                    usage,
+                ));
+                let weights = create_view(&create_target(
+                    device,
+                    "SMAA Weights",
+                    size,
+                    wgpu::TextureFormat::Rgba8Unorm,
+                    1,
+                    usage,
+                ));
+                let edges_bind_group = self
+                    .smaa_edges
+                    .bind_group(device, &[(0, wgpu::BindingResource::TextureView(&scene))]);
+                let weights_bind_group = self
+                    .smaa_weights
+                    .bind_group(device, &[(1, wgpu::BindingResource::TextureView(&edges))]);
+                let blend_bind_group = self.smaa_blend.bind_group(
+                    device,
+                    &[
+                        (0, wgpu::BindingResource::TextureView(&scene)),
+                        (2, wgpu::BindingResource::TextureView(&weights)),
+                    ],
+                );
+                Targets::Smaa {
+                    scene,
+                    edges,
+                    weights,
+                    edges_bind_group,
+                    weights_bind_group,
+                    blend_bind_group,
+                }
+            }
+            AntialiasingMode::Taa => {
+                let scene = scene();
+                let history = create_target(
+                    device,
+                    "TAA History",
+                    size,
+                    self.format,
+                    1,
+                    wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                );
+                let history_view = create_view(&history);
+                let bind_group = self.taa.bind_group(
+                    device,
+                    &[
+                        (0, wgpu::BindingResource::TextureView(&scene)),
+                        (1, wgpu::BindingResource::TextureView(&history_view)),
+                        (2, wgpu::BindingResource::TextureView(depth_view)),
+                        (3, wgpu::BindingResource::Sampler(&self.sampler)),
+                        (4, self.taa_buffer.as_entire_binding()),
+                    ],
+                );
+                Targets::Taa {
+                    scene,
+                    history,
+                    bind_group,
+                }
+            }
+        };
+    }
+
+    /// Color attachment of the scene pass as `(view, resolve_target)`: the multisampled
+    /// buffer resolving into `hdr_target`, the intermediate texture of the post-process
+    /// modes, or `hdr_target` itself.
+    pub fn scene_attachment<'a>(
+        &'a self,
+        hdr_target: &'a wgpu::TextureView,
+    ) -> (&'a wgpu::TextureView, Option<&'a wgpu::TextureView>) {
+        match &self.targets {
+            Targets::None => (hdr_target, None),
+            Targets::Msaa { color } => (color, Some(hdr_target)),
+            Targets::Fxaa { scene, .. }
+            | Targets::Smaa { scene, .. }
+            | Targets::Taa { scene, .. } => (scene, None),
+        }
+    }
+
+    /// Projection offset in NDC for the next frame, zero unless TAA is active.
+    pub fn next_jitter(&mut self, width: u32, height: u32) -> Vec2 {
+        if self.mode != AntialiasingMode::Taa || width == 0 || height == 0 {
+            return Vec2::ZERO;
+        }
+        self.taa_frame = (self.taa_frame + 1) % TAA_JITTER_PHASES;
+        // One pixel is 2 / size in NDC
+        taa_jitter(self.taa_frame) * 2.0 / Vec2::new(width as f32, height as f32)
+    }
+
+    /// Runs the post-process passes writing the anti-aliased image into `hdr_target`.
+    /// `view_proj` is the (jittered) matrix the scene was rendered with.
+    pub fn resolve(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        hdr_target: &crate::textures::Texture,
+        view_proj: Mat4,
+    ) {
+        let target = &hdr_target.view;
+        match &self.targets {
+            // Rendered (or resolved by the pass) straight into the HDR target
+            Targets::None | Targets::Msaa { .. } => {}
+            Targets::Fxaa { bind_group, .. } => {
+                run_fullscreen_pass(
+                    encoder,
+                    "FXAA Pass",
+                    target,
+                    None,
+                    &self.fxaa.pipeline,
+                    &[bind_group],
+                );
+            }
+            Targets::Smaa {
+                edges,
+                weights,
+                edges_bind_group,
+                weights_bind_group,
+                blend_bind_group,
+                ..
+            } => {
+                let clear = Some(wgpu::Color::TRANSPARENT);
+                run_fullscreen_pass(
+                    encoder,
+                    "SMAA Edge Pass",
+                    edges,
+                    clear,
+                    &self.smaa_edges.pipeline,
+                    &[edges_bind_group],
+                );
+                run_fullscreen_pass(
+                    encoder,
+                    "SMAA Weight Pass",
+                    weights,
+                    clear,
+                    &self.smaa_weights.pipeline,
+                    &[weights_bind_group],
+                );
+                run_fullscreen_pass(
+                    encoder,
+                    "SMAA Blend Pass",
+                    target,
+                    None,
+                    &self.smaa_blend.pipeline,
+                    &[blend_bind_group],
+                );
+            }
+            Targets::Taa {
+                history,
+                bind_group,
+                ..
+            } => {
+                let uniform = TaaUniform {
+                    inv_view_proj: view_proj.inverse().to_cols_array_2d(),
+                    prev_view_proj: self.prev_view_proj.to_cols_array_2d(),
+                    blend: TAA_BLEND,
+                    history_valid: if self.taa_history_valid { 1.0 } else { 0.0 },
+                    _padding: [0.0; 2],
+                };
+                queue.write_buffer(&self.taa_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+                run_fullscreen_pass(
+                    encoder,
+                    "TAA Pass",
+                    target,
+                    None,
+                    &self.taa.pipeline,
+                    &[bind_group],
+                );
+                encoder.copy_texture_to_texture(
+                    hdr_target.texture.as_image_copy(),
+                    history.as_image_copy(),
+                    history.size(),
+                );
+
+                self.prev_view_proj = view_proj;
+                self.taa_history_valid = true;
+            }
+        }
+    }
+}
+
+fn create_target(
+    device: &wgpu::Device,
+    label: &str,
+    (width, height): (u32, u32),
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    usage: wgpu::TextureUsages,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage,
+        view_formats: &[],
+    })
+}
+
+fn create_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_counts() {
+        assert_eq!(AntialiasingMode::None.sample_count(), 1);
+        assert_eq!(AntialiasingMode::Msaa8x.sample_count(), 8);
+        assert_eq!(AntialiasingMode::Smaa.sample_count(), 1);
+    }
+
+    #[test]
+    fn test_from_sample_count() {
+        for samples in [1, 2, 4, 8] {
+            let mode = AntialiasingMode::from_sample_count(samples).unwrap();
+            assert_eq!(mode.sample_count(), samples);
+        }
+        assert_eq!(AntialiasingMode::from_sample_count(0), None);
+        assert_eq!(AntialiasingMode::from_sample_count(3), None);
+        assert_eq!(AntialiasingMode::from_sample_count(16), None);
+    }
+
+    #[test]
+    fn test_taa_jitter_is_subpixel_and_varies() {
+        let offsets = (0..TAA_JITTER_PHASES).map(taa_jitter).collect::<Vec<_>>();
+        for (i, offset) in offsets.iter().enumerate() {
+            assert!(offset.abs().max_element() < 0.5);
+            assert!(!offsets[..i].contains(offset));
+        }
+    }
+}
@@ -0,0 +1,206 @@
+// Offline ambient occlusion: for every texel of a mesh's UV layout, the fraction of the
+// hemisphere above the surface that is not blocked by the scene geometry. Plain CPU ray
+// casting, slow but only run on demand.
+
+use crate::{camera::Ray, models::Mesh, utils::linear_to_srgb};
+use glam::{Vec2, Vec3};
+use image::{GrayImage, Luma};
+
+/// Offset of the ray origins along the normal, so that rays do not hit their own triangle
+const RAY_BIAS: f32 = 1e-4;
+/// Texels around the UV islands filled from their neighbours, hiding seams when filtering
+const DILATION_PASSES: u32 = 2;
+
+/// Point `i` of a Hammersley set of `n` points in [0, 1)², evenly spread without clumping.
+fn hammersley(i: u32, n: u32) -> Vec2 {
+    Vec2::new(
+        i as f32 / n as f32,
+        i.reverse_bits() as f32 / (1u64 << 32) as f32,
+    )
+}
+
+/// Cosine-weighted direction around `normal` from a point of [0, 1)². With this
+/// distribution, the fraction of rays that escape is directly the ambient visibility.
+pub fn cosine_hemisphere_direction(sample: Vec2, normal: Vec3) -> Vec3 {
+    let radius = sample.y.sqrt();
+    let phi = 2.0 * std::f32::consts::PI * sample.x;
+    let (tangent, bitangent) = normal.any_orthonormal_pair();
+    tangent * radius * phi.cos()
+        + bitangent * radius * phi.sin()
+        + normal * (1.0 - sample.y).max(0.0).sqrt()
+}
+
+/// Barycentric coordinates of `p` in the 2D triangle `tri`, `None` if outside.
+fn barycentric(p: Vec2, [a, b, c]: [Vec2; 3]) -> Option<Vec3> {
+    let area = (b - a).perp_dot(c - a);
+    if area.abs() < f32::EPSILON {
+        return None; // Degenerate in UV space
+    }
+    let w1 = (p - a).perp_dot(c - a) / area;
+    let w2 = (b - a).perp_dot(p - a) / area;
+    let weights = Vec3::new(1.0 - w1 - w2, w1, w2);
+    weights.cmpge(Vec3::ZERO).all().then_some(weights)
+}
+
+fn is_occluded(ray: &Ray, occluders: &[Mesh]) -> bool {
+    occluders.iter().any(|mesh| {
+        ray.intersect_aabb(&mesh.aabb).is_some()
+            && mesh.indices.chunks_exact(3).any(|tri| {
+                let [v0, v1, v2] =
+                    [tri[0], tri[1], tri[2]].map(|i| mesh.vertices[i as usize].position.into());
+                ray.intersect_triangle(v0, v1, v2).is_some()
+            })
+    })
+}
+
+/// Bakes the ambient occlusion of `mesh` into a `resolution` x `resolution` image laid out
+/// like its texture coordinates, casting `num_rays` rays per texel against `occluders`
+/// (usually every mesh of the scene, `mesh` included for self-occlusion).
+///
+/// Values are sRGB encoded like the other textures, so that sampling the image through an
+/// sRGB texture gives back the linear visibility. Texels outside of the UV layout are white.
+pub fn bake_ambient_occlusion(
+    mesh: &Mesh,
+    occluders: &[Mesh],
+    resolution: u32,
+    num_rays: u32,
+) -> GrayImage {
+    let mut image = GrayImage::from_pixel(resolution, resolution, Luma([255]));
+    let mut covered = vec![false; (resolution * resolution) as usize];
+    let size = resolution as f32;
+
+    for tri in mesh.indices.chunks_exact(3) {
+        let vertices = [tri[0], tri[1], tri[2]].map(|i| &mesh.vertices[i as usize]);
+        let uvs = vertices.map(|v| Vec2::from(v.tex_coords) * size);
+        let positions = vertices.map(|v| Vec3::from(v.position));
+        let normals = vertices.map(|v| Vec3::from(v.normal));
+
+        let min = uvs[0].min(uvs[1]).min(uvs[2]).floor().max(Vec2::ZERO);
+        let max = uvs[0].max(uvs[1]).max(uvs[2]).ceil().min(Vec2::splat(size));
+        for y in min.y as u32..max.y as u32 {
+            for x in min.x as u32..max.x as u32 {
+                // Texel centers, like the GPU samples them
+                let Some(w) = barycentric(Vec2::new(x as f32 + 0.5, y as f32 + 0.5), uvs) else {
+                    continue;
+                };
+                let position = positions[0] * w.x + positions[1] * w.y + positions[2] * w.z;
+                let normal = (normals[0] * w.x + normals[1] * w.y + normals[2] * w.z)
+                    .try_normalize()
+                    .unwrap_or_else(|| {
+                        (positions[1] - positions[0])
+                            .cross(positions[2] - positions[0])
+                            .normalize_or_zero()
+                    });
+
+                let origin = position + normal * RAY_BIAS;
+                let visible = (0..num_rays)
+                    .filter(|&i| {
+                        let direction =
+                            cosine_hemisphere_direction(hammersley(i, num_rays), normal);
+                        !is_occluded(&Ray::new(origin, direction), occluders)
+                    })
+                    .count();
+
+                let visibility = visible as f32 / num_rays.max(1) as f32;
+                image.put_pixel(
+                    x,
+                    y,
+                    Luma([(linear_to_srgb(visibility) * 255.0).round() as u8]),
+                );
+                covered[(y * resolution + x) as usize] = true;
+            }
+        }
+    }
+
+    for _ in 0..DILATION_PASSES {
+        dilate(&mut image, &mut covered);
+    }
+    image
+}
+
+/// Extends the covered texels by one texel, averaging the covered neighbours.
+fn dilate(image: &mut GrayImage, covered: &mut [bool]) {
+    let (width, height) = image.dimensions();
+    let source = image.clone();
+    let was_covered = covered.to_vec();
+
+    for y in 0..height {
+        for x in 0..width {
+            if was_covered[(y * width + x) as usize] {
+                continue;
+            }
+            let (mut sum, mut count) = (0u32, 0u32);
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                    continue;
+                }
+                let (nx, ny) = (nx as u32, ny as u32);
+                if was_covered[(ny * width + nx) as usize] {
+                    sum += source.get_pixel(nx, ny)[0] as u32;
+                    count += 1;
+                }
+            }
+            if let Some(average) = sum.checked_div(count) {
+                image.put_pixel(x, y, Luma([average as u8]));
+                covered[(y * width + x) as usize] = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::Aabb, vertex::Vertex};
+
+    /// Square in the XZ plane at height `y`, facing up or down, mapped to the whole UV space.
+    fn quad(y: f32, facing_up: bool) -> Mesh {
+        let normal = if facing_up { 1.0 } else { -1.0 };
+        let vertices = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+            .map(|(u, v)| Vertex {
+                position: [u * 2.0 - 1.0, y, v * 2.0 - 1.0],
+                color: [1.0; 3],
+                tex_coords: [u, v],
+                normal: [0.0, normal, 0.0],
+                tangent: [1.0, 0.0, 0.0, 1.0],
+                tex_coords2: [0.0; 2],
+            })
+            .to_vec();
+        let aabb = Aabb::from_points(vertices.iter().map(|v| Vec3::from(v.position)));
+        Mesh {
+            name: "quad".into(),
+            vertices,
+            indices: vec![0, 1, 2, 0, 2, 3],
+            material_id: 0,
+            aabb,
+        }
+    }
+
+    #[test]
+    fn test_cosine_hemisphere_direction() {
+        let normal = Vec3::new(1.0, 2.0, -0.5).normalize();
+        for i in 0..64 {
+            let d = cosine_hemisphere_direction(hammersley(i, 64), normal);
+            assert!((d.length() - 1.0).abs() < 1e-4);
+            assert!(d.dot(normal) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn test_bake_open_quad_is_unoccluded() {
+        let floor = quad(0.0, true);
+        let image = bake_ambient_occlusion(&floor, std::slice::from_ref(&floor), 8, 32);
+        assert!(image.pixels().all(|p| p[0] == 255));
+    }
+
+    #[test]
+    fn test_bake_covered_quad_is_occluded() {
+        let floor = quad(0.0, true);
+        // A lid close above blocks nearly every ray in the middle of the floor
+        let lid = quad(0.05, false);
+        let image = bake_ambient_occlusion(&floor, &[quad(0.0, true), lid], 8, 32);
+        assert!(image.get_pixel(4, 4)[0] < 100);
+        assert!(image.get_pixel(4, 4)[0] < image.get_pixel(0, 0)[0]);
+    }
+}
@@ -0,0 +1,280 @@
+// Bounding volume hierarchy over the triangles of a model, in the model's local space.
+// Picking traverses it per instance instead of testing every triangle.
+
+use crate::{camera::Ray, models::Aabb, models::Mesh};
+use glam::Vec3;
+
+pub type Triangle = (Vec3, Vec3, Vec3);
+
+/// Leaves hold at most this many triangles, past that a split is always attempted.
+const MAX_LEAF_TRIANGLES: usize = 4;
+/// Candidate split planes per axis when evaluating the surface area heuristic.
+const SAH_BINS: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BvhNode {
+    pub aabb: Aabb,
+    /// Child node indices, only meaningful for inner nodes
+    pub left: u32,
+    pub right: u32,
+    /// Range of `Bvh::triangles` owned by a leaf, `tri_count` is 0 for inner nodes
+    pub tri_start: u32,
+    pub tri_count: u32,
+}
+
+impl BvhNode {
+    pub fn is_leaf(&self) -> bool {
+        self.tri_count > 0
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    /// The root is the first node, empty when there are no triangles
+    nodes: Vec<BvhNode>,
+    /// Input triangles, reordered so that every leaf owns a contiguous range
+    triangles: Vec<Triangle>,
+}
+
+impl Bvh {
+    /// Builds the hierarchy top-down, splitting where the surface area heuristic is lowest.
+    pub fn build(triangles: &[Triangle]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * triangles.len().div_ceil(MAX_LEAF_TRIANGLES)),
+            triangles: triangles.to_vec(),
+        };
+        if !triangles.is_empty() {
+            bvh.build_node(0, triangles.len());
+        }
+        bvh
+    }
+
+    /// One hierarchy over the triangles of every mesh.
+    pub fn from_meshes(meshes: &[Mesh]) -> Self {
+        let triangles: Vec<Triangle> = meshes
+            .iter()
+            .flat_map(|mesh| {
+                mesh.indices.chunks_exact(3).map(|tri| {
+                    let [v0, v1, v2] =
+                        [tri[0], tri[1], tri[2]].map(|i| mesh.vertices[i as usize].position.into());
+                    (v0, v1, v2)
+                })
+            })
+            .collect();
+        Self::build(&triangles)
+    }
+
+    pub fn nodes(&self) -> &[BvhNode] {
+        &self.nodes
+    }
+
+    pub fn triangles(&self) -> &[Triangle] {
+        &self.triangles
+    }
+
+    /// Distance to the closest triangle hit by `ray`, both faces count.
+    pub fn intersect(&self, ray: &Ray) -> Option<f32> {
+        let mut closest: Option<f32> = None;
+        let mut stack = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index as usize];
+            let Some(t_box) = ray.intersect_aabb(&node.aabb) else {
+                continue;
+            };
+            if closest.is_some_and(|best| t_box > best) {
+                continue;
+            }
+
+            if node.is_leaf() {
+                let range = node.tri_start as usize..(node.tri_start + node.tri_count) as usize;
+                for &(v0, v1, v2) in &self.triangles[range] {
+                    if let Some(t) = ray.intersect_triangle(v0, v1, v2)
+                        && closest.is_none_or(|best| t < best)
+                    {
+                        closest = Some(t);
+                    }
+                }
+            } else {
+                stack.push(node.right);
+                stack.push(node.left);
+            }
+        }
+
+        closest
+    }
+
+    /// Builds the node for `triangles[start..start + count]` and returns its index.
+    fn build_node(&mut self, start: usize, count: usize) -> u32 {
+        let tris = &self.triangles[start..start + count];
+        let aabb = Aabb::from_points(tris.iter().flat_map(|&(a, b, c)| [a, b, c]));
+        let index = self.nodes.len() as u32;
+        self.nodes.push(BvhNode {
+            aabb,
+            left: 0,
+            right: 0,
+            tri_start: start as u32,
+            tri_count: count as u32,
+        });
+
+        if count <= MAX_LEAF_TRIANGLES {
+            return index;
+        }
+        let Some((axis, split)) = self.find_split(start, count, &aabb) else {
+            return index;
+        };
+
+        // Partition in place around the chosen plane
+        let tris = &mut self.triangles[start..start + count];
+        let mut left_count = 0;
+        for i in 0..count {
+            if centroid(&tris[i])[axis] < split {
+                tris.swap(i, left_count);
+                left_count += 1;
+            }
+        }
+        if left_count == 0 || left_count == count {
+            return index;
+        }
+
+        let left = self.build_node(start, left_count);
+        let right = self.build_node(start + left_count, count - left_count);
+        let node = &mut self.nodes[index as usize];
+        node.left = left;
+        node.right = right;
+        node.tri_count = 0;
+        index
+    }
+
+    /// Binned SAH: the axis and centroid coordinate of the cheapest split, `None` when
+    /// keeping all the triangles in a single leaf is cheaper.
+    fn find_split(&self, start: usize, count: usize, aabb: &Aabb) -> Option<(usize, f32)> {
+        let tris = &self.triangles[start..start + count];
+        let centroids = Aabb::from_points(tris.iter().map(centroid));
+        let leaf_cost = surface_area(aabb) * count as f32;
+        let mut best: Option<(usize, f32, f32)> = None;
+
+        for axis in 0..3 {
+            let (min, max) = (centroids.min[axis], centroids.max[axis]);
+            let extent = max - min;
+            if extent <= 0.0 {
+                continue;
+            }
+
+            let mut bins: [(Option<Aabb>, usize); SAH_BINS] = [(None, 0); SAH_BINS];
+            for tri in tris {
+                let bin = (((centroid(tri)[axis] - min) / extent * SAH_BINS as f32) as usize)
+                    .min(SAH_BINS - 1);
+                let tri_aabb = Aabb::from_points([tri.0, tri.1, tri.2]);
+                let (bounds, n) = &mut bins[bin];
+                *bounds = Some(bounds.map_or(tri_aabb, |b| b.expand_to_include(&tri_aabb)));
+                *n += 1;
+            }
+
+            // Split after bin `i`: everything in bins 0..=i goes left
+            for i in 0..SAH_BINS - 1 {
+                let (left, right) = bins.split_at(i + 1);
+                let cost = side_cost(left) + side_cost(right);
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    let split = min + extent * (i + 1) as f32 / SAH_BINS as f32;
+                    best = Some((axis, split, cost));
+                }
+            }
+        }
+
+        best.filter(|&(_, _, cost)| cost < leaf_cost)
+            .map(|(axis, split, _)| (axis, split))
+    }
+}
+
+fn centroid((a, b, c): &Triangle) -> Vec3 {
+    (*a + *b + *c) / 3.0
+}
+
+fn surface_area(aabb: &Aabb) -> f32 {
+    let size = aabb.max - aabb.min;
+    2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+}
+
+/// Surface area of the bins' union times their triangle count.
+fn side_cost(bins: &[(Option<Aabb>, usize)]) -> f32 {
+    let (bounds, count) = bins
+        .iter()
+        .fold((None::<Aabb>, 0), |(acc, total), (bounds, n)| {
+            let acc = match (acc, bounds) {
+                (Some(a), Some(b)) => Some(a.expand_to_include(b)),
+                (a, b) => a.or(*b),
+            };
+            (acc, total + n)
+        });
+    bounds.map_or(0.0, |b| surface_area(&b) * count as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two triangles per cell of a `size` x `size` grid on the XZ plane.
+    fn grid(size: usize) -> Vec<Triangle> {
+        (0..size * size)
+            .flat_map(|i| {
+                let (x, z) = ((i % size) as f32, (i / size) as f32);
+                let [a, b, c, d] = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+                    .map(|(dx, dz)| Vec3::new(x + dx, 0.0, z + dz));
+                [(a, b, c), (a, c, d)]
+            })
+            .collect()
+    }
+
+    fn brute_force(triangles: &[Triangle], ray: &Ray) -> Option<f32> {
+        triangles
+            .iter()
+            .filter_map(|&(a, b, c)| ray.intersect_triangle(a, b, c))
+            .min_by(f32::total_cmp)
+    }
+
+    #[test]
+    fn test_bvh_leaves_cover_every_triangle() {
+        let triangles = grid(16);
+        let bvh = Bvh::build(&triangles);
+
+        let leaves: Vec<_> = bvh.nodes().iter().filter(|n| n.is_leaf()).collect();
+        assert!(leaves.len() > 1);
+        let covered: u32 = leaves.iter().map(|n| n.tri_count).sum();
+        assert_eq!(covered as usize, triangles.len());
+        assert!(
+            leaves
+                .iter()
+                .all(|n| n.tri_count as usize <= MAX_LEAF_TRIANGLES)
+        );
+        assert_eq!(bvh.triangles().len(), triangles.len());
+    }
+
+    #[test]
+    fn test_bvh_matches_brute_force() {
+        // Two stacked grids so that the closest hit matters
+        let mut triangles = grid(8);
+        triangles.extend(grid(8).into_iter().map(|(a, b, c)| {
+            let up = Vec3::Y * 2.0;
+            (a + up, b + up, c + up)
+        }));
+        let bvh = Bvh::build(&triangles);
+
+        for (x, z) in [(0.5, 0.5), (3.2, 7.9), (7.5, 1.1), (-1.0, 2.0), (9.0, 9.0)] {
+            let ray = Ray::new(Vec3::new(x, 5.0, z), Vec3::new(0.1, -1.0, 0.05));
+            assert_eq!(bvh.intersect(&ray), brute_force(&triangles, &ray));
+        }
+        let from_below = Ray::new(Vec3::new(4.5, -1.0, 4.5), Vec3::Y);
+        assert_eq!(bvh.intersect(&from_below), Some(1.0));
+    }
+
+    #[test]
+    fn test_bvh_empty() {
+        let bvh = Bvh::build(&[]);
+        assert!(bvh.nodes().is_empty());
+        assert_eq!(bvh.intersect(&Ray::new(Vec3::ZERO, Vec3::Y)), None);
+    }
+}
@@ -1,21 +1,259 @@
+use crate::{
+    keybindings::{KeyAction, KeyBindings},
+    models::Aabb,
+};
+use std::time::{Duration, Instant};
 use winit::event::ElementState;
 use winit::keyboard::KeyCode;
 
+/// How the camera maps view space to clip space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// `fovy` is the vertical field of view in radians
+    Perspective { fovy: f32, znear: f32, zfar: f32 },
+    /// `width` and `height` are the size of the view volume in world units
+    Orthographic {
+        width: f32,
+        height: f32,
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+impl Projection {
+    pub fn znear(&self) -> f32 {
+        match *self {
+            Projection::Perspective { znear, .. } | Projection::Orthographic { znear, .. } => znear,
+        }
+    }
+
+    pub fn zfar(&self) -> f32 {
+        match *self {
+            Projection::Perspective { zfar, .. } | Projection::Orthographic { zfar, .. } => zfar,
+        }
+    }
+
+    /// Vertical field of view, `None` for an orthographic projection.
+    pub fn fovy(&self) -> Option<f32> {
+        match *self {
+            Projection::Perspective { fovy, .. } => Some(fovy),
+            Projection::Orthographic { .. } => None,
+        }
+    }
+
+    pub fn is_orthographic(&self) -> bool {
+        matches!(self, Projection::Orthographic { .. })
+    }
+}
+
+/// Narrowest field of view reachable with the optical zoom, in radians
+pub const MIN_FOVY: f32 = 5.0 * std::f32::consts::PI / 180.0;
+/// Widest field of view reachable with the optical zoom, in radians
+pub const MAX_FOVY: f32 = 120.0 * std::f32::consts::PI / 180.0;
+
 pub struct Camera {
     pub eye: glam::Vec3,
     pub target: glam::Vec3,
     pub up: glam::Vec3,
+    /// Width / height of the viewport, only used by the perspective projection
     pub aspect: f32,
-    pub fovy: f32,
-    pub znear: f32,
-    pub zfar: f32,
+    pub projection: Projection,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            eye: glam::Vec3::new(0.0, 2.0, 5.0),
+            target: glam::Vec3::ZERO,
+            up: glam::Vec3::Y,
+            aspect: 1.0,
+            projection: Projection::Perspective {
+                fovy: 45.0_f32.to_radians(),
+                znear: 0.1,
+                zfar: 1000.0,
+            },
+        }
+    }
 }
 
 impl Camera {
+    /// Default camera for a viewport of the given width / height ratio.
+    pub fn with_aspect(aspect: f32) -> Self {
+        Self {
+            aspect,
+            ..Default::default()
+        }
+    }
+
+    /// Default camera placed at `eye` and looking at `target`.
+    pub fn looking_at(eye: glam::Vec3, target: glam::Vec3) -> Self {
+        Self {
+            eye,
+            target,
+            ..Default::default()
+        }
+    }
+
+    /// Narrows (positive `lines`) or widens the field of view, keeping it within
+    /// `MIN_FOVY..=MAX_FOVY`. An orthographic view is shrunk or grown instead.
+    pub fn zoom_optical(&mut self, lines: f32) {
+        const FOVY_STEP: f32 = 2.0 * std::f32::consts::PI / 180.0;
+        match &mut self.projection {
+            Projection::Perspective { fovy, .. } => {
+                *fovy = (*fovy - lines * FOVY_STEP).clamp(MIN_FOVY, MAX_FOVY);
+            }
+            Projection::Orthographic { width, height, .. } => {
+                let scale = 0.9_f32.powf(lines);
+                *width *= scale;
+                *height *= scale;
+            }
+        }
+    }
+
     pub fn build_view_projection_matrix(&self) -> glam::Mat4 {
         let view = glam::Mat4::look_at_rh(self.eye, self.target, self.up);
-        let proj = glam::Mat4::perspective_rh(self.fovy, self.aspect, self.znear, self.zfar);
-        proj * view
+        self.build_projection_matrix() * view
+    }
+
+    /// View space to clip space, without the camera placement.
+    pub fn build_projection_matrix(&self) -> glam::Mat4 {
+        match self.projection {
+            Projection::Perspective { fovy, znear, zfar } => {
+                glam::Mat4::perspective_rh(fovy, self.aspect, znear, zfar)
+            }
+            Projection::Orthographic {
+                width,
+                height,
+                znear,
+                zfar,
+            } => glam::Mat4::orthographic_rh(
+                -width * 0.5,
+                width * 0.5,
+                -height * 0.5,
+                height * 0.5,
+                znear,
+                zfar,
+            ),
+        }
+    }
+
+    /// The six planes bounding what the camera sees (left, right, bottom, top, near, far) as
+    /// `(normal, distance)` with normalized inward-facing normals: a point `p` is inside
+    /// when `normal.dot(p) + distance >= 0` for every plane.
+    pub fn build_frustum_planes(&self) -> [glam::Vec4; 6] {
+        // Gribb & Hartmann: the planes are sums of the rows of the view-projection matrix,
+        // with wgpu's depth range of [0, 1]
+        let m = self.build_view_projection_matrix();
+        let (x, y, z, w) = (m.row(0), m.row(1), m.row(2), m.row(3));
+        [w + x, w - x, w + y, w - y, z, w - z].map(|plane| plane / plane.truncate().length())
+    }
+}
+
+/// Whether a box, given by its world-space center and half extents, touches the frustum
+/// `planes` from `Camera::build_frustum_planes`. Conservative: boxes near the corners of the
+/// frustum may pass without being visible.
+pub fn aabb_in_frustum(
+    planes: &[glam::Vec4; 6],
+    world_center: glam::Vec3,
+    half_extents: glam::Vec3,
+) -> bool {
+    planes.iter().all(|plane| {
+        let normal = plane.truncate();
+        // Distance from the center to the box corner furthest along the normal
+        let radius = normal.abs().dot(half_extents);
+        normal.dot(world_center) + plane.w >= -radius
+    })
+}
+
+/// A half-line used for picking. `direction` is not required to be normalized,
+/// distances returned by the intersection tests are expressed in multiples of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: glam::Vec3,
+    pub direction: glam::Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: glam::Vec3, direction: glam::Vec3) -> Self {
+        Self { origin, direction }
+    }
+
+    /// Ray starting on the near plane and going through `ndc` (x and y in [-1, 1]).
+    pub fn from_ndc(camera: &Camera, ndc: glam::Vec2) -> Self {
+        let inv_view_proj = camera.build_view_projection_matrix().inverse();
+        let near = inv_view_proj.project_point3(ndc.extend(0.0));
+        let far = inv_view_proj.project_point3(ndc.extend(1.0));
+        Self::new(near, (far - near).normalize())
+    }
+
+    pub fn at(&self, t: f32) -> glam::Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// Same ray expressed in another space, e.g. an instance's local space with
+    /// `model.inverse()`. The direction is not renormalized so `t` values stay comparable.
+    pub fn transform(&self, matrix: &glam::Mat4) -> Ray {
+        Ray::new(
+            matrix.transform_point3(self.origin),
+            matrix.transform_vector3(self.direction),
+        )
+    }
+
+    /// Slab test, returns the entry distance (0 if the origin is inside the box).
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let inv_dir = self.direction.recip();
+        let t1 = (aabb.min - self.origin) * inv_dir;
+        let t2 = (aabb.max - self.origin) * inv_dir;
+
+        let t_near = t1.min(t2).max_element();
+        let t_far = t1.max(t2).min_element();
+
+        (t_near <= t_far && t_far >= 0.0).then_some(t_near.max(0.0))
+    }
+
+    /// Distance to the plane through `point` facing `normal`, from either side. `None` when
+    /// parallel to it or when the plane is behind the origin.
+    pub fn intersect_plane(&self, point: glam::Vec3, normal: glam::Vec3) -> Option<f32> {
+        let denominator = self.direction.dot(normal);
+        if denominator.abs() < 1e-7 {
+            return None;
+        }
+        let t = (point - self.origin).dot(normal) / denominator;
+        (t >= 0.0).then_some(t)
+    }
+
+    /// Möller–Trumbore ray/triangle intersection, both faces are hit.
+    pub fn intersect_triangle(
+        &self,
+        v0: glam::Vec3,
+        v1: glam::Vec3,
+        v2: glam::Vec3,
+    ) -> Option<f32> {
+        const EPSILON: f32 = 1e-7;
+
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+        let p = self.direction.cross(edge2);
+        let det = edge1.dot(p);
+        if det.abs() < EPSILON {
+            return None; // Parallel to the triangle
+        }
+
+        let inv_det = 1.0 / det;
+        let s = self.origin - v0;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(q) * inv_det;
+        (t > EPSILON).then_some(t)
     }
 }
 
@@ -24,6 +262,14 @@ impl Camera {
 pub struct CameraUniform {
     view_proj: [[f32; 4]; 4],
     view_pos: [f32; 4],
+    /// `view_proj` of the previous frame, to compute the screen-space velocity
+    prev_view_proj: [[f32; 4]; 4],
+}
+
+impl Default for CameraUniform {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CameraUniform {
@@ -32,14 +278,98 @@ impl CameraUniform {
         Self {
             view_proj: Mat4::IDENTITY.to_cols_array_2d(),
             view_pos: [0.0; 4],
+            prev_view_proj: Mat4::IDENTITY.to_cols_array_2d(),
         }
     }
 
+    /// Copies the camera matrices into the uniform. This is the only way to feed it:
+    /// animate the `Camera` (e.g. through a `CameraController`), then call this.
     pub fn update_view_proj(&mut self, camera: &Camera) {
         self.view_proj = camera.build_view_projection_matrix().to_cols_array_2d();
         // We use [x, y, z, 1.0] to align with 16 bytes (vec4)
         self.view_pos = [camera.eye.x, camera.eye.y, camera.eye.z, 1.0];
     }
+
+    /// Shifts the projected image by `offset` in NDC, used for the sub-pixel jitter of TAA.
+    /// Must be called after `update_view_proj`, which resets it.
+    pub fn apply_jitter(&mut self, offset: glam::Vec2) {
+        self.apply_clip_transform(glam::Mat4::from_translation(offset.extend(0.0)));
+    }
+
+    /// Applies `transform` after the projection, in clip space.
+    pub fn apply_clip_transform(&mut self, transform: glam::Mat4) {
+        self.view_proj = (transform * self.view_proj()).to_cols_array_2d();
+    }
+
+    pub fn view_proj(&self) -> glam::Mat4 {
+        glam::Mat4::from_cols_array_2d(&self.view_proj)
+    }
+
+    /// Keeps the current `view_proj` as the previous frame's. Call once per frame, before
+    /// `update_view_proj`.
+    pub fn store_previous_view_proj(&mut self) {
+        self.prev_view_proj = self.view_proj;
+    }
+
+    pub fn prev_view_proj(&self) -> glam::Mat4 {
+        glam::Mat4::from_cols_array_2d(&self.prev_view_proj)
+    }
+}
+
+/// How long restoring a `CameraBookmark` takes
+pub const CAMERA_TRANSITION_DURATION: Duration = Duration::from_millis(500);
+
+/// A saved view of the scene.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraBookmark {
+    pub name: String,
+    pub eye: glam::Vec3,
+    pub target: glam::Vec3,
+}
+
+impl CameraBookmark {
+    pub fn from_camera(name: impl Into<String>, camera: &Camera) -> Self {
+        Self {
+            name: name.into(),
+            eye: camera.eye,
+            target: camera.target,
+        }
+    }
+}
+
+/// Timed move of the camera to another view: the eye and the distance to the target are
+/// interpolated linearly, the view direction spherically.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraTransition {
+    pub from_eye: glam::Vec3,
+    pub from_target: glam::Vec3,
+    pub to_eye: glam::Vec3,
+    pub to_target: glam::Vec3,
+    pub start: Instant,
+    pub duration: Duration,
+}
+
+impl CameraTransition {
+    /// Eye and target at `t` in [0, 1], eased in and out.
+    pub fn sample(&self, t: f32) -> (glam::Vec3, glam::Vec3) {
+        let t = t.clamp(0.0, 1.0);
+        let t = t * t * (3.0 - 2.0 * t);
+        let from = self.from_target - self.from_eye;
+        let to = self.to_target - self.to_eye;
+        let turn = glam::Quat::from_rotation_arc(from.normalize_or_zero(), to.normalize_or_zero());
+        let direction = glam::Quat::IDENTITY.slerp(turn, t) * from.normalize_or_zero();
+        let eye = self.from_eye.lerp(self.to_eye, t);
+        let distance = from.length() + (to.length() - from.length()) * t;
+        (eye, eye + direction * distance)
+    }
+
+    /// Fraction of the duration elapsed at `now`, 1 once over.
+    pub fn progress(&self, now: Instant) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        (now.duration_since(self.start).as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+    }
 }
 
 pub struct CameraController {
@@ -53,6 +383,20 @@ pub struct CameraController {
     yaw: f32,
     pitch: f32,
     mouse_sensitivity: f32,
+    /// World units moved per scroll line
+    zoom_speed: f32,
+    /// Scroll lines received since the last `update_camera`, moving or changing the FOV
+    pending_dolly: f32,
+    pending_optical_zoom: f32,
+    /// Running move to a bookmark, which takes over the camera until it ends
+    transition: Option<CameraTransition>,
+}
+
+impl Default for CameraController {
+    /// `speed` is applied once per `update_camera` call, i.e. per frame.
+    fn default() -> Self {
+        Self::new(0.05)
+    }
 }
 
 impl CameraController {
@@ -68,41 +412,45 @@ impl CameraController {
             yaw: -90.0_f32.to_radians(),
             pitch: 0.0,
             mouse_sensitivity: 0.003,
+            zoom_speed: 0.5,
+            pending_dolly: 0.0,
+            pending_optical_zoom: 0.0,
+            transition: None,
         }
     }
 
-    pub fn process_keyboard(&mut self, keycode: KeyCode, state: ElementState) -> bool {
+    /// Builder-style alternative to `new`: `CameraController::default().with_speed(0.1)`.
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Moves with the keys of `bindings`, or the arrow keys.
+    pub fn process_keyboard(
+        &mut self,
+        keycode: KeyCode,
+        state: ElementState,
+        bindings: &KeyBindings,
+    ) -> bool {
         let is_pressed = state == ElementState::Pressed;
-        match keycode {
-            // ZQSD (AZERTY) physically corresponds to WASD (QWERTY)
-            // So KeyW = Z, KeyA = Q, KeyS = S, KeyD = D
-            KeyCode::KeyW | KeyCode::ArrowUp => {
-                self.is_forward_pressed = is_pressed;
-                true
-            }
-            KeyCode::KeyA | KeyCode::ArrowLeft => {
-                // Key 'Q' on AZERTY (left)
-                self.is_left_pressed = is_pressed;
-                true
-            }
-            KeyCode::KeyS | KeyCode::ArrowDown => {
-                self.is_backward_pressed = is_pressed;
-                true
-            }
-            KeyCode::KeyD | KeyCode::ArrowRight => {
-                self.is_right_pressed = is_pressed;
-                true
-            }
-            KeyCode::KeyQ => {
-                self.is_up_pressed = is_pressed;
-                true
-            }
-            KeyCode::KeyE => {
-                self.is_down_pressed = is_pressed;
-                true
-            }
-            _ => false,
-        }
+        let action = match keycode {
+            KeyCode::ArrowUp => Some(KeyAction::Forward),
+            KeyCode::ArrowLeft => Some(KeyAction::Left),
+            KeyCode::ArrowDown => Some(KeyAction::Backward),
+            KeyCode::ArrowRight => Some(KeyAction::Right),
+            _ => bindings.action(keycode),
+        };
+        let pressed = match action {
+            Some(KeyAction::Forward) => &mut self.is_forward_pressed,
+            Some(KeyAction::Backward) => &mut self.is_backward_pressed,
+            Some(KeyAction::Left) => &mut self.is_left_pressed,
+            Some(KeyAction::Right) => &mut self.is_right_pressed,
+            Some(KeyAction::Up) => &mut self.is_up_pressed,
+            Some(KeyAction::Down) => &mut self.is_down_pressed,
+            Some(KeyAction::Screenshot) | None => return false,
+        };
+        *pressed = is_pressed;
+        true
     }
 
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
@@ -111,7 +459,51 @@ impl CameraController {
         self.pitch = self.pitch.clamp(-1.54, 1.54);
     }
 
+    /// Positive `lines` (scrolling up) zoom in, by moving forward or, with `optical`, by
+    /// narrowing the field of view. Applied by the next `update_camera`.
+    pub fn process_scroll(&mut self, lines: f32, optical: bool) {
+        if optical {
+            self.pending_optical_zoom += lines;
+        } else {
+            self.pending_dolly += lines;
+        }
+    }
+
+    /// Points the controller where `camera` currently looks, so that taking over from
+    /// another controller does not make the view jump.
+    pub fn look_along(&mut self, camera: &Camera) {
+        (self.yaw, self.pitch) = yaw_pitch(camera.target - camera.eye);
+    }
+
+    /// Moves `camera` from its current view to `eye` looking at `target` over
+    /// `CAMERA_TRANSITION_DURATION`, through `update_camera`.
+    pub fn start_transition(&mut self, camera: &Camera, eye: glam::Vec3, target: glam::Vec3) {
+        self.transition = Some(CameraTransition {
+            from_eye: camera.eye,
+            from_target: camera.target,
+            to_eye: eye,
+            to_target: target,
+            start: Instant::now(),
+            duration: CAMERA_TRANSITION_DURATION,
+        });
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.transition.is_some()
+    }
+
     pub fn update_camera(&mut self, camera: &mut Camera) {
+        if let Some(transition) = self.transition {
+            let t = transition.progress(Instant::now());
+            (camera.eye, camera.target) = transition.sample(t);
+            if t >= 1.0 {
+                self.transition = None;
+                // The keys take over from the bookmarked view
+                self.look_along(camera);
+            }
+            return;
+        }
+
         // 1. Recalculate orientation
         let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
         let (pitch_sin, pitch_cos) = self.pitch.sin_cos();
@@ -152,5 +544,338 @@ impl CameraController {
             camera.eye += glam::Vec3::Y * self.speed;
             camera.target += glam::Vec3::Y * self.speed;
         }
+
+        // 3. Scroll zoom
+        let dolly = std::mem::take(&mut self.pending_dolly) * self.zoom_speed;
+        camera.eye += forward_norm * dolly;
+        camera.target += forward_norm * dolly;
+
+        let optical_zoom = std::mem::take(&mut self.pending_optical_zoom);
+        if optical_zoom != 0.0 {
+            camera.zoom_optical(optical_zoom);
+        }
+    }
+}
+
+/// Yaw and pitch of a direction, in the convention of `CameraController`.
+fn yaw_pitch(direction: glam::Vec3) -> (f32, f32) {
+    let direction = direction.normalize_or_zero();
+    (
+        direction.z.atan2(direction.x),
+        direction.y.clamp(-1.0, 1.0).asin().clamp(-1.54, 1.54),
+    )
+}
+
+/// Inspection controller turning around `target`: drag to orbit, pan the target,
+/// scroll to zoom, then dolly past the target once the closest distance is reached. `yaw`
+/// and `pitch` give the direction the camera looks in, like for `CameraController`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitController {
+    pub target: glam::Vec3,
+    /// Distance from the camera to `target`
+    pub radius: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// World units per pixel of drag, for each unit of `radius`
+    pub pan_sensitivity: f32,
+    /// Fraction of `radius` gained or lost per scroll line
+    pub zoom_sensitivity: f32,
+    /// Closest `zoom` gets to the target, twice the near plane
+    pub min_radius: f32,
+    /// Farthest `zoom` gets from the target, half the far plane
+    pub max_radius: f32,
+}
+
+impl OrbitController {
+    /// Radians per pixel of drag
+    const ROTATE_SENSITIVITY: f32 = 0.005;
+    /// Keeps the view direction defined when orbiting from a camera on its target
+    const MIN_RADIUS: f32 = 0.05;
+
+    /// Zoom limits follow the default camera's clipping planes.
+    pub fn new(target: glam::Vec3, radius: f32) -> Self {
+        let projection = Camera::default().projection;
+        Self {
+            target,
+            radius: radius.max(Self::MIN_RADIUS),
+            yaw: -90.0_f32.to_radians(),
+            pitch: 0.0,
+            pan_sensitivity: 0.002,
+            zoom_sensitivity: 0.1,
+            min_radius: projection.znear() * 2.0,
+            max_radius: projection.zfar() * 0.5,
+        }
+    }
+
+    /// Orbits around `target` keeping the current view of `camera`, zooming within its
+    /// clipping planes.
+    pub fn from_camera(camera: &Camera, target: glam::Vec3) -> Self {
+        let (yaw, pitch) = yaw_pitch(target - camera.eye);
+        Self {
+            yaw,
+            pitch,
+            min_radius: camera.projection.znear() * 2.0,
+            max_radius: camera.projection.zfar() * 0.5,
+            ..Self::new(target, camera.eye.distance(target))
+        }
+    }
+
+    fn forward(&self) -> glam::Vec3 {
+        let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
+        let (pitch_sin, pitch_cos) = self.pitch.sin_cos();
+        glam::Vec3::new(yaw_cos * pitch_cos, pitch_sin, yaw_sin * pitch_cos)
+    }
+
+    pub fn rotate(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        self.yaw += mouse_dx as f32 * Self::ROTATE_SENSITIVITY;
+        self.pitch -= mouse_dy as f32 * Self::ROTATE_SENSITIVITY;
+        self.pitch = self.pitch.clamp(-1.54, 1.54);
+    }
+
+    /// Moves the target in the view plane, following the cursor.
+    pub fn pan(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        let forward = self.forward();
+        let right = forward.cross(glam::Vec3::Y).normalize();
+        let up = right.cross(forward);
+        let scale = self.pan_sensitivity * self.radius;
+        self.target += (up * mouse_dy as f32 - right * mouse_dx as f32) * scale;
+    }
+
+    /// Positive `lines` (scrolling up) move closer, by a fraction of `radius` per line.
+    /// Past `min_radius`, each line pushes the target `min_radius` further instead, so the
+    /// camera keeps moving forward.
+    pub fn zoom(&mut self, lines: f32) {
+        let factor = 1.0 - self.zoom_sensitivity;
+        let radius = self.radius * factor.powf(lines);
+        if lines > 0.0 && radius < self.min_radius {
+            let lines_to_min = ((self.min_radius / self.radius).ln() / factor.ln()).max(0.0);
+            self.target += self.forward() * (lines - lines_to_min) * self.min_radius;
+        }
+        self.radius = radius.clamp(self.min_radius, self.max_radius);
+    }
+
+    pub fn update_camera(&self, camera: &mut Camera) {
+        camera.target = self.target;
+        camera.eye = self.target - self.forward() * self.radius;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    fn test_aabb_in_frustum() {
+        let camera = Camera::looking_at(glam::Vec3::ZERO, glam::Vec3::NEG_Z);
+        let planes = camera.build_frustum_planes();
+        let visible = |center| aabb_in_frustum(&planes, center, glam::Vec3::splat(0.5));
+
+        assert!(visible(glam::Vec3::new(0.0, 0.0, -5.0)));
+        // Behind the camera, beyond the far plane, far off to the side
+        assert!(!visible(glam::Vec3::new(0.0, 0.0, 5.0)));
+        assert!(!visible(glam::Vec3::new(0.0, 0.0, -2000.0)));
+        assert!(!visible(glam::Vec3::new(50.0, 0.0, -5.0)));
+        // Straddling the near plane
+        assert!(visible(glam::Vec3::ZERO));
+    }
+
+    #[test]
+    fn test_ray_intersect_aabb() {
+        let aabb = Aabb::new(Vec3::splat(-1.0), Vec3::splat(1.0));
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::NEG_Z);
+        assert_eq!(ray.intersect_aabb(&aabb), Some(4.0));
+
+        let inside = Ray::new(Vec3::ZERO, Vec3::X);
+        assert_eq!(inside.intersect_aabb(&aabb), Some(0.0));
+
+        let miss = Ray::new(Vec3::new(3.0, 0.0, 5.0), Vec3::NEG_Z);
+        assert_eq!(miss.intersect_aabb(&aabb), None);
+
+        let behind = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::Z);
+        assert_eq!(behind.intersect_aabb(&aabb), None);
+    }
+
+    #[test]
+    fn test_ray_intersect_triangle() {
+        let (v0, v1, v2) = (Vec3::ZERO, Vec3::X, Vec3::Y);
+
+        let ray = Ray::new(Vec3::new(0.25, 0.25, 2.0), Vec3::NEG_Z);
+        let t = ray.intersect_triangle(v0, v1, v2).unwrap();
+        assert!((t - 2.0).abs() < 1e-6);
+
+        let outside = Ray::new(Vec3::new(1.0, 1.0, 2.0), Vec3::NEG_Z);
+        assert_eq!(outside.intersect_triangle(v0, v1, v2), None);
+
+        let parallel = Ray::new(Vec3::new(0.25, 0.25, 2.0), Vec3::X);
+        assert_eq!(parallel.intersect_triangle(v0, v1, v2), None);
+    }
+
+    #[test]
+    fn test_ray_intersect_plane() {
+        let ray = Ray::new(Vec3::new(1.0, 3.0, 0.0), Vec3::NEG_Y);
+        // Either side of the plane
+        assert_eq!(ray.intersect_plane(Vec3::Y, Vec3::Y), Some(2.0));
+        assert_eq!(ray.intersect_plane(Vec3::Y, Vec3::NEG_Y), Some(2.0));
+        assert_eq!(ray.intersect_plane(Vec3::Y * 4.0, Vec3::Y), None);
+        assert_eq!(ray.intersect_plane(Vec3::ZERO, Vec3::X), None);
+    }
+
+    #[test]
+    fn test_ray_from_ndc_center_points_at_target() {
+        let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO);
+        let ray = Ray::from_ndc(&camera, glam::Vec2::ZERO);
+        assert!((ray.direction - Vec3::NEG_Z).length() < 1e-4);
+    }
+
+    #[test]
+    fn test_camera_transition_ends_on_bookmark() {
+        let transition = CameraTransition {
+            from_eye: Vec3::new(0.0, 0.0, 5.0),
+            from_target: Vec3::ZERO,
+            to_eye: Vec3::new(10.0, 0.0, 0.0),
+            to_target: Vec3::new(10.0, 0.0, -2.0),
+            start: Instant::now(),
+            duration: CAMERA_TRANSITION_DURATION,
+        };
+        assert_eq!(
+            transition.sample(0.0),
+            (transition.from_eye, transition.from_target)
+        );
+        let (eye, target) = transition.sample(1.0);
+        assert!(eye.distance(transition.to_eye) < 1e-5);
+        assert!(target.distance(transition.to_target) < 1e-5);
+
+        // Halfway, still looking down -Z, at the average distance
+        let (eye, target) = transition.sample(0.5);
+        assert!(eye.distance(Vec3::new(5.0, 0.0, 2.5)) < 1e-5);
+        assert!((target - eye).distance(Vec3::new(0.0, 0.0, -3.5)) < 1e-5);
+
+        let later = transition.start + CAMERA_TRANSITION_DURATION * 2;
+        assert_eq!(transition.progress(later), 1.0);
+    }
+
+    #[test]
+    fn test_orbit_from_camera_keeps_view() {
+        let mut camera = Camera::looking_at(Vec3::new(3.0, 4.0, 5.0), Vec3::new(1.0, 0.0, 1.0));
+        let orbit = OrbitController::from_camera(&camera, camera.target);
+        assert!((orbit.radius - 6.0).abs() < 1e-5);
+
+        let eye = camera.eye;
+        orbit.update_camera(&mut camera);
+        assert!((camera.eye - eye).length() < 1e-4);
+    }
+
+    #[test]
+    fn test_orbit_rotate_and_zoom_keep_target() {
+        let mut orbit = OrbitController::new(Vec3::new(1.0, 2.0, 3.0), 10.0);
+        let mut camera = Camera::default();
+
+        orbit.rotate(120.0, -40.0);
+        orbit.zoom(2.0);
+        orbit.update_camera(&mut camera);
+        assert_eq!(camera.target, orbit.target);
+        assert!((camera.eye.distance(orbit.target) - 8.1).abs() < 1e-4);
+
+        orbit.zoom(100.0);
+        assert_eq!(orbit.radius, orbit.min_radius);
+        orbit.zoom(-100.0);
+        assert_eq!(orbit.radius, orbit.max_radius);
+    }
+
+    #[test]
+    fn test_orbit_zoom_dollies_past_target() {
+        let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 1.0), Vec3::ZERO);
+        let mut orbit = OrbitController::from_camera(&camera, Vec3::ZERO);
+        assert_eq!(orbit.min_radius, camera.projection.znear() * 2.0);
+        assert_eq!(orbit.max_radius, camera.projection.zfar() * 0.5);
+
+        // Reaching the minimum takes about 15 lines, the rest moves the target forward
+        orbit.zoom(25.0);
+        assert_eq!(orbit.radius, orbit.min_radius);
+        assert!(orbit.target.z < -1.5 && orbit.target.z > -2.5);
+        assert!(orbit.target.x.abs() < 1e-5 && orbit.target.y.abs() < 1e-5);
+
+        let target = orbit.target;
+        orbit.zoom(-1.0);
+        assert_eq!(orbit.target, target);
+        assert!(orbit.radius > orbit.min_radius);
+    }
+
+    #[test]
+    fn test_orbit_pan_moves_in_view_plane() {
+        let mut orbit = OrbitController::new(Vec3::ZERO, 10.0);
+        orbit.pan(100.0, 0.0);
+        // Looking down -Z: dragging right moves the target to the left
+        assert!(orbit.target.x < 0.0);
+        assert!(orbit.target.y.abs() < 1e-5 && orbit.target.z.abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_fly_scroll_zoom() {
+        let mut camera = Camera::looking_at(Vec3::ZERO, Vec3::NEG_Z);
+        let mut controller = CameraController::new(0.05);
+        controller.look_along(&camera);
+
+        controller.process_scroll(2.0, false);
+        controller.update_camera(&mut camera);
+        assert!((camera.eye - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-5);
+
+        controller.process_scroll(1000.0, true);
+        controller.update_camera(&mut camera);
+        assert_eq!(camera.projection.fovy(), Some(MIN_FOVY));
+        // Applied once
+        controller.update_camera(&mut camera);
+        assert!((camera.eye - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-5);
+
+        camera.zoom_optical(-1000.0);
+        assert_eq!(camera.projection.fovy(), Some(MAX_FOVY));
+    }
+
+    #[test]
+    fn test_orthographic_rays_are_parallel() {
+        let camera = Camera {
+            projection: Projection::Orthographic {
+                width: 4.0,
+                height: 2.0,
+                znear: 0.1,
+                zfar: 100.0,
+            },
+            ..Camera::looking_at(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO)
+        };
+        let corner = Ray::from_ndc(&camera, glam::Vec2::ONE);
+        assert!((corner.direction - Vec3::NEG_Z).length() < 1e-4);
+        assert!((corner.at(10.0 - 0.1) - Vec3::new(2.0, 1.0, 0.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn test_camera_uniform_jitter_shifts_ndc() {
+        let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO);
+        let mut uniform = CameraUniform::new();
+        uniform.update_view_proj(&camera);
+        uniform.apply_jitter(glam::Vec2::new(0.01, -0.02));
+
+        let clip = uniform.view_proj() * Vec3::new(1.0, 1.0, 0.0).extend(1.0);
+        let expected = camera.build_view_projection_matrix() * Vec3::new(1.0, 1.0, 0.0).extend(1.0);
+        let offset =
+            clip.truncate().truncate() / clip.w - expected.truncate().truncate() / expected.w;
+        assert!((offset - glam::Vec2::new(0.01, -0.02)).length() < 1e-5);
+    }
+
+    #[test]
+    fn test_camera_uniform_keeps_previous_view_proj() {
+        let mut camera = Camera::looking_at(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO);
+        let mut uniform = CameraUniform::new();
+        uniform.update_view_proj(&camera);
+        let first = uniform.view_proj();
+
+        camera.eye.x += 1.0;
+        uniform.store_previous_view_proj();
+        uniform.update_view_proj(&camera);
+        assert_eq!(uniform.prev_view_proj(), first);
+        assert_ne!(uniform.view_proj(), first);
+        // Matches `CameraUniform` in shader.wgsl
+        assert_eq!(std::mem::size_of::<CameraUniform>(), 144);
     }
 }
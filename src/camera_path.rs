@@ -0,0 +1,186 @@
+// Fly-through animation of the camera: timed waypoints joined by Catmull-Rom splines, for
+// both the eye and the point it looks at.
+
+use crate::camera::Camera;
+use glam::Vec3;
+use std::time::Instant;
+
+/// Time between the last waypoint and one added after it from the editor, in seconds
+pub const CAMERA_WAYPOINT_INTERVAL: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraWaypoint {
+    /// Eye of the camera
+    pub position: Vec3,
+    pub target: Vec3,
+    /// From the start of the path
+    pub time_seconds: f32,
+}
+
+/// Waypoints sorted by time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CameraPath {
+    pub waypoints: Vec<CameraWaypoint>,
+}
+
+impl CameraPath {
+    pub fn new(mut waypoints: Vec<CameraWaypoint>) -> Self {
+        waypoints.sort_by(|a, b| a.time_seconds.total_cmp(&b.time_seconds));
+        Self { waypoints }
+    }
+
+    /// Inserts `waypoint` in time order, after those at the same time.
+    pub fn push(&mut self, waypoint: CameraWaypoint) {
+        let index = self
+            .waypoints
+            .partition_point(|w| w.time_seconds <= waypoint.time_seconds);
+        self.waypoints.insert(index, waypoint);
+    }
+
+    /// Time of the last waypoint, 0 for an empty path.
+    pub fn end_time(&self) -> f32 {
+        self.waypoints.last().map_or(0.0, |w| w.time_seconds)
+    }
+
+    /// A path needs two waypoints to move the camera.
+    pub fn is_playable(&self) -> bool {
+        self.waypoints.len() >= 2
+    }
+
+    /// Eye and target at `t` seconds, held at the first and last waypoints outside of the
+    /// path. An empty path gives the default camera.
+    pub fn sample(&self, t: f32) -> (Vec3, Vec3) {
+        let (Some(first), Some(last)) = (self.waypoints.first(), self.waypoints.last()) else {
+            let camera = Camera::default();
+            return (camera.eye, camera.target);
+        };
+        if t <= first.time_seconds {
+            return (first.position, first.target);
+        }
+        if t >= last.time_seconds {
+            return (last.position, last.target);
+        }
+
+        // Segment from waypoint i to i + 1, the outer control points repeat the ends
+        let i = self.waypoints.partition_point(|w| w.time_seconds <= t) - 1;
+        let point = |index: isize| {
+            let index = index.clamp(0, self.waypoints.len() as isize - 1);
+            self.waypoints[index as usize]
+        };
+        let [p0, p1, p2, p3] = [-1, 0, 1, 2].map(|offset| point(i as isize + offset));
+        let span = p2.time_seconds - p1.time_seconds;
+        let u = if span > 0.0 {
+            (t - p1.time_seconds) / span
+        } else {
+            1.0
+        };
+        (
+            catmull_rom(p0.position, p1.position, p2.position, p3.position, u),
+            catmull_rom(p0.target, p1.target, p2.target, p3.target, u),
+        )
+    }
+}
+
+/// Uniform Catmull-Rom spline between `p1` (at `u` = 0) and `p2` (at `u` = 1).
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, u: f32) -> Vec3 {
+    let u2 = u * u;
+    let u3 = u2 * u;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * u
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * u2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * u3)
+}
+
+/// A path being played, from the first waypoint when it started.
+#[derive(Debug, Clone)]
+pub struct CameraPathPlayback {
+    pub path: CameraPath,
+    pub start: Instant,
+}
+
+impl CameraPathPlayback {
+    pub fn new(path: CameraPath) -> Self {
+        Self {
+            path,
+            start: Instant::now(),
+        }
+    }
+
+    /// Eye and target at `now`, and whether the path is over.
+    pub fn sample(&self, now: Instant) -> ((Vec3, Vec3), bool) {
+        let first = self.path.waypoints.first().map_or(0.0, |w| w.time_seconds);
+        let t = first + now.duration_since(self.start).as_secs_f32();
+        (self.path.sample(t), t >= self.path.end_time())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn waypoint(x: f32, time_seconds: f32) -> CameraWaypoint {
+        CameraWaypoint {
+            position: Vec3::new(x, 1.0, 5.0),
+            target: Vec3::new(x, 0.0, 0.0),
+            time_seconds,
+        }
+    }
+
+    #[test]
+    fn test_camera_path_passes_through_waypoints() {
+        let path = CameraPath::new(vec![
+            waypoint(4.0, 3.0),
+            waypoint(0.0, 0.0),
+            waypoint(1.0, 1.0),
+        ]);
+        assert_eq!(path.waypoints[0].time_seconds, 0.0);
+        assert_eq!(path.end_time(), 3.0);
+
+        for w in &path.waypoints {
+            let (position, target) = path.sample(w.time_seconds);
+            assert!(position.distance(w.position) < 1e-5);
+            assert!(target.distance(w.target) < 1e-5);
+        }
+        // Held outside of the path
+        assert_eq!(path.sample(-1.0).0, path.waypoints[0].position);
+        assert_eq!(path.sample(10.0).0, path.waypoints[2].position);
+        // Smooth between them
+        let (position, _) = path.sample(2.0);
+        assert!(position.x > 1.0 && position.x < 4.0);
+    }
+
+    #[test]
+    fn test_catmull_rom_on_a_line() {
+        // Evenly spaced points on a line are followed at constant speed
+        let point = catmull_rom(Vec3::ZERO, Vec3::X, Vec3::X * 2.0, Vec3::X * 3.0, 0.25);
+        assert!(point.distance(Vec3::X * 1.25) < 1e-6);
+    }
+
+    #[test]
+    fn test_camera_path_push_keeps_order() {
+        let mut path = CameraPath::default();
+        assert!(!path.is_playable());
+        path.push(waypoint(0.0, 2.0));
+        path.push(waypoint(1.0, 0.0));
+        path.push(waypoint(2.0, 2.0));
+        let times: Vec<_> = path.waypoints.iter().map(|w| w.time_seconds).collect();
+        assert_eq!(times, [0.0, 2.0, 2.0]);
+        assert_eq!(path.waypoints[2].position.x, 2.0);
+        assert!(path.is_playable());
+    }
+
+    #[test]
+    fn test_camera_path_playback_ends() {
+        let playback = CameraPathPlayback::new(CameraPath::new(vec![
+            waypoint(0.0, 1.0),
+            waypoint(2.0, 3.0),
+        ]));
+        let (start, over) = playback.sample(playback.start);
+        assert_eq!(start.0, playback.path.waypoints[0].position);
+        assert!(!over);
+        let ((end, _), over) = playback.sample(playback.start + Duration::from_secs(2));
+        assert_eq!(end, playback.path.waypoints[1].position);
+        assert!(over);
+    }
+}
@@ -0,0 +1,197 @@
+// Chromatic aberration: the tone mapped image is rendered into `input_view()`, then copied to
+// the render target with its color channels shifted apart (chromatic_aberration.wgsl).
+
+use crate::{
+    post_process::{
+        create_fullscreen_pipeline, create_fullscreen_shader, run_fullscreen_pass,
+        sampler_layout_entry, texture_layout_entry, uniform_layout_entry,
+    },
+    textures::Texture,
+};
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct ChromaticAberrationUniform {
+    /// Offset of the red and blue channels at the middle of the screen edges, in texture
+    /// coordinates. 0 disables the effect
+    pub strength: f32,
+    pub _pad: [f32; 3],
+}
+
+impl ChromaticAberrationUniform {
+    pub fn new(strength: f32) -> Self {
+        Self {
+            strength,
+            _pad: [0.0; 3],
+        }
+    }
+}
+
+/// Post-process pass after tone mapping. It cannot read the texture it writes, so while
+/// enabled the passes before it render into `input_view()` instead of the render target.
+pub struct ChromaticAberration {
+    strength: f32,
+    format: wgpu::TextureFormat,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    input: Texture,
+}
+
+impl ChromaticAberration {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        target_format: wgpu::TextureFormat,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("chromatic_aberration_bind_group_layout"),
+            entries: &[
+                texture_layout_entry(0, wgpu::TextureSampleType::Float { filterable: true }),
+                sampler_layout_entry(1),
+                uniform_layout_entry(2),
+            ],
+        });
+        let shader = create_fullscreen_shader(
+            device,
+            "Chromatic Aberration Shader",
+            include_str!("../chromatic_aberration.wgsl"),
+        );
+        let pipeline = create_fullscreen_pipeline(
+            device,
+            "Chromatic Aberration Pipeline",
+            &[&bind_group_layout],
+            &shader,
+            "fs_chromatic_aberration",
+            target_format,
+            None,
+        );
+        // Clamped, the shifted channels of the border pixels stretch instead of wrapping
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Chromatic Aberration Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Chromatic Aberration Buffer"),
+            contents: bytemuck::cast_slice(&[ChromaticAberrationUniform::new(0.0)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let input = Self::create_input(device, config, target_format);
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &input,
+            &sampler,
+            &uniform_buffer,
+        );
+
+        Self {
+            strength: 0.0,
+            format: target_format,
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            sampler,
+            uniform_buffer,
+            input,
+        }
+    }
+
+    fn create_input(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+    ) -> Texture {
+        Texture::create_color_target(device, config, format, &[], "Chromatic Aberration Input")
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        input: &Texture,
+        sampler: &wgpu::Sampler,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("chromatic_aberration_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&input.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// The input texture follows the surface size.
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.input = Self::create_input(device, config, self.format);
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.input,
+            &self.sampler,
+            &self.uniform_buffer,
+        );
+    }
+
+    pub fn strength(&self) -> f32 {
+        self.strength
+    }
+
+    /// Whether the pass runs at all, the other passes keep their usual target otherwise.
+    pub fn enabled(&self) -> bool {
+        self.strength > 0.0
+    }
+
+    pub fn set(&mut self, queue: &wgpu::Queue, strength: f32) {
+        self.strength = strength;
+        let uniform = ChromaticAberrationUniform::new(strength);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    /// Where the tone mapped image goes while `enabled()`.
+    pub fn input_view(&self) -> &wgpu::TextureView {
+        &self.input.view
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        run_fullscreen_pass(
+            encoder,
+            "Chromatic Aberration Pass",
+            target,
+            None,
+            &self.pipeline,
+            &[&self.bind_group],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chromatic_aberration_uniform_layout() {
+        // Matches `ChromaticAberrationUniform` in chromatic_aberration.wgsl
+        assert_eq!(std::mem::size_of::<ChromaticAberrationUniform>(), 16);
+        assert_eq!(ChromaticAberrationUniform::new(0.01).strength, 0.01);
+    }
+}
@@ -0,0 +1,234 @@
+// Color grading: the tone mapped image is rendered into `input_view()`, then remapped through
+// a 3D lookup table into the next target (color_grade.wgsl).
+
+use crate::{
+    post_process::{
+        create_fullscreen_pipeline, create_fullscreen_shader, run_fullscreen_pass,
+        texture_layout_entry,
+    },
+    textures::Texture,
+};
+
+/// Post-process pass right after tone mapping. Always runs, with `Texture::identity_lut`
+/// until another LUT is loaded.
+pub struct ColorGrading {
+    format: wgpu::TextureFormat,
+    color_grade_pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    input: Texture,
+    lut: Texture,
+}
+
+impl ColorGrading {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        target_format: wgpu::TextureFormat,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("color_grade_bind_group_layout"),
+            entries: &[
+                texture_layout_entry(0, wgpu::TextureSampleType::Float { filterable: false }),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let shader = create_fullscreen_shader(
+            device,
+            "Color Grade Shader",
+            include_str!("../color_grade.wgsl"),
+        );
+        let color_grade_pipeline = create_fullscreen_pipeline(
+            device,
+            "Color Grade Pipeline",
+            &[&bind_group_layout],
+            &shader,
+            "fs_color_grade",
+            target_format,
+            None,
+        );
+        let input = Self::create_input(device, config, target_format);
+        let lut = Texture::identity_lut(device, queue);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &input, &lut);
+
+        Self {
+            format: target_format,
+            color_grade_pipeline,
+            bind_group_layout,
+            bind_group,
+            input,
+            lut,
+        }
+    }
+
+    fn create_input(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+    ) -> Texture {
+        Texture::create_color_target(device, config, format, &[], "Color Grade Input")
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        input: &Texture,
+        lut: &Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("color_grade_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&input.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&lut.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&lut.sampler),
+                },
+            ],
+        })
+    }
+
+    /// The input texture follows the surface size.
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.input = Self::create_input(device, config, self.format);
+        self.bind_group =
+            Self::create_bind_group(device, &self.bind_group_layout, &self.input, &self.lut);
+    }
+
+    /// Replaces the lookup table, a 3D texture such as `Texture::from_lut_image` creates.
+    pub fn set_lut(&mut self, device: &wgpu::Device, lut: Texture) {
+        self.lut = lut;
+        self.bind_group =
+            Self::create_bind_group(device, &self.bind_group_layout, &self.input, &self.lut);
+    }
+
+    /// Where the tone mapped image goes.
+    pub fn input_view(&self) -> &wgpu::TextureView {
+        &self.input.view
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        run_fullscreen_pass(
+            encoder,
+            "Color Grade Pass",
+            target,
+            None,
+            &self.color_grade_pipeline,
+            &[&self.bind_group],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_lut_keeps_colors() {
+        let (device, queue) = pollster::block_on(async {
+            let adapter = wgpu::Instance::new(wgpu::InstanceDescriptor::default())
+                .request_adapter(&wgpu::RequestAdapterOptions::default())
+                .await
+                .unwrap();
+            adapter
+                .request_device(&wgpu::DeviceDescriptor::default(), None)
+                .await
+                .unwrap()
+        });
+
+        // One row of 256 bytes, the copy alignment
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            width: 64,
+            height: 1,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        let grading = ColorGrading::new(&device, &queue, &config, config.format);
+        let target = Texture::create_render_target(&device, &config, "Render Target");
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 256,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let graded = |color: wgpu::Color| {
+            let mut encoder = device.create_command_encoder(&Default::default());
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: grading.input_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            grading.render(&mut encoder, &target.view);
+            encoder.copy_texture_to_buffer(
+                target.texture.as_image_copy(),
+                wgpu::ImageCopyBuffer {
+                    buffer: &readback,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(256),
+                        rows_per_image: None,
+                    },
+                },
+                target.texture.size(),
+            );
+            queue.submit(Some(encoder.finish()));
+
+            readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+            device.poll(wgpu::Maintain::Wait);
+            let texel: [u8; 4] = readback.slice(..).get_mapped_range()[..4]
+                .try_into()
+                .unwrap();
+            readback.unmap();
+            texel
+        };
+
+        let color = wgpu::Color {
+            r: 0.1,
+            g: 0.5,
+            b: 0.9,
+            a: 1.0,
+        };
+        let expected = [0.1, 0.5, 0.9_f32].map(|c| (c * 255.0).round() as i32);
+        let texel = graded(color);
+        for (channel, expected) in texel.iter().zip(expected) {
+            assert!((*channel as i32 - expected).abs() <= 2, "{texel:?}");
+        }
+    }
+}
@@ -0,0 +1,509 @@
+// Undoable editor operations on the instances, run through `State::execute`.
+
+use crate::{error::Result, instance::Instance, state::State};
+use glam::{Quat, Vec3};
+use std::collections::HashSet;
+
+/// World-space shift of the copies made by `DuplicateCommand`, so that they do not overlap
+/// their original
+pub const DUPLICATE_OFFSET: Vec3 = Vec3::splat(0.5);
+
+/// An edit of the scene that can be reverted. `undo` is only called right after `execute`
+/// (or after later commands were undone), on the state `execute` left.
+pub trait Command: Send {
+    fn execute(&mut self, state: &mut State) -> Result<()>;
+    fn undo(&mut self, state: &mut State) -> Result<()>;
+}
+
+/// History of the executed commands. Those before `cursor` are applied, those after it
+/// were undone and can be redone until a new command is executed.
+#[derive(Default)]
+pub struct CommandStack {
+    pub history: Vec<Box<dyn Command>>,
+    pub cursor: usize,
+}
+
+impl CommandStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.cursor > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.cursor < self.history.len()
+    }
+
+    /// Records a command that was just executed, dropping the undone ones.
+    pub fn push(&mut self, command: Box<dyn Command>) {
+        self.history.truncate(self.cursor);
+        self.history.push(command);
+        self.cursor = self.history.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.cursor = 0;
+    }
+}
+
+/// Adds an instance at the end of the scene.
+pub struct AddInstanceCommand {
+    /// Taken while the instance is in the scene
+    instance: Option<Instance>,
+    index: usize,
+}
+
+impl AddInstanceCommand {
+    pub fn new(instance: Instance) -> Self {
+        Self {
+            instance: Some(instance),
+            index: 0,
+        }
+    }
+}
+
+impl Command for AddInstanceCommand {
+    fn execute(&mut self, state: &mut State) -> Result<()> {
+        if let Some(instance) = self.instance.take() {
+            self.index = state.add_instance(instance)?;
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self, state: &mut State) -> Result<()> {
+        // Keeps the name it was given
+        self.instance = state.remove_instance(self.index);
+        Ok(())
+    }
+}
+
+/// Removes instances, restored at the same indices and selected again by `undo`. The
+/// children of a removed instance are detached, and attached back by `undo`.
+pub struct DeleteInstancesCommand {
+    ids: Vec<usize>,
+    /// In removal order, with the children the instance had. `State::remove_instance`
+    /// swap-removes, so each index is only valid once the instances removed after it are
+    /// back
+    removed: Vec<(usize, Instance, Vec<usize>)>,
+    previous_selection: HashSet<usize>,
+}
+
+impl DeleteInstancesCommand {
+    pub fn new(mut ids: Vec<usize>) -> Self {
+        // From the end, so that swapped-in instances have already been handled
+        ids.sort_unstable_by(|a, b| b.cmp(a));
+        ids.dedup();
+        Self {
+            ids,
+            removed: Vec::new(),
+            previous_selection: HashSet::new(),
+        }
+    }
+}
+
+impl Command for DeleteInstancesCommand {
+    fn execute(&mut self, state: &mut State) -> Result<()> {
+        self.previous_selection = state.selection().clone();
+        self.removed = self
+            .ids
+            .iter()
+            .filter_map(|&id| {
+                let children = state.children(id);
+                Some((id, state.remove_instance(id)?, children))
+            })
+            .collect();
+        Ok(())
+    }
+
+    fn undo(&mut self, state: &mut State) -> Result<()> {
+        while let Some((id, instance, children)) = self.removed.pop() {
+            state.restore_instance(id, instance)?;
+            for child in children {
+                state.set_parent(child, Some(id))?;
+            }
+        }
+        state.set_selection(self.previous_selection.drain());
+        Ok(())
+    }
+}
+
+/// Moves instances to new positions.
+pub struct MoveInstanceCommand {
+    /// Instance and position, swapped with the instance's on every execute and undo
+    moves: Vec<(usize, Vec3)>,
+}
+
+impl MoveInstanceCommand {
+    pub fn new(id: usize, position: Vec3) -> Self {
+        Self::many(vec![(id, position)])
+    }
+
+    /// Moves several instances at once, undone together.
+    pub fn many(moves: Vec<(usize, Vec3)>) -> Self {
+        Self { moves }
+    }
+}
+
+impl Command for MoveInstanceCommand {
+    fn execute(&mut self, state: &mut State) -> Result<()> {
+        for (id, position) in &mut self.moves {
+            if let Some(previous) = state.move_instance(*id, *position) {
+                *position = previous;
+            }
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self, state: &mut State) -> Result<()> {
+        self.execute(state)
+    }
+}
+
+/// Turns instances, moving them too when they turn around a shared center.
+pub struct RotateInstanceCommand {
+    /// Instance, position and rotation, swapped with the instance's on every execute and
+    /// undo
+    transforms: Vec<(usize, Vec3, Quat)>,
+}
+
+impl RotateInstanceCommand {
+    pub fn new(transforms: Vec<(usize, Vec3, Quat)>) -> Self {
+        Self { transforms }
+    }
+}
+
+impl Command for RotateInstanceCommand {
+    fn execute(&mut self, state: &mut State) -> Result<()> {
+        for (id, position, rotation) in &mut self.transforms {
+            if let Some(previous) = state.set_instance_transform(*id, *position, *rotation) {
+                (*position, *rotation) = previous;
+            }
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self, state: &mut State) -> Result<()> {
+        self.execute(state)
+    }
+}
+
+/// Scales instances, moving them too when they scale from a shared center.
+pub struct ScaleInstanceCommand {
+    /// Instance, position and scale, swapped with the instance's on every execute and undo
+    transforms: Vec<(usize, Vec3, Vec3)>,
+}
+
+impl ScaleInstanceCommand {
+    pub fn new(transforms: Vec<(usize, Vec3, Vec3)>) -> Self {
+        Self { transforms }
+    }
+}
+
+impl Command for ScaleInstanceCommand {
+    fn execute(&mut self, state: &mut State) -> Result<()> {
+        for (id, position, scale) in &mut self.transforms {
+            if let Some(previous) = state.scale_instance(*id, *position, *scale) {
+                (*position, *scale) = previous;
+            }
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self, state: &mut State) -> Result<()> {
+        self.execute(state)
+    }
+}
+
+/// Replaces instances with edited copies, as the inspector and the hierarchy lock toggle
+/// make them.
+pub struct EditInstancesCommand {
+    /// Instance and its edited copy, swapped with the instance on every execute and undo
+    instances: Vec<(usize, Instance)>,
+}
+
+impl EditInstancesCommand {
+    pub fn new(instances: Vec<(usize, Instance)>) -> Self {
+        Self { instances }
+    }
+}
+
+impl Command for EditInstancesCommand {
+    fn execute(&mut self, state: &mut State) -> Result<()> {
+        for (id, instance) in &mut self.instances {
+            if let Some(previous) = state.replace_instance(*id, instance.clone()) {
+                *instance = previous;
+            }
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self, state: &mut State) -> Result<()> {
+        self.execute(state)
+    }
+}
+
+/// Renames an instance.
+pub struct RenameInstanceCommand {
+    id: usize,
+    /// Swapped with the instance's on every execute and undo
+    name: String,
+}
+
+impl RenameInstanceCommand {
+    pub fn new(id: usize, name: impl Into<String>) -> Self {
+        Self {
+            id,
+            name: name.into(),
+        }
+    }
+}
+
+impl Command for RenameInstanceCommand {
+    fn execute(&mut self, state: &mut State) -> Result<()> {
+        let name = std::mem::take(&mut self.name);
+        self.name = state.rename_instance(self.id, name).unwrap_or_default();
+        Ok(())
+    }
+
+    fn undo(&mut self, state: &mut State) -> Result<()> {
+        self.execute(state)
+    }
+}
+
+/// Changes the parent of instances in the hierarchy.
+pub struct SetParentCommand {
+    /// Instance and parent, swapped with the instance's on every execute and undo
+    parents: Vec<(usize, Option<usize>)>,
+}
+
+impl SetParentCommand {
+    /// Puts every instance of `ids` under `parent`, or at the root for `None`.
+    pub fn new(ids: impl IntoIterator<Item = usize>, parent: Option<usize>) -> Self {
+        Self {
+            parents: ids.into_iter().map(|id| (id, parent)).collect(),
+        }
+    }
+}
+
+impl Command for SetParentCommand {
+    /// Fails without changing anything when one of the instances cannot take its parent.
+    fn execute(&mut self, state: &mut State) -> Result<()> {
+        for done in 0..self.parents.len() {
+            let (id, parent) = self.parents[done];
+            match state.set_parent(id, parent) {
+                Ok(previous) => self.parents[done].1 = previous,
+                Err(e) => {
+                    for (id, previous) in &mut self.parents[..done] {
+                        *previous = state.set_parent(*id, *previous)?;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// In reverse order, so that no intermediate hierarchy has a cycle.
+    fn undo(&mut self, state: &mut State) -> Result<()> {
+        self.parents.reverse();
+        let result = self.execute(state);
+        self.parents.reverse();
+        result
+    }
+}
+
+/// Replaces the selection.
+pub struct SelectCommand {
+    /// Swapped with the selection on every execute and undo
+    selection: HashSet<usize>,
+}
+
+impl SelectCommand {
+    pub fn new(ids: impl IntoIterator<Item = usize>) -> Self {
+        Self {
+            selection: ids.into_iter().collect(),
+        }
+    }
+}
+
+impl Command for SelectCommand {
+    fn execute(&mut self, state: &mut State) -> Result<()> {
+        let previous = state.selection().clone();
+        state.set_selection(self.selection.drain());
+        self.selection = previous;
+        Ok(())
+    }
+
+    fn undo(&mut self, state: &mut State) -> Result<()> {
+        self.execute(state)
+    }
+}
+
+/// Selects the visible instances that are not selected, and only those.
+#[derive(Default)]
+pub struct InvertSelectionCommand {
+    /// Restored by `undo`
+    previous_selection: HashSet<usize>,
+}
+
+impl InvertSelectionCommand {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Command for InvertSelectionCommand {
+    fn execute(&mut self, state: &mut State) -> Result<()> {
+        self.previous_selection = state.selection().clone();
+        let inverted: Vec<_> = (0..state.instances().len())
+            .filter(|i| !self.previous_selection.contains(i))
+            .collect();
+        state.set_selection(inverted);
+        Ok(())
+    }
+
+    fn undo(&mut self, state: &mut State) -> Result<()> {
+        state.set_selection(self.previous_selection.drain());
+        Ok(())
+    }
+}
+
+/// Selects the instances drawn with a material, see `State::instances_with_material`.
+pub struct SelectByMaterialCommand {
+    material_id: usize,
+    /// Restored by `undo`
+    previous_selection: HashSet<usize>,
+}
+
+impl SelectByMaterialCommand {
+    pub fn new(material_id: usize) -> Self {
+        Self {
+            material_id,
+            previous_selection: HashSet::new(),
+        }
+    }
+}
+
+impl Command for SelectByMaterialCommand {
+    fn execute(&mut self, state: &mut State) -> Result<()> {
+        self.previous_selection = state.selection().clone();
+        let ids = state.instances_with_material(self.material_id);
+        state.set_selection(ids);
+        Ok(())
+    }
+
+    fn undo(&mut self, state: &mut State) -> Result<()> {
+        state.set_selection(self.previous_selection.drain());
+        Ok(())
+    }
+}
+
+/// Copies instances, shifted by `DUPLICATE_OFFSET`, and selects the copies.
+pub struct DuplicateCommand {
+    ids: Vec<usize>,
+    /// Indices of the copies while they are in the scene
+    copies: Vec<usize>,
+    /// Restored by `undo`
+    previous_selection: HashSet<usize>,
+}
+
+impl DuplicateCommand {
+    pub fn new(mut ids: Vec<usize>) -> Self {
+        // The copies follow the order of the originals
+        ids.sort_unstable();
+        ids.dedup();
+        Self {
+            ids,
+            copies: Vec::new(),
+            previous_selection: HashSet::new(),
+        }
+    }
+}
+
+/// Copy of `instance` next to it, named after it.
+pub fn duplicate_instance(instance: &Instance) -> Instance {
+    let mut copy = instance.clone();
+    copy.name.push_str(" (copy)");
+    copy.position += DUPLICATE_OFFSET;
+    copy
+}
+
+impl Command for DuplicateCommand {
+    fn execute(&mut self, state: &mut State) -> Result<()> {
+        let copies: Vec<_> = self
+            .ids
+            .iter()
+            .filter_map(|&id| state.instances().get(id).map(duplicate_instance))
+            .collect();
+        self.previous_selection = state.selection().clone();
+        self.copies.clear();
+        for copy in copies {
+            match state.add_instance(copy) {
+                Ok(index) => self.copies.push(index),
+                Err(e) => {
+                    // All or nothing, the instance buffer is full
+                    self.undo(state)?;
+                    return Err(e);
+                }
+            }
+        }
+        state.set_selection(self.copies.iter().copied());
+        Ok(())
+    }
+
+    fn undo(&mut self, state: &mut State) -> Result<()> {
+        // From the end, the copies are the last instances
+        while let Some(index) = self.copies.pop() {
+            state.remove_instance(index);
+        }
+        state.set_selection(self.previous_selection.drain());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Noop;
+
+    impl Command for Noop {
+        fn execute(&mut self, _: &mut State) -> Result<()> {
+            Ok(())
+        }
+
+        fn undo(&mut self, _: &mut State) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_duplicate_instance() {
+        let mut instance = Instance::new(Vec3::new(1.0, 2.0, 3.0), glam::Quat::IDENTITY);
+        instance.name = "Pizza".to_string();
+        instance.tint = glam::Vec4::new(1.0, 0.0, 0.0, 1.0);
+
+        let copy = duplicate_instance(&instance);
+        assert_eq!(copy.name, "Pizza (copy)");
+        assert_eq!(copy.position, Vec3::new(1.5, 2.5, 3.5));
+        assert_eq!(copy.tint, instance.tint);
+    }
+
+    #[test]
+    fn test_command_stack_drops_undone_commands() {
+        let mut stack = CommandStack::new();
+        assert!(!stack.can_undo() && !stack.can_redo());
+        stack.push(Box::new(Noop));
+        stack.push(Box::new(Noop));
+        stack.cursor = 1;
+        assert!(stack.can_undo() && stack.can_redo());
+
+        stack.push(Box::new(Noop));
+        assert_eq!(stack.history.len(), 2);
+        assert_eq!(stack.cursor, 2);
+        assert!(!stack.can_redo());
+    }
+}
@@ -0,0 +1,147 @@
+// Compute passes: GPU work that does not draw anything, such as generating data consumed by
+// the render passes. Only built with the `compute` feature.
+
+/// Number of hemisphere samples in the SSAO kernel
+pub const SSAO_KERNEL_SIZE: u32 = 64;
+const SSAO_WORKGROUP_SIZE: u32 = 64;
+
+/// Whether the adapter can run compute shaders (WebGL cannot).
+pub fn compute_supported(adapter: &wgpu::Adapter) -> bool {
+    adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+}
+
+/// A compute pipeline together with the resources it reads and writes.
+pub trait ComputePass {
+    fn setup(device: &wgpu::Device) -> Self
+    where
+        Self: Sized;
+
+    /// Records the work into `encoder`, to be submitted by the caller.
+    fn dispatch(&self, encoder: &mut wgpu::CommandEncoder);
+}
+
+/// Fills the `SSAO_KERNEL` storage buffer with `SSAO_KERNEL_SIZE` random offsets in the
+/// tangent space hemisphere (`vec4` each, `w` unused). Dispatched once at startup.
+pub struct SsaoKernelGenerator {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    kernel: wgpu::Buffer,
+}
+
+impl SsaoKernelGenerator {
+    pub fn kernel(&self) -> &wgpu::Buffer {
+        &self.kernel
+    }
+}
+
+impl ComputePass for SsaoKernelGenerator {
+    fn setup(device: &wgpu::Device) -> Self {
+        let kernel = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SSAO_KERNEL"),
+            size: (SSAO_KERNEL_SIZE as usize * std::mem::size_of::<[f32; 4]>())
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ssao_kernel_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ssao_kernel_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: kernel.as_entire_binding(),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("SSAO Kernel Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../ssao_kernel.wgsl"));
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("SSAO Kernel Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: "cs_ssao_kernel",
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            kernel,
+        }
+    }
+
+    fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("SSAO Kernel Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(SSAO_KERNEL_SIZE.div_ceil(SSAO_WORKGROUP_SIZE), 1, 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    fn test_ssao_kernel_in_hemisphere() {
+        let (device, queue) = pollster::block_on(async {
+            let adapter = wgpu::Instance::new(wgpu::InstanceDescriptor::default())
+                .request_adapter(&wgpu::RequestAdapterOptions::default())
+                .await
+                .unwrap();
+            adapter
+                .request_device(&wgpu::DeviceDescriptor::default(), None)
+                .await
+                .unwrap()
+        });
+
+        let generator = SsaoKernelGenerator::setup(&device);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: generator.kernel().size(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&Default::default());
+        generator.dispatch(&mut encoder);
+        encoder.copy_buffer_to_buffer(generator.kernel(), 0, &readback, 0, readback.size());
+        queue.submit(Some(encoder.finish()));
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let data = readback.slice(..).get_mapped_range();
+        let samples = bytemuck::cast_slice::<u8, [f32; 4]>(&data)
+            .iter()
+            .map(|s| Vec3::new(s[0], s[1], s[2]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(samples.len(), SSAO_KERNEL_SIZE as usize);
+        assert!(samples.iter().all(|s| s.z >= 0.0 && s.length() <= 1.0));
+        // Later samples reach further out
+        let mean_length = |s: &[Vec3]| s.iter().map(|v| v.length()).sum::<f32>() / s.len() as f32;
+        assert!(mean_length(&samples[..16]) < mean_length(&samples[48..]));
+    }
+}
@@ -0,0 +1,18 @@
+use crate::{antialias::AntialiasingMode, instance::DEFAULT_INSTANCE_CAPACITY};
+
+/// Engine settings that can be changed at runtime from the editor.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub antialiasing: AntialiasingMode,
+    /// Instances the instance buffer has room for, see `State::set_instance_capacity`
+    pub instance_capacity: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            antialiasing: AntialiasingMode::default(),
+            instance_capacity: DEFAULT_INSTANCE_CAPACITY,
+        }
+    }
+}
@@ -0,0 +1,143 @@
+// Messages of the engine shown in the editor's console panel, the last ones only. Every
+// message also goes to the `log` facade, so that it still reaches the terminal.
+
+use std::{collections::VecDeque, fmt, time::Duration, time::Instant};
+
+/// Messages kept by `Console`, the oldest are dropped first
+pub const CONSOLE_CAPACITY: usize = 512;
+
+/// Logs a formatted message to the console of `state` (anything with a `console` field),
+/// e.g. `engine_log!(self, LogLevel::Warn, "Cannot load {:?}", path)`.
+#[macro_export]
+macro_rules! engine_log {
+    ($state:expr, $level:expr, $($arg:tt)+) => {
+        $state.console.push($level, format!($($arg)+))
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn label(self) -> &'static str {
+        match self {
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+
+    /// Color of the console lines at this level
+    pub fn color(self) -> egui::Color32 {
+        match self {
+            LogLevel::Info => egui::Color32::from_rgb(110, 200, 110),
+            LogLevel::Warn => egui::Color32::from_rgb(230, 200, 60),
+            LogLevel::Error => egui::Color32::from_rgb(230, 80, 80),
+        }
+    }
+}
+
+impl From<LogLevel> for log::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Info => log::Level::Info,
+            LogLevel::Warn => log::Level::Warn,
+            LogLevel::Error => log::Level::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub level: LogLevel,
+    pub message: String,
+    /// Since the console was created
+    pub timestamp: Duration,
+}
+
+impl fmt::Display for LogEntry {
+    /// As copied from the console: `[12.345s] WARN message`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:.3}s] {} {}",
+            self.timestamp.as_secs_f32(),
+            self.level.label(),
+            self.message
+        )
+    }
+}
+
+pub struct Console {
+    /// Oldest first
+    pub entries: VecDeque<LogEntry>,
+    start: Instant,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self {
+            entries: VecDeque::with_capacity(CONSOLE_CAPACITY),
+            start: Instant::now(),
+        }
+    }
+
+    /// Records a message, dropping the oldest one when full, and passes it to `log`.
+    pub fn push(&mut self, level: LogLevel, message: String) {
+        log::log!(level.into(), "{}", message);
+        if self.entries.len() == CONSOLE_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LogEntry {
+            level,
+            message,
+            timestamp: self.start.elapsed(),
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::OrengineError;
+
+    struct Logged {
+        console: Console,
+    }
+
+    #[test]
+    fn test_console_drops_oldest_entries() {
+        let mut logged = Logged {
+            console: Console::new(),
+        };
+        for i in 0..CONSOLE_CAPACITY + 2 {
+            engine_log!(logged, LogLevel::Info, "Message {}", i);
+        }
+        let entries = &logged.console.entries;
+        assert_eq!(entries.len(), CONSOLE_CAPACITY);
+        assert_eq!(entries[0].message, "Message 2");
+
+        let e = OrengineError::Generic("Invalid scene file".to_string());
+        engine_log!(logged, LogLevel::Error, "{}", e);
+        let last = logged.console.entries.back().unwrap();
+        assert_eq!(last.level, LogLevel::Error);
+        assert!(
+            last.to_string()
+                .ends_with("ERROR Generic error: Invalid scene file")
+        );
+    }
+}
@@ -0,0 +1,462 @@
+// Depth of field: the circle of confusion of each pixel is computed from the depth buffer,
+// the HDR image is blurred by it in two separable passes, then blended back over the sharp
+// image (dof.wgsl).
+
+use crate::{
+    post_process::{
+        create_fullscreen_pipeline, create_fullscreen_shader, run_fullscreen_pass,
+        texture_layout_entry, uniform_layout_entry,
+    },
+    textures::HDR_FORMAT,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use wgpu::util::DeviceExt;
+
+/// Largest blur radius in pixels, reached by the most out of focus surfaces
+pub const MAX_COC_RADIUS: f32 = 16.0;
+const COC_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg8Unorm;
+
+/// Thin lens camera model of the depth of field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DofUniform {
+    /// Distance to the sharp plane, in world units
+    pub focal_distance: f32,
+    /// In millimeters, for a 35 mm film frame (50 = normal lens)
+    pub focal_length: f32,
+    /// f-number, lower values blur more
+    pub aperture: f32,
+}
+
+impl Default for DofUniform {
+    fn default() -> Self {
+        Self {
+            focal_distance: 5.0,
+            focal_length: 50.0,
+            aperture: 2.8,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct DofPassUniform {
+    inverse_projection: [[f32; 4]; 4],
+    focal_distance: f32,
+    /// Meters
+    focal_length: f32,
+    aperture: f32,
+    max_radius: f32,
+}
+
+/// Intermediate textures and bind groups that follow the surface size.
+struct DofTargets {
+    /// Reads the depth buffer, writes `coc`
+    coc_bind_group: wgpu::BindGroup,
+    /// Reads the HDR target, writes `horizontal`
+    horizontal_bind_group: wgpu::BindGroup,
+    /// Reads `horizontal`, writes `blurred`
+    vertical_bind_group: wgpu::BindGroup,
+    /// Reads `blurred`, blends into the HDR target
+    composite_bind_group: wgpu::BindGroup,
+    coc: wgpu::TextureView,
+    horizontal: wgpu::TextureView,
+    blurred: wgpu::TextureView,
+}
+
+/// Post-process pass blurring what is out of focus. Reads the depth buffer, so it is skipped
+/// with MSAA like the outlines.
+pub struct DofRenderer {
+    pub settings: DofUniform,
+    coc_pipeline: wgpu::RenderPipeline,
+    horizontal_pipeline: wgpu::RenderPipeline,
+    vertical_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    coc_layout: wgpu::BindGroupLayout,
+    blur_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    targets: DofTargets,
+}
+
+impl DofRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        hdr_view: &wgpu::TextureView,
+    ) -> Self {
+        let unfilterable = wgpu::TextureSampleType::Float { filterable: false };
+        // The CoC pass writes the texture the blur layout reads, it cannot be bound with it
+        let coc_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("dof_coc_bind_group_layout"),
+            entries: &[
+                uniform_layout_entry(0),
+                texture_layout_entry(1, unfilterable),
+            ],
+        });
+        let blur_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("dof_blur_bind_group_layout"),
+            entries: &[
+                uniform_layout_entry(0),
+                texture_layout_entry(1, unfilterable),
+                texture_layout_entry(2, unfilterable),
+            ],
+        });
+
+        let shader = create_fullscreen_shader(device, "DOF Shader", include_str!("../dof.wgsl"));
+        let pipeline = |label, layout, entry_point, format, blend| {
+            create_fullscreen_pipeline(
+                device,
+                label,
+                &[layout],
+                &shader,
+                entry_point,
+                format,
+                blend,
+            )
+        };
+        let coc_pipeline = pipeline("DOF CoC Pipeline", &coc_layout, "fs_coc", COC_FORMAT, None);
+        let horizontal_pipeline = pipeline(
+            "DOF Horizontal Blur Pipeline",
+            &blur_layout,
+            "fs_blur_horizontal",
+            HDR_FORMAT,
+            None,
+        );
+        let vertical_pipeline = pipeline(
+            "DOF Vertical Blur Pipeline",
+            &blur_layout,
+            "fs_blur_vertical",
+            HDR_FORMAT,
+            None,
+        );
+        let composite_pipeline = pipeline(
+            "DOF Composite Pipeline",
+            &blur_layout,
+            "fs_composite",
+            HDR_FORMAT,
+            Some(wgpu::BlendState::ALPHA_BLENDING),
+        );
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("DOF Buffer"),
+            contents: bytemuck::cast_slice(&[DofPassUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let targets = Self::create_targets(
+            device,
+            config,
+            depth_view,
+            hdr_view,
+            &coc_layout,
+            &blur_layout,
+            &uniform_buffer,
+        );
+
+        Self {
+            settings: DofUniform::default(),
+            coc_pipeline,
+            horizontal_pipeline,
+            vertical_pipeline,
+            composite_pipeline,
+            coc_layout,
+            blur_layout,
+            uniform_buffer,
+            targets,
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        hdr_view: &wgpu::TextureView,
+        coc_layout: &wgpu::BindGroupLayout,
+        blur_layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> DofTargets {
+        let create_target = |label, format| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: config.width,
+                        height: config.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let coc = create_target("DOF CoC", COC_FORMAT);
+        let horizontal = create_target("DOF Horizontal Blur", HDR_FORMAT);
+        let blurred = create_target("DOF Blurred", HDR_FORMAT);
+
+        let blur_bind_group = |label, input| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: blur_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(input),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&coc),
+                    },
+                ],
+            })
+        };
+
+        DofTargets {
+            coc_bind_group: device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("dof_coc_bind_group"),
+                layout: coc_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(depth_view),
+                    },
+                ],
+            }),
+            horizontal_bind_group: blur_bind_group("dof_horizontal_bind_group", hdr_view),
+            vertical_bind_group: blur_bind_group("dof_vertical_bind_group", &horizontal),
+            composite_bind_group: blur_bind_group("dof_composite_bind_group", &blurred),
+            coc,
+            horizontal,
+            blurred,
+        }
+    }
+
+    /// The depth and HDR textures are recreated on resize, the intermediate targets follow
+    /// their size.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        hdr_view: &wgpu::TextureView,
+    ) {
+        self.targets = Self::create_targets(
+            device,
+            config,
+            depth_view,
+            hdr_view,
+            &self.coc_layout,
+            &self.blur_layout,
+            &self.uniform_buffer,
+        );
+    }
+
+    /// Blurs `hdr_target` (the view given to `new`/`resize`) where out of focus, the depth
+    /// having been rendered with `projection`.
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        hdr_target: &wgpu::TextureView,
+        projection: Mat4,
+    ) {
+        let settings = &self.settings;
+        let uniform = DofPassUniform {
+            inverse_projection: projection.inverse().to_cols_array_2d(),
+            focal_distance: settings.focal_distance,
+            focal_length: settings.focal_length / 1000.0,
+            aperture: settings.aperture.max(f32::EPSILON),
+            max_radius: MAX_COC_RADIUS,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        let targets = &self.targets;
+        let passes = [
+            (
+                "DOF CoC Pass",
+                &targets.coc,
+                &self.coc_pipeline,
+                &targets.coc_bind_group,
+            ),
+            (
+                "DOF Horizontal Blur Pass",
+                &targets.horizontal,
+                &self.horizontal_pipeline,
+                &targets.horizontal_bind_group,
+            ),
+            (
+                "DOF Vertical Blur Pass",
+                &targets.blurred,
+                &self.vertical_pipeline,
+                &targets.vertical_bind_group,
+            ),
+            (
+                "DOF Composite Pass",
+                hdr_target,
+                &self.composite_pipeline,
+                &targets.composite_bind_group,
+            ),
+        ];
+        for (label, target, pipeline, bind_group) in passes {
+            run_fullscreen_pass(encoder, label, target, None, pipeline, &[bind_group]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dof_uniform_layout() {
+        // Matches `DofUniform` in dof.wgsl
+        assert_eq!(std::mem::size_of::<DofPassUniform>(), 80);
+        assert_eq!(std::mem::offset_of!(DofPassUniform, focal_distance), 64);
+    }
+
+    #[test]
+    fn test_dof_orthographic_focal_plane_stays_sharp() {
+        let (device, queue) = pollster::block_on(async {
+            let adapter = wgpu::Instance::new(wgpu::InstanceDescriptor::default())
+                .request_adapter(&wgpu::RequestAdapterOptions::default())
+                .await
+                .unwrap();
+            adapter
+                .request_device(&wgpu::DeviceDescriptor::default(), None)
+                .await
+                .unwrap()
+        });
+
+        // Rows of 512 bytes, a multiple of the copy alignment
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            width: 64,
+            height: 64,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        let size = wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        };
+        let depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let depth_view = depth.create_view(&Default::default());
+        // Written and read back directly, the engine's HDR target does not allow it
+        let hdr = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let hdr_view = hdr.create_view(&Default::default());
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 512 * 64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let layout = wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(512),
+            rows_per_image: None,
+        };
+
+        let mut dof = DofRenderer::new(&device, &config, &depth_view, &hdr_view);
+        dof.settings = DofUniform {
+            focal_distance: 5.0,
+            focal_length: 200.0,
+            aperture: 1.0,
+        };
+        let projection = Mat4::orthographic_rh(-5.0, 5.0, -5.0, 5.0, 0.1, 100.0);
+
+        // Red of the middle pixel of one-pixel-wide black and white stripes, seen at `distance`
+        let blurred_stripe = |distance: f32| {
+            // Half float 1.0 in the even columns
+            let white: [u16; 4] = [0x3c00; 4];
+            let texels: Vec<[u16; 4]> = (0..64 * 64)
+                .map(|i| if i % 2 == 0 { white } else { [0; 4] })
+                .collect();
+            queue.write_texture(
+                hdr.as_image_copy(),
+                bytemuck::cast_slice(&texels),
+                layout,
+                size,
+            );
+
+            let mut encoder = device.create_command_encoder(&Default::default());
+            let clip_depth = projection
+                .project_point3(glam::Vec3::new(0.0, 0.0, -distance))
+                .z;
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(clip_depth),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            dof.render(&queue, &mut encoder, &hdr_view, projection);
+            encoder.copy_texture_to_buffer(
+                hdr.as_image_copy(),
+                wgpu::ImageCopyBuffer {
+                    buffer: &readback,
+                    layout,
+                },
+                size,
+            );
+            queue.submit(Some(encoder.finish()));
+
+            readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+            device.poll(wgpu::Maintain::Wait);
+            let texels: Vec<u16> =
+                bytemuck::cast_slice(&readback.slice(..).get_mapped_range()).to_vec();
+            readback.unmap();
+            // Row 32, column 32: a white stripe, staying a positive normal half float
+            let bits = texels[(32 * 64 + 32) * 4];
+            2f32.powi((bits >> 10) as i32 - 15) * (1.0 + (bits & 0x3ff) as f32 / 1024.0)
+        };
+
+        assert_eq!(blurred_stripe(5.0), 1.0);
+        let far = blurred_stripe(50.0);
+        assert!(far < 0.9, "{far}");
+    }
+}
@@ -11,6 +11,15 @@ pub enum OrengineError {
     #[error("Tobj model loading error")]
     Tobj(#[from] tobj::LoadError),
 
+    #[error("glTF model loading error")]
+    Gltf(#[from] gltf::Error),
+
+    #[error("Scene archive error")]
+    Archive(#[from] zip::result::ZipError),
+
+    #[error("File watcher error")]
+    Watch(#[from] notify::Error),
+
     #[error("Image loading error")]
     Image(#[from] image::ImageError),
 
@@ -26,6 +35,18 @@ pub enum OrengineError {
     #[error("Mismatched material count in model")]
     MismatchedMaterials,
 
+    #[error("Too many instances: {count} requested, the limit is {limit}")]
+    InstanceLimitExceeded { count: usize, limit: usize },
+
+    #[error("Invalid MSAA sample count {0}, expected 1, 2, 4 or 8")]
+    InvalidSampleCount(u32),
+
+    #[error("Too many lights, the limit is {limit}")]
+    LightLimitExceeded { limit: usize },
+
+    #[error("Transparent instances {first} and {second} overlap, their draw order is ambiguous")]
+    TransparentDepthSort { first: usize, second: usize },
+
     #[error("Surface error: {0}")]
     SurfaceError(#[from] wgpu::SurfaceError),
 }
@@ -0,0 +1,369 @@
+// Automatic exposure: compute passes measure the average luminance of the HDR scene
+// (see exposure.wgsl), then a tone mapping pass scales and compresses it into the render
+// target (tonemap.wgsl).
+// The measured luminance never leaves the GPU.
+
+use crate::post_process::{
+    create_fullscreen_pipeline, create_fullscreen_shader, run_fullscreen_pass,
+    texture_layout_entry, uniform_layout_entry,
+};
+use crate::tonemap::TONEMAP_CURVES_WGSL;
+use bytemuck::{Pod, Zeroable};
+use std::time::Instant;
+use wgpu::util::DeviceExt;
+
+const HISTOGRAM_BINS: u64 = 256;
+/// Side of the square tile counted by each histogram workgroup
+const TILE_SIZE: u32 = 16;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct ExposureUniform {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    adaptation: f32,
+    pixel_count: f32,
+    key: f32,
+    _padding: [f32; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExposureSettings {
+    /// Time constant of the adaptation in seconds, 0 to adapt instantly
+    pub lag: f32,
+    /// Middle grey the average luminance is mapped to
+    pub key: f32,
+    /// Luminance range covered by the histogram, in log2 units
+    pub min_log_luminance: f32,
+    pub max_log_luminance: f32,
+}
+
+impl Default for ExposureSettings {
+    fn default() -> Self {
+        Self {
+            lag: 1.0,
+            key: 0.18,
+            min_log_luminance: -8.0,
+            max_log_luminance: 4.0,
+        }
+    }
+}
+
+/// Weight of the luminance measured this frame after `dt` seconds: exponential smoothing,
+/// so the adaptation speed does not depend on the frame rate.
+pub fn adaptation_rate(dt: f32, lag: f32) -> f32 {
+    if lag <= 0.0 {
+        return 1.0;
+    }
+    1.0 - (-dt / lag).exp()
+}
+
+/// Whether the adapter can run the exposure compute passes (WebGL cannot).
+pub fn auto_exposure_supported(adapter: &wgpu::Adapter) -> bool {
+    adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+}
+
+pub struct AutoExposure {
+    pub enabled: bool,
+    pub settings: ExposureSettings,
+    histogram_pipeline: wgpu::ComputePipeline,
+    average_pipeline: wgpu::ComputePipeline,
+    compute_layout: wgpu::BindGroupLayout,
+    compute_bind_group: wgpu::BindGroup,
+    tonemap_pipeline: wgpu::RenderPipeline,
+    tonemap_layout: wgpu::BindGroupLayout,
+    tonemap_bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    histogram_buffer: wgpu::Buffer,
+    luminance_buffer: wgpu::Buffer,
+    /// `None` until the first measure, which is then used as is
+    last_frame: Option<Instant>,
+}
+
+impl AutoExposure {
+    /// `tonemap_buffer` holds the `TonemapUniform` picking the curve, see `Tonemapper`.
+    pub fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        hdr_view: &wgpu::TextureView,
+        tonemap_buffer: &wgpu::Buffer,
+    ) -> Self {
+        let storage_entry = |binding, visibility, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let compute_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("exposure_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ..texture_layout_entry(0, wgpu::TextureSampleType::Float { filterable: false })
+                },
+                storage_entry(1, wgpu::ShaderStages::COMPUTE, false),
+                storage_entry(2, wgpu::ShaderStages::COMPUTE, false),
+                wgpu::BindGroupLayoutEntry {
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ..uniform_layout_entry(3)
+                },
+            ],
+        });
+        let compute_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Exposure Pipeline Layout"),
+                bind_group_layouts: &[&compute_layout],
+                push_constant_ranges: &[],
+            });
+        let exposure_shader = device.create_shader_module(wgpu::include_wgsl!("../exposure.wgsl"));
+        let compute_pipeline = |label, entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&compute_pipeline_layout),
+                module: &exposure_shader,
+                entry_point,
+            })
+        };
+        let histogram_pipeline = compute_pipeline("Luminance Histogram Pipeline", "cs_histogram");
+        let average_pipeline = compute_pipeline("Luminance Average Pipeline", "cs_average");
+
+        let tonemap_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("tonemap_bind_group_layout"),
+            entries: &[
+                texture_layout_entry(0, wgpu::TextureSampleType::Float { filterable: false }),
+                storage_entry(1, wgpu::ShaderStages::FRAGMENT, true),
+                uniform_layout_entry(2),
+                uniform_layout_entry(3),
+            ],
+        });
+        let tonemap_shader = create_fullscreen_shader(
+            device,
+            "Tonemap Shader",
+            &format!("{TONEMAP_CURVES_WGSL}\n{}", include_str!("../tonemap.wgsl")),
+        );
+        let tonemap_pipeline = create_fullscreen_pipeline(
+            device,
+            "Tonemap Pipeline",
+            &[&tonemap_layout],
+            &tonemap_shader,
+            "fs_tonemap",
+            target_format,
+            None,
+        );
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Exposure Buffer"),
+            contents: bytemuck::cast_slice(&[ExposureUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let histogram_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Luminance Histogram Buffer"),
+            contents: &[0; (HISTOGRAM_BINS * 4) as usize],
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let luminance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Adapted Luminance Buffer"),
+            contents: bytemuck::cast_slice(&[ExposureSettings::default().key]),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let (compute_bind_group, tonemap_bind_group) = Self::create_bind_groups(
+            device,
+            hdr_view,
+            &compute_layout,
+            &tonemap_layout,
+            [
+                &uniform_buffer,
+                &histogram_buffer,
+                &luminance_buffer,
+                tonemap_buffer,
+            ],
+        );
+
+        Self {
+            enabled: false,
+            settings: ExposureSettings::default(),
+            histogram_pipeline,
+            average_pipeline,
+            compute_layout,
+            compute_bind_group,
+            tonemap_pipeline,
+            tonemap_layout,
+            tonemap_bind_group,
+            uniform_buffer,
+            histogram_buffer,
+            luminance_buffer,
+            last_frame: None,
+        }
+    }
+
+    fn create_bind_groups(
+        device: &wgpu::Device,
+        hdr_view: &wgpu::TextureView,
+        compute_layout: &wgpu::BindGroupLayout,
+        tonemap_layout: &wgpu::BindGroupLayout,
+        [
+            uniform_buffer,
+            histogram_buffer,
+            luminance_buffer,
+            tonemap_buffer,
+        ]: [&wgpu::Buffer; 4],
+    ) -> (wgpu::BindGroup, wgpu::BindGroup) {
+        let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("exposure_bind_group"),
+            layout: compute_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(hdr_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: histogram_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: luminance_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let tonemap_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tonemap_bind_group"),
+            layout: tonemap_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(hdr_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: luminance_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: tonemap_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        (compute_bind_group, tonemap_bind_group)
+    }
+
+    /// The HDR target is recreated on resize, so are the bind groups reading it.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        hdr_view: &wgpu::TextureView,
+        tonemap_buffer: &wgpu::Buffer,
+    ) {
+        (self.compute_bind_group, self.tonemap_bind_group) = Self::create_bind_groups(
+            device,
+            hdr_view,
+            &self.compute_layout,
+            &self.tonemap_layout,
+            [
+                &self.uniform_buffer,
+                &self.histogram_buffer,
+                &self.luminance_buffer,
+                tonemap_buffer,
+            ],
+        );
+    }
+
+    /// Measures `hdr_target` and tone maps it into `target`. Returns `false` without
+    /// writing anything when disabled.
+    pub fn render(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        hdr_target: &crate::textures::Texture,
+        target: &wgpu::TextureView,
+    ) -> bool {
+        if !self.enabled {
+            // Adapting from a stale measure would show a visible fade when re-enabled
+            self.last_frame = None;
+            return false;
+        }
+
+        let now = Instant::now();
+        let adaptation = self.last_frame.map_or(1.0, |last| {
+            adaptation_rate(now.duration_since(last).as_secs_f32(), self.settings.lag)
+        });
+        self.last_frame = Some(now);
+
+        let size = hdr_target.texture.size();
+        let settings = &self.settings;
+        let uniform = ExposureUniform {
+            min_log_luminance: settings.min_log_luminance,
+            log_luminance_range: (settings.max_log_luminance - settings.min_log_luminance)
+                .max(f32::EPSILON),
+            adaptation,
+            pixel_count: (size.width * size.height) as f32,
+            key: settings.key,
+            _padding: [0.0; 3],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Exposure Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_bind_group(0, &self.compute_bind_group, &[]);
+            pass.set_pipeline(&self.histogram_pipeline);
+            pass.dispatch_workgroups(
+                size.width.div_ceil(TILE_SIZE),
+                size.height.div_ceil(TILE_SIZE),
+                1,
+            );
+            pass.set_pipeline(&self.average_pipeline);
+            pass.dispatch_workgroups(1, 1, 1);
+        }
+
+        run_fullscreen_pass(
+            encoder,
+            "Tonemap Pass",
+            target,
+            None,
+            &self.tonemap_pipeline,
+            &[&self.tonemap_bind_group],
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adaptation_rate() {
+        assert_eq!(adaptation_rate(0.016, 0.0), 1.0);
+        assert_eq!(adaptation_rate(0.0, 1.0), 0.0);
+
+        // Two half frames adapt as much as one full frame
+        let full = adaptation_rate(0.1, 0.5);
+        let half = adaptation_rate(0.05, 0.5);
+        let two_halves = 1.0 - (1.0 - half) * (1.0 - half);
+        assert!((full - two_halves).abs() < 1e-6);
+
+        // After one time constant, ~63% of the way there
+        assert!((adaptation_rate(1.0, 1.0) - 0.632).abs() < 1e-3);
+    }
+}
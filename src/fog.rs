@@ -0,0 +1,125 @@
+// Distance fog, blended over the lit color in the scene shader (see `fog_amount` in
+// shader.wgsl, which `FogUniform::factor` mirrors).
+
+use bytemuck::{Pod, Zeroable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum FogMode {
+    /// Grows from nothing at `start` to full at `end`
+    Linear = 0,
+    /// `1 - e^(-density * depth)`
+    Exponential = 1,
+    /// `1 - e^(-(density * depth)²)`, clearer close to the camera
+    ExponentialSquared = 2,
+}
+
+impl FogMode {
+    pub const ALL: [FogMode; 3] = [
+        FogMode::Linear,
+        FogMode::Exponential,
+        FogMode::ExponentialSquared,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FogMode::Linear => "Linéaire",
+            FogMode::Exponential => "Exponentiel",
+            FogMode::ExponentialSquared => "Exponentiel carré",
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct FogUniform {
+    /// Linear RGB, alpha is the opacity of the fog at its thickest (0 disables it)
+    pub color: [f32; 4],
+    /// For the exponential modes
+    pub density: f32,
+    /// View-space depths for the linear mode
+    pub start: f32,
+    pub end: f32,
+    /// A `FogMode`
+    pub mode: u32,
+}
+
+impl FogUniform {
+    /// Unknown modes fall back to linear, like in the shader.
+    pub fn mode(&self) -> FogMode {
+        FogMode::ALL
+            .into_iter()
+            .find(|&mode| mode as u32 == self.mode)
+            .unwrap_or(FogMode::Linear)
+    }
+
+    /// Amount of fog in [0, 1] at a view-space `depth`, before the color opacity.
+    pub fn factor(&self, depth: f32) -> f32 {
+        let factor = match self.mode() {
+            FogMode::Linear => {
+                let range = self.end - self.start;
+                if range > 0.0 {
+                    (depth - self.start) / range
+                } else {
+                    // No transition, everything past `start` is fogged
+                    if depth >= self.start { 1.0 } else { 0.0 }
+                }
+            }
+            FogMode::Exponential => 1.0 - (-self.density * depth).exp(),
+            FogMode::ExponentialSquared => 1.0 - (-(self.density * depth).powi(2)).exp(),
+        };
+        factor.clamp(0.0, 1.0)
+    }
+}
+
+impl Default for FogUniform {
+    /// Linear fog the color of the background, disabled.
+    fn default() -> Self {
+        Self {
+            color: [0.1, 0.2, 0.3, 0.0],
+            density: 0.05,
+            start: 10.0,
+            end: 50.0,
+            mode: FogMode::Linear as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_fog() {
+        let fog = FogUniform::default();
+        assert_eq!(fog.factor(0.0), 0.0);
+        assert_eq!(fog.factor(30.0), 0.5);
+        assert_eq!(fog.factor(100.0), 1.0);
+    }
+
+    #[test]
+    fn test_linear_fog_empty_range() {
+        for end in [10.0, 5.0] {
+            let fog = FogUniform {
+                end,
+                ..Default::default()
+            };
+            assert_eq!(fog.factor(9.0), 0.0);
+            assert_eq!(fog.factor(10.0), 1.0);
+            assert_eq!(fog.factor(20.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn test_exponential_fog() {
+        for mode in [FogMode::Exponential, FogMode::ExponentialSquared] {
+            let fog = FogUniform {
+                mode: mode as u32,
+                ..Default::default()
+            };
+            assert_eq!(fog.factor(0.0), 0.0);
+            assert!(fog.factor(10.0) > 0.0 && fog.factor(10.0) < fog.factor(20.0));
+            assert!(fog.factor(1000.0) > 0.99);
+        }
+    }
+}
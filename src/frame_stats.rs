@@ -0,0 +1,105 @@
+// Duration of the last frames and work of the scene pass, for the performance readouts of
+// the editor.
+
+use std::{collections::VecDeque, time::Instant};
+
+/// Frames kept by `FrameStats`, about two seconds at 60 FPS
+pub const FRAME_STATS_CAPACITY: usize = 128;
+
+pub struct FrameStats {
+    /// Milliseconds, oldest first
+    pub frame_times: VecDeque<f32>,
+    pub last_instant: Instant,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Self {
+            frame_times: VecDeque::with_capacity(FRAME_STATS_CAPACITY),
+            last_instant: Instant::now(),
+        }
+    }
+
+    /// Records the time elapsed since the previous call, once per frame.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        self.push(now.duration_since(self.last_instant).as_secs_f32() * 1000.0);
+        self.last_instant = now;
+    }
+
+    /// Records a frame time in milliseconds, dropping the oldest one when full.
+    pub fn push(&mut self, frame_time: f32) {
+        if self.frame_times.len() == FRAME_STATS_CAPACITY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+
+    /// Over the kept frames, 0 before the first one.
+    pub fn average_frame_time(&self) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+        self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32
+    }
+
+    pub fn fps(&self) -> f32 {
+        let average = self.average_frame_time();
+        if average > 0.0 { 1000.0 / average } else { 0.0 }
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the 3D scene pass of the last frame drew, post-process passes excluded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    /// Vertex shader invocations, every instance counted
+    pub vertices_rendered: u64,
+    pub triangles_rendered: u64,
+}
+
+impl RenderStats {
+    /// Counts a triangle list draw of `vertex_count` vertices or indices.
+    pub fn record_draw(&mut self, vertex_count: u32, instance_count: u32) {
+        let instance_count = instance_count as u64;
+        self.draw_calls += 1;
+        self.vertices_rendered += vertex_count as u64 * instance_count;
+        self.triangles_rendered += (vertex_count / 3) as u64 * instance_count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_stats_keeps_last_frames() {
+        let mut stats = FrameStats::new();
+        assert_eq!(stats.fps(), 0.0);
+
+        stats.push(40.0);
+        for _ in 0..FRAME_STATS_CAPACITY {
+            stats.push(10.0);
+        }
+        // The 40 ms frame is gone
+        assert_eq!(stats.frame_times.len(), FRAME_STATS_CAPACITY);
+        assert_eq!(stats.average_frame_time(), 10.0);
+        assert_eq!(stats.fps(), 100.0);
+    }
+
+    #[test]
+    fn test_render_stats_counts_instances() {
+        let mut stats = RenderStats::default();
+        stats.record_draw(36, 1);
+        stats.record_draw(6, 10);
+        assert_eq!(stats.draw_calls, 2);
+        assert_eq!(stats.vertices_rendered, 96);
+        assert_eq!(stats.triangles_rendered, 32);
+    }
+}
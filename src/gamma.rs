@@ -0,0 +1,236 @@
+use crate::{
+    post_process::{
+        create_fullscreen_pipeline, create_fullscreen_shader, run_fullscreen_pass,
+        texture_layout_entry,
+    },
+    textures::{HDR_FORMAT, Texture},
+};
+
+/// Whether presenting to `surface_format` needs `GammaCorrection`. sRGB formats encode on
+/// write, the GPU applies the transfer function itself.
+///
+/// egui reads the corrected render target through an sRGB view, which needs
+/// `DownlevelFlags::VIEW_FORMATS`. Without it, egui's own encoding of the linear viewport
+/// texture for non-sRGB framebuffers is left to do the job.
+pub fn needs_gamma_correction(
+    surface_format: wgpu::TextureFormat,
+    downlevel_flags: wgpu::DownlevelFlags,
+) -> bool {
+    !surface_format.is_srgb()
+        && surface_format.add_srgb_suffix() != surface_format
+        && downlevel_flags.contains(wgpu::DownlevelFlags::VIEW_FORMATS)
+}
+
+/// Final pass for surfaces without an sRGB format: the tone mapped image is rendered into
+/// `linear_view()` instead of the render target, then gamma encoded into it.
+pub struct GammaCorrection {
+    gamma_correct_pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    /// Half floats, 8 bits of linear light would band in the shadows
+    linear_target: Texture,
+}
+
+impl GammaCorrection {
+    /// Format of `linear_view()`, for the pipelines rendering into it.
+    pub const LINEAR_FORMAT: wgpu::TextureFormat = HDR_FORMAT;
+
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("gamma_bind_group_layout"),
+            entries: &[texture_layout_entry(
+                0,
+                wgpu::TextureSampleType::Float { filterable: false },
+            )],
+        });
+        let shader =
+            create_fullscreen_shader(device, "Gamma Shader", include_str!("../gamma.wgsl"));
+        let gamma_correct_pipeline = create_fullscreen_pipeline(
+            device,
+            "Gamma Correct Pipeline",
+            &[&bind_group_layout],
+            &shader,
+            "fs_gamma",
+            config.format,
+            None,
+        );
+        let linear_target = Texture::create_hdr_target(device, config);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &linear_target);
+
+        Self {
+            gamma_correct_pipeline,
+            bind_group_layout,
+            bind_group,
+            linear_target,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        linear_target: &Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gamma_bind_group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&linear_target.view),
+            }],
+        })
+    }
+
+    /// Render target receiving the gamma encoded image, with the sRGB view format that
+    /// `Texture::viewport_view` decodes it through.
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> Texture {
+        Texture::create_color_target(
+            device,
+            config,
+            config.format,
+            &[config.format.add_srgb_suffix()],
+            "Render Target",
+        )
+    }
+
+    /// The linear target follows the surface size.
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.linear_target = Texture::create_hdr_target(device, config);
+        self.bind_group =
+            Self::create_bind_group(device, &self.bind_group_layout, &self.linear_target);
+    }
+
+    /// Where the passes after tone mapping render, in `LINEAR_FORMAT`.
+    pub fn linear_view(&self) -> &wgpu::TextureView {
+        &self.linear_target.view
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, render_target: &wgpu::TextureView) {
+        run_fullscreen_pass(
+            encoder,
+            "Gamma Correct Pass",
+            render_target,
+            None,
+            &self.gamma_correct_pipeline,
+            &[&self.bind_group],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_gamma_correction() {
+        let flags = wgpu::DownlevelFlags::all();
+        assert!(needs_gamma_correction(
+            wgpu::TextureFormat::Rgba8Unorm,
+            flags
+        ));
+        assert!(needs_gamma_correction(
+            wgpu::TextureFormat::Bgra8Unorm,
+            flags
+        ));
+        assert!(!needs_gamma_correction(
+            wgpu::TextureFormat::Bgra8UnormSrgb,
+            flags
+        ));
+        // No sRGB variant to decode through
+        assert!(!needs_gamma_correction(
+            wgpu::TextureFormat::Rgba16Float,
+            flags
+        ));
+        assert!(!needs_gamma_correction(
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::DownlevelFlags::empty()
+        ));
+    }
+
+    #[test]
+    fn test_gamma_correct_grey_ramp() {
+        let (device, queue) = pollster::block_on(async {
+            let adapter = wgpu::Instance::new(wgpu::InstanceDescriptor::default())
+                .request_adapter(&wgpu::RequestAdapterOptions::default())
+                .await
+                .unwrap();
+            adapter
+                .request_device(&wgpu::DeviceDescriptor::default(), None)
+                .await
+                .unwrap()
+        });
+
+        // One row of 256 bytes, the copy alignment
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            width: 64,
+            height: 1,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        let gamma = GammaCorrection::new(&device, &config);
+        let render_target = Texture::create_render_target(&device, &config, "Render Target");
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 256,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let displayed = |grey: f64| {
+            let mut encoder = device.create_command_encoder(&Default::default());
+            // Cleared to the linear grey, as if tone mapped
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: gamma.linear_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: grey,
+                            g: grey,
+                            b: grey,
+                            a: 1.0,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            gamma.render(&mut encoder, &render_target.view);
+            encoder.copy_texture_to_buffer(
+                render_target.texture.as_image_copy(),
+                wgpu::ImageCopyBuffer {
+                    buffer: &readback,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(256),
+                        rows_per_image: None,
+                    },
+                },
+                render_target.texture.size(),
+            );
+            queue.submit(Some(encoder.finish()));
+
+            readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+            device.poll(wgpu::Maintain::Wait);
+            let value = readback.slice(..).get_mapped_range()[0] as f32 / 255.0;
+            readback.unmap();
+            value
+        };
+
+        let ramp = [0.0, 0.25, 0.5, 0.75, 1.0].map(displayed);
+        assert_eq!(ramp[0], 0.0);
+        assert_eq!(ramp[4], 1.0);
+        // The linear midpoint is displayed at about 0.735, not 0.5
+        assert!((ramp[2] - 0.735).abs() < 0.01, "{}", ramp[2]);
+        assert!(ramp.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
@@ -0,0 +1,794 @@
+// Transform gizmo at the center of the selection, drawn over the scene (gizmo.wgsl): three
+// arrows that move the selection along their axis, three rings that turn it around theirs,
+// or three cube handles that stretch it along theirs and a center one that scales it evenly.
+// The axes are the world ones, or those of the first selected instance in local space.
+
+use crate::{
+    camera::{Camera, Projection, Ray},
+    post_process::uniform_layout_entry,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Quat, Vec2, Vec3};
+use std::ops::Range;
+use wgpu::util::DeviceExt;
+
+/// Length of the arrows, as a fraction of the viewport height
+pub const GIZMO_SCREEN_SIZE: f32 = 0.15;
+/// How close to an arrow or a ring the cursor grabs it, in pixels
+const PICK_RADIUS: f32 = 8.0;
+const SEGMENTS: usize = 12;
+/// Around the rings, the tubes use `SEGMENTS`
+const RING_SEGMENTS: usize = 48;
+/// Half size of the cubes ending the scale axes, in the unit space of the gizmo
+const HANDLE_SIZE: f32 = 0.05;
+/// Half size of the cube at the center of the scale gizmo
+const CENTER_HANDLE_SIZE: f32 = 0.07;
+/// Scale factors of a drag never go below it, the instances would vanish or mirror
+const MIN_SCALE_FACTOR: f32 = 0.01;
+/// Snapping increments offered by the editor
+pub const SNAP_GRID_PRESETS: [f32; 5] = [0.1, 0.25, 0.5, 1.0, 2.5];
+pub const DEFAULT_SNAP_GRID_SIZE: f32 = 0.5;
+/// Rotation snapping increments offered by the editor, in degrees
+pub const SNAP_ROTATION_PRESETS: [f32; 4] = [5.0, 15.0, 45.0, 90.0];
+pub const DEFAULT_SNAP_ROTATION_DEGREES: f32 = 15.0;
+
+/// What dragging the gizmo does to the selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    pub const ALL: [GizmoMode; 3] = [GizmoMode::Translate, GizmoMode::Rotate, GizmoMode::Scale];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GizmoMode::Translate => "Déplacer",
+            GizmoMode::Rotate => "Pivoter",
+            GizmoMode::Scale => "Redimensionner",
+        }
+    }
+}
+
+/// Which axes the gizmo follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GizmoSpace {
+    #[default]
+    World,
+    /// Those of the first selected instance
+    Local,
+}
+
+impl GizmoSpace {
+    pub const ALL: [GizmoSpace; 2] = [GizmoSpace::World, GizmoSpace::Local];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GizmoSpace::World => "Monde",
+            GizmoSpace::Local => "Local",
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            GizmoSpace::World => GizmoSpace::Local,
+            GizmoSpace::Local => GizmoSpace::World,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoAxis {
+    X,
+    Y,
+    Z,
+}
+
+impl GizmoAxis {
+    pub const ALL: [GizmoAxis; 3] = [GizmoAxis::X, GizmoAxis::Y, GizmoAxis::Z];
+
+    pub fn direction(self) -> Vec3 {
+        match self {
+            GizmoAxis::X => Vec3::X,
+            GizmoAxis::Y => Vec3::Y,
+            GizmoAxis::Z => Vec3::Z,
+        }
+    }
+}
+
+/// Part of the gizmo the cursor can grab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoHandle {
+    Axis(GizmoAxis),
+    /// Center cube of the scale gizmo
+    Center,
+}
+
+impl GizmoHandle {
+    pub fn axis(self) -> Option<GizmoAxis> {
+        match self {
+            GizmoHandle::Axis(axis) => Some(axis),
+            GizmoHandle::Center => None,
+        }
+    }
+
+    /// `axis` of the vertices in gizmo.wgsl
+    fn index(self) -> u32 {
+        match self {
+            GizmoHandle::Axis(axis) => axis as u32,
+            GizmoHandle::Center => 3,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct GizmoVertex {
+    position: [f32; 3],
+    axis: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct GizmoUniform {
+    transform: [[f32; 4]; 4],
+    highlighted: u32,
+    _padding: [u32; 3],
+}
+
+/// Where the gizmo stands and how it is turned and sized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GizmoFrame {
+    pub center: Vec3,
+    /// Identity in world space
+    pub rotation: Quat,
+    /// Length of the arrows and radius of the rings, see `gizmo_scale`
+    pub scale: f32,
+}
+
+impl GizmoFrame {
+    /// World direction of `axis`.
+    pub fn direction(&self, axis: GizmoAxis) -> Vec3 {
+        self.rotation * axis.direction()
+    }
+
+    /// World space from the unit space of the arrows and rings.
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(Vec3::splat(self.scale), self.rotation, self.center)
+    }
+}
+
+/// World length of the arrows at `center`, so that they keep the same size on screen.
+pub fn gizmo_scale(camera: &Camera, center: Vec3) -> f32 {
+    match camera.projection {
+        Projection::Perspective { fovy, znear, .. } => {
+            let forward = (camera.target - camera.eye).normalize_or_zero();
+            let depth = forward.dot(center - camera.eye).max(znear);
+            depth * (fovy * 0.5).tan() * 2.0 * GIZMO_SCREEN_SIZE
+        }
+        Projection::Orthographic { height, .. } => height * GIZMO_SCREEN_SIZE,
+    }
+}
+
+/// The arrow under the cursor at `ndc`, the closest one if several are. `viewport` is the
+/// size of the viewport in pixels.
+pub fn pick_axis(
+    view_proj: Mat4,
+    frame: &GizmoFrame,
+    ndc: Vec2,
+    viewport: Vec2,
+) -> Option<GizmoAxis> {
+    let to_screen = |point: Vec3| {
+        let clip = view_proj * point.extend(1.0);
+        (clip.w > 0.0).then(|| clip.truncate().truncate() / clip.w * 0.5 * viewport)
+    };
+    let cursor = ndc * 0.5 * viewport;
+    let origin = to_screen(frame.center)?;
+    GizmoAxis::ALL
+        .into_iter()
+        .filter_map(|axis| {
+            let tip = to_screen(frame.center + frame.direction(axis) * frame.scale)?;
+            Some((axis, distance_to_segment(cursor, origin, tip)))
+        })
+        .filter(|&(_, distance)| distance <= PICK_RADIUS)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(axis, _)| axis)
+}
+
+/// The scale handle under the cursor at `ndc`: the center cube first, then the axes
+/// (shafts included). `viewport` is the size of the viewport in pixels.
+pub fn pick_scale_handle(
+    view_proj: Mat4,
+    frame: &GizmoFrame,
+    ndc: Vec2,
+    viewport: Vec2,
+) -> Option<GizmoHandle> {
+    let clip = view_proj * frame.center.extend(1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let center = clip.truncate().truncate() / clip.w * 0.5 * viewport;
+    let center_radius = CENTER_HANDLE_SIZE * GIZMO_SCREEN_SIZE * viewport.y;
+    if center.distance(ndc * 0.5 * viewport) <= center_radius.max(PICK_RADIUS) {
+        return Some(GizmoHandle::Center);
+    }
+    pick_axis(view_proj, frame, ndc, viewport).map(GizmoHandle::Axis)
+}
+
+/// The ring `ray` passes over, the front one if several are. `viewport` is the size of the
+/// viewport in pixels.
+pub fn pick_ring(ray: &Ray, frame: &GizmoFrame, viewport: Vec2) -> Option<GizmoAxis> {
+    // The gizmo is GIZMO_SCREEN_SIZE of the viewport height, whatever the projection
+    let tolerance = frame.scale * PICK_RADIUS / (GIZMO_SCREEN_SIZE * viewport.y);
+    GizmoAxis::ALL
+        .into_iter()
+        .filter_map(|axis| {
+            let t = ray.intersect_plane(frame.center, frame.direction(axis))?;
+            let radius = (ray.at(t) - frame.center).length();
+            ((radius - frame.scale).abs() <= tolerance).then_some((axis, t))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(axis, _)| axis)
+}
+
+/// Where `ray` crosses the plane of the ring around `normal`, from `center`. `None` when it
+/// does not, or too close to the center to tell an angle.
+pub fn ring_point(ray: &Ray, center: Vec3, normal: Vec3) -> Option<Vec3> {
+    let t = ray.intersect_plane(center, normal)?;
+    let point = ray.at(t) - center;
+    (point.length_squared() > 1e-10).then_some(point)
+}
+
+/// Angle from `from` to `to` around `normal`, counter-clockwise when `normal` faces the
+/// viewer, in (-π, π].
+pub fn signed_angle(from: Vec3, to: Vec3, normal: Vec3) -> f32 {
+    normal.dot(from.cross(to)).atan2(from.dot(to))
+}
+
+/// Where `ray` crosses the plane facing `camera` through `center`, along the screen
+/// diagonal (right and up): dragging up or right grows it. `None` when it does not cross.
+pub fn uniform_scale_parameter(ray: &Ray, camera: &Camera, center: Vec3) -> Option<f32> {
+    let forward = (camera.target - camera.eye).normalize_or_zero();
+    let right = forward.cross(camera.up).normalize_or_zero();
+    let up = right.cross(forward);
+    let t = ray.intersect_plane(center, forward)?;
+    Some((ray.at(t) - center).dot((right + up) * std::f32::consts::FRAC_1_SQRT_2))
+}
+
+/// Scale factor of a drag from `start` to `current` (gizmo parameters), a gizmo length
+/// doubling the size.
+pub fn scale_factor(start: f32, current: f32, scale: f32) -> f32 {
+    (1.0 + (current - start) / scale).max(MIN_SCALE_FACTOR)
+}
+
+/// `position` rounded to the nearest multiple of `size` on each axis, unchanged when `size`
+/// is not positive.
+pub fn snap_to_grid(position: Vec3, size: f32) -> Vec3 {
+    if size > 0.0 {
+        (position / size).round() * size
+    } else {
+        position
+    }
+}
+
+/// `angle` (radians) rounded to the nearest multiple of `step_degrees`, unchanged when
+/// the step is not positive.
+pub fn snap_angle(angle: f32, step_degrees: f32) -> f32 {
+    if step_degrees > 0.0 {
+        let step = step_degrees.to_radians();
+        (angle / step).round() * step
+    } else {
+        angle
+    }
+}
+
+fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let segment = end - start;
+    let t = if segment.length_squared() > 0.0 {
+        ((point - start).dot(segment) / segment.length_squared()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    point.distance(start + segment * t)
+}
+
+/// Where on the line through `origin` along `direction` (normalized) the ray passes
+/// closest, as a distance from `origin`. `None` when they are almost parallel.
+pub fn axis_parameter(ray: &Ray, origin: Vec3, direction: Vec3) -> Option<f32> {
+    let w0 = ray.origin - origin;
+    let a = ray.direction.dot(ray.direction);
+    let b = ray.direction.dot(direction);
+    let d = ray.direction.dot(w0);
+    let e = direction.dot(w0);
+    let denominator = a - b * b;
+    (denominator > 1e-6 * a).then(|| (a * e - b * d) / denominator)
+}
+
+/// Vertex of the part of the gizmo along `axis`, built along X: rotated by permuting the
+/// coordinates.
+fn axis_vertex(axis: GizmoAxis, x: f32, y: f32, z: f32) -> GizmoVertex {
+    let position = match axis {
+        GizmoAxis::X => [x, y, z],
+        GizmoAxis::Y => [z, x, y],
+        GizmoAxis::Z => [y, z, x],
+    };
+    GizmoVertex {
+        position,
+        axis: axis as u32,
+    }
+}
+
+/// Point `i` of a circle of `SEGMENTS` around `axis`, `x` along it.
+fn circle_vertex(axis: GizmoAxis, i: usize, x: f32, radius: f32) -> GizmoVertex {
+    let angle = i as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+    axis_vertex(axis, x, radius * angle.cos(), radius * angle.sin())
+}
+
+/// Thin open cylinder from the center along `axis`, wound counter-clockwise outward.
+fn shaft_vertices(axis: GizmoAxis, length: f32) -> Vec<GizmoVertex> {
+    const SHAFT_RADIUS: f32 = 0.02;
+
+    let mut vertices = Vec::with_capacity(SEGMENTS * 6);
+    for i in 0..SEGMENTS {
+        let [a, b, c, d] = [
+            circle_vertex(axis, i, 0.0, SHAFT_RADIUS),
+            circle_vertex(axis, i, length, SHAFT_RADIUS),
+            circle_vertex(axis, i + 1, length, SHAFT_RADIUS),
+            circle_vertex(axis, i + 1, 0.0, SHAFT_RADIUS),
+        ];
+        vertices.extend([a, c, b, a, d, c]);
+    }
+    vertices
+}
+
+/// Unit arrow along `axis`: a thin shaft then a cone, wound counter-clockwise outward.
+fn arrow_vertices(axis: GizmoAxis) -> Vec<GizmoVertex> {
+    const SHAFT_LENGTH: f32 = 0.8;
+    const TIP_RADIUS: f32 = 0.06;
+
+    let mut vertices = shaft_vertices(axis, SHAFT_LENGTH);
+    for i in 0..SEGMENTS {
+        let base = circle_vertex(axis, i, SHAFT_LENGTH, TIP_RADIUS);
+        let next = circle_vertex(axis, i + 1, SHAFT_LENGTH, TIP_RADIUS);
+        vertices.extend([base, next, axis_vertex(axis, 1.0, 0.0, 0.0)]);
+        vertices.extend([axis_vertex(axis, SHAFT_LENGTH, 0.0, 0.0), next, base]);
+    }
+    vertices
+}
+
+/// Cube of half size `half` at `center`, wound counter-clockwise outward.
+fn cube_vertices(center: Vec3, half: f32, axis: u32) -> Vec<GizmoVertex> {
+    // Outward normal, then two tangents whose cross product is the normal
+    const FACES: [[Vec3; 3]; 6] = [
+        [Vec3::X, Vec3::Y, Vec3::Z],
+        [Vec3::Y, Vec3::Z, Vec3::X],
+        [Vec3::Z, Vec3::X, Vec3::Y],
+        [Vec3::NEG_X, Vec3::Z, Vec3::Y],
+        [Vec3::NEG_Y, Vec3::X, Vec3::Z],
+        [Vec3::NEG_Z, Vec3::Y, Vec3::X],
+    ];
+    let mut vertices = Vec::with_capacity(36);
+    for [normal, u, v] in FACES {
+        let [a, b, c, d] =
+            [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(s, t)| GizmoVertex {
+                position: (center + (normal + u * s + v * t) * half).to_array(),
+                axis,
+            });
+        vertices.extend([a, b, c, a, c, d]);
+    }
+    vertices
+}
+
+/// Unit scale handle along `axis`: a thin shaft ended by a cube.
+fn scale_handle_vertices(axis: GizmoAxis) -> Vec<GizmoVertex> {
+    let mut vertices = shaft_vertices(axis, 1.0 - HANDLE_SIZE);
+    vertices.extend(cube_vertices(axis.direction(), HANDLE_SIZE, axis as u32));
+    vertices
+}
+
+/// Unit ring around `axis`: a torus, wound counter-clockwise outward.
+fn ring_vertices(axis: GizmoAxis) -> Vec<GizmoVertex> {
+    const TUBE_RADIUS: f32 = 0.015;
+
+    // Built around X, then rotated by permuting the coordinates like the arrows
+    let vertex = |i: usize, j: usize| {
+        let around = i as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+        let tube = j as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+        let radius = 1.0 + TUBE_RADIUS * tube.cos();
+        let [x, y, z] = [
+            TUBE_RADIUS * tube.sin(),
+            radius * around.cos(),
+            radius * around.sin(),
+        ];
+        let position = match axis {
+            GizmoAxis::X => [x, y, z],
+            GizmoAxis::Y => [z, x, y],
+            GizmoAxis::Z => [y, z, x],
+        };
+        GizmoVertex {
+            position,
+            axis: axis as u32,
+        }
+    };
+
+    let mut vertices = Vec::with_capacity(RING_SEGMENTS * SEGMENTS * 6);
+    for i in 0..RING_SEGMENTS {
+        for j in 0..SEGMENTS {
+            let [a, b, c, d] = [
+                vertex(i, j),
+                vertex(i + 1, j),
+                vertex(i + 1, j + 1),
+                vertex(i, j + 1),
+            ];
+            vertices.extend([a, b, c, a, c, d]);
+        }
+    }
+    vertices
+}
+
+/// What a drag tracks of the cursor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GizmoMotion {
+    /// `axis_parameter` of the cursor when the drag started
+    Translate { start: f32 },
+    /// `ring_point` of the cursor at the last update, and the angle swept since the start
+    Rotate { last: Vec3, angle: f32 },
+    /// `axis_parameter` of the cursor when the drag started, or `uniform_scale_parameter`
+    /// for the center handle
+    Scale { start: f32 },
+}
+
+/// A drag of one of the arrows, rings or scale handles, from the press to the release.
+pub struct GizmoDrag {
+    pub handle: GizmoHandle,
+    /// Gizmo when the drag started, its axes stay put during the drag
+    pub frame: GizmoFrame,
+    pub motion: GizmoMotion,
+    /// Where the dragged instances were, how they were turned and scaled, restored before
+    /// recording the edit
+    pub start_transforms: Vec<(usize, Vec3, Quat, Vec3)>,
+}
+
+impl GizmoDrag {
+    /// World direction of the dragged axis, zero for the center handle.
+    pub fn direction(&self) -> Vec3 {
+        self.handle
+            .axis()
+            .map_or(Vec3::ZERO, |axis| self.frame.direction(axis))
+    }
+}
+
+pub struct GizmoRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    /// Of each `GizmoMode`, in the order of `GizmoMode::ALL`
+    vertex_ranges: [Range<u32>; 3],
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl GizmoRenderer {
+    pub fn new(device: &wgpu::Device, target_format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("gizmo_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                visibility: wgpu::ShaderStages::VERTEX,
+                ..uniform_layout_entry(0)
+            }],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Gizmo Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../gizmo.wgsl"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Gizmo Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_gizmo",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<GizmoVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Uint32],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_gizmo",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            // No depth buffer: the arrows stay visible through the scene, culling hides
+            // their far side
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let mut vertices = Vec::new();
+        let vertex_ranges = GizmoMode::ALL.map(|mode| {
+            let start = vertices.len() as u32;
+            let parts = GizmoAxis::ALL.into_iter();
+            match mode {
+                GizmoMode::Translate => vertices.extend(parts.flat_map(arrow_vertices)),
+                GizmoMode::Rotate => vertices.extend(parts.flat_map(ring_vertices)),
+                GizmoMode::Scale => {
+                    vertices.extend(parts.flat_map(scale_handle_vertices));
+                    vertices.extend(cube_vertices(
+                        Vec3::ZERO,
+                        CENTER_HANDLE_SIZE,
+                        GizmoHandle::Center.index(),
+                    ));
+                }
+            }
+            start..vertices.len() as u32
+        });
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Gizmo Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Gizmo Buffer"),
+            contents: bytemuck::cast_slice(&[GizmoUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gizmo_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            vertex_ranges,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    /// Draws the arrows, rings or scale handles of `mode` into `target`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        view_proj: Mat4,
+        frame: &GizmoFrame,
+        mode: GizmoMode,
+        highlighted: Option<GizmoHandle>,
+    ) {
+        let transform = view_proj * frame.matrix();
+        let uniform = GizmoUniform {
+            transform: transform.to_cols_array_2d(),
+            highlighted: highlighted.map_or(u32::MAX, GizmoHandle::index),
+            _padding: [0; 3],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Gizmo Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.draw(self.vertex_ranges[mode as usize].clone(), 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gizmo_uniform_layout() {
+        // Matches `Gizmo` in gizmo.wgsl
+        assert_eq!(std::mem::size_of::<GizmoUniform>(), 80);
+    }
+
+    #[test]
+    fn test_axis_parameter() {
+        // Straight down through (2, 0, 1)
+        let ray = Ray::new(Vec3::new(2.0, 5.0, 1.0), Vec3::NEG_Y);
+        let t = axis_parameter(&ray, Vec3::ZERO, Vec3::X).unwrap();
+        assert!((t - 2.0).abs() < 1e-5);
+        assert_eq!(axis_parameter(&ray, Vec3::ZERO, Vec3::Y), None);
+    }
+
+    #[test]
+    fn test_pick_axis_on_projected_arrow() {
+        let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO);
+        let view_proj = camera.build_view_projection_matrix();
+        let viewport = Vec2::new(800.0, 800.0);
+        let frame = GizmoFrame {
+            center: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: gizmo_scale(&camera, Vec3::ZERO),
+        };
+
+        // Halfway along the X arrow
+        let middle = view_proj
+            .project_point3(Vec3::X * frame.scale * 0.5)
+            .truncate();
+        assert_eq!(
+            pick_axis(view_proj, &frame, middle, viewport),
+            Some(GizmoAxis::X)
+        );
+        let above = view_proj
+            .project_point3(Vec3::Y * frame.scale * 0.5)
+            .truncate();
+        assert_eq!(
+            pick_axis(view_proj, &frame, above, viewport),
+            Some(GizmoAxis::Y)
+        );
+        assert_eq!(
+            pick_axis(view_proj, &frame, Vec2::new(-0.8, -0.8), viewport),
+            None
+        );
+
+        // In local space the X arrow points along the turned axis
+        let turned = GizmoFrame {
+            rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+            ..frame
+        };
+        assert_eq!(
+            pick_axis(view_proj, &turned, above, viewport),
+            Some(GizmoAxis::X)
+        );
+    }
+
+    #[test]
+    fn test_pick_ring() {
+        let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO);
+        let viewport = Vec2::new(800.0, 800.0);
+        let frame = GizmoFrame {
+            center: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: gizmo_scale(&camera, Vec3::ZERO),
+        };
+        let view_proj = camera.build_view_projection_matrix();
+        let ray_at =
+            |point: Vec3| Ray::from_ndc(&camera, view_proj.project_point3(point).truncate());
+
+        // The Z ring faces the camera, the X and Y ones are seen edge-on
+        let diagonal = Vec3::new(1.0, 1.0, 0.0).normalize() * frame.scale;
+        assert_eq!(
+            pick_ring(&ray_at(diagonal), &frame, viewport),
+            Some(GizmoAxis::Z)
+        );
+        assert_eq!(pick_ring(&ray_at(diagonal * 0.5), &frame, viewport), None);
+        assert_eq!(pick_ring(&ray_at(diagonal * 1.5), &frame, viewport), None);
+    }
+
+    #[test]
+    fn test_signed_angle() {
+        let quarter = signed_angle(Vec3::X, Vec3::Y, Vec3::Z);
+        assert!((quarter - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert!((signed_angle(Vec3::Y, Vec3::X, Vec3::Z) + quarter).abs() < 1e-6);
+        // Matches the rotations of glam
+        let turned = Quat::from_axis_angle(Vec3::Z, 0.3) * Vec3::X;
+        assert!((signed_angle(Vec3::X, turned, Vec3::Z) - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_pick_scale_handle() {
+        let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO);
+        let view_proj = camera.build_view_projection_matrix();
+        let viewport = Vec2::new(800.0, 800.0);
+        let frame = GizmoFrame {
+            center: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: gizmo_scale(&camera, Vec3::ZERO),
+        };
+        let pick = |point: Vec3| {
+            let ndc = view_proj.project_point3(point).truncate();
+            pick_scale_handle(view_proj, &frame, ndc, viewport)
+        };
+
+        // The axes start at the center, which takes precedence
+        assert_eq!(pick(Vec3::ZERO), Some(GizmoHandle::Center));
+        assert_eq!(
+            pick(Vec3::Y * frame.scale),
+            Some(GizmoHandle::Axis(GizmoAxis::Y))
+        );
+        assert_eq!(pick(Vec3::new(-1.0, -1.0, 0.0) * frame.scale), None);
+    }
+
+    #[test]
+    fn test_scale_factor() {
+        assert_eq!(scale_factor(0.5, 0.5, 2.0), 1.0);
+        assert_eq!(scale_factor(0.5, 2.5, 2.0), 2.0);
+        // Never flips the instances
+        assert_eq!(scale_factor(0.5, -10.0, 2.0), MIN_SCALE_FACTOR);
+
+        let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO);
+        let toward = |point: Vec3| Ray::new(camera.eye, point - camera.eye);
+        let up_right =
+            uniform_scale_parameter(&toward(Vec3::new(1.0, 1.0, 0.0)), &camera, Vec3::ZERO);
+        assert!((up_right.unwrap() - std::f32::consts::SQRT_2).abs() < 1e-5);
+        let down = uniform_scale_parameter(&toward(Vec3::NEG_Y), &camera, Vec3::ZERO);
+        assert!(down.unwrap() < 0.0);
+    }
+
+    #[test]
+    fn test_snap_to_grid() {
+        assert_eq!(
+            snap_to_grid(Vec3::new(0.3, -0.74, 1.26), 0.5),
+            Vec3::new(0.5, -0.5, 1.5)
+        );
+        assert_eq!(
+            snap_to_grid(Vec3::new(1.2, 3.8, 0.0), 2.5),
+            Vec3::new(0.0, 5.0, 0.0)
+        );
+        assert_eq!(snap_to_grid(Vec3::splat(0.3), 0.0), Vec3::splat(0.3));
+    }
+
+    #[test]
+    fn test_snap_angle() {
+        let snapped = snap_angle(50f32.to_radians(), 45.0);
+        assert!((snapped - 45f32.to_radians()).abs() < 1e-6);
+        let snapped = snap_angle(-100f32.to_radians(), 90.0);
+        assert!((snapped + 90f32.to_radians()).abs() < 1e-6);
+        assert_eq!(snap_angle(0.3, 0.0), 0.3);
+    }
+
+    #[test]
+    fn test_cube_winds_outward() {
+        let center = Vec3::new(1.0, 0.0, 0.0);
+        for triangle in cube_vertices(center, 0.1, 0).chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(triangle[i].position));
+            let centroid = (a + b + c) / 3.0;
+            assert!((b - a).cross(c - a).dot(centroid - center) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_ring_winds_outward() {
+        for axis in GizmoAxis::ALL {
+            for triangle in ring_vertices(axis).chunks(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(triangle[i].position));
+                let centroid = (a + b + c) / 3.0;
+                // Away from the circle the tube goes around
+                let in_plane = centroid - axis.direction() * centroid.dot(axis.direction());
+                let outward = centroid - in_plane.normalize();
+                assert!((b - a).cross(c - a).dot(outward) > 0.0, "{axis:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_gizmo_keeps_screen_size() {
+        let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO);
+        let near = gizmo_scale(&camera, Vec3::ZERO);
+        let far = gizmo_scale(&camera, Vec3::new(0.0, 0.0, -5.0));
+        assert!((far / near - 2.0).abs() < 1e-5);
+    }
+}
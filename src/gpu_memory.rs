@@ -0,0 +1,134 @@
+// Estimate of the GPU memory in use. wgpu reports no allocations, so the sizes of the
+// resources are added up as they are created and subtracted as they are replaced.
+
+/// Bytes held by the resources `State` creates. Intermediate targets owned by the
+/// post-process renderers are not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GpuMemoryTracker {
+    pub vertex_bytes: u64,
+    pub index_bytes: u64,
+    pub texture_bytes: u64,
+    /// Uniform, storage and readback buffers
+    pub buffer_bytes: u64,
+}
+
+impl GpuMemoryTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn track_buffer(&mut self, buffer: &wgpu::Buffer) {
+        *self.buffer_category(buffer.usage()) += buffer.size();
+    }
+
+    /// To call before `buffer` is dropped or replaced.
+    pub fn untrack_buffer(&mut self, buffer: &wgpu::Buffer) {
+        let bytes = self.buffer_category(buffer.usage());
+        *bytes = bytes.saturating_sub(buffer.size());
+    }
+
+    pub fn track_texture(&mut self, texture: &wgpu::Texture) {
+        self.texture_bytes += wgpu_texture_bytes(texture);
+    }
+
+    /// To call before `texture` is dropped or replaced.
+    pub fn untrack_texture(&mut self, texture: &wgpu::Texture) {
+        self.texture_bytes = self
+            .texture_bytes
+            .saturating_sub(wgpu_texture_bytes(texture));
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.vertex_bytes + self.index_bytes + self.texture_bytes + self.buffer_bytes
+    }
+
+    fn buffer_category(&mut self, usage: wgpu::BufferUsages) -> &mut u64 {
+        if usage.contains(wgpu::BufferUsages::VERTEX) {
+            &mut self.vertex_bytes
+        } else if usage.contains(wgpu::BufferUsages::INDEX) {
+            &mut self.index_bytes
+        } else {
+            &mut self.buffer_bytes
+        }
+    }
+}
+
+fn wgpu_texture_bytes(texture: &wgpu::Texture) -> u64 {
+    texture_bytes(
+        texture.size(),
+        texture.dimension(),
+        texture.format(),
+        texture.mip_level_count(),
+        texture.sample_count(),
+    )
+}
+
+/// Size of a texture and of its mip chain. Formats without a fixed texel size (packed
+/// depth) are counted as 4 bytes per texel, drivers often pad them to it anyway.
+pub fn texture_bytes(
+    size: wgpu::Extent3d,
+    dimension: wgpu::TextureDimension,
+    format: wgpu::TextureFormat,
+    mip_level_count: u32,
+    sample_count: u32,
+) -> u64 {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_bytes = format.block_copy_size(None).unwrap_or(4) as u64;
+    (0..mip_level_count)
+        .map(|level| {
+            let mip = size.mip_level_size(level, dimension);
+            let layers = match dimension {
+                wgpu::TextureDimension::D3 => mip.depth_or_array_layers,
+                _ => size.depth_or_array_layers,
+            };
+            mip.width.div_ceil(block_width) as u64
+                * mip.height.div_ceil(block_height) as u64
+                * layers as u64
+                * block_bytes
+        })
+        .sum::<u64>()
+        * sample_count as u64
+}
+
+/// For display, in mebibytes.
+pub fn bytes_to_megabytes(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_texture_bytes() {
+        let size = |width, height, depth_or_array_layers| wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers,
+        };
+        let d2 = wgpu::TextureDimension::D2;
+        let rgba8 = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+        assert_eq!(texture_bytes(size(64, 32, 1), d2, rgba8, 1, 1), 64 * 32 * 4);
+        // The mip chain adds about a third
+        assert_eq!(
+            texture_bytes(size(4, 4, 1), d2, rgba8, 3, 1),
+            (16 + 4 + 1) * 4
+        );
+        // Cube maps are six layers
+        assert_eq!(texture_bytes(size(8, 8, 6), d2, rgba8, 1, 1), 8 * 8 * 6 * 4);
+        assert_eq!(
+            texture_bytes(size(16, 16, 16), wgpu::TextureDimension::D3, rgba8, 1, 1),
+            16 * 16 * 16 * 4
+        );
+        assert_eq!(
+            texture_bytes(size(8, 8, 1), d2, wgpu::TextureFormat::Depth24Plus, 1, 4),
+            8 * 8 * 4 * 4
+        );
+        // 4x4 blocks of 8 bytes
+        assert_eq!(
+            texture_bytes(size(8, 8, 1), d2, wgpu::TextureFormat::Bc1RgbaUnorm, 1, 1),
+            4 * 8
+        );
+    }
+}
@@ -0,0 +1,98 @@
+// Reference grid on the Y = 0 plane, drawn procedurally over the opaque geometry of the
+// scene pass (grid.wgsl).
+
+use crate::{camera::Camera, post_process::uniform_layout_entry};
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use wgpu::util::DeviceExt;
+
+/// World distance between two grid lines unless configured otherwise
+pub const DEFAULT_GRID_SPACING: f32 = 1.0;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct GridUniform {
+    /// Inverse of the view projection the scene is drawn with, jitter included
+    pub inv_view_proj: [[f32; 4]; 4],
+    pub spacing: f32,
+    /// From the camera, where the grid has faded out
+    pub fade_distance: f32,
+    pub _padding: [f32; 2],
+}
+
+impl GridUniform {
+    pub fn new(view_proj: Mat4, camera: &Camera, spacing: f32) -> Self {
+        Self {
+            inv_view_proj: view_proj.inverse().to_cols_array_2d(),
+            spacing,
+            fade_distance: grid_fade_distance(camera),
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// Half the far plane, the lines further away would only be noise.
+pub fn grid_fade_distance(camera: &Camera) -> f32 {
+    camera.projection.zfar() * 0.5
+}
+
+/// Uniform of the grid. Its pipeline is one of the scene pipelines, which follow the
+/// sample count.
+pub struct Grid {
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Grid {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("grid_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ..uniform_layout_entry(0)
+            }],
+        });
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Grid Buffer"),
+            contents: bytemuck::cast_slice(&[GridUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("grid_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        Self {
+            bind_group_layout,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, uniform: GridUniform) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_uniform_layout() {
+        // Matches `Grid` in grid.wgsl
+        assert_eq!(std::mem::size_of::<GridUniform>(), 80);
+    }
+
+    #[test]
+    fn test_grid_fades_at_half_the_far_plane() {
+        let camera = Camera::default();
+        let uniform = GridUniform::new(camera.build_view_projection_matrix(), &camera, 2.0);
+        assert_eq!(uniform.fade_distance, camera.projection.zfar() * 0.5);
+        assert_eq!(uniform.spacing, 2.0);
+    }
+}
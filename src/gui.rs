@@ -6,6 +6,8 @@ use winit::{event::WindowEvent, window::Window};
 pub struct Gui {
     pub context: egui::Context,
     pub viewport_texture_id: Option<egui::TextureId>,
+    /// Shared by the texture thumbnails, see `thumbnail_texture`
+    thumbnail_texture_id: Option<egui::TextureId>,
     state: State,
     renderer: Renderer,
 }
@@ -22,6 +24,7 @@ impl Gui {
             state,
             renderer,
             viewport_texture_id: None,
+            thumbnail_texture_id: None,
         }
     }
 
@@ -59,6 +62,42 @@ impl Gui {
         // TODO: Handle resizing if necessary
     }
 
+    /// Collects the input gathered since the last frame.
+    /// Run it through a clone of `context`, then hand the output to `render`.
+    pub fn take_input(&mut self, window: &Window) -> egui::RawInput {
+        self.state.take_egui_input(window)
+    }
+
+    /// Makes `texture_view` drawable by egui for this frame, in the single slot the
+    /// thumbnails share. Called every frame a thumbnail is shown, so that it follows the
+    /// texture when it is replaced.
+    pub fn thumbnail_texture(
+        &mut self,
+        device: &Device,
+        texture_view: &wgpu::TextureView,
+    ) -> egui::TextureId {
+        match self.thumbnail_texture_id {
+            Some(id) => {
+                self.renderer.update_egui_texture_from_wgpu_texture(
+                    device,
+                    texture_view,
+                    wgpu::FilterMode::Linear,
+                    id,
+                );
+                id
+            }
+            None => {
+                let id = self.renderer.register_native_texture(
+                    device,
+                    texture_view,
+                    wgpu::FilterMode::Linear,
+                );
+                self.thumbnail_texture_id = Some(id);
+                id
+            }
+        }
+    }
+
     pub fn render(
         &mut self,
         device: &Device,
@@ -66,10 +105,11 @@ impl Gui {
         encoder: &mut wgpu::CommandEncoder,
         window: &Window,
         view: &wgpu::TextureView,
-        ui_callback: impl FnOnce(&egui::Context),
+        full_output: egui::FullOutput,
     ) {
-        let raw_input = self.state.take_egui_input(window);
-        let full_output = self.context.run(raw_input, ui_callback);
+        self.state
+            .handle_platform_output(window, full_output.platform_output);
+
         let tris = self
             .context
             .tessellate(full_output.shapes, full_output.pixels_per_point);
@@ -1,22 +1,210 @@
-use crate::camera::CameraController;
+use crate::{
+    camera::{Camera, CameraController, OrbitController},
+    input_recorder::RecordedEvent,
+    keybindings::{KEY_BINDINGS_FILE, KeyAction, KeyBindings, is_bindable, parse_key},
+};
 use winit::{
-    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
-    keyboard::PhysicalKey,
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
     window::Window,
 };
 
+/// Editor commands triggered by keyboard shortcuts, drained by `State::update`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorAction {
+    /// Alt+I
+    ToggleInstanceIdView,
+    /// F, see `InputHandler::toggle_camera_mode`
+    ToggleCameraMode,
+    /// `KeyBindings::screenshot`, see `State::take_screenshot`
+    TakeScreenshot,
+    /// Delete or Backspace, see `State::delete_selection`
+    DeleteSelection,
+    /// Ctrl+D, see `State::duplicate_selection`
+    Duplicate,
+    /// Ctrl+A, see `State::select_all`
+    SelectAll,
+    /// Escape or Ctrl+Shift+A, see `State::deselect_all`
+    DeselectAll,
+    /// Ctrl+I, see `State::invert_selection`
+    InvertSelection,
+    /// Ctrl+Z, see `State::undo`
+    Undo,
+    /// Ctrl+Y, see `State::redo`
+    Redo,
+    /// G, switches `State::gizmo_space`
+    ToggleGizmoSpace,
+    /// Ctrl+1 to Ctrl+9, see `State::save_camera_bookmark`
+    SaveCameraBookmark(usize),
+    /// 1 to 9, see `State::restore_camera_bookmark`
+    RestoreCameraBookmark(usize),
+}
+
+/// Which controller drives the camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraMode {
+    /// First person, through `InputHandler::camera_controller`. Scroll moves forward, or
+    /// zooms optically while Alt is held
+    Fly,
+    /// Left (or right) drag orbits, middle drag pans, scroll zooms
+    Orbit(OrbitController),
+}
+
 pub struct InputHandler {
     pub camera_controller: CameraController,
+    pub camera_mode: CameraMode,
     pub right_mouse_pressed: bool,
+    /// Only tracked for drags starting on the scene
+    left_mouse_pressed: bool,
+    middle_mouse_pressed: bool,
     pub is_scene_focused: bool,
+    modifiers: ModifiersState,
+    pending_actions: Vec<EditorAction>,
+    /// Drained into the console by `State::update`, which the handler cannot reach
+    pending_warnings: Vec<String>,
+    pub key_bindings: KeyBindings,
+    /// Action bound to the next key pressed, see `start_remap`
+    remapping: Option<KeyAction>,
 }
 
 impl InputHandler {
+    /// Loads the key bindings from `KEY_BINDINGS_FILE`, keeping the defaults if it is
+    /// missing or invalid.
     pub fn new(camera_speed: f32) -> Self {
+        let mut pending_warnings = Vec::new();
+        let key_bindings = KeyBindings::load(std::path::Path::new(KEY_BINDINGS_FILE))
+            .unwrap_or_else(|e| {
+                pending_warnings.push(format!("Cannot load {KEY_BINDINGS_FILE}: {e}"));
+                KeyBindings::default()
+            });
         Self {
             camera_controller: CameraController::new(camera_speed),
+            camera_mode: CameraMode::Fly,
             right_mouse_pressed: false,
+            left_mouse_pressed: false,
+            middle_mouse_pressed: false,
             is_scene_focused: false,
+            modifiers: ModifiersState::empty(),
+            pending_actions: Vec::new(),
+            pending_warnings,
+            key_bindings,
+            remapping: None,
+        }
+    }
+
+    /// Binds the next key pressed to `action`, anywhere in the window. Escape cancels.
+    pub fn start_remap(&mut self, action: KeyAction) {
+        self.remapping = Some(action);
+    }
+
+    pub fn remapping(&self) -> Option<KeyAction> {
+        self.remapping
+    }
+
+    /// Ends the remap started by `start_remap` with `key`. Returns whether the bindings
+    /// changed: Escape, keys that cannot be bound and keys bound to another action leave
+    /// them as they were.
+    fn finish_remap(&mut self, key: KeyCode) -> bool {
+        let Some(action) = self.remapping else {
+            return false;
+        };
+        if key == KeyCode::Escape {
+            self.remapping = None;
+            return false;
+        }
+        if !is_bindable(key) {
+            return false;
+        }
+        if let Some(other) = self.key_bindings.action(key)
+            && other != action
+        {
+            self.pending_warnings
+                .push(format!("{key:?} is already bound to {}", other.name()));
+            return false;
+        }
+        self.remapping = None;
+        self.key_bindings.set(action, key);
+        true
+    }
+
+    /// Returns the actions triggered since the last call.
+    pub fn take_actions(&mut self) -> Vec<EditorAction> {
+        std::mem::take(&mut self.pending_actions)
+    }
+
+    /// Returns the warnings raised since the last call, loading the key bindings included.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.pending_warnings)
+    }
+
+    fn shortcut_action(&self, keycode: KeyCode) -> Option<EditorAction> {
+        if let Some(digit) = digit(keycode) {
+            // Bookmarks are numbered from 1, stored from 0
+            return Some(if self.modifiers.control_key() {
+                EditorAction::SaveCameraBookmark(digit - 1)
+            } else {
+                EditorAction::RestoreCameraBookmark(digit - 1)
+            });
+        }
+        if keycode == self.key_bindings.screenshot {
+            return Some(EditorAction::TakeScreenshot);
+        }
+        match keycode {
+            KeyCode::KeyI if self.modifiers.alt_key() => Some(EditorAction::ToggleInstanceIdView),
+            KeyCode::KeyI if self.modifiers.control_key() => Some(EditorAction::InvertSelection),
+            KeyCode::KeyF => Some(EditorAction::ToggleCameraMode),
+            KeyCode::Delete | KeyCode::Backspace => Some(EditorAction::DeleteSelection),
+            KeyCode::KeyD if self.modifiers.control_key() => Some(EditorAction::Duplicate),
+            KeyCode::KeyA if self.modifiers.control_key() && self.modifiers.shift_key() => {
+                Some(EditorAction::DeselectAll)
+            }
+            KeyCode::KeyA if self.modifiers.control_key() => Some(EditorAction::SelectAll),
+            KeyCode::Escape => Some(EditorAction::DeselectAll),
+            KeyCode::KeyZ if self.modifiers.control_key() => Some(EditorAction::Undo),
+            KeyCode::KeyY if self.modifiers.control_key() => Some(EditorAction::Redo),
+            KeyCode::KeyG => Some(EditorAction::ToggleGizmoSpace),
+            _ => None,
+        }
+    }
+
+    pub fn is_orbit_mode(&self) -> bool {
+        matches!(self.camera_mode, CameraMode::Orbit(_))
+    }
+
+    /// Takes over a camera moved from outside (e.g. by loading a scene) so that the next
+    /// `update_camera` does not move it back. Orbit mode turns around the camera's target.
+    pub fn sync_with_camera(&mut self, camera: &Camera) {
+        self.camera_controller.look_along(camera);
+        if let CameraMode::Orbit(orbit) = &mut self.camera_mode {
+            *orbit = OrbitController::from_camera(camera, camera.target);
+        }
+    }
+
+    /// Switches between fly and orbit mode without moving the camera. Orbiting starts
+    /// around `pivot`.
+    pub fn toggle_camera_mode(&mut self, camera: &Camera, pivot: glam::Vec3) {
+        self.camera_mode = match self.camera_mode {
+            CameraMode::Fly => CameraMode::Orbit(OrbitController::from_camera(camera, pivot)),
+            CameraMode::Orbit(_) => {
+                self.camera_controller.look_along(camera);
+                CameraMode::Fly
+            }
+        };
+    }
+
+    /// Moves `camera` with the active controller, once per frame. A transition to a
+    /// bookmark runs first, whatever the mode.
+    pub fn update_camera(&mut self, camera: &mut Camera) {
+        if self.camera_controller.is_transitioning() {
+            self.camera_controller.update_camera(camera);
+            if !self.camera_controller.is_transitioning() {
+                self.sync_with_camera(camera);
+            }
+            return;
+        }
+        match &self.camera_mode {
+            CameraMode::Fly => self.camera_controller.update_camera(camera),
+            CameraMode::Orbit(orbit) => orbit.update_camera(camera),
         }
     }
 
@@ -28,62 +216,310 @@ impl InputHandler {
         is_scene_hovered: bool,
     ) -> bool {
         match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                false
+            }
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
                         state,
                         physical_key: PhysicalKey::Code(keycode),
+                        repeat,
                         ..
                     },
                 ..
-            } => {
-                if self.is_scene_focused && !egui_consumed {
-                    self.camera_controller.process_keyboard(*keycode, *state)
-                } else {
-                    false
+            } => self.process_key(*keycode, *state, *repeat, egui_consumed),
+            WindowEvent::MouseInput { state, button, .. } => self.process_mouse_button(
+                *button,
+                *state,
+                Some(window),
+                egui_consumed,
+                is_scene_hovered,
+            ),
+            WindowEvent::MouseWheel { delta, .. } if is_scene_hovered => {
+                self.process_scroll(scroll_lines(delta));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Replays an input recorded by `InputRecorder`. egui never sees it, so it only
+    /// reaches the camera and the shortcuts.
+    pub fn process_recorded(&mut self, event: &RecordedEvent) {
+        match event {
+            RecordedEvent::Key {
+                key,
+                pressed,
+                repeat,
+                egui_consumed,
+            } => match parse_key(key) {
+                Some(keycode) => {
+                    self.process_key(keycode, element_state(*pressed), *repeat, *egui_consumed);
                 }
+                None => self
+                    .pending_warnings
+                    .push(format!("Cannot replay unknown key {key:?}")),
+            },
+            RecordedEvent::Modifiers {
+                shift,
+                control,
+                alt,
+                super_key,
+            } => {
+                let mut modifiers = ModifiersState::empty();
+                modifiers.set(ModifiersState::SHIFT, *shift);
+                modifiers.set(ModifiersState::CONTROL, *control);
+                modifiers.set(ModifiersState::ALT, *alt);
+                modifiers.set(ModifiersState::SUPER, *super_key);
+                self.modifiers = modifiers;
             }
-            WindowEvent::MouseInput {
-                state,
-                button: MouseButton::Right,
-                ..
+            RecordedEvent::MouseButton {
+                button,
+                pressed,
+                egui_consumed,
+                scene_hovered,
             } => {
-                let is_pressed = *state == ElementState::Pressed;
-                self.right_mouse_pressed = is_pressed;
+                self.process_mouse_button(
+                    (*button).into(),
+                    element_state(*pressed),
+                    None,
+                    *egui_consumed,
+                    *scene_hovered,
+                );
+            }
+            RecordedEvent::Scroll { lines } => self.process_scroll(*lines),
+            RecordedEvent::MouseMotion { dx, dy } => self.handle_mouse_motion((*dx, *dy)),
+        }
+    }
+
+    fn process_key(
+        &mut self,
+        keycode: KeyCode,
+        state: ElementState,
+        repeat: bool,
+        egui_consumed: bool,
+    ) -> bool {
+        let is_pressed = state == ElementState::Pressed;
+        if is_pressed && self.remapping.is_some() {
+            if self.finish_remap(keycode)
+                && let Err(e) = self
+                    .key_bindings
+                    .save(std::path::Path::new(KEY_BINDINGS_FILE))
+            {
+                self.pending_warnings
+                    .push(format!("Cannot save {KEY_BINDINGS_FILE}: {e}"));
+            }
+            return true;
+        }
+        if !self.is_scene_focused || egui_consumed {
+            return false;
+        }
+        if is_pressed && let Some(action) = self.shortcut_action(keycode) {
+            if !repeat {
+                self.pending_actions.push(action);
+            }
+            return true;
+        }
+        self.camera_controller
+            .process_keyboard(keycode, state, &self.key_bindings)
+    }
 
-                if is_pressed {
-                    if is_scene_hovered {
-                        self.is_scene_focused = true;
+    /// `window` confines the cursor during right drags, replays have none.
+    fn process_mouse_button(
+        &mut self,
+        button: MouseButton,
+        state: ElementState,
+        window: Option<&Window>,
+        egui_consumed: bool,
+        is_scene_hovered: bool,
+    ) -> bool {
+        let is_pressed = state == ElementState::Pressed;
+        if button == MouseButton::Right {
+            self.right_mouse_pressed = is_pressed;
+
+            return if is_pressed {
+                if is_scene_hovered {
+                    self.is_scene_focused = true;
+                    if let Some(window) = window {
                         let _ = window.set_cursor_grab(winit::window::CursorGrabMode::Confined);
                         window.set_cursor_visible(false);
-                        true
-                    } else {
-                        false
                     }
+                    true
                 } else {
+                    false
+                }
+            } else {
+                if let Some(window) = window {
                     let _ = window.set_cursor_grab(winit::window::CursorGrabMode::None);
                     window.set_cursor_visible(true);
-                    true
-                }
-            }
-            WindowEvent::MouseInput {
-                state: ElementState::Pressed,
-                ..
-            } => {
-                if is_scene_hovered {
-                    self.is_scene_focused = true;
-                } else if egui_consumed {
-                    self.is_scene_focused = false;
                 }
-                false
+                true
+            };
+        }
+
+        if is_pressed {
+            if is_scene_hovered {
+                self.is_scene_focused = true;
+            } else if egui_consumed {
+                self.is_scene_focused = false;
             }
-            _ => false,
+        }
+        // Drags start on the scene but may end anywhere
+        let dragging = is_pressed && is_scene_hovered;
+        match button {
+            MouseButton::Left => self.left_mouse_pressed = dragging,
+            MouseButton::Middle => self.middle_mouse_pressed = dragging,
+            _ => {}
+        }
+        // Clicks still reach egui for picking
+        false
+    }
+
+    /// Scroll over the scene, `lines` from `scroll_lines`.
+    fn process_scroll(&mut self, lines: f32) {
+        match &mut self.camera_mode {
+            // Alt changes the field of view instead of moving
+            CameraMode::Fly => self
+                .camera_controller
+                .process_scroll(lines, self.modifiers.alt_key()),
+            CameraMode::Orbit(orbit) => orbit.zoom(lines),
         }
     }
 
     pub fn handle_mouse_motion(&mut self, delta: (f64, f64)) {
-        if self.right_mouse_pressed {
-            self.camera_controller.process_mouse(delta.0, delta.1);
+        match &mut self.camera_mode {
+            CameraMode::Fly => {
+                if self.right_mouse_pressed {
+                    self.camera_controller.process_mouse(delta.0, delta.1);
+                }
+            }
+            CameraMode::Orbit(orbit) => {
+                if self.left_mouse_pressed || self.right_mouse_pressed {
+                    orbit.rotate(delta.0, delta.1);
+                } else if self.middle_mouse_pressed {
+                    orbit.pan(delta.0, delta.1);
+                }
+            }
         }
     }
 }
+
+/// Scroll lines of a wheel event, positive when scrolling up.
+pub fn scroll_lines(delta: &MouseScrollDelta) -> f32 {
+    match delta {
+        MouseScrollDelta::LineDelta(_, y) => *y,
+        // Touchpads report pixels, about 50 per line
+        MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+    }
+}
+
+fn element_state(pressed: bool) -> ElementState {
+    if pressed {
+        ElementState::Pressed
+    } else {
+        ElementState::Released
+    }
+}
+
+/// 1 to 9 for the digit keys of the main row.
+fn digit(keycode: KeyCode) -> Option<usize> {
+    let digit = match keycode {
+        KeyCode::Digit1 => 1,
+        KeyCode::Digit2 => 2,
+        KeyCode::Digit3 => 3,
+        KeyCode::Digit4 => 4,
+        KeyCode::Digit5 => 5,
+        KeyCode::Digit6 => 6,
+        KeyCode::Digit7 => 7,
+        KeyCode::Digit8 => 8,
+        KeyCode::Digit9 => 9,
+        _ => return None,
+    };
+    Some(digit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_recorded_events() {
+        let mut input = InputHandler::new(0.01);
+        input.key_bindings = KeyBindings::default();
+        let key = |key: &str, egui_consumed| RecordedEvent::Key {
+            key: key.to_string(),
+            pressed: true,
+            repeat: false,
+            egui_consumed,
+        };
+
+        // Keys only reach the scene once it was clicked
+        input.process_recorded(&key("F12", false));
+        assert!(input.take_actions().is_empty());
+        input.process_recorded(&RecordedEvent::MouseButton {
+            button: crate::input_recorder::RecordedButton::Right,
+            pressed: true,
+            egui_consumed: false,
+            scene_hovered: true,
+        });
+        assert!(input.right_mouse_pressed && input.is_scene_focused);
+        input.process_recorded(&key("F12", true));
+        assert!(input.take_actions().is_empty());
+        input.process_recorded(&RecordedEvent::Modifiers {
+            shift: false,
+            control: true,
+            alt: false,
+            super_key: false,
+        });
+        input.process_recorded(&key("Digit2", false));
+        assert_eq!(input.take_actions(), [EditorAction::SaveCameraBookmark(1)]);
+    }
+
+    #[test]
+    fn test_remap_key_binding() {
+        let mut input = InputHandler::new(0.01);
+        input.key_bindings = KeyBindings::default();
+        assert!(!input.finish_remap(KeyCode::KeyZ));
+
+        input.start_remap(KeyAction::Screenshot);
+        // Not bindable, still waiting
+        assert!(!input.finish_remap(KeyCode::Delete));
+        assert_eq!(input.remapping(), Some(KeyAction::Screenshot));
+        assert!(input.finish_remap(KeyCode::F5));
+        assert_eq!(input.remapping(), None);
+        assert_eq!(
+            input.shortcut_action(KeyCode::F5),
+            Some(EditorAction::TakeScreenshot)
+        );
+        assert_eq!(input.shortcut_action(KeyCode::F12), None);
+
+        input.start_remap(KeyAction::Forward);
+        assert!(!input.finish_remap(KeyCode::Escape));
+        assert_eq!(input.remapping(), None);
+        assert_eq!(input.key_bindings.forward, KeyCode::KeyW);
+    }
+
+    #[test]
+    fn test_remap_rejects_shortcut_and_bound_keys() {
+        let mut input = InputHandler::new(0.01);
+        input.key_bindings = KeyBindings::default();
+        input.take_warnings();
+
+        input.start_remap(KeyAction::Forward);
+        // F switches the camera mode before the bindings are looked up
+        assert!(!input.finish_remap(KeyCode::KeyF));
+        assert!(!input.finish_remap(KeyCode::Digit3));
+        // S already moves backward
+        assert!(!input.finish_remap(KeyCode::KeyS));
+        assert_eq!(input.take_warnings(), ["KeyS is already bound to backward"]);
+        assert_eq!(input.remapping(), Some(KeyAction::Forward));
+        assert!(input.finish_remap(KeyCode::KeyW));
+        assert_eq!(input.key_bindings, KeyBindings::default());
+        assert_eq!(
+            input.shortcut_action(KeyCode::KeyF),
+            Some(EditorAction::ToggleCameraMode)
+        );
+    }
+}
@@ -0,0 +1,293 @@
+// Recording of the inputs reaching `InputHandler`, timestamped from the start of the
+// recording and replayed at the same pace by `State::play_recording`, for repeatable
+// benchmarks and demos. Replayed events bypass egui: they drive the camera and the
+// shortcuts, not the widgets.
+
+use crate::{
+    error::{OrengineError, Result},
+    input::scroll_lines,
+    keybindings::key_name,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+use winit::{
+    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
+    keyboard::PhysicalKey,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordedButton {
+    Left,
+    Right,
+    Middle,
+    Back,
+    Forward,
+    Other(u16),
+}
+
+impl From<MouseButton> for RecordedButton {
+    fn from(button: MouseButton) -> Self {
+        match button {
+            MouseButton::Left => RecordedButton::Left,
+            MouseButton::Right => RecordedButton::Right,
+            MouseButton::Middle => RecordedButton::Middle,
+            MouseButton::Back => RecordedButton::Back,
+            MouseButton::Forward => RecordedButton::Forward,
+            MouseButton::Other(id) => RecordedButton::Other(id),
+        }
+    }
+}
+
+impl From<RecordedButton> for MouseButton {
+    fn from(button: RecordedButton) -> Self {
+        match button {
+            RecordedButton::Left => MouseButton::Left,
+            RecordedButton::Right => MouseButton::Right,
+            RecordedButton::Middle => MouseButton::Middle,
+            RecordedButton::Back => MouseButton::Back,
+            RecordedButton::Forward => MouseButton::Forward,
+            RecordedButton::Other(id) => MouseButton::Other(id),
+        }
+    }
+}
+
+/// An input as `InputHandler` received it, with what egui made of it at the time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum RecordedEvent {
+    Key {
+        /// See `key_name`
+        key: String,
+        pressed: bool,
+        repeat: bool,
+        egui_consumed: bool,
+    },
+    Modifiers {
+        shift: bool,
+        control: bool,
+        alt: bool,
+        super_key: bool,
+    },
+    MouseButton {
+        button: RecordedButton,
+        pressed: bool,
+        egui_consumed: bool,
+        scene_hovered: bool,
+    },
+    /// Over the scene, see `scroll_lines`
+    Scroll { lines: f32 },
+    /// Raw motion, which drives the camera look and orbit
+    MouseMotion { dx: f64, dy: f64 },
+}
+
+impl RecordedEvent {
+    /// `None` for the events `InputHandler` ignores.
+    pub fn from_window_event(
+        event: &WindowEvent,
+        egui_consumed: bool,
+        scene_hovered: bool,
+    ) -> Option<Self> {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                let state = modifiers.state();
+                Some(RecordedEvent::Modifiers {
+                    shift: state.shift_key(),
+                    control: state.control_key(),
+                    alt: state.alt_key(),
+                    super_key: state.super_key(),
+                })
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state,
+                        physical_key: PhysicalKey::Code(keycode),
+                        repeat,
+                        ..
+                    },
+                ..
+            } => Some(RecordedEvent::Key {
+                key: key_name(*keycode),
+                pressed: *state == ElementState::Pressed,
+                repeat: *repeat,
+                egui_consumed,
+            }),
+            WindowEvent::MouseInput { state, button, .. } => Some(RecordedEvent::MouseButton {
+                button: (*button).into(),
+                pressed: *state == ElementState::Pressed,
+                egui_consumed,
+                scene_hovered,
+            }),
+            WindowEvent::MouseWheel { delta, .. } if scene_hovered => Some(RecordedEvent::Scroll {
+                lines: scroll_lines(delta),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Events in time order, each at its time from the start of the recording.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub events: Vec<(Duration, RecordedEvent)>,
+}
+
+impl InputRecording {
+    /// Time of the last event.
+    pub fn duration(&self) -> Duration {
+        self.events.last().map_or(Duration::ZERO, |(time, _)| *time)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| OrengineError::Generic(format!("Cannot serialize the recording: {e}")))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| OrengineError::Generic(format!("Invalid input recording: {e}")))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// Records events from the moment it is created.
+#[derive(Debug)]
+pub struct InputRecorder {
+    start: Instant,
+    recording: InputRecording,
+}
+
+impl Default for InputRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputRecorder {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            recording: InputRecording::default(),
+        }
+    }
+
+    pub fn record(&mut self, event: RecordedEvent) {
+        self.recording.events.push((self.start.elapsed(), event));
+    }
+
+    pub fn finish(self) -> InputRecording {
+        self.recording
+    }
+}
+
+/// Replays a recording from the moment it is created.
+#[derive(Debug)]
+pub struct InputPlayback {
+    recording: InputRecording,
+    start: Instant,
+    /// First event not replayed yet
+    next: usize,
+}
+
+impl InputPlayback {
+    pub fn new(recording: InputRecording) -> Self {
+        Self {
+            recording,
+            start: Instant::now(),
+            next: 0,
+        }
+    }
+
+    /// Events due at `now` that were not returned yet. Those that fell between two
+    /// frames all come out on the next one, in their order.
+    pub fn take_due(&mut self, now: Instant) -> Vec<RecordedEvent> {
+        let elapsed = now.saturating_duration_since(self.start);
+        let events = &self.recording.events[self.next..];
+        let due = events.partition_point(|(time, _)| *time <= elapsed);
+        self.next += due;
+        events[..due]
+            .iter()
+            .map(|(_, event)| event.clone())
+            .collect()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.recording.events.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording() -> InputRecording {
+        InputRecording {
+            events: vec![
+                (
+                    Duration::from_millis(10),
+                    RecordedEvent::Key {
+                        key: "KeyW".to_string(),
+                        pressed: true,
+                        repeat: false,
+                        egui_consumed: false,
+                    },
+                ),
+                (
+                    Duration::from_millis(20),
+                    RecordedEvent::MouseButton {
+                        button: RecordedButton::Right,
+                        pressed: true,
+                        egui_consumed: false,
+                        scene_hovered: true,
+                    },
+                ),
+                (
+                    Duration::from_millis(500),
+                    RecordedEvent::MouseMotion { dx: 4.0, dy: -2.0 },
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_input_recording_json_roundtrip() {
+        let recording = recording();
+        let json = recording.to_json().unwrap();
+        assert_eq!(InputRecording::from_json(&json).unwrap(), recording);
+        assert_eq!(recording.duration(), Duration::from_millis(500));
+        assert!(InputRecording::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn test_input_playback_timing() {
+        let mut playback = InputPlayback::new(recording());
+        let start = playback.start;
+        assert!(playback.take_due(start).is_empty());
+        assert_eq!(
+            playback.take_due(start + Duration::from_millis(100)).len(),
+            2
+        );
+        assert!(
+            playback
+                .take_due(start + Duration::from_millis(100))
+                .is_empty()
+        );
+        assert!(!playback.is_finished());
+        assert_eq!(
+            playback.take_due(start + Duration::from_secs(1)),
+            [RecordedEvent::MouseMotion { dx: 4.0, dy: -2.0 }]
+        );
+        assert!(playback.is_finished());
+    }
+}
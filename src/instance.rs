@@ -1,21 +1,200 @@
-use glam::{Mat4, Quat, Vec3};
+use crate::error::{OrengineError, Result};
+use glam::{Mat4, Quat, Vec3, Vec4};
+use std::{collections::HashSet, ops::Range};
+
+/// Maximum number of instances a scene can hold. Instance indices must fit in a
+/// `u16` for the tools addressing them that way, and the instance buffer is never
+/// allowed to grow beyond `MAX_INSTANCES * size_of::<InstanceRaw>()` bytes.
+pub const MAX_INSTANCES: usize = u16::MAX as usize;
+
+/// Room reserved in the instance buffer unless configured otherwise, instances can be
+/// added at runtime up to this count.
+pub const DEFAULT_INSTANCE_CAPACITY: usize = 1024;
+
+/// Fails with `OrengineError::InstanceLimitExceeded` if `count` instances would not fit.
+pub fn check_instance_limit(count: usize) -> Result<()> {
+    if count > MAX_INSTANCES {
+        return Err(OrengineError::InstanceLimitExceeded {
+            count,
+            limit: MAX_INSTANCES,
+        });
+    }
+    Ok(())
+}
 
 // 1. The "Logic" version (CPU)
 // This is what you'll manipulate to place your objects
+#[derive(Debug, Clone, PartialEq)]
 pub struct Instance {
+    /// Shown in the hierarchy and when hovering the instance
+    pub name: String,
     pub position: Vec3,
     pub rotation: Quat,
+    /// Per-axis, in the instance's local space. A negative component mirrors the instance.
+    pub scale: Vec3,
+    /// Hidden instances stay in the scene but are neither drawn nor pickable
+    pub visible: bool,
+    /// Linear RGBA multiplying the material's base color
+    pub tint: Vec4,
+    /// Index of the material drawn instead of each mesh's own (in `State`'s materials)
+    pub material_override: Option<usize>,
+    /// Instance it is grouped under in the hierarchy. Transforms stay in world space
+    pub parent: Option<usize>,
+    /// Locked instances cannot be picked in the viewport, only from the hierarchy
+    pub locked: bool,
+}
+
+impl Default for Instance {
+    /// At the origin, unrotated and unscaled.
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+            visible: true,
+            tint: Vec4::ONE,
+            material_override: None,
+            parent: None,
+            locked: false,
+        }
+    }
 }
 
 impl Instance {
-    // Converts logic to raw data for the GPU
-    pub fn to_raw(&self) -> InstanceRaw {
-        // Creates a transformation matrix: Translation * Rotation
-        let model = Mat4::from_rotation_translation(self.rotation, self.position);
+    /// Unscaled, visible instance.
+    pub fn new(position: Vec3, rotation: Quat) -> Self {
+        Self {
+            position,
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    // Creates a transformation matrix: Translation * Rotation * Scale
+    pub fn model_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
+    }
+
+    /// An odd number of negative scale components turns the model inside out: its
+    /// triangles wind clockwise on screen and must be drawn with the mirrored pipelines.
+    pub fn is_mirrored(&self) -> bool {
+        self.scale.x * self.scale.y * self.scale.z < 0.0
+    }
+
+    // Converts logic to raw data for the GPU. `scene_index` is where the instance is in the
+    // scene, which the buffers do not tell once culled.
+    pub fn to_raw(&self, scene_index: usize) -> InstanceRaw {
         InstanceRaw {
-            model: model.to_cols_array_2d(),
+            model: self.model_matrix().to_cols_array_2d(),
+            scene_index: scene_index as u32,
+            tint: self.tint.to_array(),
+        }
+    }
+}
+
+/// A line of the hierarchy panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HierarchyRow {
+    pub id: usize,
+    /// 0 for the instances without a parent
+    pub depth: usize,
+    pub has_children: bool,
+}
+
+/// The instances in tree order, each after its parent and before its next sibling, the
+/// children of `collapsed` ones left out. Instances whose parent is unknown, or caught in
+/// a parenting cycle, are shown as roots.
+pub fn hierarchy_rows(instances: &[Instance], collapsed: &HashSet<usize>) -> Vec<HierarchyRow> {
+    let mut children = vec![Vec::new(); instances.len()];
+    let mut roots = Vec::new();
+    for (i, instance) in instances.iter().enumerate() {
+        match instance.parent.filter(|&p| p < instances.len() && p != i) {
+            Some(parent) => children[parent].push(i),
+            None => roots.push(i),
+        }
+    }
+
+    let mut rows = Vec::with_capacity(instances.len());
+    let mut visited = vec![false; instances.len()];
+    let push_tree = |root: usize, rows: &mut Vec<HierarchyRow>, visited: &mut [bool]| {
+        // Children pushed in reverse so that they pop in index order. Those of collapsed
+        // instances are still visited, only hidden
+        let mut stack = vec![(root, 0, true)];
+        while let Some((id, depth, shown)) = stack.pop() {
+            if std::mem::replace(&mut visited[id], true) {
+                continue;
+            }
+            if shown {
+                rows.push(HierarchyRow {
+                    id,
+                    depth,
+                    has_children: !children[id].is_empty(),
+                });
+            }
+            let shown = shown && !collapsed.contains(&id);
+            stack.extend(children[id].iter().rev().map(|&c| (c, depth + 1, shown)));
+        }
+    };
+    for root in roots {
+        push_tree(root, &mut rows, &mut visited);
+    }
+    // Cycles never reach a root, each is shown from its lowest index
+    for i in 0..instances.len() {
+        if !visited[i] {
+            push_tree(i, &mut rows, &mut visited);
+        }
+    }
+    rows
+}
+
+/// Splits `instances` into consecutive ranges sharing the same winding, so that each range
+/// can be drawn with a single call and the matching pipeline.
+pub fn winding_runs<'a>(
+    instances: impl IntoIterator<Item = &'a Instance>,
+) -> Vec<(Range<u32>, bool)> {
+    let mut runs: Vec<(Range<u32>, bool)> = Vec::new();
+    for (i, instance) in instances.into_iter().enumerate() {
+        let i = i as u32;
+        let mirrored = instance.is_mirrored();
+        match runs.last_mut() {
+            Some((range, m)) if *m == mirrored => range.end = i + 1,
+            _ => runs.push((i..i + 1, mirrored)),
+        }
+    }
+    runs
+}
+
+/// Consecutive instances of the instance buffer drawn with a single call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrawRun {
+    pub instances: Range<u32>,
+    /// Drawn with the mirrored pipelines, see `Instance::is_mirrored`
+    pub mirrored: bool,
+    pub material_override: Option<usize>,
+}
+
+/// Like `winding_runs`, also splitting where the material override changes.
+pub fn draw_runs<'a>(instances: impl IntoIterator<Item = &'a Instance>) -> Vec<DrawRun> {
+    let mut runs: Vec<DrawRun> = Vec::new();
+    for (i, instance) in instances.into_iter().enumerate() {
+        let i = i as u32;
+        let mirrored = instance.is_mirrored();
+        match runs.last_mut() {
+            Some(run)
+                if run.mirrored == mirrored
+                    && run.material_override == instance.material_override =>
+            {
+                run.instances.end = i + 1
+            }
+            _ => runs.push(DrawRun {
+                instances: i..i + 1,
+                mirrored,
+                material_override: instance.material_override,
+            }),
         }
     }
+    runs
 }
 
 // 2. The "Raw" version (GPU)
@@ -24,6 +203,8 @@ impl Instance {
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct InstanceRaw {
     model: [[f32; 4]; 4],
+    scene_index: u32,
+    tint: [f32; 4],
 }
 
 impl InstanceRaw {
@@ -63,7 +244,159 @@ impl InstanceRaw {
                     shader_location: 8,
                     format: wgpu::VertexFormat::Float32x4,
                 },
+                // Location 9: Index of the instance in the scene
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                // Location 10: Tint
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 17]>() as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_instance_limit() {
+        assert!(check_instance_limit(0).is_ok());
+        assert!(check_instance_limit(MAX_INSTANCES).is_ok());
+
+        let err = check_instance_limit(MAX_INSTANCES + 1).unwrap_err();
+        assert!(matches!(
+            err,
+            OrengineError::InstanceLimitExceeded { count, limit }
+                if count == MAX_INSTANCES + 1 && limit == MAX_INSTANCES
+        ));
+    }
+
+    #[test]
+    fn test_hierarchy_rows() {
+        let parented = |parent| Instance {
+            parent,
+            ..Default::default()
+        };
+        // 0 > (2 > 3, 4), 1, and 5 <-> 6 in a cycle
+        let instances = [
+            parented(None),
+            parented(Some(9)),
+            parented(Some(0)),
+            parented(Some(2)),
+            parented(Some(0)),
+            parented(Some(6)),
+            parented(Some(5)),
+        ];
+        let rows = hierarchy_rows(&instances, &HashSet::new());
+        let ids: Vec<_> = rows.iter().map(|row| (row.id, row.depth)).collect();
+        assert_eq!(
+            ids,
+            [(0, 0), (2, 1), (3, 2), (4, 1), (1, 0), (5, 0), (6, 1)]
+        );
+        assert!(rows[0].has_children && !rows[2].has_children);
+
+        let rows = hierarchy_rows(&instances, &HashSet::from([2, 5]));
+        let ids: Vec<_> = rows.iter().map(|row| row.id).collect();
+        assert_eq!(ids, [0, 2, 4, 1, 5]);
+    }
+
+    fn instance(scale: Vec3) -> Instance {
+        Instance {
+            position: Vec3::new(1.0, 2.0, 3.0),
+            rotation: Quat::from_rotation_y(0.5),
+            scale,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_negative_scale_mirrors() {
+        let mirrored = instance(Vec3::new(-1.0, 1.0, 1.0));
+        assert!(mirrored.is_mirrored());
+        assert!(mirrored.model_matrix().determinant() < 0.0);
+        // Two flipped axes amount to a rotation
+        assert!(!instance(Vec3::new(-1.0, -1.0, 1.0)).is_mirrored());
+        assert!(!instance(Vec3::splat(2.0)).is_mirrored());
+
+        // The mirrored triangle winds the other way around its transformed normal
+        let [a, b, c] = [Vec3::ZERO, Vec3::X, Vec3::Y];
+        let m = mirrored.model_matrix();
+        let [a, b, c] = [a, b, c].map(|p| m.transform_point3(p));
+        let normal = m.transform_vector3(Vec3::Z);
+        assert!((b - a).cross(c - a).dot(normal) < 0.0);
+    }
+
+    #[test]
+    fn test_local_ray_through_scaled_instance() {
+        // Stretched 4x along X: a local unit box spans [-4, 4] in the world
+        let stretched = Instance {
+            scale: Vec3::new(4.0, 1.0, 1.0),
+            ..Default::default()
+        };
+        let ray = crate::camera::Ray::new(Vec3::new(3.0, 5.0, 0.0), Vec3::NEG_Y);
+        let local = ray.transform(&stretched.model_matrix().inverse());
+        let unit_box = crate::models::Aabb::new(Vec3::splat(-1.0), Vec3::ONE);
+        // The distance stays in world units since the direction is not renormalized
+        assert_eq!(local.intersect_aabb(&unit_box), Some(4.0));
+
+        let beside = crate::camera::Ray::new(Vec3::new(5.0, 5.0, 0.0), Vec3::NEG_Y);
+        let local = beside.transform(&stretched.model_matrix().inverse());
+        assert_eq!(local.intersect_aabb(&unit_box), None);
+    }
+
+    #[test]
+    fn test_instance_raw_layout() {
+        // Matrix at locations 5 to 8, scene index at 9, tint at 10
+        assert_eq!(std::mem::size_of::<InstanceRaw>(), 84);
+        assert_eq!(InstanceRaw::desc().attributes[4].offset, 64);
+        assert_eq!(InstanceRaw::desc().attributes[5].offset, 68);
+
+        let raw = Instance {
+            tint: Vec4::new(1.0, 0.5, 0.25, 1.0),
+            ..Default::default()
+        }
+        .to_raw(7);
+        assert_eq!(raw.scene_index, 7);
+        assert_eq!(raw.tint, [1.0, 0.5, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn test_winding_runs() {
+        let mirror = Vec3::new(-1.0, 1.0, 1.0);
+        let instances = [Vec3::ONE, Vec3::ONE, mirror, mirror, Vec3::ONE].map(instance);
+        assert_eq!(
+            winding_runs(&instances),
+            vec![(0..2, false), (2..4, true), (4..5, false)]
+        );
+        assert!(winding_runs(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_draw_runs_split_on_material() {
+        let mut instances = [Vec3::ONE, Vec3::ONE, Vec3::ONE].map(instance);
+        instances[1].material_override = Some(2);
+        instances[2].material_override = Some(2);
+        instances[2].scale = Vec3::new(-1.0, 1.0, 1.0);
+
+        let runs = draw_runs(&instances);
+        let split = runs
+            .iter()
+            .map(|run| (run.instances.clone(), run.mirrored, run.material_override))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            split,
+            vec![
+                (0..1, false, None),
+                (1..2, false, Some(2)),
+                (2..3, true, Some(2))
+            ]
+        );
+    }
+}
@@ -0,0 +1,363 @@
+// Remappable keyboard shortcuts, read from keybindings.toml when the input handler is
+// created and edited from the "Raccourcis" window. The file maps action names to winit key
+// codes, e.g. `forward = "KeyW"`; missing actions keep their default key.
+
+use crate::error::{OrengineError, Result};
+use std::path::Path;
+use winit::keyboard::KeyCode;
+
+/// Loaded by `InputHandler::new` from the working directory, when present
+pub const KEY_BINDINGS_FILE: &str = "keybindings.toml";
+
+/// Keys with a name, after their `KeyCode` variant.
+const KEY_CODES: &[KeyCode] = &[
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::Space,
+    KeyCode::Tab,
+    KeyCode::Enter,
+    KeyCode::Insert,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::AltLeft,
+    KeyCode::AltRight,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+    KeyCode::Quote,
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::Backslash,
+    KeyCode::Backquote,
+    KeyCode::Escape,
+    KeyCode::Delete,
+    KeyCode::Backspace,
+];
+
+/// Named keys that cannot be bound because `InputHandler::shortcut_action` handles them
+/// before the bindings: Escape also cancels a remap, Delete and Backspace delete the
+/// selection, the digits restore the camera bookmarks, F switches the camera mode and G the
+/// gizmo space.
+const RESERVED_KEYS: [KeyCode; 14] = [
+    KeyCode::Escape,
+    KeyCode::Delete,
+    KeyCode::Backspace,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+];
+
+/// Name of `key` in keybindings.toml.
+pub fn key_name(key: KeyCode) -> String {
+    format!("{key:?}")
+}
+
+/// Inverse of `key_name`, `None` for keys without a name.
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    KEY_CODES.iter().copied().find(|&key| key_name(key) == name)
+}
+
+pub fn is_bindable(key: KeyCode) -> bool {
+    KEY_CODES.contains(&key) && !RESERVED_KEYS.contains(&key)
+}
+
+/// What a binding triggers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyAction {
+    Forward,
+    Backward,
+    Left,
+    Right,
+    Up,
+    Down,
+    Screenshot,
+}
+
+impl KeyAction {
+    pub const ALL: [KeyAction; 7] = [
+        KeyAction::Forward,
+        KeyAction::Backward,
+        KeyAction::Left,
+        KeyAction::Right,
+        KeyAction::Up,
+        KeyAction::Down,
+        KeyAction::Screenshot,
+    ];
+
+    /// Key of the action in keybindings.toml
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyAction::Forward => "forward",
+            KeyAction::Backward => "backward",
+            KeyAction::Left => "left",
+            KeyAction::Right => "right",
+            KeyAction::Up => "up",
+            KeyAction::Down => "down",
+            KeyAction::Screenshot => "screenshot",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            KeyAction::Forward => "Avancer",
+            KeyAction::Backward => "Reculer",
+            KeyAction::Left => "Gauche",
+            KeyAction::Right => "Droite",
+            KeyAction::Up => "Monter",
+            KeyAction::Down => "Descendre",
+            KeyAction::Screenshot => "Capture d'écran",
+        }
+    }
+}
+
+/// Keys of the fly camera and of the editor shortcuts that can be remapped. The camera
+/// moves with the arrow keys too, whatever the bindings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBindings {
+    pub forward: KeyCode,
+    pub backward: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub up: KeyCode,
+    pub down: KeyCode,
+    pub screenshot: KeyCode,
+}
+
+impl Default for KeyBindings {
+    /// ZQSD on AZERTY keyboards, which physically are WASD
+    fn default() -> Self {
+        Self {
+            forward: KeyCode::KeyW,
+            backward: KeyCode::KeyS,
+            left: KeyCode::KeyA,
+            right: KeyCode::KeyD,
+            up: KeyCode::KeyQ,
+            down: KeyCode::KeyE,
+            screenshot: KeyCode::F12,
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn get(&self, action: KeyAction) -> KeyCode {
+        match action {
+            KeyAction::Forward => self.forward,
+            KeyAction::Backward => self.backward,
+            KeyAction::Left => self.left,
+            KeyAction::Right => self.right,
+            KeyAction::Up => self.up,
+            KeyAction::Down => self.down,
+            KeyAction::Screenshot => self.screenshot,
+        }
+    }
+
+    pub fn set(&mut self, action: KeyAction, key: KeyCode) {
+        let binding = match action {
+            KeyAction::Forward => &mut self.forward,
+            KeyAction::Backward => &mut self.backward,
+            KeyAction::Left => &mut self.left,
+            KeyAction::Right => &mut self.right,
+            KeyAction::Up => &mut self.up,
+            KeyAction::Down => &mut self.down,
+            KeyAction::Screenshot => &mut self.screenshot,
+        };
+        *binding = key;
+    }
+
+    /// Action bound to `key`, the first one in `KeyAction::ALL` order if several are.
+    pub fn action(&self, key: KeyCode) -> Option<KeyAction> {
+        KeyAction::ALL
+            .into_iter()
+            .find(|&action| self.get(action) == key)
+    }
+
+    /// Two actions bound to the same key, which would only trigger the first one.
+    pub fn conflict(&self) -> Option<(KeyAction, KeyAction)> {
+        KeyAction::ALL.into_iter().find_map(|action| {
+            self.action(self.get(action))
+                .filter(|&first| first != action)
+                .map(|first| (first, action))
+        })
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let document: toml_edit::DocumentMut = toml
+            .parse()
+            .map_err(|e| OrengineError::Generic(format!("Invalid key bindings: {e}")))?;
+        let mut bindings = Self::default();
+        for (name, item) in document.iter() {
+            let Some(action) = KeyAction::ALL.into_iter().find(|a| a.name() == name) else {
+                log::warn!("Unknown action {:?} in the key bindings", name);
+                continue;
+            };
+            let key = item
+                .as_str()
+                .and_then(parse_key)
+                .filter(|&key| is_bindable(key));
+            let key = key.ok_or_else(|| {
+                OrengineError::Generic(format!("Invalid key for {name:?} in the key bindings"))
+            })?;
+            bindings.set(action, key);
+        }
+        if let Some((first, second)) = bindings.conflict() {
+            return Err(OrengineError::Generic(format!(
+                "{:?} and {:?} share the same key in the key bindings",
+                first.name(),
+                second.name()
+            )));
+        }
+        Ok(bindings)
+    }
+
+    pub fn to_toml(&self) -> String {
+        KeyAction::ALL
+            .into_iter()
+            .map(|action| format!("{} = \"{}\"\n", action.name(), key_name(self.get(action))))
+            .collect()
+    }
+
+    /// Bindings saved at `path`, the defaults if there is no such file.
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(toml) => Self::from_toml(&toml),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_toml())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_bindings_toml_roundtrip() {
+        let mut bindings = KeyBindings::default();
+        bindings.set(KeyAction::Forward, KeyCode::KeyZ);
+        bindings.set(KeyAction::Screenshot, KeyCode::F5);
+        let toml = bindings.to_toml();
+        assert!(toml.contains("forward = \"KeyZ\""));
+        assert_eq!(KeyBindings::from_toml(&toml).unwrap(), bindings);
+    }
+
+    #[test]
+    fn test_key_bindings_from_partial_toml() {
+        let bindings = KeyBindings::from_toml("left = \"ArrowLeft\"\njump = \"Space\"").unwrap();
+        assert_eq!(bindings.left, KeyCode::ArrowLeft);
+        assert_eq!(bindings.forward, KeyCode::KeyW);
+        assert_eq!(bindings.action(KeyCode::ArrowLeft), Some(KeyAction::Left));
+
+        assert!(KeyBindings::from_toml("up = \"NotAKey\"").is_err());
+        assert!(KeyBindings::from_toml("up = ").is_err());
+        assert!(KeyBindings::from_toml("up = \"Delete\"").is_err());
+        assert_eq!(parse_key("Escape"), Some(KeyCode::Escape));
+        assert_eq!(parse_key("Fn"), None);
+    }
+
+    #[test]
+    fn test_key_bindings_reject_shortcut_keys() {
+        // Handled as bookmarks, camera mode and gizmo space before any binding
+        for key in ["Digit1", "Digit9", "KeyF", "KeyG"] {
+            assert!(!is_bindable(parse_key(key).unwrap()));
+            assert!(KeyBindings::from_toml(&format!("forward = \"{key}\"")).is_err());
+        }
+        assert!(is_bindable(KeyCode::Digit0));
+    }
+
+    #[test]
+    fn test_key_bindings_reject_shared_key() {
+        let mut bindings = KeyBindings::default();
+        assert_eq!(bindings.conflict(), None);
+        bindings.set(KeyAction::Up, KeyCode::KeyW);
+        assert_eq!(
+            bindings.conflict(),
+            Some((KeyAction::Forward, KeyAction::Up))
+        );
+
+        // `backward` keeps its default S
+        assert!(KeyBindings::from_toml("forward = \"KeyS\"").is_err());
+        assert!(KeyBindings::from_toml("forward = \"KeyS\"\nbackward = \"KeyW\"").is_ok());
+    }
+}
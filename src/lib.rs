@@ -1,9 +1,15 @@
 pub mod error;
 pub mod input;
+mod input_recorder;
+pub use input_recorder::*;
+mod keybindings;
+pub use keybindings::*;
 mod vertex;
 pub use vertex::*;
 mod camera;
 pub use camera::*;
+mod camera_path;
+pub use camera_path::*;
 mod models;
 pub use models::*;
 mod textures;
@@ -15,4 +21,69 @@ pub use instance::*;
 mod light;
 pub use light::*;
 mod gui;
-pub use gui::*;
\ No newline at end of file
+pub use gui::*;
+mod mesh_processing;
+pub use mesh_processing::*;
+mod selection;
+pub use selection::*;
+mod selection_outline;
+pub use selection_outline::*;
+mod gizmo;
+pub use gizmo::*;
+mod grid;
+pub use grid::*;
+mod scene_archive;
+pub use scene_archive::*;
+mod scene;
+pub use scene::*;
+mod outline;
+mod post_process;
+pub use outline::*;
+mod ssao;
+pub use ssao::*;
+mod dof;
+pub use dof::*;
+mod motion_blur;
+pub use motion_blur::*;
+mod antialias;
+pub use antialias::*;
+mod config;
+pub use config::*;
+mod utils;
+pub use utils::*;
+mod picking;
+pub use picking::*;
+mod watcher;
+pub use watcher::*;
+mod exposure;
+pub use exposure::*;
+mod tonemap;
+pub use tonemap::*;
+mod commands;
+pub use commands::*;
+mod color_grade;
+pub use color_grade::*;
+mod gamma;
+pub use gamma::*;
+mod vignette;
+pub use vignette::*;
+mod chromatic_aberration;
+pub use chromatic_aberration::*;
+mod ao_bake;
+pub use ao_bake::*;
+mod fog;
+pub use fog::*;
+mod frame_stats;
+pub use frame_stats::*;
+mod console;
+pub use console::*;
+mod gpu_memory;
+pub use gpu_memory::*;
+mod bvh;
+pub use bvh::*;
+mod transparency;
+pub use transparency::*;
+#[cfg(feature = "compute")]
+mod compute;
+#[cfg(feature = "compute")]
+pub use compute::*;
@@ -1,11 +1,252 @@
+use crate::error::{OrengineError, Result};
 use bytemuck::{Pod, Zeroable};
 
+/// Size of the light array in the shader, the most point lights a scene can hold.
+pub const MAX_LIGHTS: usize = 16;
+
 #[repr(C)]
-#[derive(Debug, Copy, Clone, Pod, Zeroable)]
-pub struct LightUniform {
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct PointLight {
     pub position: [f32; 3],
     // Due to uniforms requiring 16-byte (4 float) spacing, we need padding
-    pub _padding: u32,
+    pub _pad: u32,
+    /// Linear RGB
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Distance at which the light has faded out completely
+    pub radius: f32,
+    pub _pad2: [u32; 3],
+}
+
+impl PointLight {
+    pub fn new(position: [f32; 3], color: [f32; 3]) -> Self {
+        Self {
+            position,
+            _pad: 0,
+            color,
+            intensity: 1.0,
+            radius: 20.0,
+            _pad2: [0; 3],
+        }
+    }
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        Self::new([2.0, 2.0, 2.0], [1.0, 1.0, 1.0])
+    }
+}
+
+/// Distant light such as the sun, lighting every surface from the same direction.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct DirectionalLight {
+    /// Direction the light travels in, does not need to be normalized
+    pub direction: [f32; 3],
+    pub _pad: u32,
+    /// Linear RGB
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: [f32; 3], color: [f32; 3]) -> Self {
+        Self {
+            direction,
+            _pad: 0,
+            color,
+            intensity: 1.0,
+        }
+    }
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self::new([0.5, -1.0, 0.5], [1.0, 1.0, 1.0])
+    }
+}
+
+/// Widest outer half-angle of a spot light, a cone of 90° or more is no longer a cone
+pub const MAX_SPOT_ANGLE: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+/// Light shining in a cone: full brightness inside `inner_angle`, fading out up to
+/// `outer_angle`. Both are half-angles in radians.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SpotLight {
+    pub position: [f32; 3],
+    /// Where the cone points, does not need to be normalized
+    pub direction: [f32; 3],
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    /// Linear RGB
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl SpotLight {
+    pub fn new(position: [f32; 3], direction: [f32; 3], color: [f32; 3]) -> Self {
+        Self {
+            position,
+            direction,
+            inner_angle: 20.0_f32.to_radians(),
+            outer_angle: 30.0_f32.to_radians(),
+            color,
+            intensity: 1.0,
+        }
+    }
+
+    /// Keeps the outer angle below `MAX_SPOT_ANGLE` and the inner angle within the outer one.
+    pub fn clamp_angles(&mut self) {
+        self.outer_angle = self.outer_angle.clamp(0.0, MAX_SPOT_ANGLE);
+        self.inner_angle = self.inner_angle.clamp(0.0, self.outer_angle);
+    }
+
+    pub fn to_uniform(&self) -> SpotLightUniform {
+        let mut light = *self;
+        light.clamp_angles();
+        SpotLightUniform {
+            position: light.position,
+            enabled: 1,
+            direction: light.direction,
+            intensity: light.intensity,
+            color: light.color,
+            cos_inner: light.inner_angle.cos(),
+            cos_outer: light.outer_angle.cos(),
+            _pad: [0; 3],
+        }
+    }
+}
+
+impl Default for SpotLight {
+    fn default() -> Self {
+        Self::new([0.0, 4.0, 0.0], [0.0, -1.0, 0.0], [1.0, 1.0, 1.0])
+    }
+}
+
+/// GPU side of an optional `SpotLight`, the angles are stored as cosines.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct SpotLightUniform {
+    pub position: [f32; 3],
+    /// 0 when the scene has no spot light
+    pub enabled: u32,
+    pub direction: [f32; 3],
+    pub intensity: f32,
     pub color: [f32; 3],
-    pub _padding2: u32,
+    pub cos_inner: f32,
+    pub cos_outer: f32,
+    pub _pad: [u32; 3],
+}
+
+impl SpotLightUniform {
+    /// Uniform for `None`, lighting nothing.
+    pub fn disabled() -> Self {
+        Self::zeroed()
+    }
+}
+
+/// Every point light of the scene, uploaded as a single uniform. Only the first `count`
+/// entries are lit.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct LightArray {
+    pub lights: [PointLight; MAX_LIGHTS],
+    pub count: u32,
+    pub _pad: [u32; 3],
+}
+
+impl LightArray {
+    pub fn new() -> Self {
+        Self::zeroed()
+    }
+
+    /// The active lights.
+    pub fn lights(&self) -> &[PointLight] {
+        &self.lights[..self.count as usize]
+    }
+
+    pub fn lights_mut(&mut self) -> &mut [PointLight] {
+        &mut self.lights[..self.count as usize]
+    }
+
+    /// Adds a light and returns its index, fails with `OrengineError::LightLimitExceeded`
+    /// when all `MAX_LIGHTS` slots are taken.
+    pub fn push(&mut self, light: PointLight) -> Result<usize> {
+        let index = self.count as usize;
+        if index >= MAX_LIGHTS {
+            return Err(OrengineError::LightLimitExceeded { limit: MAX_LIGHTS });
+        }
+        self.lights[index] = light;
+        self.count += 1;
+        Ok(index)
+    }
+
+    /// Removes the light at `index`, the following ones move down by one.
+    pub fn remove(&mut self, index: usize) -> Option<PointLight> {
+        let light = *self.lights().get(index)?;
+        self.lights
+            .copy_within(index + 1..self.count as usize, index);
+        self.count -= 1;
+        self.lights[self.count as usize] = PointLight::zeroed();
+        Some(light)
+    }
+}
+
+impl Default for LightArray {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_light_layout_matches_wgsl() {
+        // vec3 + u32, vec3 + f32, f32 + 3 x u32 padding
+        assert_eq!(std::mem::size_of::<PointLight>(), 48);
+        assert_eq!(std::mem::size_of::<DirectionalLight>(), 32);
+        assert_eq!(std::mem::size_of::<SpotLightUniform>(), 64);
+        // Array, count, then rounded up to the 16-byte struct alignment
+        assert_eq!(std::mem::size_of::<LightArray>(), 48 * MAX_LIGHTS + 16);
+    }
+
+    #[test]
+    fn test_spot_light_angles_clamped() {
+        let mut light = SpotLight {
+            inner_angle: 1.0,
+            outer_angle: 0.5,
+            ..Default::default()
+        };
+        light.clamp_angles();
+        assert_eq!(light.inner_angle, 0.5);
+
+        light.outer_angle = std::f32::consts::PI;
+        let uniform = light.to_uniform();
+        assert!(uniform.cos_outer > 0.0);
+        assert!(uniform.cos_inner >= uniform.cos_outer);
+        assert_eq!(uniform.enabled, 1);
+    }
+
+    #[test]
+    fn test_light_array_push_remove() {
+        let mut lights = LightArray::new();
+        assert!(lights.lights().is_empty());
+
+        for i in 0..MAX_LIGHTS {
+            let light = PointLight::new([i as f32, 0.0, 0.0], [1.0; 3]);
+            assert_eq!(lights.push(light).unwrap(), i);
+        }
+        assert!(matches!(
+            lights.push(PointLight::default()),
+            Err(OrengineError::LightLimitExceeded { limit: MAX_LIGHTS })
+        ));
+
+        let removed = lights.remove(1).unwrap();
+        assert_eq!(removed.position[0], 1.0);
+        assert_eq!(lights.lights().len(), MAX_LIGHTS - 1);
+        assert_eq!(lights.lights()[1].position[0], 2.0);
+        assert!(lights.remove(MAX_LIGHTS - 1).is_none());
+    }
 }
@@ -0,0 +1,603 @@
+// Offline mesh utilities working on CPU-side `Mesh` data (before GPU upload)
+//
+// Library only for now: the engine has no LOD system yet, so nothing at load or
+// render time calls `decimate_mesh`/`generate_lods`; tools and future loaders do.
+
+use crate::{
+    models::{Aabb, Mesh},
+    vertex::Vertex,
+};
+use glam::{DMat3, DVec3, Vec2, Vec3};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+/// Weight applied to the virtual planes guarding open borders, so that
+/// silhouettes and holes survive the simplification.
+const BOUNDARY_WEIGHT: f64 = 1000.0;
+
+/// Collapses shrinking a triangle's area below this fraction of its original area are rejected.
+const SLIVER_RATIO: f64 = 1e-4;
+
+/// Fraction of the triangles kept at each successive LOD level.
+const LOD_REDUCTION_RATIO: f32 = 0.5;
+
+/// Below this many triangles a mesh is not worth another LOD level.
+const LOD_MIN_TRIANGLES: usize = 32;
+
+/// Symmetric 4x4 matrix of the quadric error metric, stored as its upper triangle:
+/// [a², ab, ac, ad, b², bc, bd, c², cd, d²]
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Quadric of the plane ax + by + cz + d = 0, with (a, b, c) normalized.
+    fn from_plane(normal: DVec3, d: f64, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|v| v * weight),
+        )
+    }
+
+    fn add(&self, other: &Quadric) -> Quadric {
+        let mut out = self.0;
+        for (o, v) in out.iter_mut().zip(other.0.iter()) {
+            *o += v;
+        }
+        Quadric(out)
+    }
+
+    /// Squared distance sum of `p` to every plane accumulated in the quadric.
+    fn error(&self, p: DVec3) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+
+    /// Position minimizing the error, if the 3x3 system is well conditioned.
+    fn optimal_position(&self) -> Option<DVec3> {
+        let q = &self.0;
+        let m = DMat3::from_cols(
+            DVec3::new(q[0], q[1], q[2]),
+            DVec3::new(q[1], q[4], q[5]),
+            DVec3::new(q[2], q[5], q[7]),
+        );
+        if m.determinant().abs() < 1e-10 {
+            return None;
+        }
+        Some(m.inverse() * -DVec3::new(q[3], q[6], q[8]))
+    }
+}
+
+/// Candidate edge contraction, ordered so that `BinaryHeap` pops the cheapest first.
+struct Collapse {
+    cost: f64,
+    a: usize,
+    b: usize,
+    target: DVec3,
+    // Versions of both vertices when the candidate was computed, stale entries are skipped
+    version_a: u32,
+    version_b: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Simplifier {
+    positions: Vec<DVec3>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<u32>,
+    alive: Vec<bool>,
+    /// Index of the source `Vertex` whose attributes (UV, color) are kept for each welded vertex
+    source_vertex: Vec<usize>,
+    triangles: Vec<[usize; 3]>,
+    triangle_alive: Vec<bool>,
+    /// Triangles touching each vertex (may contain dead triangles, filtered lazily)
+    vertex_triangles: Vec<Vec<usize>>,
+    live_triangles: usize,
+}
+
+impl Simplifier {
+    fn new(mesh: &Mesh) -> Self {
+        // 1. Weld vertices sharing the same position. OBJ loading duplicates vertices
+        // along UV seams, which would otherwise show up as borders and tear apart.
+        let mut welded: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut positions = Vec::new();
+        let mut source_vertex = Vec::new();
+        let remap: Vec<usize> = mesh
+            .vertices
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let key = v.position.map(f32::to_bits);
+                *welded.entry(key).or_insert_with(|| {
+                    positions.push(Vec3::from(v.position).as_dvec3());
+                    source_vertex.push(i);
+                    positions.len() - 1
+                })
+            })
+            .collect();
+
+        // 2. Rebuild the triangle list on the welded vertices, dropping degenerate ones
+        let triangles: Vec<[usize; 3]> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|t| {
+                [
+                    remap[t[0] as usize],
+                    remap[t[1] as usize],
+                    remap[t[2] as usize],
+                ]
+            })
+            .filter(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+            .collect();
+
+        let mut vertex_triangles = vec![Vec::new(); positions.len()];
+        for (ti, t) in triangles.iter().enumerate() {
+            for &v in t {
+                vertex_triangles[v].push(ti);
+            }
+        }
+
+        let vertex_count = positions.len();
+        let triangle_count = triangles.len();
+        let mut simplifier = Self {
+            positions,
+            quadrics: vec![Quadric::default(); vertex_count],
+            versions: vec![0; vertex_count],
+            alive: vec![true; vertex_count],
+            source_vertex,
+            triangle_alive: vec![true; triangle_count],
+            triangles,
+            vertex_triangles,
+            live_triangles: triangle_count,
+        };
+        simplifier.compute_quadrics();
+        simplifier
+    }
+
+    fn compute_quadrics(&mut self) {
+        // 3. Each vertex accumulates the planes of its triangles, weighted by area
+        let mut edge_use: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        for (ti, t) in self.triangles.iter().enumerate() {
+            let [p0, p1, p2] = t.map(|v| self.positions[v]);
+            let cross = (p1 - p0).cross(p2 - p0);
+            let double_area = cross.length();
+            if double_area <= f64::EPSILON {
+                continue;
+            }
+            let normal = cross / double_area;
+            let q = Quadric::from_plane(normal, -normal.dot(p0), double_area * 0.5);
+            for &v in t {
+                self.quadrics[v] = self.quadrics[v].add(&q);
+            }
+
+            for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+                let key = (a.min(b), a.max(b));
+                edge_use.entry(key).or_insert((0, ti)).0 += 1;
+            }
+        }
+
+        // 4. Border edges (used by a single triangle) get a perpendicular constraint plane
+        for ((a, b), (count, ti)) in edge_use {
+            if count != 1 {
+                continue;
+            }
+            let [p0, p1, p2] = self.triangles[ti].map(|v| self.positions[v]);
+            let face_normal = (p1 - p0).cross(p2 - p0).normalize_or_zero();
+            let edge = self.positions[b] - self.positions[a];
+            let normal = edge.cross(face_normal).normalize_or_zero();
+            if normal == DVec3::ZERO {
+                continue;
+            }
+            let d = -normal.dot(self.positions[a]);
+            let q = Quadric::from_plane(normal, d, BOUNDARY_WEIGHT * edge.length_squared());
+            self.quadrics[a] = self.quadrics[a].add(&q);
+            self.quadrics[b] = self.quadrics[b].add(&q);
+        }
+    }
+
+    fn candidate(&self, a: usize, b: usize) -> Collapse {
+        let q = self.quadrics[a].add(&self.quadrics[b]);
+        let (pa, pb) = (self.positions[a], self.positions[b]);
+
+        let target = q.optimal_position().unwrap_or_else(|| {
+            // Singular system (flat or linear neighbourhood): pick the best of the endpoints/midpoint
+            [pa, pb, (pa + pb) * 0.5]
+                .into_iter()
+                .min_by(|x, y| q.error(*x).total_cmp(&q.error(*y)))
+                .unwrap()
+        });
+
+        Collapse {
+            cost: q.error(target).max(0.0),
+            a,
+            b,
+            target,
+            version_a: self.versions[a],
+            version_b: self.versions[b],
+        }
+    }
+
+    fn push_edges_of(&self, v: usize, heap: &mut BinaryHeap<Collapse>) {
+        let mut neighbours = HashSet::new();
+        for &ti in &self.vertex_triangles[v] {
+            if !self.triangle_alive[ti] {
+                continue;
+            }
+            for &n in &self.triangles[ti] {
+                if n != v {
+                    neighbours.insert(n);
+                }
+            }
+        }
+        for n in neighbours {
+            heap.push(self.candidate(v.min(n), v.max(n)));
+        }
+    }
+
+    /// Moving `a` and `b` to `target` must not flip any triangle that survives the collapse.
+    fn collapse_flips_triangles(&self, a: usize, b: usize, target: DVec3) -> bool {
+        for &v in &[a, b] {
+            for &ti in &self.vertex_triangles[v] {
+                if !self.triangle_alive[ti] {
+                    continue;
+                }
+                let t = self.triangles[ti];
+                if t.contains(&a) && t.contains(&b) {
+                    // Removed by the collapse
+                    continue;
+                }
+                let old = t.map(|i| self.positions[i]);
+                let new = t.map(|i| {
+                    if i == a || i == b {
+                        target
+                    } else {
+                        self.positions[i]
+                    }
+                });
+                let old_normal = (old[1] - old[0]).cross(old[2] - old[0]);
+                let new_normal = (new[1] - new[0]).cross(new[2] - new[0]);
+                // Slivers count as flips too: they turn degenerate once stored back as f32
+                if old_normal.dot(new_normal) <= 0.0
+                    || new_normal.length() <= old_normal.length() * SLIVER_RATIO
+                {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn collapse(&mut self, c: &Collapse) {
+        let (a, b) = (c.a, c.b);
+        self.positions[a] = c.target;
+        self.quadrics[a] = self.quadrics[a].add(&self.quadrics[b]);
+        self.alive[b] = false;
+        self.versions[a] += 1;
+        self.versions[b] += 1;
+
+        let b_triangles = std::mem::take(&mut self.vertex_triangles[b]);
+        for ti in b_triangles {
+            if !self.triangle_alive[ti] {
+                continue;
+            }
+            let t = &mut self.triangles[ti];
+            if t.contains(&a) {
+                // Triangle shares the contracted edge, it degenerates
+                self.triangle_alive[ti] = false;
+                self.live_triangles -= 1;
+            } else {
+                for v in t.iter_mut() {
+                    if *v == b {
+                        *v = a;
+                    }
+                }
+                self.vertex_triangles[a].push(ti);
+            }
+        }
+        let alive = &self.triangle_alive;
+        self.vertex_triangles[a].retain(|&ti| alive[ti]);
+    }
+
+    fn run(&mut self, target_triangle_count: usize) {
+        let mut heap = BinaryHeap::new();
+        for v in 0..self.positions.len() {
+            for &ti in &self.vertex_triangles[v] {
+                for &n in &self.triangles[ti] {
+                    // Each edge is queued once, from its lowest vertex
+                    if n > v {
+                        heap.push(self.candidate(v, n));
+                    }
+                }
+            }
+        }
+
+        while self.live_triangles > target_triangle_count {
+            let Some(c) = heap.pop() else {
+                break;
+            };
+            if !self.alive[c.a]
+                || !self.alive[c.b]
+                || c.version_a != self.versions[c.a]
+                || c.version_b != self.versions[c.b]
+            {
+                continue;
+            }
+            if self.collapse_flips_triangles(c.a, c.b, c.target) {
+                continue;
+            }
+            self.collapse(&c);
+            self.push_edges_of(c.a, &mut heap);
+        }
+    }
+
+    fn into_mesh(self, source: &Mesh) -> Mesh {
+        let mut remap = vec![u32::MAX; self.positions.len()];
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices = Vec::with_capacity(self.live_triangles * 3);
+
+        for (ti, t) in self.triangles.iter().enumerate() {
+            if !self.triangle_alive[ti] {
+                continue;
+            }
+            for &v in t {
+                if remap[v] == u32::MAX {
+                    remap[v] = vertices.len() as u32;
+                    let mut vertex = source.vertices[self.source_vertex[v]];
+                    vertex.position = self.positions[v].as_vec3().to_array();
+                    vertex.normal = [0.0; 3];
+                    vertices.push(vertex);
+                }
+                indices.push(remap[v]);
+            }
+        }
+
+        // Smooth normals from the simplified surface, area weighted
+        for t in indices.chunks_exact(3) {
+            let [p0, p1, p2] =
+                [t[0], t[1], t[2]].map(|i| Vec3::from(vertices[i as usize].position));
+            let face_normal = (p1 - p0).cross(p2 - p0);
+            for &i in t {
+                let n = Vec3::from(vertices[i as usize].normal) + face_normal;
+                vertices[i as usize].normal = n.to_array();
+            }
+        }
+        for v in &mut vertices {
+            let n = Vec3::from(v.normal).normalize_or_zero();
+            v.normal = if n == Vec3::ZERO { Vec3::Y } else { n }.to_array();
+        }
+        compute_tangents(&mut vertices, &indices);
+
+        let aabb = Aabb::from_points(vertices.iter().map(|v| Vec3::from(v.position)));
+        Mesh {
+            name: source.name.clone(),
+            vertices,
+            indices,
+            material_id: source.material_id,
+            aabb,
+        }
+    }
+}
+
+/// Fills the vertex tangents from the positions and texture coordinates of the triangles
+/// using them (the direction of increasing U), orthogonalized against the normals.
+/// Vertices without usable texture coordinates get any tangent perpendicular to their normal.
+pub fn compute_tangents(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut tangents = vec![Vec3::ZERO; vertices.len()];
+    let mut bitangents = vec![Vec3::ZERO; vertices.len()];
+
+    for tri in indices.chunks_exact(3) {
+        let tri = [tri[0], tri[1], tri[2]].map(|i| i as usize);
+        let [p0, p1, p2] = tri.map(|i| Vec3::from(vertices[i].position));
+        let [uv0, uv1, uv2] = tri.map(|i| Vec2::from(vertices[i].tex_coords));
+        let (dp1, dp2) = (p1 - p0, p2 - p0);
+        let (duv1, duv2) = (uv1 - uv0, uv2 - uv0);
+
+        let det = duv1.perp_dot(duv2);
+        if det.abs() < f32::EPSILON {
+            continue; // Degenerate in UV space
+        }
+        // Not normalized: larger triangles weigh more
+        let tangent = (dp1 * duv2.y - dp2 * duv1.y) / det;
+        let bitangent = (dp2 * duv1.x - dp1 * duv2.x) / det;
+        for i in tri {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    for ((vertex, tangent), bitangent) in vertices.iter_mut().zip(tangents).zip(bitangents) {
+        let normal = Vec3::from(vertex.normal).try_normalize().unwrap_or(Vec3::Y);
+        // Gram-Schmidt
+        let tangent = (tangent - normal * normal.dot(tangent))
+            .try_normalize()
+            .unwrap_or_else(|| normal.any_orthonormal_vector());
+        let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        vertex.tangent = tangent.extend(handedness).to_array();
+    }
+}
+
+/// Simplifies `mesh` down to at most `target_triangle_count` triangles using the
+/// quadric error metric from Garland & Heckbert, "Surface Simplification Using
+/// Quadric Error Metrics" (1997).
+///
+/// Vertices sharing a position are welded first, so UV seams are merged and the
+/// surviving vertex keeps the texture coordinates of one of its sources. Normals are
+/// recomputed from the simplified surface. Collapses that would flip a triangle are
+/// rejected, so the result may keep more triangles than requested on tricky meshes.
+pub fn decimate_mesh(mesh: &Mesh, target_triangle_count: usize) -> Mesh {
+    let mut simplifier = Simplifier::new(mesh);
+    simplifier.run(target_triangle_count);
+    simplifier.into_mesh(mesh)
+}
+
+/// Builds successive levels of detail for `mesh`, each keeping half the triangles of
+/// the previous one, until `max_levels` is reached or the mesh gets too small.
+/// The original mesh is not part of the returned list.
+///
+/// Nothing selects these levels at render time yet; callers keep and draw them.
+pub fn generate_lods(mesh: &Mesh, max_levels: usize) -> Vec<Mesh> {
+    let mut lods: Vec<Mesh> = Vec::new();
+    let mut triangle_count = mesh.indices.len() / 3;
+
+    for _ in 0..max_levels {
+        let target = (triangle_count as f32 * LOD_REDUCTION_RATIO) as usize;
+        if target < LOD_MIN_TRIANGLES {
+            break;
+        }
+        let lod = decimate_mesh(lods.last().unwrap_or(mesh), target);
+        let reached = lod.indices.len() / 3;
+        if reached >= triangle_count {
+            // Could not simplify any further without flipping triangles
+            break;
+        }
+        triangle_count = reached;
+        lods.push(lod);
+    }
+
+    lods
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A flat grid on the XZ plane with `n * n` quads.
+    fn grid(n: u32) -> Mesh {
+        let mut vertices = Vec::new();
+        for z in 0..=n {
+            for x in 0..=n {
+                vertices.push(Vertex {
+                    position: [x as f32, 0.0, z as f32],
+                    color: [1.0; 3],
+                    tex_coords: [x as f32 / n as f32, z as f32 / n as f32],
+                    normal: [0.0, 1.0, 0.0],
+                    tangent: [0.0; 4],
+                    tex_coords2: [0.0; 2],
+                });
+            }
+        }
+        let mut indices = Vec::new();
+        for z in 0..n {
+            for x in 0..n {
+                let i = z * (n + 1) + x;
+                indices.extend_from_slice(&[i, i + n + 1, i + 1, i + 1, i + n + 1, i + n + 2]);
+            }
+        }
+        let aabb = Aabb::from_points(vertices.iter().map(|v| Vec3::from(v.position)));
+        Mesh {
+            name: "grid".into(),
+            vertices,
+            indices,
+            material_id: 0,
+            aabb,
+        }
+    }
+
+    #[test]
+    fn test_decimate_reaches_target() {
+        let mesh = grid(16);
+        let lod = decimate_mesh(&mesh, 64);
+
+        assert!(lod.indices.len() / 3 <= 64);
+        assert!(!lod.indices.is_empty());
+        assert!(
+            lod.indices
+                .iter()
+                .all(|&i| (i as usize) < lod.vertices.len())
+        );
+    }
+
+    #[test]
+    fn test_decimate_keeps_flat_surface_and_borders() {
+        let mesh = grid(8);
+        let lod = decimate_mesh(&mesh, 16);
+
+        // Planar mesh: every collapse has zero error, the grid stays flat and its outline intact
+        assert!(lod.vertices.iter().all(|v| v.position[1].abs() < 1e-4));
+        assert_eq!(lod.aabb, mesh.aabb);
+        for t in lod.indices.chunks_exact(3) {
+            let [p0, p1, p2] =
+                [t[0], t[1], t[2]].map(|i| Vec3::from(lod.vertices[i as usize].position));
+            let normal = (p1 - p0).cross(p2 - p0);
+            assert!(normal.y > 0.0, "triangle flipped or degenerate");
+        }
+    }
+
+    #[test]
+    fn test_decimate_noop_when_under_target() {
+        let mesh = grid(2);
+        let lod = decimate_mesh(&mesh, 100);
+        assert_eq!(lod.indices.len(), mesh.indices.len());
+    }
+
+    #[test]
+    fn test_generate_lods_halves_triangles() {
+        let mesh = grid(16);
+        let lods = generate_lods(&mesh, 3);
+
+        assert_eq!(lods.len(), 3);
+        let mut previous = mesh.indices.len() / 3;
+        for lod in &lods {
+            let count = lod.indices.len() / 3;
+            assert!(count <= previous / 2);
+            previous = count;
+        }
+    }
+
+    #[test]
+    fn test_compute_tangents_follow_u() {
+        let mut mesh = grid(2);
+        compute_tangents(&mut mesh.vertices, &mesh.indices);
+        for v in &mesh.vertices {
+            let tangent = Vec3::from_slice(&v.tangent[..3]);
+            assert!(tangent.abs_diff_eq(Vec3::X, 1e-5));
+            // V runs along +Z while cross(normal, tangent) is -Z
+            assert_eq!(v.tangent[3], -1.0);
+        }
+    }
+}
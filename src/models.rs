@@ -1,10 +1,178 @@
-use crate::{error::Result, vertex::Vertex};
-use std::{fmt::Debug, path::Path};
+use crate::{error::Result, mesh_processing::compute_tangents, vertex::Vertex};
+use glam::{Mat3, Mat4, Vec3};
+use std::{fmt::Debug, path::Path, sync::mpsc::Sender};
+
+/// Axis-aligned bounding box, expressed in the local space of whatever owns it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Smallest box containing every point. An empty iterator gives a box at the origin.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut points = points.into_iter();
+        let Some(first) = points.next() else {
+            return Self::new(Vec3::ZERO, Vec3::ZERO);
+        };
+        points.fold(Self::new(first, first), |aabb, p| aabb.expand_by(p))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Returns a copy grown just enough to contain `point`.
+    pub fn expand_by(&self, point: Vec3) -> Aabb {
+        Aabb {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
+    }
+
+    /// Returns the union of both boxes.
+    pub fn expand_to_include(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /// The 8 corners of the box.
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    /// World-space box enclosing this local box once transformed by `model`.
+    pub fn transform_to_world(&self, model: &glam::Mat4) -> Aabb {
+        Aabb::from_points(self.corners().map(|c| model.transform_point3(c)))
+    }
+
+    /// Points lying exactly on a face count as inside.
+    pub fn contains_point(&self, p: Vec3) -> bool {
+        p.cmpge(self.min).all() && p.cmple(self.max).all()
+    }
+
+    /// Boxes that only touch on a face count as intersecting.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+}
 
 #[derive(Debug)]
 pub struct Material {
+    /// Name from the .mtl file, often an exporter default such as "Material001"
     pub name: String,
+    /// Name shown in the editor, editable by the user. Starts out as `name`.
+    pub display_name: String,
     pub diffuse_texture: String,
+    /// Ambient occlusion map (`map_Ka` in the .mtl, or baked), empty for none
+    pub ambient_occlusion_texture: String,
+    /// Tangent space normal map (`map_Bump`, `bump` or `norm` in the .mtl)
+    pub normal_texture: Option<String>,
+    /// Roughness (red) and metalness (green) map (`map_Ks` or `map_Pr` in the .mtl),
+    /// replaces the scalar `roughness` and `metallic` of `properties` when present
+    pub metallic_roughness_texture: Option<String>,
+    /// Light emitted regardless of the scene lighting (`Ke` in the .mtl), black for none
+    pub emissive_color: [f32; 3],
+    /// Multiplies `emissive_color` (`map_Ke` in the .mtl)
+    pub emissive_texture: Option<String>,
+    /// Blended over the opaque geometry instead of replacing it: `d` below 1 or a `map_d` in
+    /// the .mtl, the blend alpha mode in glTF
+    pub is_transparent: bool,
+    /// Both faces are drawn, for thin geometry such as leaves or paper: `illum 9` in the
+    /// .mtl, `doubleSided` in glTF
+    pub double_sided: bool,
+    /// Baked lighting sampled with the second UV set, none yet: no loader or baker fills it
+    pub lightmap_texture: Option<String>,
+    /// Diffuse texture embedded in the model file (glTF), used instead of `diffuse_texture`
+    pub diffuse_image: Option<image::RgbaImage>,
+    /// Initial shading parameters, from the glTF PBR factors
+    pub properties: MaterialPropertiesUniform,
+}
+
+impl Material {
+    pub fn new(name: String, diffuse_texture: String) -> Self {
+        Self {
+            display_name: name.clone(),
+            name,
+            diffuse_texture,
+            ambient_occlusion_texture: String::new(),
+            normal_texture: None,
+            metallic_roughness_texture: None,
+            emissive_color: [0.0; 3],
+            emissive_texture: None,
+            is_transparent: false,
+            double_sided: false,
+            lightmap_texture: None,
+            diffuse_image: None,
+            properties: MaterialPropertiesUniform::default(),
+        }
+    }
+}
+
+impl Default for Material {
+    /// An untextured material, rendered with a white 1x1 texture.
+    fn default() -> Self {
+        Self::new("default".into(), "".into())
+    }
+}
+
+/// Per-material shading parameters, uploaded next to the diffuse texture.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialPropertiesUniform {
+    /// 0.0 = mirror-like, 1.0 = fully diffuse
+    pub roughness: f32,
+    /// 0.0 = dielectric, 1.0 = metal
+    pub metallic: f32,
+    /// Non-zero when the material has a metallic/roughness map, which then replaces
+    /// `roughness` (red) and `metallic` (green). Follows the bound texture, not meant to be edited.
+    pub specular_map: u32,
+    /// Ambient occlusion factor, multiplied with the ambient occlusion map.
+    /// 0.0 = no ambient light, 1.0 = unoccluded
+    pub ao: f32,
+    /// Linear emitted color, multiplied with the emissive map
+    pub emissive: [f32; 3],
+    /// Multiplies the alpha of the diffuse texture, only blended for transparent materials
+    pub opacity: f32,
+    /// Linear color multiplied with the diffuse texture, white to keep it as is
+    pub diffuse_color: [f32; 3],
+    pub _padding: f32,
+}
+
+impl Default for MaterialPropertiesUniform {
+    fn default() -> Self {
+        Self {
+            roughness: 0.5,
+            metallic: 0.0,
+            specular_map: 0,
+            ao: 1.0,
+            emissive: [0.0; 3],
+            opacity: 1.0,
+            diffuse_color: [1.0; 3],
+            _padding: 0.0,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -13,6 +181,248 @@ pub struct Mesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
     pub material_id: usize,
+    pub aabb: Aabb,
+}
+
+impl Mesh {
+    /// Wraps generated geometry, computing its tangents and bounds. Drawn with material 0,
+    /// the lightmap UVs are a copy of the texture ones.
+    fn procedural(name: &str, mut vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        for vertex in &mut vertices {
+            vertex.tex_coords2 = vertex.tex_coords;
+        }
+        compute_tangents(&mut vertices, &indices);
+        Self {
+            name: name.to_string(),
+            aabb: Aabb::from_points(vertices.iter().map(|v| Vec3::from(v.position))),
+            vertices,
+            indices,
+            material_id: 0,
+        }
+    }
+
+    /// Flat grid on the XZ plane centered on the origin, facing +Y. UVs span [0, 1] over
+    /// the whole plane. Subdivisions below 1 count as 1.
+    pub fn plane(width: f32, depth: f32, subdivisions_x: u32, subdivisions_z: u32) -> Self {
+        let (columns, rows) = (subdivisions_x.max(1), subdivisions_z.max(1));
+        let mut vertices = Vec::with_capacity(((columns + 1) * (rows + 1)) as usize);
+        for z in 0..=rows {
+            for x in 0..=columns {
+                let (u, v) = (x as f32 / columns as f32, z as f32 / rows as f32);
+                vertices.push(Vertex {
+                    position: [(u - 0.5) * width, 0.0, (v - 0.5) * depth],
+                    color: [1.0; 3],
+                    tex_coords: [u, v],
+                    normal: [0.0, 1.0, 0.0],
+                    tangent: [0.0; 4],
+                    tex_coords2: [0.0; 2],
+                });
+            }
+        }
+
+        Self::procedural("Plane", vertices, grid_indices(columns, rows))
+    }
+
+    /// Latitude / longitude sphere centered on the origin, with `u` going around and `v`
+    /// from the north pole (0) to the south pole (1). The pole rows keep their seam vertices
+    /// so that every cell has its own UVs, which leaves one zero-area triangle per pole cell.
+    /// At least 2 stacks and 3 slices are generated.
+    pub fn uv_sphere(radius: f32, stacks: u32, slices: u32) -> Self {
+        let (stacks, slices) = (stacks.max(2), slices.max(3));
+        let mut vertices = Vec::with_capacity(((stacks + 1) * (slices + 1)) as usize);
+        for stack in 0..=stacks {
+            // Measured from the south pole
+            let latitude = std::f32::consts::PI * (1.0 - stack as f32 / stacks as f32);
+            for slice in 0..=slices {
+                let longitude = std::f32::consts::TAU * slice as f32 / slices as f32;
+                // Longitude turns clockwise seen from above, so that textures are not
+                // mirrored seen from outside
+                let normal = Vec3::new(
+                    latitude.sin() * longitude.cos(),
+                    -latitude.cos(),
+                    -latitude.sin() * longitude.sin(),
+                );
+                vertices.push(Vertex {
+                    position: (normal * radius).to_array(),
+                    color: [1.0; 3],
+                    tex_coords: [
+                        longitude / std::f32::consts::TAU,
+                        1.0 - latitude / std::f32::consts::PI,
+                    ],
+                    normal: normal.to_array(),
+                    tangent: [0.0; 4],
+                    tex_coords2: [0.0; 2],
+                });
+            }
+        }
+
+        Self::procedural("Sphere", vertices, grid_indices(slices, stacks))
+    }
+
+    /// Axis-aligned cube centered on the origin. Faces do not share vertices, so each has
+    /// its own flat normal and the whole [0, 1] UV square, upright seen from outside.
+    pub fn cube(half_extent: f32) -> Self {
+        // Outward normal, then the right and up directions of the face seen from outside
+        const FACES: [(Vec3, Vec3, Vec3); 6] = [
+            (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+            (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+            (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+            (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+            (Vec3::Z, Vec3::X, Vec3::Y),
+            (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+        ];
+
+        let mut vertices = Vec::with_capacity(24);
+        let mut indices = Vec::with_capacity(36);
+        for (normal, right, up) in FACES {
+            let first = vertices.len() as u32;
+            // Top row first, like the texture
+            for (u, v) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+                let corner = normal + right * (2.0 * u - 1.0) + up * (1.0 - 2.0 * v);
+                vertices.push(Vertex {
+                    position: (corner * half_extent).to_array(),
+                    color: [1.0; 3],
+                    tex_coords: [u, v],
+                    normal: normal.to_array(),
+                    tangent: [0.0; 4],
+                    tex_coords2: [0.0; 2],
+                });
+            }
+            indices.extend(grid_indices(1, 1).into_iter().map(|i| first + i));
+        }
+
+        Self::procedural("Cube", vertices, indices)
+    }
+
+    /// Cylinder along Y centered on the origin. The side wraps `u` once around and runs `v`
+    /// down from the top rim (`v = y / height`, `y` measured from the top). Each cap is a
+    /// separate fan with a flat normal and a planar UV disc. At least 3 segments.
+    pub fn cylinder(
+        radius: f32,
+        height: f32,
+        segments: u32,
+        cap_top: bool,
+        cap_bottom: bool,
+    ) -> Self {
+        let segments = segments.max(3);
+        let half_height = height * 0.5;
+        // Clockwise seen from above, like the sphere's longitudes
+        let direction = |segment: u32| {
+            let angle = std::f32::consts::TAU * segment as f32 / segments as f32;
+            Vec3::new(angle.cos(), 0.0, -angle.sin())
+        };
+        let vertex = |position: Vec3, tex_coords: [f32; 2], normal: Vec3| Vertex {
+            position: position.to_array(),
+            color: [1.0; 3],
+            tex_coords,
+            normal: normal.to_array(),
+            tangent: [0.0; 4],
+            tex_coords2: [0.0; 2],
+        };
+
+        let mut vertices = Vec::new();
+        for (v, y) in [(0.0, half_height), (1.0, -half_height)] {
+            for segment in 0..=segments {
+                let normal = direction(segment);
+                let u = segment as f32 / segments as f32;
+                vertices.push(vertex(normal * radius + Vec3::Y * y, [u, v], normal));
+            }
+        }
+        let mut indices = grid_indices(segments, 1);
+
+        for (enabled, normal) in [(cap_top, Vec3::Y), (cap_bottom, Vec3::NEG_Y)] {
+            if !enabled {
+                continue;
+            }
+            let center = vertices.len() as u32;
+            let y = normal.y * half_height;
+            vertices.push(vertex(Vec3::Y * y, [0.5, 0.5], normal));
+            for segment in 0..segments {
+                let offset = direction(segment);
+                // Seen from outside the cap: -Z is up on the top one, +Z on the bottom one
+                let tex_coords = [0.5 + 0.5 * offset.x, 0.5 + 0.5 * offset.z * normal.y];
+                vertices.push(vertex(offset * radius + Vec3::Y * y, tex_coords, normal));
+            }
+            for segment in 0..segments {
+                let current = center + 1 + segment;
+                let next = center + 1 + (segment + 1) % segments;
+                if normal.y > 0.0 {
+                    indices.extend_from_slice(&[center, current, next]);
+                } else {
+                    indices.extend_from_slice(&[center, next, current]);
+                }
+            }
+        }
+
+        Self::procedural("Cylinder", vertices, indices)
+    }
+
+    /// Capsule along Y centered on the origin: a cylinder of `cylinder_height` between two
+    /// hemispheres of `radius`. It is a single latitude / longitude grid, so normals are
+    /// continuous at the seams. `v` covers the top hemisphere over [0, 0.25], the cylinder
+    /// over [0.25, 0.75] and the bottom hemisphere over [0.75, 1]. At least 3 segments and
+    /// 1 ring per hemisphere.
+    pub fn capsule(
+        radius: f32,
+        cylinder_height: f32,
+        segments: u32,
+        hemisphere_rings: u32,
+    ) -> Self {
+        let (segments, rings) = (segments.max(3), hemisphere_rings.max(1));
+        let half_height = cylinder_height * 0.5;
+        // Polar angle from the north pole, center height and v of each row, top to bottom
+        let hemisphere = |ring: u32, polar_start: f32, y: f32, v_start: f32| {
+            let t = ring as f32 / rings as f32;
+            (
+                polar_start + t * std::f32::consts::FRAC_PI_2,
+                y,
+                v_start + 0.25 * t,
+            )
+        };
+        let rows = (0..=rings)
+            .map(|ring| hemisphere(ring, 0.0, half_height, 0.0))
+            .chain(
+                (0..=rings)
+                    .map(|ring| hemisphere(ring, std::f32::consts::FRAC_PI_2, -half_height, 0.75)),
+            );
+
+        let mut vertices = Vec::with_capacity(((2 * rings + 2) * (segments + 1)) as usize);
+        for (polar, y, v) in rows {
+            for segment in 0..=segments {
+                let longitude = std::f32::consts::TAU * segment as f32 / segments as f32;
+                // Same orientation as `uv_sphere`
+                let normal = Vec3::new(
+                    polar.sin() * longitude.cos(),
+                    polar.cos(),
+                    -polar.sin() * longitude.sin(),
+                );
+                vertices.push(Vertex {
+                    position: (normal * radius + Vec3::Y * y).to_array(),
+                    color: [1.0; 3],
+                    tex_coords: [segment as f32 / segments as f32, v],
+                    normal: normal.to_array(),
+                    tangent: [0.0; 4],
+                    tex_coords2: [0.0; 2],
+                });
+            }
+        }
+
+        Self::procedural("Capsule", vertices, grid_indices(segments, 2 * rings + 1))
+    }
+}
+
+/// Two triangles per cell of a grid of `(columns + 1) * (rows + 1)` vertices stored row
+/// by row, counter-clockwise when columns go right and rows go down.
+fn grid_indices(columns: u32, rows: u32) -> Vec<u32> {
+    let mut indices = Vec::with_capacity((6 * columns * rows) as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = row * (columns + 1) + column;
+            let (b, c) = (a + 1, a + columns + 1);
+            indices.extend_from_slice(&[a, c, b, b, c, c + 1]);
+        }
+    }
+    indices
 }
 
 pub struct Model {
@@ -29,8 +439,34 @@ impl Debug for Model {
     }
 }
 
-pub fn load_model(file_name: &str) -> Result<Model> {
-    let path = Path::new("assets").join(file_name);
+/// Progress of a model import, sent once per completed step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadProgress {
+    pub step: &'static str,
+    /// Fraction of the whole import done, in [0, 1]
+    pub pct: f32,
+}
+
+/// Sends `progress` if someone is listening. A dropped receiver does not abort the import.
+pub(crate) fn report_progress(sender: &Option<Sender<LoadProgress>>, step: &'static str, pct: f32) {
+    if let Some(sender) = sender {
+        let _ = sender.send(LoadProgress { step, pct });
+    }
+}
+
+/// Loads an OBJ file from the assets directory. When `progress` is given, the parsing and
+/// bounds steps are reported on it; the GPU upload (`upload_meshes`) reports the last one.
+pub fn load_model(file_name: &str, progress: Option<Sender<LoadProgress>>) -> Result<Model> {
+    load_model_from(Path::new("assets"), file_name, progress)
+}
+
+/// `load_model`, with `file_name` relative to `assets_dir`.
+pub fn load_model_from(
+    assets_dir: &Path,
+    file_name: &str,
+    progress: Option<Sender<LoadProgress>>,
+) -> Result<Model> {
+    let path = assets_dir.join(file_name);
 
     // 1. Load the OBJ file
     let load_options = tobj::LoadOptions {
@@ -47,11 +483,34 @@ pub fn load_model(file_name: &str) -> Result<Model> {
     let mut out_materials = Vec::new();
     for mat in materials {
         out_materials.push(Material {
-            name: mat.name,
-            diffuse_texture: mat.diffuse_texture.unwrap_or_default(),
+            ambient_occlusion_texture: mat.ambient_texture.unwrap_or_default(),
+            normal_texture: mat
+                .normal_texture
+                .or_else(|| mat.unknown_param.get("norm").cloned()),
+            metallic_roughness_texture: mat
+                .specular_texture
+                .or_else(|| mat.unknown_param.get("map_Pr").cloned()),
+            emissive_color: mat
+                .unknown_param
+                .get("Ke")
+                .and_then(|ke| parse_color(ke))
+                .unwrap_or_default(),
+            emissive_texture: mat.unknown_param.get("map_Ke").cloned(),
+            is_transparent: mat.dissolve.is_some_and(|d| d < 1.0) || mat.dissolve_texture.is_some(),
+            double_sided: mat.illumination_model == Some(9),
+            properties: MaterialPropertiesUniform {
+                opacity: mat.dissolve.unwrap_or(1.0),
+                ..Default::default()
+            },
+            ..Material::new(mat.name, mat.diffuse_texture.unwrap_or_default())
         });
     }
 
+    // OBJ files without a .mtl still reference material 0, fall back to plain white
+    if out_materials.is_empty() {
+        out_materials.push(Material::default());
+    }
+
     // Convert meshes
     let mut out_meshes = Vec::new();
     for m in models {
@@ -88,23 +547,245 @@ pub fn load_model(file_name: &str) -> Result<Model> {
                 color: [1.0, 1.0, 1.0],
                 tex_coords,
                 normal,
+                tangent: [0.0; 4],
+                // OBJ has a single UV set, lightmaps reuse it
+                tex_coords2: tex_coords,
             });
         }
+        compute_tangents(&mut vertices, &mesh.indices);
 
         out_meshes.push(Mesh {
             name: m.name,
+            aabb: Aabb::new(Vec3::ZERO, Vec3::ZERO),
             vertices,
             indices: mesh.indices,
             material_id: mesh.material_id.unwrap_or(0),
         });
     }
 
+    report_progress(&progress, "Parsing vertices", 0.33);
+
+    // 2. Bounds, used for picking and selection
+    for mesh in &mut out_meshes {
+        mesh.aabb = Aabb::from_points(mesh.vertices.iter().map(|v| Vec3::from(v.position)));
+    }
+    report_progress(&progress, "Computing bounds", 0.66);
+
     Ok(Model {
         meshes: out_meshes,
         materials: out_materials,
     })
 }
 
+/// Loads a glTF 2.0 model (`.gltf` with external or embedded data, or `.glb`) from the assets
+/// directory. Node transforms are baked into the vertices; only triangle primitives are kept.
+/// Parses an .mtl color such as `Ke 1.0 0.5 0.0`, `None` if it is not three numbers.
+fn parse_color(value: &str) -> Option<[f32; 3]> {
+    let mut components = value.split_whitespace().map(str::parse::<f32>);
+    let color = [
+        components.next()?.ok()?,
+        components.next()?.ok()?,
+        components.next()?.ok()?,
+    ];
+    components.next().is_none().then_some(color)
+}
+
+pub fn load_gltf(file_name: &str) -> Result<Model> {
+    load_gltf_from(Path::new("assets"), file_name)
+}
+
+/// `load_gltf`, with `file_name` relative to `assets_dir`.
+pub fn load_gltf_from(assets_dir: &Path, file_name: &str) -> Result<Model> {
+    let path = assets_dir.join(file_name);
+    let (document, buffers, images) = gltf::import(&path)?;
+
+    let mut materials = document
+        .materials()
+        .map(|m| gltf_material(&m, file_name, &images))
+        .collect::<Vec<_>>();
+    // Primitives without a material share a plain white one, appended if needed
+    let default_material = materials.len();
+
+    let mut meshes = Vec::new();
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next());
+    for node in scene.iter().flat_map(|s| s.nodes()) {
+        load_gltf_node(
+            &node,
+            Mat4::IDENTITY,
+            &buffers,
+            default_material,
+            &mut meshes,
+        );
+    }
+    if meshes.iter().any(|m| m.material_id == default_material) {
+        materials.push(Material::default());
+    }
+
+    Ok(Model { meshes, materials })
+}
+
+fn load_gltf_node(
+    node: &gltf::Node,
+    parent: Mat4,
+    buffers: &[gltf::buffer::Data],
+    default_material: usize,
+    meshes: &mut Vec<Mesh>,
+) {
+    let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+    for child in node.children() {
+        load_gltf_node(&child, transform, buffers, default_material, meshes);
+    }
+    let Some(mesh) = node.mesh() else {
+        return;
+    };
+
+    let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+    let mesh_name = mesh
+        .name()
+        .map_or_else(|| format!("mesh_{}", mesh.index()), str::to_owned);
+    let primitive_count = mesh.primitives().len();
+
+    for primitive in mesh.primitives() {
+        let name = if primitive_count > 1 {
+            format!("{}_{}", mesh_name, primitive.index())
+        } else {
+            mesh_name.clone()
+        };
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            log::warn!(
+                "Skipping {name}: {:?} primitives are not supported",
+                primitive.mode()
+            );
+            continue;
+        }
+
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let Some(positions) = reader.read_positions() else {
+            log::warn!("Skipping {name}: no positions");
+            continue;
+        };
+        let positions = positions.collect::<Vec<_>>();
+        let normals = reader.read_normals().map(|n| n.collect::<Vec<_>>());
+        let tex_coords = reader
+            .read_tex_coords(0)
+            .map(|t| t.into_f32().collect::<Vec<_>>());
+        // TEXCOORD_1, the first set stands in for models without one
+        let tex_coords2 = reader
+            .read_tex_coords(1)
+            .map(|t| t.into_f32().collect::<Vec<_>>());
+        let colors = reader
+            .read_colors(0)
+            .map(|c| c.into_rgb_f32().collect::<Vec<_>>());
+
+        let mut vertices = positions
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| Vertex {
+                position: transform.transform_point3(position.into()).into(),
+                color: colors.as_ref().map_or([1.0; 3], |c| c[i]),
+                // glTF already has its UV origin at the top-left, like wgpu
+                tex_coords: tex_coords.as_ref().map_or([0.0; 2], |t| t[i]),
+                normal: normals.as_ref().map_or([0.0, 1.0, 0.0], |n| {
+                    (normal_matrix * Vec3::from(n[i]))
+                        .normalize_or_zero()
+                        .into()
+                }),
+                tangent: [0.0; 4],
+                tex_coords2: tex_coords2
+                    .as_ref()
+                    .or(tex_coords.as_ref())
+                    .map_or([0.0; 2], |t| t[i]),
+            })
+            .collect::<Vec<_>>();
+
+        let mut indices = reader.read_indices().map_or_else(
+            || (0..vertices.len() as u32).collect::<Vec<_>>(),
+            |i| i.into_u32().collect(),
+        );
+        // A mirroring transform flips the winding, restore counter-clockwise front faces
+        if transform.determinant() < 0.0 {
+            for tri in indices.chunks_exact_mut(3) {
+                tri.swap(1, 2);
+            }
+        }
+        compute_tangents(&mut vertices, &indices);
+
+        meshes.push(Mesh {
+            name,
+            aabb: Aabb::from_points(vertices.iter().map(|v| Vec3::from(v.position))),
+            vertices,
+            indices,
+            material_id: primitive.material().index().unwrap_or(default_material),
+        });
+    }
+}
+
+fn gltf_material(
+    material: &gltf::Material,
+    file_name: &str,
+    images: &[gltf::image::Data],
+) -> Material {
+    let name = material.name().map_or_else(
+        || format!("material_{}", material.index().unwrap_or_default()),
+        str::to_owned,
+    );
+    let pbr = material.pbr_metallic_roughness();
+    let mut out = Material::new(name, String::new());
+    out.properties.roughness = pbr.roughness_factor();
+    out.properties.metallic = pbr.metallic_factor();
+    out.properties.opacity = pbr.base_color_factor()[3];
+    out.is_transparent = material.alpha_mode() == gltf::material::AlphaMode::Blend;
+    out.double_sided = material.double_sided();
+    if let Some(occlusion) = material.occlusion_texture() {
+        out.properties.ao = occlusion.strength();
+    }
+    out.emissive_color = material.emissive_factor();
+    if let Some(info) = material.emissive_texture()
+        && let gltf::image::Source::Uri { uri, .. } = info.texture().source().source()
+        && !uri.starts_with("data:")
+    {
+        let directory = Path::new(file_name).parent().unwrap_or(Path::new(""));
+        out.emissive_texture = Some(directory.join(uri).to_string_lossy().into_owned());
+    }
+
+    let Some(info) = pbr.base_color_texture() else {
+        return out;
+    };
+    let image = info.texture().source();
+    match image.source() {
+        // External files go through the assets directory like OBJ textures (and hot reload)
+        gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+            let directory = Path::new(file_name).parent().unwrap_or(Path::new(""));
+            out.diffuse_texture = directory.join(uri).to_string_lossy().into_owned();
+        }
+        _ => {
+            out.diffuse_image = images.get(image.index()).and_then(gltf_rgba_image);
+            if out.diffuse_image.is_none() {
+                log::warn!("Unsupported embedded image format in {}", out.name);
+            }
+        }
+    }
+    out
+}
+
+/// 8-bit images as RGBA, the format every texture is uploaded in.
+fn gltf_rgba_image(data: &gltf::image::Data) -> Option<image::RgbaImage> {
+    use gltf::image::Format;
+    use image::{DynamicImage, ImageBuffer};
+
+    let (width, height, pixels) = (data.width, data.height, data.pixels.clone());
+    let image = match data.format {
+        Format::R8 => DynamicImage::ImageLuma8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8 => DynamicImage::ImageLumaA8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8B8 => DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8B8A8 => return ImageBuffer::from_raw(width, height, pixels),
+        _ => return None,
+    };
+    Some(image.to_rgba8())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,9 +793,275 @@ mod tests {
 
     #[test]
     fn test_load_model_not_found() {
-        let result = load_model("non_existent_model.obj");
+        let result = load_model("non_existent_model.obj", None);
         assert!(result.is_err());
         let err = result.unwrap_err();
         assert!(matches!(err, OrengineError::Tobj(_)));
     }
+
+    #[test]
+    fn test_load_model_without_materials() {
+        let model = load_model("triangle.obj", None).unwrap();
+        assert_eq!(model.materials.len(), 1);
+        assert_eq!(model.materials[0].name, "default");
+        assert_eq!(model.materials[0].display_name, "default");
+        assert!(model.materials[0].diffuse_texture.is_empty());
+        assert!(
+            model
+                .meshes
+                .iter()
+                .all(|m| m.material_id < model.materials.len())
+        );
+    }
+
+    #[test]
+    fn test_load_model_reports_progress() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        load_model("triangle.obj", Some(sender)).unwrap();
+
+        let pcts = receiver.try_iter().map(|p| p.pct).collect::<Vec<_>>();
+        assert_eq!(pcts, vec![0.33, 0.66]);
+    }
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Vec3::ZERO, Vec3::ONE)
+    }
+
+    #[test]
+    fn test_aabb_center() {
+        let aabb = Aabb::new(Vec3::new(-2.0, 0.0, 1.0), Vec3::new(4.0, 2.0, 3.0));
+        assert_eq!(aabb.center(), Vec3::new(1.0, 1.0, 2.0));
+    }
+
+    #[test]
+    fn test_aabb_half_extents() {
+        let aabb = Aabb::new(Vec3::new(-2.0, 0.0, 1.0), Vec3::new(4.0, 2.0, 3.0));
+        assert_eq!(aabb.half_extents(), Vec3::new(3.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_aabb_expand_by() {
+        let aabb = unit_box().expand_by(Vec3::new(2.0, -1.0, 0.5));
+        assert_eq!(aabb.min, Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(aabb.max, Vec3::new(2.0, 1.0, 1.0));
+
+        // A point already inside leaves the box untouched
+        assert_eq!(unit_box().expand_by(Vec3::splat(0.5)), unit_box());
+    }
+
+    #[test]
+    fn test_aabb_expand_to_include() {
+        let other = Aabb::new(Vec3::splat(-1.0), Vec3::splat(0.5));
+        let aabb = unit_box().expand_to_include(&other);
+        assert_eq!(aabb.min, Vec3::splat(-1.0));
+        assert_eq!(aabb.max, Vec3::ONE);
+    }
+
+    #[test]
+    fn test_aabb_contains_point() {
+        let aabb = unit_box();
+        assert!(aabb.contains_point(Vec3::splat(0.5)));
+        assert!(aabb.contains_point(Vec3::ONE));
+        assert!(!aabb.contains_point(Vec3::new(0.5, 1.5, 0.5)));
+    }
+
+    #[test]
+    fn test_aabb_intersects() {
+        let aabb = unit_box();
+        assert!(aabb.intersects(&Aabb::new(Vec3::splat(0.5), Vec3::splat(2.0))));
+        assert!(aabb.intersects(&Aabb::new(Vec3::ONE, Vec3::splat(2.0))));
+        assert!(!aabb.intersects(&Aabb::new(Vec3::new(1.5, 0.0, 0.0), Vec3::splat(2.0))));
+    }
+
+    #[test]
+    fn test_aabb_from_points() {
+        let aabb = Aabb::from_points([
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(-1.0, 5.0, 0.0),
+            Vec3::new(0.0, 0.0, 4.0),
+        ]);
+        assert_eq!(aabb.min, Vec3::new(-1.0, 0.0, 0.0));
+        assert_eq!(aabb.max, Vec3::new(1.0, 5.0, 4.0));
+    }
+
+    #[test]
+    fn test_load_gltf_embedded() {
+        let model = load_gltf("triangle.gltf").unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.name, "Triangle");
+        assert_eq!(mesh.vertices.len(), 3);
+        assert_eq!(mesh.indices, [0, 1, 2]);
+        assert_eq!(mesh.vertices[1].tex_coords, [1.0, 1.0]);
+        // No TEXCOORD_1, the lightmap UVs fall back to the first set
+        assert_eq!(mesh.vertices[1].tex_coords2, [1.0, 1.0]);
+        assert_eq!(mesh.aabb.max, Vec3::new(1.0, 1.0, 0.0));
+
+        // The data URI image is decoded in memory, not looked up on disk
+        assert_eq!(model.materials.len(), 1);
+        let material = &model.materials[mesh.material_id];
+        assert_eq!(material.name, "Red");
+        assert!(material.diffuse_texture.is_empty());
+        let image = material.diffuse_image.as_ref().unwrap();
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(material.properties.roughness, 0.75);
+        assert_eq!(material.properties.metallic, 0.25);
+        // No occlusion texture, unoccluded
+        assert_eq!(material.properties.ao, 1.0);
+        assert!(!material.is_transparent);
+        assert!(!material.double_sided);
+    }
+
+    #[test]
+    fn test_material_properties_layout() {
+        // Matches `MaterialProperties` in shader.wgsl
+        assert_eq!(std::mem::size_of::<MaterialPropertiesUniform>(), 48);
+        assert_eq!(std::mem::offset_of!(MaterialPropertiesUniform, ao), 12);
+        // vec3 members are 16-byte aligned
+        assert_eq!(
+            std::mem::offset_of!(MaterialPropertiesUniform, emissive),
+            16
+        );
+        assert_eq!(
+            std::mem::offset_of!(MaterialPropertiesUniform, diffuse_color),
+            32
+        );
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("1 0.5  0"), Some([1.0, 0.5, 0.0]));
+        assert_eq!(parse_color("1 0.5"), None);
+        assert_eq!(parse_color("1 0.5 0 1"), None);
+        assert_eq!(parse_color("red"), None);
+    }
+
+    /// Face normals from the index winding, which must agree with the vertex normals for the
+    /// triangles to survive back-face culling. Zero-area triangles draw nothing and are
+    /// skipped.
+    fn assert_front_faces_outward(mesh: &Mesh) {
+        for tri in mesh.indices.chunks_exact(3) {
+            let [v0, v1, v2] = [tri[0], tri[1], tri[2]].map(|i| &mesh.vertices[i as usize]);
+            let [p0, p1, p2] = [v0, v1, v2].map(|v| Vec3::from(v.position));
+            let face_normal = (p1 - p0).cross(p2 - p0);
+            if face_normal.length_squared() < 1e-12 {
+                continue;
+            }
+            let vertex_normal =
+                Vec3::from(v0.normal) + Vec3::from(v1.normal) + Vec3::from(v2.normal);
+            assert!(
+                face_normal.dot(vertex_normal) > 0.0,
+                "{} winds inward: {:?}",
+                mesh.name,
+                tri
+            );
+        }
+    }
+
+    #[test]
+    fn test_plane() {
+        let plane = Mesh::plane(4.0, 2.0, 4, 2);
+        assert_eq!(plane.vertices.len(), 5 * 3);
+        assert_eq!(plane.indices.len(), 2 * 4 * 2 * 3);
+        assert_eq!(
+            plane.aabb,
+            Aabb::new(Vec3::new(-2.0, 0.0, -1.0), Vec3::new(2.0, 0.0, 1.0))
+        );
+        assert_eq!(plane.vertices[0].tex_coords, [0.0, 0.0]);
+        assert_eq!(plane.vertices.last().unwrap().tex_coords, [1.0, 1.0]);
+        assert!(plane.vertices.iter().all(|v| v.normal == [0.0, 1.0, 0.0]));
+        assert_front_faces_outward(&plane);
+    }
+
+    #[test]
+    fn test_uv_sphere() {
+        let sphere = Mesh::uv_sphere(2.0, 8, 16);
+        assert_eq!(sphere.vertices.len(), 9 * 17);
+        assert_eq!(sphere.indices.len(), 8 * 16 * 6);
+        assert!(sphere.aabb.min.abs_diff_eq(Vec3::splat(-2.0), 1e-5));
+        assert!(sphere.aabb.max.abs_diff_eq(Vec3::splat(2.0), 1e-5));
+        for v in &sphere.vertices {
+            let position = Vec3::from(v.position);
+            assert!((position.length() - 2.0).abs() < 1e-5);
+            assert!(Vec3::from(v.normal).abs_diff_eq(position / 2.0, 1e-5));
+        }
+        // North pole at the top of the texture
+        assert_eq!(sphere.vertices[0].position[1], 2.0);
+        assert_eq!(sphere.vertices[0].tex_coords[1], 0.0);
+        assert_front_faces_outward(&sphere);
+    }
+
+    #[test]
+    fn test_cube() {
+        let cube = Mesh::cube(0.5);
+        assert_eq!(cube.vertices.len(), 24);
+        assert_eq!(cube.indices.len(), 36);
+        assert_eq!(cube.aabb, Aabb::new(Vec3::splat(-0.5), Vec3::splat(0.5)));
+        // Each face lies on the plane its normal points out of
+        for v in &cube.vertices {
+            assert_eq!(Vec3::from(v.position).dot(Vec3::from(v.normal)), 0.5);
+        }
+        assert_front_faces_outward(&cube);
+    }
+
+    #[test]
+    fn test_cylinder() {
+        let open = Mesh::cylinder(1.0, 2.0, 12, false, false);
+        assert_eq!(open.vertices.len(), 2 * 13);
+        assert_eq!(open.indices.len(), 12 * 6);
+        assert_front_faces_outward(&open);
+
+        let capped = Mesh::cylinder(1.0, 2.0, 12, true, true);
+        assert_eq!(capped.vertices.len(), 2 * 13 + 2 * 13);
+        assert_eq!(capped.indices.len(), 12 * 6 + 2 * 12 * 3);
+        assert!(
+            capped
+                .aabb
+                .min
+                .abs_diff_eq(Vec3::new(-1.0, -1.0, -1.0), 1e-5)
+        );
+        assert!(capped.aabb.max.abs_diff_eq(Vec3::new(1.0, 1.0, 1.0), 1e-5));
+        let side_top = &capped.vertices[0];
+        assert_eq!(side_top.position[1], 1.0);
+        assert_eq!(side_top.tex_coords, [0.0, 0.0]);
+        assert_front_faces_outward(&capped);
+
+        let top_only = Mesh::cylinder(1.0, 2.0, 12, true, false);
+        assert!(
+            top_only.vertices[26..]
+                .iter()
+                .all(|v| v.normal == [0.0, 1.0, 0.0])
+        );
+    }
+
+    #[test]
+    fn test_capsule() {
+        let capsule = Mesh::capsule(0.5, 1.0, 16, 4);
+        assert_eq!(capsule.vertices.len(), 10 * 17);
+        assert_eq!(capsule.indices.len(), 9 * 16 * 6);
+        assert!(
+            capsule
+                .aabb
+                .min
+                .abs_diff_eq(Vec3::new(-0.5, -1.0, -0.5), 1e-5)
+        );
+        assert!(capsule.aabb.max.abs_diff_eq(Vec3::new(0.5, 1.0, 0.5), 1e-5));
+
+        // The two equator rows share their normals and bound the cylinder's UVs
+        let row = |r: usize| &capsule.vertices[r * 17..(r + 1) * 17];
+        for (top, bottom) in row(4).iter().zip(row(5)) {
+            assert!(Vec3::from(top.normal).abs_diff_eq(Vec3::from(bottom.normal), 1e-6));
+            assert_eq!(top.position[1] - bottom.position[1], 1.0);
+            assert_eq!((top.tex_coords[1], bottom.tex_coords[1]), (0.25, 0.75));
+        }
+        assert_eq!(row(0)[0].tex_coords[1], 0.0);
+        assert_eq!(row(9)[0].tex_coords[1], 1.0);
+        assert_front_faces_outward(&capsule);
+    }
+
+    #[test]
+    fn test_load_gltf_not_found() {
+        let err = load_gltf("non_existent_model.gltf").unwrap_err();
+        assert!(matches!(err, OrengineError::Gltf(_)));
+    }
 }
@@ -0,0 +1,369 @@
+// Motion blur: the scene is drawn again into a velocity buffer holding how far each pixel
+// moved on screen since the last frame, then the HDR image is blurred along it
+// (motion_blur.wgsl).
+
+use crate::{
+    instance::InstanceRaw,
+    post_process::{
+        create_fullscreen_pipeline, create_fullscreen_shader, run_fullscreen_pass,
+        texture_layout_entry, uniform_layout_entry,
+    },
+    state::MeshRenderData,
+    textures::{HDR_FORMAT, Texture},
+    vertex::Vertex,
+};
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+pub const MAX_MOTION_BLUR_TAPS: u32 = 32;
+/// Longest trail in pixels, so that a fast turn does not smear the whole screen
+const MAX_BLUR_LENGTH: f32 = 64.0;
+const VELOCITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionBlurSettings {
+    /// Samples along the velocity of each pixel, up to `MAX_MOTION_BLUR_TAPS`
+    pub taps: u32,
+    /// Fraction of the frame the shutter stays open for, 0.5 being a 180° film shutter
+    pub strength: f32,
+}
+
+impl Default for MotionBlurSettings {
+    fn default() -> Self {
+        Self {
+            taps: 8,
+            strength: 0.5,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct MotionBlurUniform {
+    taps: u32,
+    strength: f32,
+    max_length: f32,
+    _padding: f32,
+}
+
+/// What the velocity pass draws: every mesh, once per instance.
+pub struct MotionBlurScene<'a> {
+    pub meshes: &'a [MeshRenderData],
+    pub instance_buffer: &'a wgpu::Buffer,
+    /// Instances at the start of `instance_buffer` to draw
+    pub instance_count: u32,
+}
+
+/// Intermediate textures and bind groups that follow the surface size.
+struct MotionBlurTargets {
+    velocity: wgpu::TextureView,
+    /// Single sampled, so that the velocity pass also runs with MSAA
+    depth: Texture,
+    blurred: wgpu::TextureView,
+    /// Reads the HDR target, writes `blurred`
+    blur_bind_group: wgpu::BindGroup,
+    /// Reads `blurred`, writes the HDR target
+    copy_bind_group: wgpu::BindGroup,
+}
+
+/// Post-process pass blurring the HDR target along the camera motion.
+pub struct MotionBlurRenderer {
+    pub enabled: bool,
+    pub settings: MotionBlurSettings,
+    velocity_pipeline: wgpu::RenderPipeline,
+    velocity_layout: wgpu::PipelineLayout,
+    depth_format: wgpu::TextureFormat,
+    blur_pipeline: wgpu::RenderPipeline,
+    copy_pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    targets: MotionBlurTargets,
+}
+
+impl MotionBlurRenderer {
+    /// `shader` is the scene shader, providing `vs_velocity` and `fs_velocity`.
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        shader: &wgpu::ShaderModule,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        depth_format: wgpu::TextureFormat,
+        hdr_view: &wgpu::TextureView,
+    ) -> Self {
+        let velocity_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Velocity Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let velocity_pipeline =
+            Self::create_velocity_pipeline(device, &velocity_layout, shader, depth_format);
+
+        let unfilterable = wgpu::TextureSampleType::Float { filterable: false };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("motion_blur_bind_group_layout"),
+            entries: &[
+                uniform_layout_entry(0),
+                texture_layout_entry(1, unfilterable),
+                texture_layout_entry(2, unfilterable),
+            ],
+        });
+        let blur_shader = create_fullscreen_shader(
+            device,
+            "Motion Blur Shader",
+            include_str!("../motion_blur.wgsl"),
+        );
+        let pipeline = |label, entry_point| {
+            create_fullscreen_pipeline(
+                device,
+                label,
+                &[&bind_group_layout],
+                &blur_shader,
+                entry_point,
+                HDR_FORMAT,
+                None,
+            )
+        };
+        let blur_pipeline = pipeline("Motion Blur Pipeline", "fs_motion_blur");
+        let copy_pipeline = pipeline("Motion Blur Copy Pipeline", "fs_copy");
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Motion Blur Buffer"),
+            contents: bytemuck::cast_slice(&[MotionBlurUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let targets = Self::create_targets(
+            device,
+            config,
+            depth_format,
+            hdr_view,
+            &bind_group_layout,
+            &uniform_buffer,
+        );
+
+        Self {
+            enabled: false,
+            settings: MotionBlurSettings::default(),
+            velocity_pipeline,
+            velocity_layout,
+            depth_format,
+            blur_pipeline,
+            copy_pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            targets,
+        }
+    }
+
+    /// Rebuilds the velocity pipeline after the scene shader changed.
+    pub fn reload_shader(&mut self, device: &wgpu::Device, shader: &wgpu::ShaderModule) {
+        self.velocity_pipeline = Self::create_velocity_pipeline(
+            device,
+            &self.velocity_layout,
+            shader,
+            self.depth_format,
+        );
+    }
+
+    fn create_velocity_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        depth_format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Velocity Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_velocity",
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_velocity",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: VELOCITY_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            // Not culled, like the pick pass: mirrored instances are wound the other way
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_format: wgpu::TextureFormat,
+        hdr_view: &wgpu::TextureView,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> MotionBlurTargets {
+        let create_target = |label, format| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: config.width,
+                        height: config.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let velocity = create_target("Velocity Buffer", VELOCITY_FORMAT);
+        let blurred = create_target("Motion Blurred", HDR_FORMAT);
+        let depth =
+            Texture::create_depth_texture(device, config, depth_format, 1, "velocity_depth");
+
+        let bind_group = |label, input| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&velocity),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(input),
+                    },
+                ],
+            })
+        };
+
+        MotionBlurTargets {
+            blur_bind_group: bind_group("motion_blur_bind_group", hdr_view),
+            copy_bind_group: bind_group("motion_blur_copy_bind_group", &blurred),
+            velocity,
+            depth,
+            blurred,
+        }
+    }
+
+    /// The HDR target is recreated on resize, the intermediate targets follow its size.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        hdr_view: &wgpu::TextureView,
+    ) {
+        self.targets = Self::create_targets(
+            device,
+            config,
+            self.depth_format,
+            hdr_view,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+        );
+    }
+
+    /// Blurs `hdr_target` (the view given to `new`/`resize`) along the motion between the
+    /// two matrices of the camera bound by `camera_bind_group`. Does nothing while disabled.
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        camera_bind_group: &wgpu::BindGroup,
+        scene: MotionBlurScene,
+        hdr_target: &wgpu::TextureView,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        let uniform = MotionBlurUniform {
+            taps: self.settings.taps.clamp(1, MAX_MOTION_BLUR_TAPS),
+            strength: self.settings.strength,
+            max_length: MAX_BLUR_LENGTH,
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        let targets = &self.targets;
+        {
+            // The background is cleared to no motion, the sky is not blurred
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Velocity Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &targets.velocity,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &targets.depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            pass.set_pipeline(&self.velocity_pipeline);
+            pass.set_bind_group(0, camera_bind_group, &[]);
+            pass.set_vertex_buffer(1, scene.instance_buffer.slice(..));
+            for mesh in scene.meshes {
+                pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..mesh.num_elements, 0, 0..scene.instance_count);
+            }
+        }
+
+        run_fullscreen_pass(
+            encoder,
+            "Motion Blur Pass",
+            &targets.blurred,
+            None,
+            &self.blur_pipeline,
+            &[&targets.blur_bind_group],
+        );
+        run_fullscreen_pass(
+            encoder,
+            "Motion Blur Copy Pass",
+            hdr_target,
+            None,
+            &self.copy_pipeline,
+            &[&targets.copy_bind_group],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_motion_blur_uniform_layout() {
+        // Matches `MotionBlurUniform` in motion_blur.wgsl
+        assert_eq!(std::mem::size_of::<MotionBlurUniform>(), 16);
+        assert_eq!(std::mem::offset_of!(MotionBlurUniform, max_length), 8);
+    }
+}
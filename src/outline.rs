@@ -0,0 +1,184 @@
+use crate::post_process::{
+    create_fullscreen_pipeline, create_fullscreen_shader, run_fullscreen_pass,
+    texture_layout_entry, uniform_layout_entry,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use wgpu::util::DeviceExt;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct OutlineUniform {
+    inverse_projection: [[f32; 4]; 4],
+    color: [f32; 4],
+    threshold: f32,
+    thickness: f32,
+    _padding: [f32; 2],
+}
+
+/// Tunable look of the outlines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutlineSettings {
+    pub color: [f32; 4],
+    /// Relative depth jump (0.1 = 10% of the distance) considered an edge
+    pub threshold: f32,
+    /// Line width in pixels
+    pub thickness: f32,
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            color: [0.0, 0.0, 0.0, 1.0],
+            threshold: 0.1,
+            thickness: 1.0,
+        }
+    }
+}
+
+/// Post-process pass drawing ink outlines where the depth buffer is discontinuous,
+/// blended over the already rendered image.
+pub struct OutlineRenderer {
+    pub enabled: bool,
+    /// Used when nothing is selected
+    pub settings: OutlineSettings,
+    /// Used while at least one instance is selected, to make the selection state obvious
+    pub selected_settings: OutlineSettings,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+}
+
+impl OutlineRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        depth_view: &wgpu::TextureView,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("outline_bind_group_layout"),
+            entries: &[
+                texture_layout_entry(0, wgpu::TextureSampleType::Float { filterable: false }),
+                uniform_layout_entry(1),
+            ],
+        });
+
+        let shader =
+            create_fullscreen_shader(device, "Outline Shader", include_str!("../outline.wgsl"));
+        let pipeline = create_fullscreen_pipeline(
+            device,
+            "Outline Pipeline",
+            &[&bind_group_layout],
+            &shader,
+            "fs_outline",
+            target_format,
+            Some(wgpu::BlendState::ALPHA_BLENDING),
+        );
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Outline Buffer"),
+            contents: bytemuck::cast_slice(&[OutlineUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, depth_view, &uniform_buffer);
+
+        Self {
+            enabled: false,
+            settings: OutlineSettings::default(),
+            selected_settings: OutlineSettings {
+                color: [1.0, 0.63, 0.0, 1.0],
+                thickness: 2.0,
+                ..Default::default()
+            },
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            uniform_buffer,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        depth_view: &wgpu::TextureView,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("outline_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// The depth texture is recreated on resize, so is the bind group reading it.
+    pub fn resize(&mut self, device: &wgpu::Device, depth_view: &wgpu::TextureView) {
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            depth_view,
+            &self.uniform_buffer,
+        );
+    }
+
+    /// Outlines the edges of the depth buffer over `target`, the depth having been rendered
+    /// with `projection`.
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        has_selection: bool,
+        projection: Mat4,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let settings = if has_selection {
+            &self.selected_settings
+        } else {
+            &self.settings
+        };
+        let uniform = OutlineUniform {
+            inverse_projection: projection.inverse().to_cols_array_2d(),
+            color: settings.color,
+            threshold: settings.threshold,
+            thickness: settings.thickness,
+            _padding: [0.0; 2],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        run_fullscreen_pass(
+            encoder,
+            "Outline Pass",
+            target,
+            None,
+            &self.pipeline,
+            &[&self.bind_group],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outline_uniform_layout() {
+        // Matches `OutlineUniform` in outline.wgsl
+        assert_eq!(std::mem::size_of::<OutlineUniform>(), 96);
+        assert_eq!(std::mem::offset_of!(OutlineUniform, color), 64);
+    }
+}
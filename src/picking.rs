@@ -0,0 +1,286 @@
+// GPU picking: the instance under the cursor is found by rendering instance indices into a
+// single pixel and reading it back. The readback is asynchronous, so the render loop never
+// waits for the GPU: the result is collected a frame or two later.
+
+use crate::{camera::CameraUniform, instance::InstanceRaw, state::MeshRenderData, vertex::Vertex};
+use glam::{Mat4, Vec2, Vec3};
+use std::{
+    sync::mpsc::{Receiver, TryRecvError},
+    task::Poll,
+};
+use wgpu::util::DeviceExt;
+
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// Clip-space transform enlarging the pixel at `ndc` of a `width` x `height` viewport so that
+/// it covers the whole 1x1 pick target.
+pub fn pick_matrix(ndc: Vec2, width: u32, height: u32) -> Mat4 {
+    Mat4::from_scale(Vec3::new(width as f32, height as f32, 1.0))
+        * Mat4::from_translation(Vec3::new(-ndc.x, -ndc.y, 0.0))
+}
+
+/// What the pick pass draws: every mesh, once per instance.
+pub struct PickScene<'a> {
+    pub meshes: &'a [MeshRenderData],
+    pub instance_buffer: &'a wgpu::Buffer,
+    /// Instances at the start of `instance_buffer` to draw
+    pub instance_count: u32,
+}
+
+/// A pick submitted to the GPU whose result is not read yet.
+pub struct PendingPick {
+    buffer: wgpu::Buffer,
+    mapped: Receiver<Result<(), wgpu::BufferAsyncError>>,
+    /// Scene index of each instance of the instance buffer the pick was drawn with
+    instances: Vec<usize>,
+    /// Add to the current selection instead of replacing it
+    pub extend: bool,
+}
+
+impl PendingPick {
+    /// Starts mapping `buffer`, which must come from `Picker::encode` and have been submitted.
+    /// `instances[i]` is the scene index of the instance at index `i` of the instance buffer.
+    pub fn new(buffer: wgpu::Buffer, instances: Vec<usize>, extend: bool) -> Self {
+        let (sender, mapped) = std::sync::mpsc::channel();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        Self {
+            buffer,
+            mapped,
+            instances,
+            extend,
+        }
+    }
+
+    /// The picked instance once the readback is done, `Ready(None)` for the background.
+    /// Never blocks: the device must be polled (`Maintain::Poll`) for the mapping to progress.
+    pub fn poll(&self) -> Poll<Option<usize>> {
+        match self.mapped.try_recv() {
+            Err(TryRecvError::Empty) => Poll::Pending,
+            Ok(Ok(())) => {
+                let id = {
+                    let data = self.buffer.slice(..).get_mapped_range();
+                    *bytemuck::from_bytes::<u32>(&data[..4])
+                };
+                self.buffer.unmap();
+                Poll::Ready(
+                    id.checked_sub(1)
+                        .and_then(|i| self.instances.get(i as usize).copied()),
+                )
+            }
+            // A failed readback is treated as a miss
+            Ok(Err(_)) | Err(TryRecvError::Disconnected) => Poll::Ready(None),
+        }
+    }
+}
+
+pub struct Picker {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::PipelineLayout,
+    depth_format: wgpu::TextureFormat,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    id_texture: wgpu::Texture,
+    id_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+}
+
+impl Picker {
+    /// `shader` is the scene shader, providing `vs_instance_id` and `fs_pick`.
+    pub fn new(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        depth_format: wgpu::TextureFormat,
+    ) -> Self {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pick Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = Self::create_pipeline(device, &layout, shader, depth_format);
+
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Pick Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("pick_camera_bind_group"),
+        });
+
+        let pixel = |label, format, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let id_texture = pixel(
+            "Pick ID Texture",
+            ID_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let depth_texture = pixel(
+            "Pick Depth Texture",
+            depth_format,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
+
+        Self {
+            pipeline,
+            layout,
+            depth_format,
+            camera_buffer,
+            camera_bind_group,
+            id_view: id_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            depth_view: depth_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            id_texture,
+        }
+    }
+
+    /// Rebuilds the pipeline after the scene shader changed.
+    pub fn reload_shader(&mut self, device: &wgpu::Device, shader: &wgpu::ShaderModule) {
+        self.pipeline = Self::create_pipeline(device, &self.layout, shader, self.depth_format);
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        depth_format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Pick Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_instance_id",
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_pick",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ID_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            // Not culled so that mirrored instances, wound the other way, can be picked too.
+            // Back faces lose the depth test against the front ones anyway.
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    /// Records the pick pass and the copy of its result. `camera` must already include the
+    /// `pick_matrix` of the picked pixel. Hand the returned buffer to `PendingPick::new` once
+    /// the encoder has been submitted.
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: CameraUniform,
+        scene: PickScene,
+    ) -> wgpu::Buffer {
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[camera]));
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Pick Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.id_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            pass.set_vertex_buffer(1, scene.instance_buffer.slice(..));
+            for mesh in scene.meshes {
+                pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..mesh.num_elements, 0, 0..scene.instance_count);
+            }
+        }
+
+        // A new buffer per pick: the previous one may still be mapped
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Readback Buffer"),
+            size: std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            self.id_texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout::default(),
+            },
+            self.id_texture.size(),
+        );
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec4;
+
+    #[test]
+    fn test_pick_matrix_centers_pixel() {
+        let ndc = Vec2::new(0.25, -0.5);
+        let m = pick_matrix(ndc, 100, 50);
+
+        let center = m * Vec4::new(ndc.x, ndc.y, 0.3, 1.0);
+        assert!(center.truncate().truncate().length() < 1e-5);
+        assert_eq!(center.z, 0.3);
+
+        // Half a pixel to the right (1 / width in NDC) lands on the edge of the target
+        let edge = m * Vec4::new(ndc.x + 0.01, ndc.y, 0.0, 1.0);
+        assert!((edge.x - 1.0).abs() < 1e-4);
+    }
+}
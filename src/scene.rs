@@ -0,0 +1,582 @@
+// Scene files: everything the user can edit (instances, lights, camera and its fly-through
+// path, fog, material names and parameters) saved as pretty-printed JSON or RON. The meshes and textures are not included, the
+// scene only names the model it was built on (see scene_archive.rs to bundle them).
+
+use crate::{
+    camera::{Camera, Projection},
+    camera_path::{CameraPath, CameraWaypoint},
+    error::{OrengineError, Result},
+    fog::FogUniform,
+    instance::Instance,
+    light::{DirectionalLight, PointLight, SpotLight},
+    models::{Material, MaterialPropertiesUniform},
+};
+use glam::{Quat, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneData {
+    /// Model file the instances are drawn with, relative to the assets directory
+    pub model: String,
+    pub instances: Vec<SerializedInstance>,
+    pub lights: Vec<SerializedLight>,
+    pub camera: SerializedCamera,
+    /// `None` in files saved without fog, which then keeps its default settings
+    #[serde(default)]
+    pub fog: Option<SerializedFog>,
+    #[serde(default)]
+    pub materials: Vec<SerializedMaterial>,
+    /// Fly-through waypoints, in time order
+    #[serde(default)]
+    pub camera_path: Vec<SerializedCameraWaypoint>,
+}
+
+impl SceneData {
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| OrengineError::Generic(format!("Cannot serialize the scene: {e}")))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| OrengineError::Generic(format!("Invalid scene file: {e}")))
+    }
+
+    /// Same content as `to_json`, in Rusty Object Notation (easier to edit by hand).
+    pub fn to_ron(&self) -> Result<String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| OrengineError::Generic(format!("Cannot serialize the scene: {e}")))
+    }
+
+    pub fn from_ron(ron: &str) -> Result<Self> {
+        ron::from_str(ron).map_err(|e| OrengineError::Generic(format!("Invalid scene file: {e}")))
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SerializedInstance {
+    pub name: String,
+    pub position: [f32; 3],
+    /// Quaternion, x y z w
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    #[serde(default = "default_true")]
+    pub visible: bool,
+    pub tint: [f32; 4],
+    #[serde(default)]
+    pub material_override: Option<usize>,
+    /// Index of the parent instance in the hierarchy
+    #[serde(default)]
+    pub parent: Option<usize>,
+    #[serde(default)]
+    pub locked: bool,
+}
+
+impl From<&Instance> for SerializedInstance {
+    fn from(instance: &Instance) -> Self {
+        Self {
+            name: instance.name.clone(),
+            position: instance.position.to_array(),
+            rotation: instance.rotation.to_array(),
+            scale: instance.scale.to_array(),
+            visible: instance.visible,
+            tint: instance.tint.to_array(),
+            material_override: instance.material_override,
+            parent: instance.parent,
+            locked: instance.locked,
+        }
+    }
+}
+
+impl From<SerializedInstance> for Instance {
+    fn from(instance: SerializedInstance) -> Self {
+        Self {
+            name: instance.name,
+            position: Vec3::from_array(instance.position),
+            rotation: Quat::from_array(instance.rotation).normalize(),
+            scale: Vec3::from_array(instance.scale),
+            visible: instance.visible,
+            tint: Vec4::from_array(instance.tint),
+            material_override: instance.material_override,
+            parent: instance.parent,
+            locked: instance.locked,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SerializedLight {
+    Point {
+        position: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        radius: f32,
+    },
+    Directional {
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+    },
+    /// Angles in radians
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        inner_angle: f32,
+        outer_angle: f32,
+        color: [f32; 3],
+        intensity: f32,
+    },
+}
+
+impl From<&PointLight> for SerializedLight {
+    fn from(light: &PointLight) -> Self {
+        SerializedLight::Point {
+            position: light.position,
+            color: light.color,
+            intensity: light.intensity,
+            radius: light.radius,
+        }
+    }
+}
+
+impl From<&DirectionalLight> for SerializedLight {
+    fn from(light: &DirectionalLight) -> Self {
+        SerializedLight::Directional {
+            direction: light.direction,
+            color: light.color,
+            intensity: light.intensity,
+        }
+    }
+}
+
+impl From<&SpotLight> for SerializedLight {
+    fn from(light: &SpotLight) -> Self {
+        SerializedLight::Spot {
+            position: light.position,
+            direction: light.direction,
+            inner_angle: light.inner_angle,
+            outer_angle: light.outer_angle,
+            color: light.color,
+            intensity: light.intensity,
+        }
+    }
+}
+
+/// The lights of a scene once sorted by kind.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SceneLights {
+    pub points: Vec<PointLight>,
+    /// The last one wins if the file has several
+    pub directional: Option<DirectionalLight>,
+    pub spot: Option<SpotLight>,
+}
+
+impl SceneLights {
+    pub fn from_serialized(lights: &[SerializedLight]) -> Self {
+        let mut scene_lights = Self::default();
+        for light in lights {
+            match *light {
+                SerializedLight::Point {
+                    position,
+                    color,
+                    intensity,
+                    radius,
+                } => scene_lights.points.push(PointLight {
+                    intensity,
+                    radius,
+                    ..PointLight::new(position, color)
+                }),
+                SerializedLight::Directional {
+                    direction,
+                    color,
+                    intensity,
+                } => {
+                    scene_lights.directional = Some(DirectionalLight {
+                        intensity,
+                        ..DirectionalLight::new(direction, color)
+                    })
+                }
+                SerializedLight::Spot {
+                    position,
+                    direction,
+                    inner_angle,
+                    outer_angle,
+                    color,
+                    intensity,
+                } => {
+                    let mut spot = SpotLight {
+                        inner_angle,
+                        outer_angle,
+                        intensity,
+                        ..SpotLight::new(position, direction, color)
+                    };
+                    spot.clamp_angles();
+                    scene_lights.spot = Some(spot);
+                }
+            }
+        }
+        scene_lights
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SerializedProjection {
+    Perspective {
+        fovy: f32,
+        znear: f32,
+        zfar: f32,
+    },
+    Orthographic {
+        width: f32,
+        height: f32,
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+impl From<Projection> for SerializedProjection {
+    fn from(projection: Projection) -> Self {
+        match projection {
+            Projection::Perspective { fovy, znear, zfar } => {
+                SerializedProjection::Perspective { fovy, znear, zfar }
+            }
+            Projection::Orthographic {
+                width,
+                height,
+                znear,
+                zfar,
+            } => SerializedProjection::Orthographic {
+                width,
+                height,
+                znear,
+                zfar,
+            },
+        }
+    }
+}
+
+impl From<SerializedProjection> for Projection {
+    fn from(projection: SerializedProjection) -> Self {
+        match projection {
+            SerializedProjection::Perspective { fovy, znear, zfar } => {
+                Projection::Perspective { fovy, znear, zfar }
+            }
+            SerializedProjection::Orthographic {
+                width,
+                height,
+                znear,
+                zfar,
+            } => Projection::Orthographic {
+                width,
+                height,
+                znear,
+                zfar,
+            },
+        }
+    }
+}
+
+/// The viewport's aspect ratio is not saved, it depends on the window.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SerializedCamera {
+    pub eye: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    pub projection: SerializedProjection,
+}
+
+impl From<&Camera> for SerializedCamera {
+    fn from(camera: &Camera) -> Self {
+        Self {
+            eye: camera.eye.to_array(),
+            target: camera.target.to_array(),
+            up: camera.up.to_array(),
+            projection: camera.projection.into(),
+        }
+    }
+}
+
+impl SerializedCamera {
+    /// Moves `camera` to the saved position, keeping its aspect ratio.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.eye = Vec3::from_array(self.eye);
+        camera.target = Vec3::from_array(self.target);
+        camera.up = Vec3::from_array(self.up);
+        camera.projection = self.projection.into();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SerializedCameraWaypoint {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub time_seconds: f32,
+}
+
+impl From<&CameraWaypoint> for SerializedCameraWaypoint {
+    fn from(waypoint: &CameraWaypoint) -> Self {
+        Self {
+            position: waypoint.position.to_array(),
+            target: waypoint.target.to_array(),
+            time_seconds: waypoint.time_seconds,
+        }
+    }
+}
+
+impl From<&SerializedCameraWaypoint> for CameraWaypoint {
+    fn from(waypoint: &SerializedCameraWaypoint) -> Self {
+        Self {
+            position: Vec3::from_array(waypoint.position),
+            target: Vec3::from_array(waypoint.target),
+            time_seconds: waypoint.time_seconds,
+        }
+    }
+}
+
+impl CameraPath {
+    pub fn from_serialized(waypoints: &[SerializedCameraWaypoint]) -> Self {
+        Self::new(waypoints.iter().map(CameraWaypoint::from).collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SerializedFog {
+    /// Linear RGB and opacity
+    pub color: [f32; 4],
+    pub density: f32,
+    pub start: f32,
+    pub end: f32,
+    /// A `FogMode`
+    pub mode: u32,
+}
+
+impl From<FogUniform> for SerializedFog {
+    fn from(fog: FogUniform) -> Self {
+        Self {
+            color: fog.color,
+            density: fog.density,
+            start: fog.start,
+            end: fog.end,
+            mode: fog.mode,
+        }
+    }
+}
+
+impl From<SerializedFog> for FogUniform {
+    fn from(fog: SerializedFog) -> Self {
+        Self {
+            color: fog.color,
+            density: fog.density,
+            start: fog.start,
+            end: fog.end,
+            mode: fog.mode,
+        }
+    }
+}
+
+/// User-facing name of a material and its shading parameters, matched by `name` when loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SerializedMaterial {
+    pub name: String,
+    /// Absent from older files, the material then shows its original name
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Absent from older files, the material then keeps the parameters of the model file
+    #[serde(default)]
+    pub properties: Option<SerializedMaterialProperties>,
+}
+
+impl SerializedMaterial {
+    /// `material` as currently shaded with `properties`, see `State::update_material_properties`.
+    pub fn new(material: &Material, properties: &MaterialPropertiesUniform) -> Self {
+        Self {
+            name: material.name.clone(),
+            display_name: Some(material.display_name.clone()),
+            properties: Some(properties.into()),
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// The parameters of `MaterialPropertiesUniform` edited from the inspector. The others
+/// follow the material's textures and are left to the model file.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SerializedMaterialProperties {
+    pub roughness: f32,
+    pub metallic: f32,
+    pub ao: f32,
+    /// Linear RGB
+    pub emissive: [f32; 3],
+    /// Linear RGB
+    pub diffuse_color: [f32; 3],
+}
+
+impl From<&MaterialPropertiesUniform> for SerializedMaterialProperties {
+    fn from(properties: &MaterialPropertiesUniform) -> Self {
+        Self {
+            roughness: properties.roughness,
+            metallic: properties.metallic,
+            ao: properties.ao,
+            emissive: properties.emissive,
+            diffuse_color: properties.diffuse_color,
+        }
+    }
+}
+
+impl SerializedMaterialProperties {
+    /// `properties` with the saved parameters.
+    pub fn apply(&self, properties: MaterialPropertiesUniform) -> MaterialPropertiesUniform {
+        MaterialPropertiesUniform {
+            roughness: self.roughness,
+            metallic: self.metallic,
+            ao: self.ao,
+            emissive: self.emissive,
+            diffuse_color: self.diffuse_color,
+            ..properties
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene() -> SceneData {
+        let instance = Instance {
+            name: "Pizza".to_string(),
+            position: Vec3::new(1.0, 2.0, 3.0),
+            rotation: Quat::from_rotation_y(0.5),
+            scale: Vec3::new(2.0, 1.0, 1.0),
+            visible: false,
+            tint: Vec4::new(1.0, 0.5, 0.5, 1.0),
+            material_override: Some(1),
+            parent: Some(0),
+            locked: true,
+        };
+        SceneData {
+            model: "pizza.obj".to_string(),
+            instances: vec![(&instance).into()],
+            lights: vec![
+                (&PointLight::default()).into(),
+                (&DirectionalLight::default()).into(),
+                (&SpotLight::default()).into(),
+            ],
+            camera: (&Camera::default()).into(),
+            fog: Some(FogUniform::default().into()),
+            materials: vec![SerializedMaterial {
+                name: "Material001".to_string(),
+                display_name: Some("Pâte".to_string()),
+                properties: Some(SerializedMaterialProperties {
+                    roughness: 0.2,
+                    metallic: 1.0,
+                    ao: 0.8,
+                    emissive: [0.5, 0.25, 0.0],
+                    diffuse_color: [1.0, 0.9, 0.7],
+                }),
+            }],
+            camera_path: vec![
+                SerializedCameraWaypoint {
+                    position: [0.0, 1.0, 5.0],
+                    target: [0.0, 1.0, 0.0],
+                    time_seconds: 0.0,
+                },
+                SerializedCameraWaypoint {
+                    position: [5.0, 2.0, 0.0],
+                    target: [0.0, 1.0, 0.0],
+                    time_seconds: 2.5,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_scene_json_roundtrip() {
+        let scene = scene();
+        let json = scene.to_json().unwrap();
+        assert_eq!(SceneData::from_json(&json).unwrap(), scene);
+
+        let instance = Instance::from(scene.instances[0].clone());
+        assert_eq!(instance.name, "Pizza");
+        assert!(!instance.visible);
+        assert_eq!(instance.material_override, Some(1));
+
+        let lights = SceneLights::from_serialized(&scene.lights);
+        assert_eq!(lights.points, vec![PointLight::default()]);
+        assert_eq!(lights.directional, Some(DirectionalLight::default()));
+        assert_eq!(lights.spot, Some(SpotLight::default()));
+
+        let path = CameraPath::from_serialized(&scene.camera_path);
+        assert_eq!(path.waypoints[1].position, Vec3::new(5.0, 2.0, 0.0));
+        assert_eq!(path.end_time(), 2.5);
+
+        let properties = MaterialPropertiesUniform {
+            specular_map: 1,
+            opacity: 0.5,
+            ..Default::default()
+        };
+        let properties = scene.materials[0].properties.unwrap().apply(properties);
+        assert_eq!(properties.metallic, 1.0);
+        assert_eq!(properties.emissive, [0.5, 0.25, 0.0]);
+        assert_eq!(properties.diffuse_color, [1.0, 0.9, 0.7]);
+        // Follow the textures, not saved
+        assert_eq!(properties.specular_map, 1);
+        assert_eq!(properties.opacity, 0.5);
+    }
+
+    #[test]
+    fn test_scene_ron_roundtrip() {
+        let scene = scene();
+        let ron = scene.to_ron().unwrap();
+        assert_eq!(SceneData::from_ron(&ron).unwrap(), scene);
+        assert!(matches!(
+            SceneData::from_ron("(model: 3)"),
+            Err(OrengineError::Generic(_))
+        ));
+    }
+
+    #[test]
+    fn test_scene_json_defaults() {
+        let json = r#"{
+            "model": "pizza.obj",
+            "instances": [{
+                "name": "Objet 0",
+                "position": [0, 0, 0],
+                "rotation": [0, 0, 0, 1],
+                "scale": [1, 1, 1],
+                "tint": [1, 1, 1, 1]
+            }],
+            "lights": [],
+            "camera": {
+                "eye": [0, 1, 5],
+                "target": [0, 1, 0],
+                "up": [0, 1, 0],
+                "projection": { "type": "Perspective", "fovy": 0.8, "znear": 0.1, "zfar": 100 }
+            },
+            "materials": [{ "name": "Material001" }]
+        }"#;
+        let scene = SceneData::from_json(json).unwrap();
+        assert!(scene.instances[0].visible);
+        assert_eq!(scene.fog, None);
+        assert!(scene.camera_path.is_empty());
+        // The display name falls back to the original one
+        assert_eq!(scene.materials[0].display_name(), "Material001");
+        assert_eq!(scene.materials[0].properties, None);
+    }
+
+    #[test]
+    fn test_invalid_scene_json() {
+        assert!(matches!(
+            SceneData::from_json("{ \"model\": 3 }"),
+            Err(OrengineError::Generic(_))
+        ));
+    }
+}
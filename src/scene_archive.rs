@@ -0,0 +1,170 @@
+// Self-contained scene archives (.esc): a ZIP holding the scene description and
+// every asset it references, so that a scene can be moved or shared as one file.
+//
+// Layout inside the archive:
+//   scene.json
+//   assets/<path relative to the assets directory>
+
+use crate::error::{OrengineError, Result};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
+use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+pub const SCENE_ARCHIVE_EXTENSION: &str = "esc";
+const SCENE_ENTRY: &str = "scene.json";
+const ASSETS_DIR: &str = "assets";
+
+pub fn is_scene_archive(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(SCENE_ARCHIVE_EXTENSION))
+}
+
+/// Lists the files needed to load an OBJ model: the .obj itself, its .mtl libraries
+/// and every texture they reference. Paths are relative to `assets_dir`.
+pub fn model_asset_files(assets_dir: &Path, model_file: &str) -> Result<Vec<String>> {
+    let mut files = vec![model_file.to_string()];
+    let model_dir = Path::new(model_file).parent().unwrap_or(Path::new(""));
+
+    let obj = BufReader::new(File::open(assets_dir.join(model_file))?);
+    for line in obj.lines() {
+        let line = line?;
+        let Some(mtl) = line.trim().strip_prefix("mtllib") else {
+            continue;
+        };
+        let mtl_file = model_dir.join(mtl.trim());
+        let mtl_path = assets_dir.join(&mtl_file);
+        if !mtl_path.exists() {
+            // Missing libraries are reported by the model loader, not here
+            continue;
+        }
+        files.push(mtl_file.to_string_lossy().into_owned());
+
+        let (materials, _) = tobj::load_mtl(&mtl_path)?;
+        for mat in materials {
+            let textures = [
+                mat.diffuse_texture,
+                mat.normal_texture,
+                mat.specular_texture,
+                mat.ambient_texture,
+                mat.shininess_texture,
+                mat.dissolve_texture,
+                mat.unknown_param.get("map_Ke").cloned(),
+            ];
+            for texture in textures.into_iter().flatten() {
+                let texture = model_dir.join(texture).to_string_lossy().into_owned();
+                if !files.contains(&texture) {
+                    files.push(texture);
+                }
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Writes `scene_json` and the given assets (relative to `assets_dir`) into a .esc archive.
+pub fn write_scene_archive(
+    archive_path: &Path,
+    scene_json: &str,
+    assets_dir: &Path,
+    assets: &[String],
+) -> Result<()> {
+    let mut zip = ZipWriter::new(File::create(archive_path)?);
+    let options = SimpleFileOptions::default();
+
+    zip.start_file(SCENE_ENTRY, options)?;
+    zip.write_all(scene_json.as_bytes())?;
+
+    for asset in assets {
+        let mut bytes = Vec::new();
+        File::open(assets_dir.join(asset))?.read_to_end(&mut bytes)?;
+        // ZIP entries always use forward slashes
+        let entry = format!("{ASSETS_DIR}/{}", asset.replace('\\', "/"));
+        zip.start_file(entry, options)?;
+        zip.write_all(&bytes)?;
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+/// A .esc archive unpacked into a temporary directory, removed again on drop.
+#[derive(Debug)]
+pub struct ExtractedScene {
+    dir: PathBuf,
+    pub scene_json: String,
+}
+
+impl ExtractedScene {
+    /// Directory to resolve the scene's asset paths against.
+    pub fn assets_dir(&self) -> PathBuf {
+        self.dir.join(ASSETS_DIR)
+    }
+}
+
+impl Drop for ExtractedScene {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+            log::warn!("Failed to clean up {:?}: {}", self.dir, e);
+        }
+    }
+}
+
+pub fn extract_scene_archive(archive_path: &Path) -> Result<ExtractedScene> {
+    let mut zip = ZipArchive::new(File::open(archive_path)?)?;
+
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let dir = std::env::temp_dir().join(format!("orengine-{}-{}", std::process::id(), nanos));
+    std::fs::create_dir_all(&dir)?;
+
+    // From here on the directory is cleaned up even if extraction fails
+    let mut extracted = ExtractedScene {
+        dir,
+        scene_json: String::new(),
+    };
+    zip.extract(&extracted.dir)?;
+
+    extracted.scene_json = std::fs::read_to_string(extracted.dir.join(SCENE_ENTRY))
+        .map_err(|_| OrengineError::Generic(format!("{archive_path:?} has no {SCENE_ENTRY}")))?;
+
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_asset_files() {
+        let files = model_asset_files(Path::new("assets"), "pizza.obj").unwrap();
+        assert_eq!(files, vec!["pizza.obj", "pizza.mtl", "pizzaTxt.png"]);
+    }
+
+    #[test]
+    fn test_scene_archive_roundtrip() {
+        let archive_path = std::env::temp_dir().join(format!(
+            "orengine-test-{}.{SCENE_ARCHIVE_EXTENSION}",
+            std::process::id()
+        ));
+        assert!(is_scene_archive(&archive_path));
+
+        let assets = model_asset_files(Path::new("assets"), "triangle.obj").unwrap();
+        write_scene_archive(&archive_path, "{}", Path::new("assets"), &assets).unwrap();
+
+        let extracted = extract_scene_archive(&archive_path).unwrap();
+        assert_eq!(extracted.scene_json, "{}");
+        assert!(extracted.assets_dir().join("triangle.obj").exists());
+
+        let dir = extracted.dir.clone();
+        drop(extracted);
+        assert!(!dir.exists());
+
+        std::fs::remove_file(archive_path).unwrap();
+    }
+}
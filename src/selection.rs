@@ -0,0 +1,165 @@
+use crate::models::Aabb;
+use glam::{Mat4, Vec3};
+
+/// Projects a world-space point into a viewport rectangle (egui screen coordinates).
+/// Returns `None` for points behind the camera.
+pub fn world_to_screen(point: Vec3, view_proj: Mat4, viewport: egui::Rect) -> Option<egui::Pos2> {
+    let clip = view_proj * point.extend(1.0);
+    if clip.w <= f32::EPSILON {
+        return None;
+    }
+    let ndc = clip.truncate() / clip.w;
+
+    // NDC Y points up, screen Y points down
+    Some(egui::pos2(
+        viewport.min.x + (ndc.x + 1.0) * 0.5 * viewport.width(),
+        viewport.min.y + (1.0 - ndc.y) * 0.5 * viewport.height(),
+    ))
+}
+
+/// Fixes up instance indices after `instances.swap_remove(removed)` on a list whose last
+/// index was `last`: `removed` is forgotten and `last` now lives at `removed`.
+pub fn remap_swap_removed(index: usize, removed: usize, last: usize) -> Option<usize> {
+    if index == removed {
+        None
+    } else if index == last {
+        Some(removed)
+    } else {
+        Some(index)
+    }
+}
+
+/// Whether `point` is inside `polygon`, by counting the edges a horizontal ray from it
+/// crosses (even-odd rule, so self-intersecting lassos have holes). The polygon is closed
+/// between its last and first points.
+pub fn point_in_polygon(point: egui::Pos2, polygon: &[egui::Pos2]) -> bool {
+    let mut inside = false;
+    let mut previous = match polygon.last() {
+        Some(&last) => last,
+        None => return false,
+    };
+    for &current in polygon {
+        if (current.y > point.y) != (previous.y > point.y) {
+            let x = current.x
+                + (point.y - current.y) / (previous.y - current.y) * (previous.x - current.x);
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+        previous = current;
+    }
+    inside
+}
+
+/// Screen-space bounding rectangle of a local-space AABB placed in the world by `model`.
+/// Corners behind the camera are ignored, `None` if the whole box is behind it.
+pub fn aabb_screen_rect(
+    aabb: &Aabb,
+    model: Mat4,
+    view_proj: Mat4,
+    viewport: egui::Rect,
+) -> Option<egui::Rect> {
+    let world = aabb.transform_to_world(&model);
+    let mut corners = world
+        .corners()
+        .into_iter()
+        .filter_map(|c| world_to_screen(c, view_proj, viewport));
+
+    let first = corners.next()?;
+    Some(
+        corners.fold(egui::Rect::from_min_max(first, first), |rect, p| {
+            rect.union(egui::Rect::from_min_max(p, p))
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::{Camera, Projection};
+
+    /// Camera high above the origin, looking straight down.
+    fn top_down_view_proj() -> Mat4 {
+        let camera = Camera {
+            up: Vec3::NEG_Z,
+            projection: Projection::Perspective {
+                fovy: 90.0_f32.to_radians(),
+                znear: 0.1,
+                zfar: 1000.0,
+            },
+            ..Camera::looking_at(Vec3::new(0.0, 10.0, 0.0), Vec3::ZERO)
+        };
+        camera.build_view_projection_matrix()
+    }
+
+    fn viewport() -> egui::Rect {
+        egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(200.0, 200.0))
+    }
+
+    #[test]
+    fn test_world_to_screen_center() {
+        let p = world_to_screen(Vec3::ZERO, top_down_view_proj(), viewport()).unwrap();
+        assert!((p - egui::pos2(100.0, 100.0)).length() < 1e-3);
+
+        // Behind the camera
+        assert!(
+            world_to_screen(Vec3::new(0.0, 20.0, 0.0), top_down_view_proj(), viewport()).is_none()
+        );
+    }
+
+    #[test]
+    fn test_aabb_screen_rect_covers_geometry() {
+        // A wide, flat instance whose pivot sits far to the left of its geometry
+        let aabb = Aabb::new(Vec3::new(0.0, 0.0, -1.0), Vec3::new(8.0, 0.0, 1.0));
+        let model = Mat4::from_translation(Vec3::new(-6.0, 0.0, 0.0));
+
+        let rect = aabb_screen_rect(&aabb, model, top_down_view_proj(), viewport()).unwrap();
+
+        // At 10 units with a 90° FOV the view spans [-10, 10] on both axes
+        assert!((rect.min.x - 40.0).abs() < 1e-3);
+        assert!((rect.max.x - 120.0).abs() < 1e-3);
+        assert!((rect.min.y - 90.0).abs() < 1e-3);
+        assert!((rect.max.y - 110.0).abs() < 1e-3);
+
+        // A selection box on the right misses the pivot but still hits the geometry
+        let selection = egui::Rect::from_min_max(egui::pos2(100.0, 90.0), egui::pos2(150.0, 110.0));
+        let pivot =
+            world_to_screen(Vec3::new(-6.0, 0.0, 0.0), top_down_view_proj(), viewport()).unwrap();
+        assert!(!selection.contains(pivot));
+        assert!(selection.intersects(rect));
+    }
+
+    #[test]
+    fn test_point_in_polygon() {
+        // A "U" opening upwards: (5, 2) is in its notch, inside its bounding box but out of it
+        let u = [
+            egui::pos2(0.0, 0.0),
+            egui::pos2(3.0, 0.0),
+            egui::pos2(3.0, 6.0),
+            egui::pos2(7.0, 6.0),
+            egui::pos2(7.0, 0.0),
+            egui::pos2(10.0, 0.0),
+            egui::pos2(10.0, 10.0),
+            egui::pos2(0.0, 10.0),
+        ];
+        assert!(!point_in_polygon(egui::pos2(5.0, 2.0), &u));
+        assert!(point_in_polygon(egui::pos2(1.0, 2.0), &u));
+        assert!(point_in_polygon(egui::pos2(5.0, 8.0), &u));
+        assert!(!point_in_polygon(egui::pos2(11.0, 5.0), &u));
+        assert!(!point_in_polygon(egui::pos2(1.0, 1.0), &u[..2]));
+        assert!(!point_in_polygon(egui::pos2(1.0, 1.0), &[]));
+    }
+
+    #[test]
+    fn test_remap_swap_removed() {
+        let mut instances = vec!['a', 'b', 'c', 'd'];
+        instances.swap_remove(1);
+        let remapped: Vec<_> = (0..4).map(|i| remap_swap_removed(i, 1, 3)).collect();
+        assert_eq!(remapped, [Some(0), None, Some(2), Some(1)]);
+        assert_eq!(instances[remapped[3].unwrap()], 'd');
+
+        // Removing the last one moves nothing
+        assert_eq!(remap_swap_removed(3, 3, 3), None);
+        assert_eq!(remap_swap_removed(2, 3, 3), Some(2));
+    }
+}
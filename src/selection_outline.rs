@@ -0,0 +1,272 @@
+// Outline of the selected instances through the stencil buffer: the instances mark the
+// stencil, then a copy pushed outward along their normals is drawn where it is unmarked
+// (selection_outline.wgsl).
+
+use crate::{
+    instance::InstanceRaw, post_process::uniform_layout_entry, state::MeshRenderData,
+    vertex::Vertex,
+};
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+const STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Stencil8;
+/// Stencil value written over the selected instances
+const SELECTED: u32 = 1;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct SelectionOutlineUniform {
+    color: [f32; 4],
+    viewport_size: [f32; 2],
+    thickness: f32,
+    _padding: f32,
+}
+
+/// What the outline is drawn around: every mesh, once per selected instance.
+pub struct SelectionScene<'a> {
+    pub meshes: &'a [MeshRenderData],
+    pub instance_buffer: &'a wgpu::Buffer,
+    pub instance_count: u32,
+}
+
+pub struct SelectionOutlineRenderer {
+    /// In pixels
+    pub thickness: f32,
+    mask_pipeline: wgpu::RenderPipeline,
+    outline_pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    stencil_view: wgpu::TextureView,
+    viewport_size: [f32; 2],
+}
+
+impl SelectionOutlineRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        target_format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("selection_outline_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ..uniform_layout_entry(0)
+            }],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Selection Outline Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../selection_outline.wgsl"));
+
+        let stencil_state = |compare, pass_op, write_mask| {
+            let face = wgpu::StencilFaceState {
+                compare,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Keep,
+                pass_op,
+            };
+            wgpu::DepthStencilState {
+                format: STENCIL_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState {
+                    front: face,
+                    back: face,
+                    read_mask: 0xff,
+                    write_mask,
+                },
+                bias: wgpu::DepthBiasState::default(),
+            }
+        };
+        let buffers = [Vertex::desc(), InstanceRaw::desc()];
+        let vertex = |entry_point| wgpu::VertexState {
+            module: &shader,
+            entry_point,
+            buffers: &buffers,
+        };
+
+        // Stencil only: mirrored instances are wound the other way, nothing is culled
+        let mask_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Selection Mask Pipeline"),
+            layout: Some(&layout),
+            vertex: vertex("vs_mask"),
+            fragment: None,
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(stencil_state(
+                wgpu::CompareFunction::Always,
+                wgpu::StencilOperation::Replace,
+                0xff,
+            )),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let outline_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Selection Outline Pipeline"),
+            layout: Some(&layout),
+            vertex: vertex("vs_outline"),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_outline",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            // The back faces of the enlarged copy, behind the instance itself
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Front),
+                ..Default::default()
+            },
+            depth_stencil: Some(stencil_state(
+                wgpu::CompareFunction::NotEqual,
+                wgpu::StencilOperation::Keep,
+                0,
+            )),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Selection Outline Buffer"),
+            contents: bytemuck::cast_slice(&[SelectionOutlineUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("selection_outline_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            thickness: 3.0,
+            mask_pipeline,
+            outline_pipeline,
+            bind_group,
+            uniform_buffer,
+            stencil_view: Self::create_stencil_view(device, config),
+            viewport_size: [config.width as f32, config.height as f32],
+        }
+    }
+
+    fn create_stencil_view(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> wgpu::TextureView {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Selection Stencil"),
+                size: wgpu::Extent3d {
+                    width: config.width,
+                    height: config.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: STENCIL_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// The stencil buffer follows the surface size.
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.stencil_view = Self::create_stencil_view(device, config);
+        self.viewport_size = [config.width as f32, config.height as f32];
+    }
+
+    /// Draws a `color` outline around `scene` into `target`, over everything else.
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        camera_bind_group: &wgpu::BindGroup,
+        scene: SelectionScene,
+        color: [f32; 4],
+    ) {
+        let uniform = SelectionOutlineUniform {
+            color,
+            viewport_size: self.viewport_size,
+            thickness: self.thickness,
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        let stencil_attachment = |load| {
+            Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.stencil_view,
+                depth_ops: None,
+                stencil_ops: Some(wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                }),
+            })
+        };
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Selection Mask Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: stencil_attachment(wgpu::LoadOp::Clear(0)),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            self.draw(&mut pass, &self.mask_pipeline, camera_bind_group, &scene);
+        }
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Selection Outline Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: stencil_attachment(wgpu::LoadOp::Load),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        self.draw(&mut pass, &self.outline_pipeline, camera_bind_group, &scene);
+    }
+
+    fn draw<'a>(
+        &'a self,
+        pass: &mut wgpu::RenderPass<'a>,
+        pipeline: &'a wgpu::RenderPipeline,
+        camera_bind_group: &'a wgpu::BindGroup,
+        scene: &SelectionScene<'a>,
+    ) {
+        pass.set_pipeline(pipeline);
+        pass.set_stencil_reference(SELECTED);
+        pass.set_bind_group(0, camera_bind_group, &[]);
+        pass.set_bind_group(1, &self.bind_group, &[]);
+        pass.set_vertex_buffer(1, scene.instance_buffer.slice(..));
+        for mesh in scene.meshes {
+            pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            pass.draw_indexed(0..mesh.num_elements, 0, 0..scene.instance_count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_outline_uniform_layout() {
+        // Matches `SelectionOutline` in selection_outline.wgsl
+        assert_eq!(std::mem::size_of::<SelectionOutlineUniform>(), 32);
+        assert_eq!(std::mem::offset_of!(SelectionOutlineUniform, thickness), 24);
+    }
+}
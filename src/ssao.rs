@@ -0,0 +1,359 @@
+// Screen-space ambient occlusion: the occlusion is estimated from the depth buffer into a
+// single-channel target (ssao.wgsl), blurred, then multiplied into the lit image
+// (ssao_blur.wgsl).
+
+use crate::post_process::{
+    create_fullscreen_pipeline, create_fullscreen_shader, run_fullscreen_pass,
+    texture_layout_entry, uniform_layout_entry,
+};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+/// Size of the kernel array in the shader, `SsaoSettings::kernel_size` is clamped to it
+pub const MAX_SSAO_KERNEL_SIZE: u32 = 64;
+const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct SsaoUniform {
+    projection: [[f32; 4]; 4],
+    inverse_projection: [[f32; 4]; 4],
+    radius: f32,
+    bias: f32,
+    kernel_size: u32,
+    _padding: u32,
+    kernel: [[f32; 4]; MAX_SSAO_KERNEL_SIZE as usize],
+}
+
+/// Tunable look of the ambient occlusion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SsaoSettings {
+    /// Radius of the sampled hemisphere in world units
+    pub radius: f32,
+    /// Depth difference ignored, avoids surfaces occluding themselves
+    pub bias: f32,
+    /// Samples per pixel, up to `MAX_SSAO_KERNEL_SIZE`
+    pub kernel_size: u32,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            bias: 0.025,
+            kernel_size: 32,
+        }
+    }
+}
+
+/// Random offsets in the +Z hemisphere, within the unit sphere and denser near the origin so
+/// that close occluders weigh more. Deterministic, the same as the `compute` feature's
+/// `SsaoKernelGenerator`.
+pub fn ssao_kernel(size: u32) -> Vec<Vec3> {
+    (0..size)
+        .map(|i| {
+            let mut state = pcg(i);
+            let mut random = || {
+                state = pcg(state);
+                state as f32 / u32::MAX as f32
+            };
+            // A uniform cos(theta) spreads the directions uniformly over the hemisphere
+            let cos_theta = random();
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            let phi = std::f32::consts::TAU * random();
+            let direction = Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta);
+
+            let t = i as f32 / size as f32;
+            direction * random() * (0.1 + 0.9 * t * t)
+        })
+        .collect()
+}
+
+fn pcg(value: u32) -> u32 {
+    let state = value.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+/// Render targets and bind groups that follow the surface size.
+struct SsaoTargets {
+    /// Reads the depth buffer, writes `occlusion`
+    occlusion_bind_group: wgpu::BindGroup,
+    /// Reads `occlusion`, writes `blurred`
+    blur_bind_group: wgpu::BindGroup,
+    /// Reads `blurred`
+    composite_bind_group: wgpu::BindGroup,
+    occlusion: wgpu::TextureView,
+    blurred: wgpu::TextureView,
+}
+
+/// Post-process pass darkening creases and contact areas, multiplied into the rendered
+/// image. Reads the depth buffer, so it is skipped with MSAA like the outlines.
+pub struct SsaoRenderer {
+    pub enabled: bool,
+    pub settings: SsaoSettings,
+    kernel: [[f32; 4]; MAX_SSAO_KERNEL_SIZE as usize],
+    occlusion_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    depth_layout: wgpu::BindGroupLayout,
+    occlusion_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    targets: SsaoTargets,
+}
+
+impl SsaoRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        target_format: wgpu::TextureFormat,
+        depth_view: &wgpu::TextureView,
+    ) -> Self {
+        let unfilterable = wgpu::TextureSampleType::Float { filterable: false };
+        let depth_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ssao_bind_group_layout"),
+            entries: &[
+                texture_layout_entry(0, unfilterable),
+                uniform_layout_entry(1),
+            ],
+        });
+        let occlusion_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ssao_blur_bind_group_layout"),
+            entries: &[texture_layout_entry(0, unfilterable)],
+        });
+
+        let shader = create_fullscreen_shader(device, "SSAO Shader", include_str!("../ssao.wgsl"));
+        let blur_shader = create_fullscreen_shader(
+            device,
+            "SSAO Blur Shader",
+            include_str!("../ssao_blur.wgsl"),
+        );
+        let occlusion_pipeline = create_fullscreen_pipeline(
+            device,
+            "SSAO Pipeline",
+            &[&depth_layout],
+            &shader,
+            "fs_ssao",
+            OCCLUSION_FORMAT,
+            None,
+        );
+        let blur_pipeline = create_fullscreen_pipeline(
+            device,
+            "SSAO Blur Pipeline",
+            &[&occlusion_layout],
+            &blur_shader,
+            "fs_blur",
+            OCCLUSION_FORMAT,
+            None,
+        );
+        // target = target * occlusion, alpha kept
+        let multiply = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::Src,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+        let composite_pipeline = create_fullscreen_pipeline(
+            device,
+            "SSAO Composite Pipeline",
+            &[&occlusion_layout],
+            &blur_shader,
+            "fs_composite",
+            target_format,
+            Some(multiply),
+        );
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("SSAO Buffer"),
+            contents: bytemuck::cast_slice(&[SsaoUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let mut kernel = [[0.0; 4]; MAX_SSAO_KERNEL_SIZE as usize];
+        for (slot, offset) in kernel.iter_mut().zip(ssao_kernel(MAX_SSAO_KERNEL_SIZE)) {
+            *slot = offset.extend(0.0).to_array();
+        }
+
+        let targets = Self::create_targets(
+            device,
+            config,
+            depth_view,
+            &depth_layout,
+            &occlusion_layout,
+            &uniform_buffer,
+        );
+
+        Self {
+            enabled: false,
+            settings: SsaoSettings::default(),
+            kernel,
+            occlusion_pipeline,
+            blur_pipeline,
+            composite_pipeline,
+            depth_layout,
+            occlusion_layout,
+            uniform_buffer,
+            targets,
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+        depth_layout: &wgpu::BindGroupLayout,
+        occlusion_layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> SsaoTargets {
+        let create_target = |label| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: config.width,
+                        height: config.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: OCCLUSION_FORMAT,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let occlusion = create_target("SSAO Occlusion");
+        let blurred = create_target("SSAO Blurred");
+
+        let texture_bind_group = |label, view| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: occlusion_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                }],
+            })
+        };
+
+        SsaoTargets {
+            occlusion_bind_group: device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("ssao_bind_group"),
+                layout: depth_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(depth_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            }),
+            blur_bind_group: texture_bind_group("ssao_blur_bind_group", &occlusion),
+            composite_bind_group: texture_bind_group("ssao_composite_bind_group", &blurred),
+            occlusion,
+            blurred,
+        }
+    }
+
+    /// The depth texture is recreated on resize, the occlusion targets follow its size.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+    ) {
+        self.targets = Self::create_targets(
+            device,
+            config,
+            depth_view,
+            &self.depth_layout,
+            &self.occlusion_layout,
+            &self.uniform_buffer,
+        );
+    }
+
+    /// Darkens `target` by the occlusion of the depth buffer, which was rendered with
+    /// `projection`.
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        projection: Mat4,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let uniform = SsaoUniform {
+            projection: projection.to_cols_array_2d(),
+            inverse_projection: projection.inverse().to_cols_array_2d(),
+            radius: self.settings.radius,
+            bias: self.settings.bias,
+            kernel_size: self.settings.kernel_size.min(MAX_SSAO_KERNEL_SIZE),
+            _padding: 0,
+            kernel: self.kernel,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        let targets = &self.targets;
+        run_fullscreen_pass(
+            encoder,
+            "SSAO Pass",
+            &targets.occlusion,
+            None,
+            &self.occlusion_pipeline,
+            &[&targets.occlusion_bind_group],
+        );
+        run_fullscreen_pass(
+            encoder,
+            "SSAO Blur Pass",
+            &targets.blurred,
+            None,
+            &self.blur_pipeline,
+            &[&targets.blur_bind_group],
+        );
+        run_fullscreen_pass(
+            encoder,
+            "SSAO Composite Pass",
+            target,
+            None,
+            &self.composite_pipeline,
+            &[&targets.composite_bind_group],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ssao_kernel_in_hemisphere() {
+        let kernel = ssao_kernel(MAX_SSAO_KERNEL_SIZE);
+        assert_eq!(kernel.len(), MAX_SSAO_KERNEL_SIZE as usize);
+        assert!(kernel.iter().all(|k| k.z >= 0.0 && k.length() <= 1.0));
+        // Not all the same sample
+        assert!(kernel.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn test_ssao_uniform_layout() {
+        // Matches `SsaoUniform` in ssao.wgsl
+        assert_eq!(std::mem::offset_of!(SsaoUniform, radius), 128);
+        assert_eq!(std::mem::offset_of!(SsaoUniform, kernel), 144);
+        assert_eq!(std::mem::size_of::<SsaoUniform>(), 144 + 64 * 16);
+    }
+}
@@ -1,17 +1,73 @@
+#[cfg(feature = "compute")]
+use crate::compute::{ComputePass, SsaoKernelGenerator, compute_supported};
 use crate::{
-    camera::{Camera, CameraUniform},
+    antialias::{AntialiasRenderer, AntialiasingMode, supported_antialiasing_modes},
+    ao_bake,
+    bvh::Bvh,
+    camera::{Camera, CameraBookmark, CameraUniform, Projection, Ray, aabb_in_frustum},
+    camera_path::{CAMERA_WAYPOINT_INTERVAL, CameraPath, CameraPathPlayback, CameraWaypoint},
+    chromatic_aberration::ChromaticAberration,
+    color_grade::ColorGrading,
+    commands::{
+        Command, CommandStack, DeleteInstancesCommand, DuplicateCommand, InvertSelectionCommand,
+        MoveInstanceCommand, RotateInstanceCommand, ScaleInstanceCommand, SelectByMaterialCommand,
+        SelectCommand,
+    },
+    config::Config,
+    console::{Console, LogLevel},
+    dof::{DofRenderer, DofUniform},
+    engine_log,
     error::{OrengineError, Result},
+    exposure::{AutoExposure, auto_exposure_supported},
+    fog::FogUniform,
+    frame_stats::{FrameStats, RenderStats},
+    gamma::{GammaCorrection, needs_gamma_correction},
+    gizmo::{
+        DEFAULT_SNAP_GRID_SIZE, DEFAULT_SNAP_ROTATION_DEGREES, GizmoDrag, GizmoFrame, GizmoHandle,
+        GizmoMode, GizmoMotion, GizmoRenderer, GizmoSpace, axis_parameter, gizmo_scale, pick_axis,
+        pick_ring, pick_scale_handle, ring_point, scale_factor, signed_angle, snap_angle,
+        snap_to_grid, uniform_scale_parameter,
+    },
+    gpu_memory::GpuMemoryTracker,
+    grid::{DEFAULT_GRID_SPACING, Grid, GridUniform},
     gui::Gui,
-    input::InputHandler,
-    instance::{Instance, InstanceRaw},
-    light::LightUniform,
-    models::load_model,
+    input::{EditorAction, InputHandler},
+    input_recorder::{InputPlayback, InputRecorder, InputRecording, RecordedEvent},
+    instance::{DrawRun, Instance, InstanceRaw, check_instance_limit, draw_runs},
+    light::{DirectionalLight, LightArray, PointLight, SpotLight, SpotLightUniform},
+    models::{
+        Aabb, LoadProgress, Material, MaterialPropertiesUniform, Mesh, Model, load_gltf_from,
+        load_model_from, report_progress,
+    },
+    motion_blur::{MotionBlurRenderer, MotionBlurScene},
+    outline::OutlineRenderer,
+    picking::{PendingPick, PickScene, Picker, pick_matrix},
+    scene_archive::ExtractedScene,
+    selection::{aabb_screen_rect, point_in_polygon, remap_swap_removed, world_to_screen},
+    selection_outline::{SelectionOutlineRenderer, SelectionScene},
+    ssao::SsaoRenderer,
     textures,
-    vertex::Vertex,
+    tonemap::{TonemapMode, Tonemapper},
+    transparency::{check_depth_order, sort_back_to_front},
+    vignette::{VignetteRenderer, VignetteUniform},
+    watcher::{ChangeKind, FileWatcher},
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+    task::Poll,
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 use wgpu::util::DeviceExt;
 use winit::{dpi::PhysicalSize, event::WindowEvent, window::Window};
 
+mod pipelines;
+mod scene_file;
+mod ui;
+
+use pipelines::{ScenePipelineBuilder, ScenePipelines};
+
 pub struct MeshRenderData {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
@@ -19,32 +75,663 @@ pub struct MeshRenderData {
     pub material_id: usize,
 }
 
+/// Creates the vertex and index buffers of each mesh, the last step of a model import.
+pub fn upload_meshes(
+    device: &wgpu::Device,
+    meshes: &[Mesh],
+    progress: Option<Sender<LoadProgress>>,
+) -> Vec<MeshRenderData> {
+    let meshes = meshes
+        .iter()
+        .map(|m| {
+            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{:?} Vertex Buffer", m.name)),
+                contents: bytemuck::cast_slice(&m.vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{:?} Index Buffer", m.name)),
+                contents: bytemuck::cast_slice(&m.indices),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+            MeshRenderData {
+                vertex_buffer,
+                index_buffer,
+                num_elements: m.indices.len() as u32,
+                material_id: m.material_id,
+            }
+        })
+        .collect();
+
+    report_progress(&progress, "Uploading to GPU", 1.0);
+    meshes
+}
+
+/// Every texture bound with a material, placeholders included.
+pub struct MaterialTextures {
+    pub diffuse: textures::Texture,
+    /// White when the material has no ambient occlusion map
+    ambient_occlusion: textures::Texture,
+    /// Flat (0, 0, 1) when the material has no normal map
+    normal_map: textures::Texture,
+    /// Neutral when the material has no specular map, the properties are used instead
+    specular_map: textures::Texture,
+    /// White when the material has no emissive map, the emissive color is used as is
+    emissive_map: textures::Texture,
+    /// White when the material has no lightmap
+    lightmap: textures::Texture,
+}
+
+impl MaterialTextures {
+    fn all(&self) -> [&textures::Texture; 6] {
+        [
+            &self.diffuse,
+            &self.ambient_occlusion,
+            &self.normal_map,
+            &self.specular_map,
+            &self.emissive_map,
+            &self.lightmap,
+        ]
+    }
+}
+
+/// A texture of `MaterialTextures` that can be replaced from the editor, see
+/// `State::reload_material_texture`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureSlot {
+    Diffuse,
+    AmbientOcclusion,
+    Normal,
+    MetallicRoughness,
+    Emissive,
+}
+
+impl TextureSlot {
+    pub const ALL: [TextureSlot; 5] = [
+        TextureSlot::Diffuse,
+        TextureSlot::AmbientOcclusion,
+        TextureSlot::Normal,
+        TextureSlot::MetallicRoughness,
+        TextureSlot::Emissive,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TextureSlot::Diffuse => "Diffuse",
+            TextureSlot::AmbientOcclusion => "Occlusion ambiante",
+            TextureSlot::Normal => "Normales",
+            TextureSlot::MetallicRoughness => "Métal / rugosité",
+            TextureSlot::Emissive => "Émission",
+        }
+    }
+
+    /// Whether the slot holds colors, decoded from sRGB when sampled, rather than data.
+    fn is_color(self) -> bool {
+        !matches!(self, TextureSlot::Normal | TextureSlot::MetallicRoughness)
+    }
+}
+
 pub struct MaterialRenderData {
     pub bind_group: wgpu::BindGroup,
-    #[allow(dead_code)]
-    pub texture: textures::Texture,
+    pub textures: MaterialTextures,
+    /// Where the diffuse texture was loaded from, for hot reloading
+    texture_path: Option<PathBuf>,
+    pub properties: MaterialPropertiesUniform,
+    properties_buffer: wgpu::Buffer,
+}
+
+impl MaterialRenderData {
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        textures: &MaterialTextures,
+        properties_buffer: &wgpu::Buffer,
+        label: &str,
+    ) -> wgpu::BindGroup {
+        let MaterialTextures {
+            diffuse: texture,
+            ambient_occlusion,
+            normal_map,
+            specular_map,
+            emissive_map,
+            lightmap,
+        } = textures;
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: properties_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&ambient_occlusion.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&normal_map.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&normal_map.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&specular_map.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(&emissive_map.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::TextureView(&lightmap.view),
+                },
+            ],
+            label: Some(label),
+        })
+    }
+}
+
+/// Cube map drawn behind the scene.
+struct Skybox {
+    texture: textures::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Ambient occlusion map of a material, relative to `assets_dir`. Without one (or if it
+/// cannot be loaded) nothing is occluded.
+fn load_ambient_occlusion(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    assets_dir: &Path,
+    file_name: &str,
+    label: &str,
+    console: &mut Console,
+) -> textures::Texture {
+    let white = || textures::Texture::from_color(device, queue, [255, 255, 255, 255], Some(label));
+    if file_name.is_empty() {
+        return white();
+    }
+    let path = assets_dir.join(file_name);
+    textures::Texture::from_image(device, queue, &path, Some(label)).unwrap_or_else(|e| {
+        console.push(
+            LogLevel::Warn,
+            format!("Cannot load ambient occlusion map {path:?}: {e}"),
+        );
+        white()
+    })
+}
+
+/// Normal map of a material, relative to `assets_dir`. Without one (or if it cannot be
+/// loaded) the surface normals are left untouched.
+fn load_normal_map(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    assets_dir: &Path,
+    file_name: Option<&str>,
+    label: &str,
+    console: &mut Console,
+) -> textures::Texture {
+    let flat =
+        || textures::Texture::from_color_linear(device, queue, [128, 128, 255, 255], Some(label));
+    let Some(file_name) = file_name else {
+        return flat();
+    };
+    let path = assets_dir.join(file_name);
+    textures::Texture::from_image_linear(device, queue, &path, Some(label)).unwrap_or_else(|e| {
+        console.push(
+            LogLevel::Warn,
+            format!("Cannot load normal map {path:?}: {e}"),
+        );
+        flat()
+    })
+}
+
+/// Specular map of a material, relative to `assets_dir`, `None` if there is none or it
+/// cannot be loaded.
+fn load_specular_map(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    assets_dir: &Path,
+    file_name: Option<&str>,
+    label: &str,
+    console: &mut Console,
+) -> Option<textures::Texture> {
+    let path = assets_dir.join(file_name?);
+    textures::Texture::from_image_linear(device, queue, &path, Some(label))
+        .inspect_err(|e| {
+            console.push(
+                LogLevel::Warn,
+                format!("Cannot load specular map {path:?}: {e}"),
+            );
+        })
+        .ok()
+}
+
+/// Emissive map or lightmap of a material (`kind`, for the logs), relative to `assets_dir`.
+/// Without one (or if it cannot be loaded) the texture is plain white, which changes nothing.
+fn load_color_map(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    assets_dir: &Path,
+    file_name: Option<&str>,
+    kind: &str,
+    label: &str,
+    console: &mut Console,
+) -> textures::Texture {
+    let white = || textures::Texture::from_color(device, queue, [255, 255, 255, 255], Some(label));
+    let Some(file_name) = file_name else {
+        return white();
+    };
+    let path = assets_dir.join(file_name);
+    textures::Texture::from_image(device, queue, &path, Some(label)).unwrap_or_else(|e| {
+        console.push(LogLevel::Warn, format!("Cannot load {kind} {path:?}: {e}"));
+        white()
+    })
+}
+
+/// GPU side of `materials`, their texture paths relative to `assets_dir`. Textures that
+/// cannot be loaded are replaced by a plain color, see the `load_*` functions above, with
+/// a warning in `console`.
+fn create_materials(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture_bind_group_layout: &wgpu::BindGroupLayout,
+    materials: &[Material],
+    assets_dir: &Path,
+    console: &mut Console,
+) -> Vec<MaterialRenderData> {
+    let mut render_data = Vec::new();
+    for mat in materials {
+        let texture_path = assets_dir.join(&mat.diffuse_texture);
+
+        let texture = if let Some(image) = &mat.diffuse_image {
+            textures::Texture::from_rgba(device, queue, image, Some(&mat.name))
+        } else if !mat.diffuse_texture.is_empty() {
+            textures::Texture::from_image(device, queue, &texture_path, Some(&mat.name))
+                .unwrap_or_else(|e| {
+                    console.push(
+                        LogLevel::Warn,
+                        format!("Cannot load texture {texture_path:?}, using magenta: {e}"),
+                    );
+                    textures::Texture::from_color(
+                        device,
+                        queue,
+                        [255, 0, 255, 255],
+                        Some(&mat.name),
+                    )
+                })
+        } else {
+            textures::Texture::from_color(device, queue, [255, 255, 255, 255], Some(&mat.name))
+        };
+
+        let ambient_occlusion = load_ambient_occlusion(
+            device,
+            queue,
+            assets_dir,
+            &mat.ambient_occlusion_texture,
+            &mat.name,
+            console,
+        );
+        let normal_map = load_normal_map(
+            device,
+            queue,
+            assets_dir,
+            mat.normal_texture.as_deref(),
+            &mat.name,
+            console,
+        );
+        let specular_map = load_specular_map(
+            device,
+            queue,
+            assets_dir,
+            mat.metallic_roughness_texture.as_deref(),
+            &mat.name,
+            console,
+        );
+
+        let emissive_map = load_color_map(
+            device,
+            queue,
+            assets_dir,
+            mat.emissive_texture.as_deref(),
+            "emissive map",
+            &mat.name,
+            console,
+        );
+        let lightmap = load_color_map(
+            device,
+            queue,
+            assets_dir,
+            mat.lightmap_texture.as_deref(),
+            "lightmap",
+            &mat.name,
+            console,
+        );
+
+        let mut properties = mat.properties;
+        properties.specular_map = specular_map.is_some() as u32;
+        properties.emissive = mat.emissive_color;
+        let specular_map = specular_map.unwrap_or_else(|| {
+            textures::Texture::from_color_linear(device, queue, [128, 128, 0, 255], Some(&mat.name))
+        });
+        let properties_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Properties Buffer", mat.name)),
+            contents: bytemuck::cast_slice(&[properties]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let textures = MaterialTextures {
+            diffuse: texture,
+            ambient_occlusion,
+            normal_map,
+            specular_map,
+            emissive_map,
+            lightmap,
+        };
+        let bind_group = MaterialRenderData::create_bind_group(
+            device,
+            texture_bind_group_layout,
+            &textures,
+            &properties_buffer,
+            &mat.name,
+        );
+
+        render_data.push(MaterialRenderData {
+            bind_group,
+            textures,
+            texture_path: (!mat.diffuse_texture.is_empty()).then_some(texture_path),
+            properties,
+            properties_buffer,
+        });
+    }
+    render_data
+}
+
+/// Scene shader read back from disk when hot reloading, relative to the working directory
+const SHADER_PATH: &str = "shader.wgsl";
+
+/// Watches the scene shader and the material textures. Hot reloading is a convenience:
+/// without a watcher (or for files that cannot be watched) the editor works as usual.
+fn create_file_watcher(
+    materials: &[MaterialRenderData],
+    console: &mut Console,
+) -> Option<FileWatcher> {
+    let mut watcher = FileWatcher::new()
+        .inspect_err(|e| console.push(LogLevel::Warn, format!("Hot reload disabled: {e}")))
+        .ok()?;
+
+    let textures = materials.iter().filter_map(|m| m.texture_path.as_deref());
+    for path in std::iter::once(Path::new(SHADER_PATH)).chain(textures) {
+        if let Err(e) = watcher.watch(path) {
+            console.push(
+                LogLevel::Warn,
+                format!("Cannot watch {path:?} for changes: {e}"),
+            );
+        }
+    }
+    Some(watcher)
+}
+
+/// Directory model and texture paths are relative to, unless a scene archive bundles them
+const ASSETS_DIR: &str = "assets";
+
+/// Loads `model_path`, relative to `assets_dir`, as glTF or OBJ after its extension.
+fn load_model_file(assets_dir: &Path, model_path: &str) -> Result<Model> {
+    if is_gltf_path(model_path) {
+        load_gltf_from(assets_dir, model_path)
+    } else {
+        load_model_from(assets_dir, model_path, None)
+    }
+}
+
+fn is_gltf_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("gltf") || e.eq_ignore_ascii_case("glb"))
+}
+
+/// Bounds of every mesh of a model, empty at the origin for a model without any.
+fn model_bounds(meshes: &[Mesh]) -> Aabb {
+    meshes
+        .iter()
+        .map(|m| m.aabb)
+        .reduce(|a, b| a.expand_to_include(&b))
+        .unwrap_or(Aabb::new(glam::Vec3::ZERO, glam::Vec3::ZERO))
+}
+
+/// Indices of the meshes drawn opaque and of those blended, after their material.
+fn partition_meshes(meshes: &[Mesh], materials: &[Material]) -> (Vec<usize>, Vec<usize>) {
+    (0..meshes.len()).partition(|&i| {
+        !materials
+            .get(meshes[i].material_id)
+            .is_some_and(|m| m.is_transparent)
+    })
+}
+
+/// The final image of the 3D view, readable by egui through `Texture::viewport_view`.
+fn create_render_target(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    gamma_corrected: bool,
+) -> textures::Texture {
+    if gamma_corrected {
+        GammaCorrection::create_render_target(device, config)
+    } else {
+        textures::Texture::create_render_target(device, config, "Render Target")
+    }
+}
+
+/// Device with the optional features the renderer makes use of.
+async fn request_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue)> {
+    // Needed for MSAA sample counts other than 4
+    let required_features =
+        adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+
+    Ok(adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                required_features,
+                required_limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await?)
+}
+
+/// Instance buffer with room for `capacity` instances, filled every frame by `cull_instances`.
+fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Instance Buffer"),
+        size: (capacity * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
 }
 
 /// The main state of the application, holding all WGPU and rendering data.
 /// This struct is responsible for managing the GPU resources, rendering pipeline,
 /// and handling the rendering loop.
+///
+/// # Threading
+///
+/// `State` is `Send` (checked by a test) but not `Sync`, and no unsafe impl is provided:
+/// - `gui` holds an `egui_winit::State`, whose clipboard (smithay-clipboard on Wayland) owns
+///   an `mpsc::Receiver`. That is the only field that is not `Sync` on native targets.
+/// - On native targets the wgpu handles (`Surface`, `Device`, `Queue`, buffers, pipelines)
+///   are `Send + Sync`. On wasm they are neither, unless wgpu's
+///   `fragile-send-sync-non-atomic-wasm` feature is enabled.
+/// - `window` is an `Arc<Window>`, and several winit platforms only allow a window to be
+///   used from the event loop thread, whatever its auto traits say.
+///
+/// To share the scene with other threads, split this struct instead of forcing `Sync`:
+/// - A `SceneState` (meshes on the CPU, instances, selection, camera, lights, material
+///   parameters) is plain data and can go behind an `Arc<RwLock<_>>`.
+/// - A `RenderState` keeps the surface, window, GUI and GPU resources on the main thread,
+///   and snapshots the scene into its buffers once per frame.
 pub struct State {
-    pub surface: wgpu::Surface<'static>,
+    /// `None` when headless, as are `window` and `gui`
+    pub surface: Option<wgpu::Surface<'static>>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
+    /// Also describes the render target when headless, without a surface to configure
     pub config: wgpu::SurfaceConfiguration,
     pub size: PhysicalSize<u32>,
-    pub window: std::sync::Arc<Window>,
-    pub gui: Gui,
-    pub light_uniform: LightUniform,
+    pub window: Option<std::sync::Arc<Window>>,
+    pub gui: Option<Gui>,
+    /// Uploaded every frame, edit through `add_light`, `remove_light` and `update_light`
+    lights: LightArray,
+    pub directional_light: DirectionalLight,
+    pub spot_light: Option<SpotLight>,
+    /// Edit through `set_fog`
+    fog_uniform: FogUniform,
+    pub engine_config: Config,
 
-    render_pipeline: wgpu::RenderPipeline,
+    pipeline_builder: ScenePipelineBuilder,
+    pipelines: ScenePipelines,
+    antialias: AntialiasRenderer,
+    /// The scene is rendered here in `HDR_FORMAT`, then tone mapped into `render_target`
+    hdr_target: textures::Texture,
+    /// Tone mapping curve and exposure, the whole tone mapping while auto exposure is off
+    tonemapper: Tonemapper,
+    /// Gamma encoding of the final image, `None` when the surface format is sRGB
+    gamma_correction: Option<GammaCorrection>,
+    /// Final image of the 3D view, shown by egui
     render_target: textures::Texture,
     meshes: Vec<MeshRenderData>,
+    /// Indices into `meshes`, after `Material::is_transparent`. Instances drawn with a
+    /// material override go by the override instead.
+    opaque_meshes: Vec<usize>,
+    transparent_meshes: Vec<usize>,
+    /// Model file, relative to the assets directory
+    model_path: String,
+    /// Last .esc scene loaded, whose extracted assets replace the assets directory for
+    /// `model_path` and its textures. Kept for the texture watcher and `save_scene`.
+    scene_assets: Option<ExtractedScene>,
+    /// Where the File menu saves and loads the scene, a .esc path embeds the assets
+    pub scene_path: String,
+    /// CPU copy of the geometry, used for picking
+    cpu_meshes: Vec<Mesh>,
+    /// Hierarchy over every triangle of `cpu_meshes`, in model space
+    bvh: Bvh,
+    cpu_materials: Vec<Material>,
     materials: Vec<MaterialRenderData>,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    file_watcher: Option<FileWatcher>,
 
+    /// Edit through `add_instance` and `remove_instance`
     instances: Vec<Instance>,
+    /// Room for `engine_config.instance_capacity` instances, filled with the visible ones
+    /// only (see `cull_instances`)
     instance_buffer: wgpu::Buffer,
+    /// Scene index of each instance in the instance buffer
+    visible_instances: Vec<usize>,
+    /// Ranges of the instance buffer drawn per call
+    instance_runs: Vec<DrawRun>,
+    /// Visible instances with transparent meshes, from the farthest to the closest
+    transparent_instances: Vec<usize>,
+    /// Same capacity as `instance_buffer`, filled in `transparent_instances` order
+    transparent_instance_buffer: wgpu::Buffer,
+    /// Set while `transparent_instances` has overlapping neighbours, so that it is
+    /// reported once and not every frame
+    depth_sort_conflict: bool,
+    /// Bounds of the loaded model, shared by every instance
+    model_aabb: Aabb,
+
+    selected_instances: HashSet<usize>,
+    pub hovered_instance: Option<usize>,
+    /// Above this camera speed (world units per second) hover raycasting is skipped
+    pub raycast_velocity_threshold: f32,
+    camera_velocity: f32,
+    last_camera_position: (glam::Vec3, glam::Vec3),
+    /// Projection restored by `toggle_projection`, `None` until the first toggle
+    inactive_projection: Option<Projection>,
+    last_update: Instant,
+    /// Frame times shown by the performance overlay
+    pub frame_stats: FrameStats,
+    pub show_frame_stats: bool,
+    /// Messages of the editor, see `engine_log!`
+    pub console: Console,
+    pub show_console: bool,
+    /// Shows the window remapping `InputHandler::key_bindings`
+    pub show_key_bindings: bool,
+    /// Texture slot and image path the inspector loads into the selected material
+    material_texture_slot: TextureSlot,
+    material_texture_path: String,
+    /// Counted again by every `render`
+    render_stats: RenderStats,
+    /// Updated wherever `State` creates or replaces a buffer or a texture
+    gpu_memory: GpuMemoryTracker,
+    box_selection_start: Option<egui::Pos2>,
+    /// Screen points of the lasso being drawn, see `perform_lasso_selection`
+    lasso_points: Option<Vec<egui::Pos2>>,
+    /// Instance whose name is being edited in the hierarchy
+    renaming_instance: Option<usize>,
+    /// Edited name of `renaming_instance`, applied as a command once done
+    rename_text: String,
+    /// Instances whose children the hierarchy hides
+    collapsed_instances: HashSet<usize>,
+    /// Last instance clicked in the hierarchy, where Shift-click ranges start
+    hierarchy_anchor: Option<usize>,
+    /// Instances edited from the inspector as they were before the field being edited
+    /// changed them, recorded as a single command once the field is let go
+    inspector_edit_start: Option<Vec<(usize, Instance)>>,
+    /// Undo history of the instance edits, cleared when a scene is loaded
+    commands: CommandStack,
+    picker: Picker,
+    /// Click waiting to be picked: NDC in the viewport and whether to extend the selection
+    pick_request: Option<(glam::Vec2, bool)>,
+    /// At most one pick in flight, collected by `update` once the GPU is done
+    pending_pick: Option<PendingPick>,
+
+    /// Sobel edge detection outlines, composited over the 3D view
+    pub outline: OutlineRenderer,
+    /// Stencil outline around the selected instances
+    pub selection_outline: SelectionOutlineRenderer,
+    /// Translation arrows, rotation rings or scale handles over the selection
+    gizmo: GizmoRenderer,
+    pub gizmo_mode: GizmoMode,
+    pub gizmo_space: GizmoSpace,
+    /// Arrow, ring or scale handle under the cursor, which takes left clicks from the
+    /// viewport
+    gizmo_hovered: Option<GizmoHandle>,
+    gizmo_drag: Option<GizmoDrag>,
+    /// Linear RGBA of `selection_outline`
+    pub outline_color: [f32; 4],
+    /// Screen-space ambient occlusion, multiplied into the 3D view
+    pub ssao: SsaoRenderer,
+    /// Depth of field, its passes are not recorded at all while disabled
+    pub dof_enabled: bool,
+    dof: DofRenderer,
+    /// Blur along the camera motion, from a velocity buffer drawn after the 3D pass
+    pub motion_blur: MotionBlurRenderer,
+    /// 3D LUT remapping the tone mapped colors, neutral by default
+    color_grading: ColorGrading,
+    /// Color fringes, between color grading and the vignette
+    chromatic_aberration: ChromaticAberration,
+    /// Darkened screen edges, blended over the tone mapped image
+    pub vignette_enabled: bool,
+    vignette: VignetteRenderer,
+    /// Eye adaptation and tone mapping, `None` without compute shader support
+    pub exposure: Option<AutoExposure>,
+    /// Hemisphere samples for screen-space ambient occlusion, generated at startup
+    #[cfg(feature = "compute")]
+    pub ssao_kernel: Option<SsaoKernelGenerator>,
+
+    /// Debug view coloring each instance by its index (Alt+I)
+    pub debug_instance_id_view: bool,
+    // Instance data of the selection only, `None` when nothing is selected
+    selection_instance_buffer: Option<wgpu::Buffer>,
 
     camera: Camera,
     input_handler: InputHandler,
@@ -54,17 +741,61 @@ pub struct State {
 
     #[allow(dead_code)]
     depth_texture: textures::Texture,
+    depth_format: wgpu::TextureFormat,
 
     is_scene_hovered: bool,
 
     #[allow(dead_code)]
     light_buffer: wgpu::Buffer,
+    directional_light_buffer: wgpu::Buffer,
+    spot_light_buffer: wgpu::Buffer,
+    fog_buffer: wgpu::Buffer,
     light_bind_group: wgpu::BindGroup,
+
+    /// Drawn instead of the clear color when enabled and loaded, see `load_skybox`
+    pub skybox_enabled: bool,
+    skybox: Option<Skybox>,
+    skybox_bind_group_layout: wgpu::BindGroupLayout,
+
+    /// Saved views, restored with the digit keys in this order
+    pub camera_bookmarks: Vec<CameraBookmark>,
+    /// Fly-through edited from the toolbar and saved with the scene
+    pub camera_path: CameraPath,
+    /// Drives the camera instead of the controller while a path plays
+    camera_path_playback: Option<CameraPathPlayback>,
+
+    /// Running input recording, see `start_recording`
+    input_recorder: Option<InputRecorder>,
+    /// Last recording stopped, written by `save_recording`
+    last_recording: Option<InputRecording>,
+    /// Fed to the input handler by `update`, see `play_recording`
+    input_playback: Option<InputPlayback>,
+    /// Where the editor saves and replays recordings
+    pub recording_path: String,
+
+    /// Rounds the positions the translate gizmo drags to `snap_grid_size`, and the angles
+    /// the rotate gizmo turns by to `snap_rotation_degrees`. Ctrl inverts it during a drag
+    pub snap_enabled: bool,
+    pub snap_grid_size: f32,
+    pub snap_rotation_degrees: f32,
+
+    /// Reference grid on the Y = 0 plane
+    pub show_grid: bool,
+    /// World distance between the grid lines
+    pub grid_spacing: f32,
+    grid: Grid,
 }
 
 impl State {
     // We pass the mode path as parameter now
-    pub async fn new(window: std::sync::Arc<Window>, model_path: &str) -> Result<Self> {
+    /// `msaa_samples` (1, 2, 4 or 8) is the initial anti-aliasing of the 3D view, lowered to
+    /// no anti-aliasing if the adapter cannot render with it. It can be changed later with
+    /// `set_antialiasing`.
+    pub async fn new(
+        window: std::sync::Arc<Window>,
+        model_path: &str,
+        msaa_samples: u32,
+    ) -> Result<Self> {
         let size = window.inner_size();
 
         // 1. Instance & Surface
@@ -84,17 +815,7 @@ impl State {
             })
             .await
             .ok_or(OrengineError::NoGpuAdapter)?;
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
-                },
-                None,
-            )
-            .await?;
+        let (device, queue) = request_device(&adapter).await?;
 
         // 3. Config
         let surface_caps = surface.get_capabilities(&adapter);
@@ -117,8 +838,80 @@ impl State {
         };
         surface.configure(&device, &config);
 
+        let gui = Gui::new(&window, &device, config.format);
+        Self::with_device(
+            &adapter,
+            device,
+            queue,
+            config,
+            Some((window, surface, gui)),
+            model_path,
+            msaa_samples,
+        )
+    }
+
+    /// Renders without a window into a `width` x `height` texture, read back with
+    /// `render_to_image`. There is no editor UI and no input, the scene is driven through
+    /// the API only.
+    ///
+    /// Prefers the Vulkan, Metal and DX12 backends, and falls back to the others (OpenGL)
+    /// on machines without them, such as most CI containers.
+    pub fn new_headless(width: u32, height: u32, model_path: &str) -> Result<Self> {
+        pollster::block_on(async {
+            let adapter_options = wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            };
+            let primary = wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends: wgpu::Backends::VULKAN | wgpu::Backends::METAL | wgpu::Backends::DX12,
+                ..Default::default()
+            });
+            let adapter = match primary.request_adapter(&adapter_options).await {
+                Some(adapter) => adapter,
+                None => wgpu::Instance::new(wgpu::InstanceDescriptor::default())
+                    .request_adapter(&adapter_options)
+                    .await
+                    .ok_or(OrengineError::NoGpuAdapter)?,
+            };
+            let (device, queue) = request_device(&adapter).await?;
+
+            // Never presented, only describes the render target
+            let config = wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                width: width.max(1),
+                height: height.max(1),
+                present_mode: wgpu::PresentMode::Fifo,
+                alpha_mode: wgpu::CompositeAlphaMode::Auto,
+                view_formats: vec![],
+                desired_maximum_frame_latency: 2,
+            };
+            Self::with_device(&adapter, device, queue, config, None, model_path, 1)
+        })
+    }
+
+    /// Everything but the window, surface and UI, which `windowed` holds if any.
+    fn with_device(
+        adapter: &wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        windowed: Option<(std::sync::Arc<Window>, wgpu::Surface<'static>, Gui)>,
+        model_path: &str,
+        msaa_samples: u32,
+    ) -> Result<Self> {
+        let initial_antialiasing = AntialiasingMode::from_sample_count(msaa_samples)
+            .ok_or(OrengineError::InvalidSampleCount(msaa_samples))?;
+        let size = PhysicalSize::new(config.width, config.height);
+
+        // Built first, for the warnings raised while loading the assets
+        let mut console = Console::new();
+
         // 4. Assets (Model & Textures)
-        let model = load_model(model_path)?;
+        let model = load_model_file(Path::new(ASSETS_DIR), model_path)?;
+        let model_aabb = model_bounds(&model.meshes);
+        let (opaque_meshes, transparent_meshes) = partition_meshes(&model.meshes, &model.materials);
 
         const NUM_INSTANCES_PER_ROW: u32 = 10;
         const INSTANCE_DISPLACEMENT: glam::Vec3 = glam::Vec3::new(
@@ -139,17 +932,17 @@ impl State {
                         glam::Quat::from_axis_angle(position.normalize(), 45.0f32.to_radians())
                     };
 
-                    Instance { position, rotation }
+                    Instance {
+                        name: format!("Objet {}", z * NUM_INSTANCES_PER_ROW + x),
+                        ..Instance::new(position, rotation)
+                    }
                 })
             })
             .collect::<Vec<_>>();
-
-        let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
-        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Instance Buffer"),
-            contents: bytemuck::cast_slice(&instance_data),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        check_instance_limit(instances.len())?;
+        let instance_capacity = Config::default().instance_capacity.max(instances.len());
+        let instance_buffer = create_instance_buffer(&device, instance_capacity);
+        let transparent_instance_buffer = create_instance_buffer(&device, instance_capacity);
 
         // 6. Camera
         let camera = Camera {
@@ -157,9 +950,11 @@ impl State {
             target: (0.0, 1.0, 0.0).into(),
             up: glam::Vec3::Y,
             aspect: config.width as f32 / config.height as f32,
-            fovy: 45.0_f32.to_radians(),
-            znear: 0.1,
-            zfar: 100.0,
+            projection: Projection::Perspective {
+                fovy: 45.0_f32.to_radians(),
+                znear: 0.1,
+                zfar: 100.0,
+            },
         };
 
         let input_handler = InputHandler::new(0.01);
@@ -217,121 +1012,194 @@ impl State {
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Ambient occlusion, sampled with the diffuse sampler
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    // Normal map, stored linearly
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    // Roughness and metalness map, sampled with the diffuse sampler
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    // Emissive map, sampled with the diffuse sampler
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 7,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    // Lightmap, sampled with the diffuse sampler and the second UV set
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 8,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
                 ],
                 label: Some("texture_bind_group_layout"),
             });
 
         // Process Materials
-        let mut materials = Vec::new();
-        for mat in &model.materials {
-            let texture_path = std::path::Path::new("assets").join(&mat.diffuse_texture);
-
-            let texture = if !mat.diffuse_texture.is_empty() {
-                textures::Texture::from_image(&device, &queue, &texture_path, Some(&mat.name))
-                    .unwrap_or_else(|_| {
-                        eprintln!(
-                            "Erreur chargement texture: {:?}. Utilisation texture magenta.",
-                            texture_path
-                        );
-                        textures::Texture::from_color(
-                            &device,
-                            &queue,
-                            [255, 0, 255, 255],
-                            Some(&mat.name),
-                        )
-                    })
-            } else {
-                textures::Texture::from_color(
-                    &device,
-                    &queue,
-                    [255, 255, 255, 255],
-                    Some(&mat.name),
-                )
-            };
-
-            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &texture_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                    },
-                ],
-                label: Some(&mat.name),
-            });
+        let materials = create_materials(
+            &device,
+            &queue,
+            &texture_bind_group_layout,
+            &model.materials,
+            Path::new(ASSETS_DIR),
+            &mut console,
+        );
 
-            materials.push(MaterialRenderData {
-                bind_group,
-                texture,
-            });
-        }
+        let file_watcher = create_file_watcher(&materials, &mut console);
 
         // Process Meshes
-        let meshes = model
-            .meshes
-            .iter()
-            .map(|m| {
-                let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some(&format!("{:?} Vertex Buffer", m.name)),
-                    contents: bytemuck::cast_slice(&m.vertices),
-                    usage: wgpu::BufferUsages::VERTEX,
-                });
-                let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some(&format!("{:?} Index Buffer", m.name)),
-                    contents: bytemuck::cast_slice(&m.indices),
-                    usage: wgpu::BufferUsages::INDEX,
-                });
-                MeshRenderData {
-                    vertex_buffer,
-                    index_buffer,
-                    num_elements: m.indices.len() as u32,
-                    material_id: m.material_id,
-                }
-            })
-            .collect::<Vec<_>>();
+        let meshes = upload_meshes(&device, &model.meshes, None);
 
-        // 8. Depth Texture
-        let depth_texture =
-            textures::Texture::create_depth_texture(&device, &config, "depth_texture");
-
-        let light_uniform = crate::light::LightUniform {
-            position: [2.0, 2.0, 2.0],
-            _padding: 0,
-            color: [1.0, 0.0, 0.0],
-            _padding2: 0,
+        // 8. Depth Texture, multisampled like the scene pass
+        let depth_format = textures::best_depth_format(adapter);
+        let antialiasing_modes =
+            supported_antialiasing_modes(adapter, textures::HDR_FORMAT, depth_format);
+        let mut engine_config = Config {
+            antialiasing: initial_antialiasing,
+            instance_capacity,
         };
+        if !antialiasing_modes.contains(&engine_config.antialiasing) {
+            console.push(
+                LogLevel::Warn,
+                format!("{} is not supported", engine_config.antialiasing.label()),
+            );
+            engine_config.antialiasing = AntialiasingMode::None;
+        }
+        let depth_texture = textures::Texture::create_depth_texture(
+            &device,
+            &config,
+            depth_format,
+            engine_config.antialiasing.sample_count(),
+            "depth_texture",
+        );
+
+        let mut lights = LightArray::new();
+        lights.push(PointLight::new([2.0, 2.0, 2.0], [1.0, 0.0, 0.0]))?;
 
         let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Light Buffer"),
-            contents: bytemuck::cast_slice(&[light_uniform]),
+            contents: bytemuck::cast_slice(&[lights]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let directional_light = DirectionalLight {
+            intensity: 0.5,
+            ..Default::default()
+        };
+        let directional_light_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Directional Light Buffer"),
+                contents: bytemuck::cast_slice(&[directional_light]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let spot_light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Spot Light Buffer"),
+            contents: bytemuck::cast_slice(&[SpotLightUniform::disabled()]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let fog_uniform = FogUniform::default();
+        let fog_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Fog Buffer"),
+            contents: bytemuck::cast_slice(&[fog_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Every kind of light shares group 2, one binding each, followed by the fog
+        let light_binding = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
         let light_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
+                entries: &[
+                    light_binding(0),
+                    light_binding(1),
+                    light_binding(2),
+                    light_binding(3),
+                ],
                 label: Some("light_bind_group_layout"),
             });
 
         let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &light_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: directional_light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: spot_light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: fog_buffer.as_entire_binding(),
+                },
+            ],
             label: Some("light_bind_group"),
         });
 
@@ -349,75 +1217,285 @@ impl State {
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::desc(), InstanceRaw::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: crate::textures::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
+        let overlay_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overlay Pipeline Layout"),
+            bind_group_layouts: &[&camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let skybox_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+                label: Some("skybox_bind_group_layout"),
+            });
+        let skybox_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            bind_group_layouts: &[&camera_bind_group_layout, &skybox_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let grid = Grid::new(&device);
+        let grid_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grid Pipeline Layout"),
+            bind_group_layouts: &[&camera_bind_group_layout, &grid.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let picker = Picker::new(&device, &shader, &camera_bind_group_layout, depth_format);
+
+        let pipeline_builder = ScenePipelineBuilder {
+            shader,
+            render_layout: render_pipeline_layout,
+            overlay_layout,
+            skybox_shader: device.create_shader_module(wgpu::include_wgsl!("../skybox.wgsl")),
+            skybox_layout,
+            grid_shader: device.create_shader_module(wgpu::include_wgsl!("../grid.wgsl")),
+            grid_layout,
+            color_format: textures::HDR_FORMAT,
+            depth_format,
+        };
+
+        let antialias = AntialiasRenderer::new(
+            &device,
+            &config,
+            textures::HDR_FORMAT,
+            engine_config.antialiasing,
+            antialiasing_modes,
+            &depth_texture.view,
+        );
+        let pipelines = pipeline_builder.build(&device, antialias.sample_count());
+
+        // Without an sRGB surface, the passes after tone mapping write linear light into the
+        // gamma correction input instead of the render target
+        let gamma_correction =
+            needs_gamma_correction(config.format, adapter.get_downlevel_capabilities().flags)
+                .then(|| GammaCorrection::new(&device, &config));
+        let output_format = match gamma_correction {
+            Some(_) => GammaCorrection::LINEAR_FORMAT,
+            None => config.format,
+        };
+
+        let outline = OutlineRenderer::new(&device, output_format, &depth_texture.view);
+        let ssao = SsaoRenderer::new(&device, &config, textures::HDR_FORMAT, &depth_texture.view);
+
+        let hdr_target = textures::Texture::create_hdr_target(&device, &config);
+        let dof = DofRenderer::new(&device, &config, &depth_texture.view, &hdr_target.view);
+        let motion_blur = MotionBlurRenderer::new(
+            &device,
+            &config,
+            &pipeline_builder.shader,
+            &camera_bind_group_layout,
+            depth_format,
+            &hdr_target.view,
+        );
+        let tonemapper = Tonemapper::new(&device, output_format, &hdr_target.view);
+        let color_grading = ColorGrading::new(&device, &queue, &config, output_format);
+        let chromatic_aberration = ChromaticAberration::new(&device, &config, output_format);
+        let vignette = VignetteRenderer::new(&device, output_format);
+        let selection_outline = SelectionOutlineRenderer::new(
+            &device,
+            &config,
+            output_format,
+            &camera_bind_group_layout,
+        );
+        let gizmo = GizmoRenderer::new(&device, output_format);
+        let render_target = create_render_target(&device, &config, gamma_correction.is_some());
+
+        let exposure = auto_exposure_supported(adapter).then(|| {
+            AutoExposure::new(
+                &device,
+                output_format,
+                &hdr_target.view,
+                tonemapper.uniform_buffer(),
+            )
         });
 
-        let render_target =
-            crate::textures::Texture::create_render_target(&device, &config, "Render Target");
+        #[cfg(feature = "compute")]
+        let ssao_kernel = compute_supported(adapter).then(|| {
+            let generator = SsaoKernelGenerator::setup(&device);
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("SSAO Kernel Encoder"),
+            });
+            generator.dispatch(&mut encoder);
+            queue.submit(std::iter::once(encoder.finish()));
+            generator
+        });
 
-        let mut gui = Gui::new(&window, &device, config.format);
+        let mut gpu_memory = GpuMemoryTracker::new();
+        for mesh in &meshes {
+            gpu_memory.track_buffer(&mesh.vertex_buffer);
+            gpu_memory.track_buffer(&mesh.index_buffer);
+        }
+        for material in &materials {
+            for texture in material.textures.all() {
+                gpu_memory.track_texture(&texture.texture);
+            }
+            gpu_memory.track_buffer(&material.properties_buffer);
+        }
+        for buffer in [
+            &instance_buffer,
+            &transparent_instance_buffer,
+            &camera_buffer,
+            &light_buffer,
+            &directional_light_buffer,
+            &spot_light_buffer,
+            &fog_buffer,
+        ] {
+            gpu_memory.track_buffer(buffer);
+        }
+        for texture in [&depth_texture, &hdr_target, &render_target] {
+            gpu_memory.track_texture(&texture.texture);
+        }
 
-        gui.register_viewport_texture(&device, &render_target.view, config.format);
+        let (window, surface, gui) = match windowed {
+            Some((window, surface, mut gui)) => {
+                gui.register_viewport_texture(
+                    &device,
+                    &render_target.viewport_view(),
+                    config.format,
+                );
+                (Some(window), Some(surface), Some(gui))
+            }
+            None => (None, None, None),
+        };
 
-        Ok(Self {
+        let mut state = Self {
             surface,
             device,
             queue,
             config,
             size,
             window,
-            render_pipeline,
+            engine_config,
+            pipeline_builder,
+            pipelines,
+            antialias,
+            hdr_target,
+            tonemapper,
+            gamma_correction,
             render_target,
             meshes,
+            opaque_meshes,
+            transparent_meshes,
+            model_path: model_path.to_string(),
+            scene_assets: None,
+            scene_path: "scene.json".to_string(),
+            bvh: Bvh::from_meshes(&model.meshes),
+            cpu_meshes: model.meshes,
+            cpu_materials: model.materials,
             materials,
+            texture_bind_group_layout,
+            file_watcher,
+            last_camera_position: (camera.eye, camera.target),
+            inactive_projection: None,
             camera,
             input_handler,
             camera_uniform,
             camera_buffer,
             camera_bind_group,
             depth_texture,
+            depth_format,
             is_scene_hovered: false,
+            visible_instances: Vec::new(),
+            instance_runs: Vec::new(),
+            transparent_instances: Vec::new(),
+            transparent_instance_buffer,
+            depth_sort_conflict: false,
             instances,
             instance_buffer,
-            light_uniform,
+            model_aabb,
+            selected_instances: HashSet::new(),
+            hovered_instance: None,
+            raycast_velocity_threshold: 2.0,
+            camera_velocity: 0.0,
+            last_update: Instant::now(),
+            frame_stats: FrameStats::new(),
+            show_frame_stats: true,
+            console,
+            show_console: true,
+            show_key_bindings: false,
+            material_texture_slot: TextureSlot::Diffuse,
+            material_texture_path: String::new(),
+            render_stats: RenderStats::default(),
+            gpu_memory,
+            box_selection_start: None,
+            lasso_points: None,
+            renaming_instance: None,
+            rename_text: String::new(),
+            collapsed_instances: HashSet::new(),
+            hierarchy_anchor: None,
+            inspector_edit_start: None,
+            commands: CommandStack::new(),
+            picker,
+            pick_request: None,
+            pending_pick: None,
+            selection_instance_buffer: None,
+            outline,
+            ssao,
+            dof_enabled: false,
+            dof,
+            motion_blur,
+            selection_outline,
+            gizmo,
+            gizmo_mode: GizmoMode::default(),
+            gizmo_space: GizmoSpace::default(),
+            gizmo_hovered: None,
+            gizmo_drag: None,
+            outline_color: [1.0, 0.63, 0.0, 1.0],
+            color_grading,
+            chromatic_aberration,
+            vignette_enabled: false,
+            vignette,
+            exposure,
+            #[cfg(feature = "compute")]
+            ssao_kernel,
+            debug_instance_id_view: false,
+            lights,
+            directional_light,
+            spot_light: None,
+            fog_uniform,
             light_buffer,
+            directional_light_buffer,
+            spot_light_buffer,
+            fog_buffer,
             light_bind_group,
+            skybox_enabled: false,
+            skybox: None,
+            skybox_bind_group_layout,
+            camera_bookmarks: Vec::new(),
+            camera_path: CameraPath::default(),
+            camera_path_playback: None,
+            input_recorder: None,
+            last_recording: None,
+            input_playback: None,
+            recording_path: "recording.json".to_string(),
+            snap_enabled: false,
+            snap_grid_size: DEFAULT_SNAP_GRID_SIZE,
+            snap_rotation_degrees: DEFAULT_SNAP_ROTATION_DEGREES,
+            show_grid: true,
+            grid_spacing: DEFAULT_GRID_SPACING,
+            grid,
             gui,
-        })
+        };
+        state.cull_instances();
+        Ok(state)
     }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
@@ -425,167 +1503,2619 @@ impl State {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
 
-            self.render_target = crate::textures::Texture::create_render_target(
-                &self.device,
-                &self.config,
-                "Render Target",
-            );
-            self.depth_texture = textures::Texture::create_depth_texture(
+            self.gpu_memory.untrack_texture(&self.hdr_target.texture);
+            self.gpu_memory.untrack_texture(&self.render_target.texture);
+            self.hdr_target = textures::Texture::create_hdr_target(&self.device, &self.config);
+            self.tonemapper.resize(&self.device, &self.hdr_target.view);
+            self.motion_blur
+                .resize(&self.device, &self.config, &self.hdr_target.view);
+            self.selection_outline.resize(&self.device, &self.config);
+            self.color_grading.resize(&self.device, &self.config);
+            self.chromatic_aberration.resize(&self.device, &self.config);
+            self.render_target =
+                create_render_target(&self.device, &self.config, self.gamma_correction.is_some());
+            self.gpu_memory.track_texture(&self.hdr_target.texture);
+            self.gpu_memory.track_texture(&self.render_target.texture);
+            self.recreate_depth_texture();
+            self.antialias
+                .resize(&self.device, &self.config, &self.depth_texture.view);
+            if let Some(exposure) = &mut self.exposure {
+                exposure.resize(
+                    &self.device,
+                    &self.hdr_target.view,
+                    self.tonemapper.uniform_buffer(),
+                );
+            }
+            if let Some(gamma_correction) = &mut self.gamma_correction {
+                gamma_correction.resize(&self.device, &self.config);
+            }
+            if let Some(gui) = &mut self.gui {
+                gui.update_viewport_texture(&self.device, &self.render_target.viewport_view());
+            }
+        }
+    }
+
+    /// The depth buffer follows the surface size and the MSAA sample count.
+    fn recreate_depth_texture(&mut self) {
+        self.gpu_memory.untrack_texture(&self.depth_texture.texture);
+        self.depth_texture = textures::Texture::create_depth_texture(
+            &self.device,
+            &self.config,
+            self.depth_format,
+            self.antialias.sample_count(),
+            "depth_texture",
+        );
+        self.gpu_memory.track_texture(&self.depth_texture.texture);
+        // The outline, SSAO and DOF shaders cannot read a multisampled depth buffer, they are
+        // skipped with MSAA
+        if self.antialias.sample_count() == 1 {
+            self.outline.resize(&self.device, &self.depth_texture.view);
+            self.ssao
+                .resize(&self.device, &self.config, &self.depth_texture.view);
+            self.dof.resize(
                 &self.device,
                 &self.config,
-                "depth_texture",
+                &self.depth_texture.view,
+                &self.hdr_target.view,
             );
-            self.gui
-                .update_viewport_texture(&self.device, &self.render_target.view);
         }
     }
 
-    pub fn input(&mut self, event: &WindowEvent) -> bool {
-        let consumed = self.gui.handle_event(&self.window, event);
+    /// Hot reloads the scene shader and material textures changed on disk.
+    fn process_file_changes(&mut self) {
+        let Some(watcher) = &self.file_watcher else {
+            return;
+        };
+        // A single save usually comes as several events
+        let mut changed = watcher
+            .poll()
+            .into_iter()
+            .filter(|e| e.kind != ChangeKind::Removed)
+            .map(|e| e.path)
+            .collect::<Vec<_>>();
+        changed.sort();
+        changed.dedup();
 
-        let handled =
-            self.input_handler
-                .process_input(event, &self.window, consumed, self.is_scene_hovered);
+        let shader_path = Path::new(SHADER_PATH).canonicalize().ok();
+        for path in changed {
+            if shader_path.as_ref() == Some(&path) {
+                self.reload_shader(&path);
+            } else {
+                self.reload_texture(&path);
+            }
+        }
+    }
 
-        consumed || handled
+    /// Rebuilds every pipeline using the scene shader. An invalid shader is reported and the
+    /// previous one kept.
+    fn reload_shader(&mut self, path: &Path) {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                engine_log!(self, LogLevel::Warn, "Cannot read {:?}: {}", path, e);
+                return;
+            }
+        };
+
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(SHADER_PATH),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let previous = std::mem::replace(&mut self.pipeline_builder.shader, shader);
+        let pipelines = self
+            .pipeline_builder
+            .build(&self.device, self.antialias.sample_count());
+        self.picker
+            .reload_shader(&self.device, &self.pipeline_builder.shader);
+        self.motion_blur
+            .reload_shader(&self.device, &self.pipeline_builder.shader);
+
+        if let Some(error) = pollster::block_on(self.device.pop_error_scope()) {
+            engine_log!(
+                self,
+                LogLevel::Error,
+                "Shader reload failed, keeping the previous one: {}",
+                error
+            );
+            self.pipeline_builder.shader = previous;
+            self.picker
+                .reload_shader(&self.device, &self.pipeline_builder.shader);
+            self.motion_blur
+                .reload_shader(&self.device, &self.pipeline_builder.shader);
+            return;
+        }
+        self.pipelines = pipelines;
+        engine_log!(self, LogLevel::Info, "Reloaded {:?}", path);
     }
 
-    pub fn handle_mouse_motion(&mut self, delta: (f64, f64)) {
-        self.input_handler.handle_mouse_motion(delta);
+    /// Reloads the materials using the texture at `path`. A file that cannot be decoded
+    /// (possibly still being written) keeps the previous texture.
+    fn reload_texture(&mut self, path: &Path) {
+        for material_id in 0..self.materials.len() {
+            let uses_path = self.materials[material_id]
+                .texture_path
+                .as_ref()
+                .and_then(|p| p.canonicalize().ok())
+                .is_some_and(|p| p == path);
+            if !uses_path {
+                continue;
+            }
+            match self.reload_material_texture(material_id, TextureSlot::Diffuse, path) {
+                Ok(()) => engine_log!(self, LogLevel::Info, "Reloaded {:?}", path),
+                Err(e) => engine_log!(self, LogLevel::Warn, "Cannot reload {:?}: {}", path, e),
+            }
+        }
     }
 
-    pub fn update(&mut self) {
-        self.input_handler
-            .camera_controller
-            .update_camera(&mut self.camera);
-        self.camera_uniform.update_view_proj(&self.camera);
-        self.queue.write_buffer(
-            &self.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[self.camera_uniform]),
+    /// Replaces a texture of a material with the image at `path`, only its bind group is
+    /// rebuilt. A metallic/roughness map then replaces the scalar `roughness` and
+    /// `metallic`, and a new diffuse texture is hot reloaded from then on.
+    pub fn reload_material_texture(
+        &mut self,
+        material_id: usize,
+        slot: TextureSlot,
+        path: &Path,
+    ) -> Result<()> {
+        let material = self
+            .materials
+            .get_mut(material_id)
+            .ok_or_else(|| OrengineError::Generic(format!("No material {material_id}")))?;
+        let label = path.to_string_lossy();
+        let texture = if slot.is_color() {
+            textures::Texture::from_image(&self.device, &self.queue, path, Some(&label))?
+        } else {
+            textures::Texture::from_image_linear(&self.device, &self.queue, path, Some(&label))?
+        };
+
+        let textures = &mut material.textures;
+        let replaced = match slot {
+            TextureSlot::Diffuse => &mut textures.diffuse,
+            TextureSlot::AmbientOcclusion => &mut textures.ambient_occlusion,
+            TextureSlot::Normal => &mut textures.normal_map,
+            TextureSlot::MetallicRoughness => &mut textures.specular_map,
+            TextureSlot::Emissive => &mut textures.emissive_map,
+        };
+        self.gpu_memory.untrack_texture(&replaced.texture);
+        self.gpu_memory.track_texture(&texture.texture);
+        *replaced = texture;
+        material.bind_group = MaterialRenderData::create_bind_group(
+            &self.device,
+            &self.texture_bind_group_layout,
+            &material.textures,
+            &material.properties_buffer,
+            &label,
         );
+
+        match slot {
+            TextureSlot::Diffuse if material.texture_path.as_deref() != Some(path) => {
+                material.texture_path = Some(path.to_path_buf());
+                if let Some(watcher) = &mut self.file_watcher
+                    && let Err(e) = watcher.watch(path)
+                {
+                    engine_log!(
+                        self,
+                        LogLevel::Warn,
+                        "Cannot watch {:?} for changes: {}",
+                        path,
+                        e
+                    );
+                }
+            }
+            TextureSlot::MetallicRoughness => {
+                material.properties.specular_map = 1;
+                self.queue.write_buffer(
+                    &material.properties_buffer,
+                    0,
+                    bytemuck::cast_slice(&[material.properties]),
+                );
+            }
+            _ => {}
+        }
+        Ok(())
     }
 
-    pub fn render(&mut self) -> Result<()> {
-        let output = self.surface.get_current_texture()?;
-        let view_surface = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    pub fn fog(&self) -> FogUniform {
+        self.fog_uniform
+    }
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
+    /// Uploads new fog settings, visible from the next frame.
+    pub fn set_fog(&mut self, fog: FogUniform) {
+        self.fog_uniform = fog;
+        self.queue
+            .write_buffer(&self.fog_buffer, 0, bytemuck::cast_slice(&[fog]));
+    }
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("3D Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.render_target.view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
+    pub fn dof(&self) -> DofUniform {
+        self.dof.settings
+    }
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-            render_pass.set_bind_group(2, &self.light_bind_group, &[]);
+    /// Changes the lens of the depth of field, used while `dof_enabled` is set.
+    pub fn set_dof(&mut self, dof: DofUniform) {
+        self.dof.settings = dof;
+    }
 
-            for mesh in &self.meshes {
-                let material = &self.materials[mesh.material_id];
-                render_pass.set_bind_group(1, &material.bind_group, &[]);
+    /// Estimated size of the buffers and textures `State` holds.
+    pub fn gpu_memory(&self) -> GpuMemoryTracker {
+        self.gpu_memory
+    }
 
-                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-                render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-                render_pass
-                    .set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                render_pass.draw_indexed(0..mesh.num_elements, 0, 0..self.instances.len() as _);
-            }
+    /// Draws of the scene pass in the last `render`.
+    pub fn render_stats(&self) -> RenderStats {
+        self.render_stats
+    }
+
+    pub fn chromatic_aberration(&self) -> f32 {
+        self.chromatic_aberration.strength()
+    }
+
+    /// Offset of the color channels at the screen edges, in texture coordinates. 0 disables
+    /// the pass altogether.
+    pub fn set_chromatic_aberration(&mut self, strength: f32) {
+        self.chromatic_aberration
+            .set(&self.queue, strength.max(0.0));
+    }
+
+    pub fn vignette(&self) -> VignetteUniform {
+        self.vignette.settings()
+    }
+
+    /// Uploads new vignette settings, used while `vignette_enabled` is set.
+    pub fn set_vignette(&mut self, vignette: VignetteUniform) {
+        self.vignette.set(&self.queue, vignette);
+    }
+
+    pub fn tonemap_mode(&self) -> TonemapMode {
+        self.tonemapper.mode()
+    }
+
+    pub fn tonemap_exposure(&self) -> f32 {
+        self.tonemapper.exposure()
+    }
+
+    /// Picks the tone mapping curve and the exposure it is applied at, visible from the next
+    /// frame. With auto exposure on, `exposure` compensates the measured one.
+    pub fn set_tonemap(&mut self, mode: TonemapMode, exposure: f32) {
+        self.tonemapper.set(&self.queue, mode, exposure);
+    }
+
+    pub fn lights(&self) -> &[PointLight] {
+        self.lights.lights()
+    }
+
+    /// Adds a point light and returns its index, fails once `MAX_LIGHTS` are in the scene.
+    pub fn add_light(&mut self, light: PointLight) -> Result<usize> {
+        self.lights.push(light)
+    }
+
+    /// Removes the light at `index`, the following ones move down by one.
+    pub fn remove_light(&mut self, index: usize) -> Option<PointLight> {
+        self.lights.remove(index)
+    }
+
+    /// Replaces the light at `index`, returns false if there is none.
+    pub fn update_light(&mut self, index: usize, light: PointLight) -> bool {
+        let Some(slot) = self.lights.lights_mut().get_mut(index) else {
+            return false;
+        };
+        *slot = light;
+        true
+    }
+
+    /// Bakes the ambient occlusion of mesh `mesh_idx`, occluded by every mesh of the model,
+    /// into `assets/<mesh>_ao.png` and makes it the ambient occlusion map of its material.
+    /// Runs on the calling thread and can take a while, see `ao_bake::bake_ambient_occlusion`.
+    pub fn bake_ambient_occlusion(
+        &mut self,
+        mesh_idx: usize,
+        resolution: u32,
+        num_rays: u32,
+    ) -> Result<image::GrayImage> {
+        let mesh = self
+            .cpu_meshes
+            .get(mesh_idx)
+            .ok_or_else(|| OrengineError::Generic(format!("No mesh {mesh_idx} to bake")))?;
+        if resolution == 0 || num_rays == 0 {
+            return Err(OrengineError::Generic(
+                "Ambient occlusion needs a resolution and at least one ray".into(),
+            ));
         }
 
-        let texture_id = self.gui.viewport_texture_id;
+        let image = ao_bake::bake_ambient_occlusion(mesh, &self.cpu_meshes, resolution, num_rays);
+        let file_name = format!(
+            "{}_ao.png",
+            mesh.name
+                .replace(|c: char| !c.is_alphanumeric() && c != '-', "_")
+        );
+        image.save(Path::new(ASSETS_DIR).join(&file_name))?;
+
+        let material_id = mesh.material_id;
+        let cpu_material = &mut self.cpu_materials[material_id];
+        cpu_material.ambient_occlusion_texture = file_name;
+        let material = &mut self.materials[material_id];
+        material.textures.ambient_occlusion = load_ambient_occlusion(
+            &self.device,
+            &self.queue,
+            Path::new(ASSETS_DIR),
+            &cpu_material.ambient_occlusion_texture,
+            &cpu_material.name,
+            &mut self.console,
+        );
+        material.bind_group = MaterialRenderData::create_bind_group(
+            &self.device,
+            &self.texture_bind_group_layout,
+            &material.textures,
+            &material.properties_buffer,
+            &cpu_material.name,
+        );
+
+        Ok(image)
+    }
 
-        let mut temp_light_position = self.light_uniform.position;
-        let mut temp_light_color = self.light_uniform.color;
+    /// Saves the last rendered frame, without the UI, in the format of the `path` extension.
+    pub fn take_screenshot(&self, path: &Path) -> Result<()> {
+        let mut image = self.render_target.read_rgba(&self.device, &self.queue)?;
+        // The frame is opaque on screen, whatever the passes left in alpha
+        image.pixels_mut().for_each(|pixel| pixel.0[3] = 255);
+        image.save(path)?;
+        Ok(())
+    }
 
-        let mut is_scene_hovered = self.is_scene_hovered;
+    /// Replaces the color grading LUT by an image relative to the assets directory, a
+    /// horizontal strip or a HALD CLUT (see `textures::lut_texels`). The previous LUT is kept
+    /// on error.
+    pub fn load_lut(&mut self, path: &str) -> Result<()> {
+        let path = Path::new(ASSETS_DIR).join(path);
+        let lut = textures::Texture::from_lut_image(&self.device, &self.queue, &path)?;
+        self.color_grading.set_lut(&self.device, lut);
+        Ok(())
+    }
 
-        self.gui.render(
+    /// Loads the six faces of the skybox (`+X, -X, +Y, -Y, +Z, -Z`, relative to the assets
+    /// directory) and enables it. The previous skybox is kept on error.
+    pub fn load_skybox(&mut self, paths: [&str; 6]) -> Result<()> {
+        let paths = paths.map(|path| {
+            Path::new(ASSETS_DIR)
+                .join(path)
+                .to_string_lossy()
+                .into_owned()
+        });
+        let texture = textures::Texture::from_cubemap_files(
             &self.device,
             &self.queue,
-            &mut encoder,
-            &self.window,
-            &view_surface,
-            |ctx| {
-                egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
-                    egui::menu::bar(ui, |ui| {
-                        ui.menu_button("Fichier", |_| {});
-                    });
-                });
-                egui::SidePanel::left("hierarchy").show(ctx, |ui| {
-                    ui.label("Scène 3D");
-                    ui.separator();
-                    ui.label("Pizzas (x100)");
-                });
+            paths.each_ref().map(String::as_str),
+        )?;
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.skybox_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some("skybox_bind_group"),
+        });
+        if let Some(skybox) = &self.skybox {
+            self.gpu_memory.untrack_texture(&skybox.texture.texture);
+        }
+        self.gpu_memory.track_texture(&texture.texture);
+        self.skybox = Some(Skybox {
+            texture,
+            bind_group,
+        });
+        self.skybox_enabled = true;
+        Ok(())
+    }
 
-                egui::SidePanel::right("inspector").show(ctx, |ui| {
-                    ui.heading("Lumière");
-                    ui.add(egui::Slider::new(&mut temp_light_position[0], -10.0..=10.0).text("X"));
-                    ui.add(egui::Slider::new(&mut temp_light_position[1], -10.0..=10.0).text("Y"));
-                    ui.add(egui::Slider::new(&mut temp_light_position[2], -10.0..=10.0).text("Z"));
+    pub fn has_skybox(&self) -> bool {
+        self.skybox.is_some()
+    }
 
-                    ui.separator();
-                    ui.label("Couleur");
-                    ui.color_edit_button_rgb(&mut temp_light_color);
-                });
+    /// Switches between perspective and orthographic projection. The first orthographic
+    /// view is sized to the scene's bounds, later toggles restore the previous settings.
+    pub fn toggle_projection(&mut self) {
+        let next = self.inactive_projection.take().unwrap_or_else(|| {
+            let scene_aabb = self
+                .instances
+                .iter()
+                .map(|instance| self.model_aabb.transform_to_world(&instance.model_matrix()))
+                .reduce(|a, b| a.expand_to_include(&b))
+                .unwrap_or(self.model_aabb);
+            let width = (scene_aabb.max - scene_aabb.min).length() * 0.5;
+            Projection::Orthographic {
+                width,
+                height: width / self.camera.aspect,
+                znear: self.camera.projection.znear(),
+                zfar: self.camera.projection.zfar(),
+            }
+        });
+        self.inactive_projection = Some(std::mem::replace(&mut self.camera.projection, next));
+    }
 
-                egui::CentralPanel::default().show(ctx, |ui| {
-                    if let Some(id) = texture_id {
-                        let response =
-                            ui.image(egui::load::SizedTexture::new(id, ui.available_size()));
-                        is_scene_hovered = response.hovered();
-                    } else {
-                        ui.label("Chargement de la texture...");
-                    }
-                });
-            },
-        );
+    /// Switches anti-aliasing technique, ignored if the adapter does not support it.
+    pub fn set_antialiasing(&mut self, mode: AntialiasingMode) {
+        if mode == self.antialias.mode() || !self.antialias.supported_modes().contains(&mode) {
+            return;
+        }
+
+        let previous_sample_count = self.antialias.sample_count();
+        self.engine_config.antialiasing = mode;
+        self.antialias.set_mode(mode);
+        self.recreate_depth_texture();
+        self.antialias
+            .resize(&self.device, &self.config, &self.depth_texture.view);
 
-        self.is_scene_hovered = is_scene_hovered;
+        if mode.sample_count() != previous_sample_count {
+            self.pipelines = self
+                .pipeline_builder
+                .build(&self.device, mode.sample_count());
+        }
+    }
 
-        self.light_uniform.position = temp_light_position;
-        self.light_uniform.color = temp_light_color;
+    /// Window events, never sent to a headless state.
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        let (Some(gui), Some(window)) = (&mut self.gui, &self.window) else {
+            return false;
+        };
+        let consumed = gui.handle_event(window, event);
 
-        self.queue.write_buffer(
-            &self.light_buffer,
-            0,
-            bytemuck::cast_slice(&[self.light_uniform]),
-        );
+        // Left drags on an arrow move the selection, they do not orbit
+        let scene_hovered = self.is_scene_hovered && self.gizmo_hovered.is_none();
+        let handled = self
+            .input_handler
+            .process_input(event, window, consumed, scene_hovered);
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        if let Some(recorder) = &mut self.input_recorder
+            && let Some(event) = RecordedEvent::from_window_event(event, consumed, scene_hovered)
+        {
+            recorder.record(event);
+        }
+
+        consumed || handled
+    }
+
+    pub fn handle_mouse_motion(&mut self, delta: (f64, f64)) {
+        self.input_handler.handle_mouse_motion(delta);
+        if let Some(recorder) = &mut self.input_recorder {
+            recorder.record(RecordedEvent::MouseMotion {
+                dx: delta.0,
+                dy: delta.1,
+            });
+        }
+    }
+
+    /// Records the inputs from now on, replacing a running recording.
+    pub fn start_recording(&mut self) {
+        self.input_recorder = Some(InputRecorder::new());
+    }
+
+    /// Ends the running recording, empty if there was none. It is kept for
+    /// `save_recording`.
+    pub fn stop_recording(&mut self) -> InputRecording {
+        let recording = self
+            .input_recorder
+            .take()
+            .map(InputRecorder::finish)
+            .unwrap_or_default();
+        self.last_recording = Some(recording.clone());
+        recording
+    }
 
+    pub fn is_recording(&self) -> bool {
+        self.input_recorder.is_some()
+    }
+
+    /// Writes the last recording as JSON, stopping the running one first.
+    pub fn save_recording(&mut self, path: &Path) -> Result<()> {
+        if self.is_recording() {
+            self.stop_recording();
+        }
+        match &self.last_recording {
+            Some(recording) => recording.save(path),
+            None => Err(OrengineError::Generic(
+                "No input recording to save".to_string(),
+            )),
+        }
+    }
+
+    /// Replays a recording saved by `save_recording` from now on, through `update`.
+    pub fn play_recording(&mut self, path: &Path) -> Result<()> {
+        let recording = InputRecording::load(path)?;
+        self.input_playback = Some(InputPlayback::new(recording));
         Ok(())
     }
+
+    pub fn stop_playback(&mut self) {
+        self.input_playback = None;
+    }
+
+    pub fn is_playing_recording(&self) -> bool {
+        self.input_playback.is_some()
+    }
+
+    pub fn update(&mut self) {
+        self.process_file_changes();
+
+        if let Some(playback) = &mut self.input_playback {
+            let events = playback.take_due(Instant::now());
+            if playback.is_finished() {
+                self.input_playback = None;
+                engine_log!(self, LogLevel::Info, "Input recording replayed");
+            }
+            for event in &events {
+                self.input_handler.process_recorded(event);
+            }
+        }
+
+        if let Some(pick) = &self.pending_pick {
+            // Lets the mapping callback run without waiting for the GPU
+            self.device.poll(wgpu::Maintain::Poll);
+            if let Poll::Ready(hit) = pick.poll() {
+                let extend = pick.extend;
+                self.pending_pick = None;
+                // Locked instances are only selected from the hierarchy
+                let hit = hit.filter(|&i| self.instances.get(i).is_some_and(|i| !i.locked));
+                self.apply_pick(hit, extend);
+            }
+        }
+
+        for warning in self.input_handler.take_warnings() {
+            engine_log!(self, LogLevel::Warn, "{}", warning);
+        }
+        for action in self.input_handler.take_actions() {
+            match action {
+                EditorAction::ToggleInstanceIdView => {
+                    self.debug_instance_id_view = !self.debug_instance_id_view;
+                }
+                EditorAction::ToggleCameraMode => {
+                    let pivot = self.orbit_pivot();
+                    self.input_handler.toggle_camera_mode(&self.camera, pivot);
+                }
+                EditorAction::SaveCameraBookmark(index) => {
+                    self.save_camera_bookmark(index);
+                }
+                EditorAction::RestoreCameraBookmark(index) => {
+                    self.restore_camera_bookmark(index);
+                }
+                EditorAction::ToggleGizmoSpace => {
+                    self.gizmo_space = self.gizmo_space.toggled();
+                }
+                EditorAction::DeleteSelection => self.delete_selection(),
+                EditorAction::Duplicate => self.duplicate_selection(),
+                EditorAction::SelectAll => self.select_all(),
+                EditorAction::DeselectAll => self.deselect_all(),
+                EditorAction::InvertSelection => self.invert_selection(),
+                EditorAction::Undo => {
+                    if let Err(e) = self.undo() {
+                        engine_log!(self, LogLevel::Error, "{}", e);
+                    }
+                }
+                EditorAction::Redo => {
+                    if let Err(e) = self.redo() {
+                        engine_log!(self, LogLevel::Error, "{}", e);
+                    }
+                }
+                EditorAction::TakeScreenshot => {
+                    let seconds = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |elapsed| elapsed.as_secs());
+                    let path = PathBuf::from(format!("screenshot_{seconds}.png"));
+                    match self.take_screenshot(&path) {
+                        Ok(()) => engine_log!(self, LogLevel::Info, "Saved {:?}", path),
+                        Err(e) => {
+                            engine_log!(self, LogLevel::Error, "Cannot save {:?}: {}", path, e)
+                        }
+                    }
+                }
+            }
+        }
+
+        match self
+            .camera_path_playback
+            .as_ref()
+            .map(|p| p.sample(Instant::now()))
+        {
+            Some(((eye, target), over)) => {
+                self.camera.eye = eye;
+                self.camera.target = target;
+                if over {
+                    self.stop_camera_path();
+                }
+            }
+            None => self.input_handler.update_camera(&mut self.camera),
+        }
+
+        // Target moves with rotations too, so both are tracked
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_update).as_secs_f32();
+        let (last_eye, last_target) = self.last_camera_position;
+        let displacement = (self.camera.eye - last_eye)
+            .length()
+            .max((self.camera.target - last_target).length());
+        self.camera_velocity = if dt > 0.0 { displacement / dt } else { 0.0 };
+        self.last_camera_position = (self.camera.eye, self.camera.target);
+        self.last_update = now;
+
+        self.frame_stats.tick();
+        self.camera_uniform.store_previous_view_proj();
+        self.camera_uniform.update_view_proj(&self.camera);
+        let jitter = self
+            .antialias
+            .next_jitter(self.config.width, self.config.height);
+        self.camera_uniform.apply_jitter(jitter);
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform]),
+        );
+
+        self.cull_instances();
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    /// Adds an instance to the scene and returns its index, an unnamed instance is named
+    /// after it. Fails with `OrengineError::InstanceLimitExceeded` when the instance buffer
+    /// is full.
+    pub fn add_instance(&mut self, mut instance: Instance) -> Result<usize> {
+        let index = self.instances.len();
+        let capacity = self.engine_config.instance_capacity;
+        if index >= capacity {
+            return Err(OrengineError::InstanceLimitExceeded {
+                count: index + 1,
+                limit: capacity,
+            });
+        }
+        if instance.name.is_empty() {
+            instance.name = format!("Objet {index}");
+        }
+        self.instances.push(instance);
+        self.cull_instances();
+        Ok(index)
+    }
+
+    /// Removes an instance, the last one takes its index (the selection follows it).
+    pub fn remove_instance(&mut self, id: usize) -> Option<Instance> {
+        if id >= self.instances.len() {
+            return None;
+        }
+        let last = self.instances.len() - 1;
+        let removed = self.instances.swap_remove(id);
+
+        self.selected_instances = self
+            .selected_instances
+            .iter()
+            .filter_map(|&i| remap_swap_removed(i, id, last))
+            .collect();
+        self.hovered_instance = self
+            .hovered_instance
+            .and_then(|i| remap_swap_removed(i, id, last));
+        // Its children become roots
+        for instance in &mut self.instances {
+            instance.parent = instance
+                .parent
+                .and_then(|p| remap_swap_removed(p, id, last));
+        }
+        self.collapsed_instances = self
+            .collapsed_instances
+            .iter()
+            .filter_map(|&i| remap_swap_removed(i, id, last))
+            .collect();
+        // Its result would name the old indices
+        self.pending_pick = None;
+        self.renaming_instance = None;
+        self.hierarchy_anchor = None;
+        self.inspector_edit_start = None;
+
+        self.update_selection_buffer();
+        self.cull_instances();
+        Some(removed)
+    }
+
+    /// Shows or hides an instance, hiding it also removes it from the selection.
+    pub fn set_visibility(&mut self, id: usize, visible: bool) {
+        let Some(instance) = self.instances.get_mut(id) else {
+            return;
+        };
+        instance.visible = visible;
+        if !visible && self.selected_instances.remove(&id) {
+            self.update_selection_buffer();
+        }
+        if !visible && self.hovered_instance == Some(id) {
+            self.hovered_instance = None;
+        }
+        self.cull_instances();
+    }
+
+    /// Removes every selected instance.
+    pub fn remove_selected_instances(&mut self) {
+        let mut selected: Vec<_> = self.selected_instances.iter().copied().collect();
+        // From the end, so that swapped-in instances have already been handled
+        selected.sort_unstable_by(|a, b| b.cmp(a));
+        for id in selected {
+            self.remove_instance(id);
+        }
+    }
+
+    /// Inverse of `remove_instance`: puts `instance` back at `id`, the instance that took
+    /// its place moves back to the end.
+    pub fn restore_instance(&mut self, id: usize, instance: Instance) -> Result<()> {
+        let last = self.add_instance(instance)?;
+        if id < last {
+            self.instances.swap(id, last);
+            let remap = |i: usize| if i == id { last } else { i };
+            self.selected_instances = self.selected_instances.iter().map(|&i| remap(i)).collect();
+            self.hovered_instance = self.hovered_instance.map(remap);
+            for instance in &mut self.instances {
+                instance.parent = instance.parent.map(remap);
+            }
+            self.collapsed_instances = self.collapsed_instances.iter().map(|&i| remap(i)).collect();
+            self.pending_pick = None;
+            self.renaming_instance = None;
+            self.hierarchy_anchor = None;
+            self.inspector_edit_start = None;
+            self.update_selection_buffer();
+            self.cull_instances();
+        }
+        Ok(())
+    }
+
+    /// Returns the previous position, `None` if there is no such instance.
+    pub fn move_instance(&mut self, id: usize, position: glam::Vec3) -> Option<glam::Vec3> {
+        let instance = self.instances.get_mut(id)?;
+        let previous = std::mem::replace(&mut instance.position, position);
+        if self.selected_instances.contains(&id) {
+            self.update_selection_buffer();
+        }
+        self.cull_instances();
+        Some(previous)
+    }
+
+    /// Returns the previous position and rotation, `None` if there is no such instance.
+    pub fn set_instance_transform(
+        &mut self,
+        id: usize,
+        position: glam::Vec3,
+        rotation: glam::Quat,
+    ) -> Option<(glam::Vec3, glam::Quat)> {
+        let instance = self.instances.get_mut(id)?;
+        let previous = (instance.position, instance.rotation);
+        instance.rotation = rotation;
+        self.move_instance(id, position);
+        Some(previous)
+    }
+
+    /// Returns the previous position and scale, `None` if there is no such instance.
+    pub fn scale_instance(
+        &mut self,
+        id: usize,
+        position: glam::Vec3,
+        scale: glam::Vec3,
+    ) -> Option<(glam::Vec3, glam::Vec3)> {
+        let instance = self.instances.get_mut(id)?;
+        let previous_scale = std::mem::replace(&mut instance.scale, scale);
+        let previous_position = self.move_instance(id, position)?;
+        Some((previous_position, previous_scale))
+    }
+
+    /// Puts `instance` in place of instance `id` and returns the previous one, `None` if
+    /// there is no such instance.
+    pub fn replace_instance(&mut self, id: usize, instance: Instance) -> Option<Instance> {
+        let previous = std::mem::replace(self.instances.get_mut(id)?, instance);
+        if self.selected_instances.contains(&id) {
+            self.update_selection_buffer();
+        }
+        self.cull_instances();
+        Some(previous)
+    }
+
+    /// Instances whose parent is `id`, in index order.
+    pub fn children(&self, id: usize) -> Vec<usize> {
+        self.instances
+            .iter()
+            .enumerate()
+            .filter(|&(i, instance)| i != id && instance.parent == Some(id))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Children of `id`, their children and so on, without `id` itself.
+    pub fn descendants(&self, id: usize) -> Vec<usize> {
+        let mut descendants = Vec::new();
+        let mut visited = HashSet::from([id]);
+        let mut stack = vec![id];
+        while let Some(parent) = stack.pop() {
+            for child in self.children(parent) {
+                if visited.insert(child) {
+                    descendants.push(child);
+                    stack.push(child);
+                }
+            }
+        }
+        descendants
+    }
+
+    /// Puts `id` under `parent` in the hierarchy, or at the root for `None`, and returns its
+    /// previous parent. Its transform is unchanged. Fails for unknown instances and for a
+    /// parent that is `id` or one of its descendants.
+    pub fn set_parent(&mut self, id: usize, parent: Option<usize>) -> Result<Option<usize>> {
+        if id >= self.instances.len() {
+            return Err(OrengineError::Generic(format!("No instance {id}")));
+        }
+        if let Some(parent) = parent {
+            if parent >= self.instances.len() {
+                return Err(OrengineError::Generic(format!("No instance {parent}")));
+            }
+            if parent == id || self.descendants(id).contains(&parent) {
+                return Err(OrengineError::Generic(format!(
+                    "{} cannot be parented to its own descendant {}",
+                    self.instances[id].name, self.instances[parent].name
+                )));
+            }
+        }
+        Ok(std::mem::replace(&mut self.instances[id].parent, parent))
+    }
+
+    /// Returns the previous name, `None` if there is no such instance.
+    pub fn rename_instance(&mut self, id: usize, name: String) -> Option<String> {
+        let instance = self.instances.get_mut(id)?;
+        Some(std::mem::replace(&mut instance.name, name))
+    }
+
+    /// Removes the selected instances, as an undoable command.
+    pub fn delete_selection(&mut self) {
+        if self.selected_instances.is_empty() {
+            return;
+        }
+        let ids = self.selected_instances.iter().copied().collect();
+        if let Err(e) = self.execute(Box::new(DeleteInstancesCommand::new(ids))) {
+            engine_log!(self, LogLevel::Error, "{}", e);
+        }
+    }
+
+    /// Copies the selected instances next to them, as an undoable command.
+    pub fn duplicate_selection(&mut self) {
+        if self.selected_instances.is_empty() {
+            return;
+        }
+        let ids = self.selected_instances.iter().copied().collect();
+        if let Err(e) = self.execute(Box::new(DuplicateCommand::new(ids))) {
+            engine_log!(self, LogLevel::Error, "{}", e);
+        }
+    }
+
+    /// Selects every visible instance, as an undoable command.
+    pub fn select_all(&mut self) {
+        let visible = self.instances.iter().filter(|i| i.visible).count();
+        if self.selected_instances.len() == visible {
+            return;
+        }
+        let ids = 0..self.instances.len();
+        if let Err(e) = self.execute(Box::new(SelectCommand::new(ids))) {
+            engine_log!(self, LogLevel::Error, "{}", e);
+        }
+    }
+
+    /// Clears the selection, as an undoable command.
+    pub fn deselect_all(&mut self) {
+        if self.selected_instances.is_empty() {
+            return;
+        }
+        if let Err(e) = self.execute(Box::new(SelectCommand::new([]))) {
+            engine_log!(self, LogLevel::Error, "{}", e);
+        }
+    }
+
+    /// Swaps the selected and unselected visible instances, as an undoable command.
+    pub fn invert_selection(&mut self) {
+        if self.instances.is_empty() {
+            return;
+        }
+        if let Err(e) = self.execute(Box::new(InvertSelectionCommand::new())) {
+            engine_log!(self, LogLevel::Error, "{}", e);
+        }
+    }
+
+    /// Instances drawn with `material_id`: through their override, or through one of the
+    /// model's meshes when they have none.
+    pub fn instances_with_material(&self, material_id: usize) -> Vec<usize> {
+        self.instances
+            .iter()
+            .enumerate()
+            .filter(|(_, instance)| {
+                match instance
+                    .material_override
+                    .filter(|&id| id < self.materials.len())
+                {
+                    Some(id) => id == material_id,
+                    None => self.meshes.iter().any(|m| m.material_id == material_id),
+                }
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Replaces the selection with the visible instances drawn with `material_id`, as an
+    /// undoable command. Returns how many are selected.
+    pub fn select_by_material(&mut self, material_id: usize) -> usize {
+        if let Err(e) = self.execute(Box::new(SelectByMaterialCommand::new(material_id))) {
+            engine_log!(self, LogLevel::Error, "{}", e);
+        }
+        self.selected_instances.len()
+    }
+
+    /// Runs `command` and records it in the undo history. A failed command is not recorded.
+    pub fn execute(&mut self, mut command: Box<dyn Command>) -> Result<()> {
+        command.execute(self)?;
+        self.commands.push(command);
+        Ok(())
+    }
+
+    /// Reverts the last applied command, if any.
+    pub fn undo(&mut self) -> Result<()> {
+        if !self.commands.can_undo() {
+            return Ok(());
+        }
+        self.commands.cursor -= 1;
+        self.run_command(self.commands.cursor, true)
+    }
+
+    /// Applies again the last undone command, if any.
+    pub fn redo(&mut self) -> Result<()> {
+        if !self.commands.can_redo() {
+            return Ok(());
+        }
+        self.commands.cursor += 1;
+        self.run_command(self.commands.cursor - 1, false)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.commands.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.commands.can_redo()
+    }
+
+    /// The command needs the whole state, the history is set aside meanwhile.
+    fn run_command(&mut self, index: usize, undo: bool) -> Result<()> {
+        let mut history = std::mem::take(&mut self.commands.history);
+        let command = &mut history[index];
+        let result = if undo {
+            command.undo(self)
+        } else {
+            command.execute(self)
+        };
+        self.commands.history = history;
+        result
+    }
+
+    /// Resizes the instance buffer, failing if `capacity` is below the current instance
+    /// count or above `MAX_INSTANCES`.
+    pub fn set_instance_capacity(&mut self, capacity: usize) -> Result<()> {
+        check_instance_limit(capacity)?;
+        if capacity < self.instances.len() {
+            return Err(OrengineError::InstanceLimitExceeded {
+                count: self.instances.len(),
+                limit: capacity,
+            });
+        }
+        self.gpu_memory.untrack_buffer(&self.instance_buffer);
+        self.gpu_memory
+            .untrack_buffer(&self.transparent_instance_buffer);
+        self.instance_buffer = create_instance_buffer(&self.device, capacity);
+        self.transparent_instance_buffer = create_instance_buffer(&self.device, capacity);
+        self.gpu_memory.track_buffer(&self.instance_buffer);
+        self.gpu_memory
+            .track_buffer(&self.transparent_instance_buffer);
+        self.engine_config.instance_capacity = capacity;
+        self.cull_instances();
+        Ok(())
+    }
+
+    /// Uploads the instances whose bounds touch the view frustum, the others are not drawn.
+    fn cull_instances(&mut self) {
+        let planes = self.camera.build_frustum_planes();
+        self.visible_instances.clear();
+        for (i, instance) in self.instances.iter().enumerate() {
+            if !instance.visible {
+                continue;
+            }
+            let bounds = self.model_aabb.transform_to_world(&instance.model_matrix());
+            if aabb_in_frustum(&planes, bounds.center(), bounds.half_extents()) {
+                self.visible_instances.push(i);
+            }
+        }
+        // Instances sharing a material and a winding end up next to each other, so that
+        // they are drawn with a single call
+        self.visible_instances.sort_by_key(|&i| {
+            let instance = &self.instances[i];
+            (instance.material_override, instance.is_mirrored())
+        });
+
+        let instance_data = self
+            .visible_instances
+            .iter()
+            .map(|&i| self.instances[i].to_raw(i))
+            .collect::<Vec<_>>();
+
+        if !instance_data.is_empty() {
+            self.queue.write_buffer(
+                &self.instance_buffer,
+                0,
+                bytemuck::cast_slice(&instance_data),
+            );
+        }
+        self.instance_runs = draw_runs(self.visible_instances.iter().map(|&i| &self.instances[i]));
+
+        let mut transparent: Vec<(usize, Aabb)> = self
+            .visible_instances
+            .iter()
+            .filter(|&&i| {
+                self.pass_meshes(self.instances[i].material_override, true)
+                    .next()
+                    .is_some()
+            })
+            .map(|&i| {
+                let bounds = self
+                    .model_aabb
+                    .transform_to_world(&self.instances[i].model_matrix());
+                (i, bounds)
+            })
+            .collect();
+        sort_back_to_front(self.camera.eye, &mut transparent);
+        match check_depth_order(&transparent) {
+            Err(e) if !self.depth_sort_conflict => {
+                engine_log!(self, LogLevel::Warn, "{}", e);
+                self.depth_sort_conflict = true;
+            }
+            Err(_) => {}
+            Ok(()) => self.depth_sort_conflict = false,
+        }
+        self.transparent_instances = transparent.into_iter().map(|(i, _)| i).collect();
+
+        let transparent_data = self
+            .transparent_instances
+            .iter()
+            .map(|&i| self.instances[i].to_raw(i))
+            .collect::<Vec<_>>();
+        if !transparent_data.is_empty() {
+            self.queue.write_buffer(
+                &self.transparent_instance_buffer,
+                0,
+                bytemuck::cast_slice(&transparent_data),
+            );
+        }
+    }
+
+    /// Meshes drawn in the opaque or in the transparent pass for instances drawn with
+    /// `material_override`, each with the material it is drawn with.
+    fn pass_meshes(
+        &self,
+        material_override: Option<usize>,
+        transparent: bool,
+    ) -> impl Iterator<Item = (&MeshRenderData, usize)> {
+        let material_override = material_override.filter(|&id| id < self.materials.len());
+        let own_materials: &[usize] = match material_override {
+            None if transparent => &self.transparent_meshes,
+            None => &self.opaque_meshes,
+            Some(_) => &[],
+        };
+        let overridden = material_override
+            .filter(|&id| {
+                self.cpu_materials.get(id).is_some_and(|m| m.is_transparent) == transparent
+            })
+            .map(|id| self.meshes.iter().map(move |mesh| (mesh, id)));
+        own_materials
+            .iter()
+            .map(|&i| (&self.meshes[i], self.meshes[i].material_id))
+            .chain(overridden.into_iter().flatten())
+    }
+
+    /// Selects every instance whose on-screen bounds touch `selection_rect`.
+    /// Both rectangles are in egui screen coordinates, `viewport_rect` being where
+    /// the 3D view is displayed.
+    pub fn perform_box_selection(
+        &mut self,
+        selection_rect: egui::Rect,
+        viewport_rect: egui::Rect,
+        extend: bool,
+    ) {
+        if !extend {
+            self.selected_instances.clear();
+        }
+
+        let view_proj = self.camera.build_view_projection_matrix();
+        for (i, instance) in self.instances.iter().enumerate() {
+            if !instance.visible || instance.locked {
+                continue;
+            }
+            let screen_rect = aabb_screen_rect(
+                &self.model_aabb,
+                instance.model_matrix(),
+                view_proj,
+                viewport_rect,
+            );
+            if screen_rect.is_some_and(|r| r.intersects(selection_rect)) {
+                self.selected_instances.insert(i);
+            }
+        }
+
+        self.update_selection_buffer();
+    }
+
+    /// Selects every instance whose pivot lies inside `polygon`. Like for
+    /// `perform_box_selection`, the points are in egui screen coordinates.
+    pub fn perform_lasso_selection(
+        &mut self,
+        polygon: &[egui::Pos2],
+        viewport_rect: egui::Rect,
+        extend: bool,
+    ) {
+        if !extend {
+            self.selected_instances.clear();
+        }
+
+        let view_proj = self.camera.build_view_projection_matrix();
+        for (i, instance) in self.instances.iter().enumerate() {
+            if !instance.visible || instance.locked {
+                continue;
+            }
+            let pivot = world_to_screen(instance.position, view_proj, viewport_rect);
+            if pivot.is_some_and(|p| point_in_polygon(p, polygon)) {
+                self.selected_instances.insert(i);
+            }
+        }
+
+        self.update_selection_buffer();
+    }
+
+    /// Closest instance hit by a world-space ray, through the model's BVH.
+    pub fn get_hit_instance(&self, ray: &Ray) -> Option<usize> {
+        let mut closest: Option<(usize, f32)> = None;
+
+        for (i, instance) in self.instances.iter().enumerate().filter(|(_, i)| i.visible) {
+            // Work in the instance's local space rather than transforming every triangle. The
+            // inverse also undoes the scale, and hit distances stay in world units.
+            let local_ray = ray.transform(&instance.model_matrix().inverse());
+            let Some(t_box) = local_ray.intersect_aabb(&self.model_aabb) else {
+                continue;
+            };
+            if closest.is_some_and(|(_, t)| t_box > t) {
+                continue;
+            }
+
+            if let Some(t) = self.bvh.intersect(&local_ray)
+                && closest.is_none_or(|(_, best)| t < best)
+            {
+                closest = Some((i, t));
+            }
+        }
+
+        closest.map(|(i, _)| i)
+    }
+
+    /// Saves the current view as bookmark `index`, replacing it, or after the last one when
+    /// there are fewer. Returns where it was stored.
+    pub fn save_camera_bookmark(&mut self, index: usize) -> usize {
+        let index = index.min(self.camera_bookmarks.len());
+        let bookmark = CameraBookmark::from_camera(format!("Vue {}", index + 1), &self.camera);
+        match self.camera_bookmarks.get_mut(index) {
+            // Keeps the name it was given
+            Some(saved) => {
+                saved.eye = bookmark.eye;
+                saved.target = bookmark.target;
+            }
+            None => self.camera_bookmarks.push(bookmark),
+        }
+        index
+    }
+
+    /// Moves the camera to bookmark `index` over `CAMERA_TRANSITION_DURATION`. Returns
+    /// false if there is no such bookmark.
+    pub fn restore_camera_bookmark(&mut self, index: usize) -> bool {
+        let Some(bookmark) = self.camera_bookmarks.get(index) else {
+            return false;
+        };
+        self.input_handler.camera_controller.start_transition(
+            &self.camera,
+            bookmark.eye,
+            bookmark.target,
+        );
+        true
+    }
+
+    /// Flies the camera along `path` from its first waypoint, ignoring the controller until
+    /// it ends or `stop_camera_path` is called.
+    pub fn play_camera_path(&mut self, path: &CameraPath) {
+        if path.waypoints.is_empty() {
+            return;
+        }
+        self.camera_path_playback = Some(CameraPathPlayback::new(path.clone()));
+    }
+
+    /// Leaves the camera where the path took it.
+    pub fn stop_camera_path(&mut self) {
+        if self.camera_path_playback.take().is_some() {
+            self.input_handler.sync_with_camera(&self.camera);
+        }
+    }
+
+    pub fn is_playing_camera_path(&self) -> bool {
+        self.camera_path_playback.is_some()
+    }
+
+    /// Adds the current view to `camera_path`, `CAMERA_WAYPOINT_INTERVAL` after its last
+    /// waypoint.
+    pub fn add_camera_waypoint(&mut self) {
+        let time_seconds = if self.camera_path.waypoints.is_empty() {
+            0.0
+        } else {
+            self.camera_path.end_time() + CAMERA_WAYPOINT_INTERVAL
+        };
+        self.camera_path.push(CameraWaypoint {
+            position: self.camera.eye,
+            target: self.camera.target,
+            time_seconds,
+        });
+    }
+
+    /// Average position of the selected instances, where the gizmo stands.
+    pub fn selection_center(&self) -> Option<glam::Vec3> {
+        if self.selected_instances.is_empty() {
+            return None;
+        }
+        let sum: glam::Vec3 = self
+            .selected_instances
+            .iter()
+            .map(|&i| self.instances[i].position)
+            .sum();
+        Some(sum / self.selected_instances.len() as f32)
+    }
+
+    /// The gizmo over the selection, turned after the first selected instance in local
+    /// space.
+    pub fn gizmo_frame(&self) -> Option<GizmoFrame> {
+        let center = self.selection_center()?;
+        let rotation = match self.gizmo_space {
+            GizmoSpace::World => glam::Quat::IDENTITY,
+            GizmoSpace::Local => {
+                let first = self.selected_instances.iter().min()?;
+                self.instances[*first].rotation
+            }
+        };
+        Some(GizmoFrame {
+            center,
+            rotation,
+            scale: gizmo_scale(&self.camera, center),
+        })
+    }
+
+    /// Tracks the arrow, ring or scale handle under the cursor at `ndc` (`viewport` in
+    /// pixels) and drags it. Shift scales evenly with any handle, Ctrl inverts the snapping
+    /// of moves. Returns true while the gizmo holds the pointer, the viewport must then
+    /// ignore it.
+    fn update_gizmo(
+        &mut self,
+        ndc: Option<glam::Vec2>,
+        viewport: glam::Vec2,
+        drag_started: bool,
+        drag_stopped: bool,
+        modifiers: egui::Modifiers,
+    ) -> bool {
+        if self.gizmo_drag.is_some() {
+            if let Some(ndc) = ndc {
+                self.drag_gizmo(&Ray::from_ndc(&self.camera, ndc), modifiers);
+            }
+            if drag_stopped {
+                self.finish_gizmo_drag();
+            }
+            return true;
+        }
+
+        let frame = self.gizmo_frame();
+        let view_proj = self.camera.build_view_projection_matrix();
+        self.gizmo_hovered = frame
+            .zip(ndc)
+            .and_then(|(frame, ndc)| match self.gizmo_mode {
+                GizmoMode::Translate => {
+                    pick_axis(view_proj, &frame, ndc, viewport).map(GizmoHandle::Axis)
+                }
+                GizmoMode::Rotate => pick_ring(&Ray::from_ndc(&self.camera, ndc), &frame, viewport)
+                    .map(GizmoHandle::Axis),
+                GizmoMode::Scale => pick_scale_handle(view_proj, &frame, ndc, viewport),
+            });
+        let (Some(handle), Some(frame), Some(ndc)) = (self.gizmo_hovered, frame, ndc) else {
+            return false;
+        };
+        if drag_started {
+            let ray = Ray::from_ndc(&self.camera, ndc);
+            let direction = handle
+                .axis()
+                .map_or(glam::Vec3::ZERO, |axis| frame.direction(axis));
+            let motion = match (self.gizmo_mode, handle) {
+                (GizmoMode::Translate, _) => axis_parameter(&ray, frame.center, direction)
+                    .map(|start| GizmoMotion::Translate { start }),
+                (GizmoMode::Rotate, _) => ring_point(&ray, frame.center, direction)
+                    .map(|last| GizmoMotion::Rotate { last, angle: 0.0 }),
+                (GizmoMode::Scale, GizmoHandle::Axis(_)) => {
+                    axis_parameter(&ray, frame.center, direction)
+                        .map(|start| GizmoMotion::Scale { start })
+                }
+                (GizmoMode::Scale, GizmoHandle::Center) => {
+                    uniform_scale_parameter(&ray, &self.camera, frame.center)
+                        .map(|start| GizmoMotion::Scale { start })
+                }
+            };
+            if let Some(motion) = motion {
+                self.gizmo_drag = Some(GizmoDrag {
+                    handle,
+                    frame,
+                    motion,
+                    start_transforms: self
+                        .selected_instances
+                        .iter()
+                        .map(|&i| {
+                            let instance = &self.instances[i];
+                            (i, instance.position, instance.rotation, instance.scale)
+                        })
+                        .collect(),
+                });
+            }
+        }
+        true
+    }
+
+    /// Moves, turns or scales the dragged instances after the cursor, from where they
+    /// started.
+    fn drag_gizmo(&mut self, ray: &Ray, modifiers: egui::Modifiers) {
+        let snapping = self.snap_enabled != modifiers.ctrl;
+        let (grid_size, rotation_degrees) = (self.snap_grid_size, self.snap_rotation_degrees);
+        let Some(drag) = &mut self.gizmo_drag else {
+            return;
+        };
+        let center = drag.frame.center;
+        let direction = drag
+            .handle
+            .axis()
+            .map_or(glam::Vec3::ZERO, |axis| drag.frame.direction(axis));
+        let parameter = match drag.handle {
+            GizmoHandle::Axis(_) => axis_parameter(ray, center, direction),
+            GizmoHandle::Center => uniform_scale_parameter(ray, &self.camera, center),
+        };
+        let transforms: Vec<_> = match &mut drag.motion {
+            GizmoMotion::Translate { start } => {
+                let Some(t) = parameter else {
+                    return;
+                };
+                let offset = direction * (t - *start);
+                drag.start_transforms
+                    .iter()
+                    .map(|&(id, position, rotation, scale)| {
+                        let position = position + offset;
+                        let position = if snapping {
+                            snap_to_grid(position, grid_size)
+                        } else {
+                            position
+                        };
+                        (id, position, rotation, scale)
+                    })
+                    .collect()
+            }
+            GizmoMotion::Rotate { last, angle } => {
+                let Some(point) = ring_point(ray, center, direction) else {
+                    return;
+                };
+                // Step by step, so that the angle keeps adding up past half a turn
+                *angle += signed_angle(*last, point, direction);
+                *last = point;
+                // The sum itself stays exact, the next steps add up from it
+                let angle = if snapping {
+                    snap_angle(*angle, rotation_degrees)
+                } else {
+                    *angle
+                };
+                let turn = glam::Quat::from_axis_angle(direction, angle);
+                drag.start_transforms
+                    .iter()
+                    .map(|&(id, position, rotation, scale)| {
+                        (
+                            id,
+                            center + turn * (position - center),
+                            turn * rotation,
+                            scale,
+                        )
+                    })
+                    .collect()
+            }
+            GizmoMotion::Scale { start } => {
+                let Some(t) = parameter else {
+                    return;
+                };
+                let factor = scale_factor(*start, t, drag.frame.scale);
+                let axis = drag.handle.axis().filter(|_| !modifiers.shift);
+                drag.start_transforms
+                    .iter()
+                    .map(|&(id, position, rotation, scale)| {
+                        let offset = position - center;
+                        match axis {
+                            // Along the instance's own axis, exact in local space
+                            Some(axis) => {
+                                let mut stretch = glam::Vec3::ONE;
+                                stretch[axis as usize] = factor;
+                                let along = direction * offset.dot(direction);
+                                (
+                                    id,
+                                    position + along * (factor - 1.0),
+                                    rotation,
+                                    scale * stretch,
+                                )
+                            }
+                            None => (id, center + offset * factor, rotation, scale * factor),
+                        }
+                    })
+                    .collect()
+            }
+        };
+        for (id, position, rotation, scale) in transforms {
+            self.set_instance_transform(id, position, rotation);
+            self.scale_instance(id, position, scale);
+        }
+    }
+
+    /// Records the drag as a single command, undone at once.
+    fn finish_gizmo_drag(&mut self) {
+        let Some(drag) = self.gizmo_drag.take() else {
+            return;
+        };
+        let mut transforms = Vec::with_capacity(drag.start_transforms.len());
+        for (id, start_position, start_rotation, start_scale) in drag.start_transforms {
+            let Some((position, rotation)) =
+                self.set_instance_transform(id, start_position, start_rotation)
+            else {
+                continue;
+            };
+            if let Some((_, scale)) = self.scale_instance(id, start_position, start_scale) {
+                transforms.push((id, position, rotation, scale));
+            }
+        }
+        // Cannot fail, the commands skip missing instances
+        let _ = match drag.motion {
+            GizmoMotion::Translate { .. } => self.execute(Box::new(MoveInstanceCommand::many(
+                transforms
+                    .into_iter()
+                    .map(|(id, position, ..)| (id, position))
+                    .collect(),
+            ))),
+            GizmoMotion::Rotate { .. } => self.execute(Box::new(RotateInstanceCommand::new(
+                transforms
+                    .into_iter()
+                    .map(|(id, position, rotation, _)| (id, position, rotation))
+                    .collect(),
+            ))),
+            GizmoMotion::Scale { .. } => self.execute(Box::new(ScaleInstanceCommand::new(
+                transforms
+                    .into_iter()
+                    .map(|(id, position, _, scale)| (id, position, scale))
+                    .collect(),
+            ))),
+        };
+    }
+
+    /// Point orbit mode turns around: the center of the selection, or else the point in
+    /// front of the camera as far as the origin of the scene.
+    fn orbit_pivot(&self) -> glam::Vec3 {
+        let selection = self
+            .selected_instances
+            .iter()
+            .filter_map(|&i| self.instances.get(i))
+            .map(|instance| self.model_aabb.transform_to_world(&instance.model_matrix()))
+            .reduce(|a, b| a.expand_to_include(&b));
+        if let Some(aabb) = selection {
+            return aabb.center();
+        }
+
+        let forward = (self.camera.target - self.camera.eye).normalize_or_zero();
+        let distance = forward.dot(-self.camera.eye).max(1.0);
+        self.camera.eye + forward * distance
+    }
+
+    /// Hover picking is pointless (and costly) while the camera is flying around.
+    pub fn is_camera_moving_fast(&self) -> bool {
+        self.camera_velocity > self.raycast_velocity_threshold
+    }
+
+    /// Updates `hovered_instance` for a cursor at `ndc` in the viewport, `None` when outside.
+    fn update_hover(&mut self, ndc: Option<glam::Vec2>) {
+        self.hovered_instance = match ndc {
+            Some(ndc) if !self.is_camera_moving_fast() => self
+                .get_hit_instance(&Ray::from_ndc(&self.camera, ndc))
+                .filter(|&i| !self.instances[i].locked),
+            _ => None,
+        };
+    }
+
+    /// Uploads new shading parameters for a material, visible from the next frame.
+    /// `specular_map` is kept as is, it follows the bound texture, and so is `opacity`, which
+    /// goes with the mesh partition made at load time.
+    pub fn update_material_properties(
+        &mut self,
+        material_id: usize,
+        mut properties: MaterialPropertiesUniform,
+    ) {
+        if let Some(material) = self.materials.get_mut(material_id) {
+            properties.specular_map = material.properties.specular_map;
+            properties.opacity = material.properties.opacity;
+            material.properties = properties;
+            self.queue.write_buffer(
+                &material.properties_buffer,
+                0,
+                bytemuck::cast_slice(&[properties]),
+            );
+        }
+    }
+
+    /// Picks the instance under `ndc` on the GPU. The selection changes once the result is
+    /// read back, a frame or two later.
+    pub fn request_pick(&mut self, ndc: glam::Vec2, extend: bool) {
+        self.pick_request = Some((ndc, extend));
+    }
+
+    /// Selects the picked instance, or toggles it when extending. Missing clears the
+    /// selection unless extending.
+    fn apply_pick(&mut self, hit: Option<usize>, extend: bool) {
+        if !extend {
+            self.selected_instances.clear();
+        }
+        if let Some(i) = hit.filter(|&i| i < self.instances.len())
+            && !self.selected_instances.insert(i)
+        {
+            self.selected_instances.remove(&i);
+        }
+        self.update_selection_buffer();
+    }
+
+    pub fn selection(&self) -> &HashSet<usize> {
+        &self.selected_instances
+    }
+
+    /// Replaces the selection, hidden and unknown instances are left out.
+    pub fn set_selection(&mut self, ids: impl IntoIterator<Item = usize>) {
+        self.selected_instances = ids
+            .into_iter()
+            .filter(|&i| {
+                self.instances
+                    .get(i)
+                    .is_some_and(|instance| instance.visible)
+            })
+            .collect();
+        self.update_selection_buffer();
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selected_instances.clear();
+        self.update_selection_buffer();
+    }
+
+    /// Rebuilds the instance buffer used to draw the selection outline.
+    fn update_selection_buffer(&mut self) {
+        let mut selected = self.selected_instances.iter().copied().collect::<Vec<_>>();
+        selected.sort_unstable();
+        let instance_data = selected
+            .into_iter()
+            .map(|i| self.instances[i].to_raw(i))
+            .collect::<Vec<_>>();
+
+        if let Some(buffer) = &self.selection_instance_buffer {
+            self.gpu_memory.untrack_buffer(buffer);
+        }
+        self.selection_instance_buffer = (!instance_data.is_empty()).then(|| {
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Selection Instance Buffer"),
+                    contents: bytemuck::cast_slice(&instance_data),
+                    usage: wgpu::BufferUsages::VERTEX,
+                })
+        });
+        if let Some(buffer) = &self.selection_instance_buffer {
+            self.gpu_memory.track_buffer(buffer);
+        }
+    }
+
+    /// Draws the 3D view into the render target, then the editor UI showing it on the
+    /// surface. Headless, only the 3D view is drawn.
+    pub fn render(&mut self) -> Result<()> {
+        let (Some(surface), Some(window), Some(gui)) = (&self.surface, &self.window, &mut self.gui)
+        else {
+            return self.render_offscreen();
+        };
+        let output = surface.get_current_texture()?;
+        let view_surface = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        // The UI runs first so that its edits show up in this very frame
+        let raw_input = gui.take_input(window);
+        let context = gui.context.clone();
+        let full_output = context.run(raw_input, |ctx| self.draw_ui(ctx));
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+        let pick = self.encode_scene(&mut encoder);
+
+        if let (Some(gui), Some(window)) = (&mut self.gui, &self.window) {
+            gui.render(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                window,
+                &view_surface,
+                full_output,
+            );
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        output.present();
+        self.start_pick(pick);
+
+        Ok(())
+    }
+
+    /// Renders a single frame, after an `update`, and reads the render target back.
+    pub fn render_to_image(&mut self) -> Result<image::RgbaImage> {
+        self.update();
+        self.render_offscreen()?;
+        self.render_target.read_rgba(&self.device, &self.queue)
+    }
+
+    /// The 3D view alone, into the render target.
+    fn render_offscreen(&mut self) -> Result<()> {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+        let pick = self.encode_scene(&mut encoder);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.start_pick(pick);
+        Ok(())
+    }
+
+    /// Waits for the readback of the pick `encode_scene` recorded, once submitted.
+    fn start_pick(&mut self, pick: Option<(wgpu::Buffer, bool)>) {
+        if let Some((buffer, extend)) = pick {
+            self.pending_pick = Some(PendingPick::new(
+                buffer,
+                self.visible_instances.clone(),
+                extend,
+            ));
+        }
+    }
+
+    /// Records every pass of the 3D view, up to the final render target, and the pick
+    /// requested by a click if any.
+    fn encode_scene(&mut self, encoder: &mut wgpu::CommandEncoder) -> Option<(wgpu::Buffer, bool)> {
+        self.queue
+            .write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[self.lights]));
+        self.queue.write_buffer(
+            &self.directional_light_buffer,
+            0,
+            bytemuck::cast_slice(&[self.directional_light]),
+        );
+        let spot_light = self
+            .spot_light
+            .map_or_else(SpotLightUniform::disabled, |light| light.to_uniform());
+        self.queue.write_buffer(
+            &self.spot_light_buffer,
+            0,
+            bytemuck::cast_slice(&[spot_light]),
+        );
+
+        let mut render_stats = RenderStats::default();
+        {
+            let (view, resolve_target) = self.antialias.scene_attachment(&self.hdr_target.view);
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("3D Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: 0.1,
+                            g: 0.2,
+                            b: 0.3,
+                            a: 1.0,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            // Without perspective the sky would be a small cube around the eye
+            if !self.debug_instance_id_view && !self.camera.projection.is_orthographic() {
+                if let Some(skybox) = self.skybox.as_ref().filter(|_| self.skybox_enabled) {
+                    render_pass.set_pipeline(&self.pipelines.skybox);
+                    render_pass.set_bind_group(1, &skybox.bind_group, &[]);
+                    render_pass.draw(0..36, 0..1);
+                    render_stats.record_draw(36, 1);
+                }
+                render_pass.set_bind_group(2, &self.light_bind_group, &[]);
+            }
+
+            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+            for run in &self.instance_runs {
+                if self.debug_instance_id_view {
+                    // Every mesh, transparent ones included
+                    render_pass.set_pipeline(self.pipelines.instance_id(run.mirrored));
+                    for mesh in &self.meshes {
+                        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                        render_pass.set_index_buffer(
+                            mesh.index_buffer.slice(..),
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        render_pass.draw_indexed(0..mesh.num_elements, 0, run.instances.clone());
+                        render_stats.record_draw(mesh.num_elements, run.instances.len() as u32);
+                    }
+                    continue;
+                }
+                for (mesh, material_id) in self.pass_meshes(run.material_override, false) {
+                    let double_sided = self.cpu_materials[material_id].double_sided;
+                    render_pass.set_pipeline(self.pipelines.render.get(run.mirrored, double_sided));
+                    render_pass.set_bind_group(1, &self.materials[material_id].bind_group, &[]);
+                    render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                    render_pass
+                        .set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    render_pass.draw_indexed(0..mesh.num_elements, 0, run.instances.clone());
+                    render_stats.record_draw(mesh.num_elements, run.instances.len() as u32);
+                }
+            }
+
+            if self.show_grid && !self.debug_instance_id_view {
+                self.grid.update(
+                    &self.queue,
+                    GridUniform::new(
+                        self.camera_uniform.view_proj(),
+                        &self.camera,
+                        self.grid_spacing,
+                    ),
+                );
+                render_pass.set_pipeline(&self.pipelines.grid);
+                render_pass.set_bind_group(1, &self.grid.bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+                render_stats.record_draw(3, 1);
+            }
+
+            // Blended over the opaque geometry, from the farthest instance
+            if !self.debug_instance_id_view {
+                render_pass.set_vertex_buffer(1, self.transparent_instance_buffer.slice(..));
+                for (slot, &i) in self.transparent_instances.iter().enumerate() {
+                    let instance = &self.instances[i];
+                    let slot = slot as u32;
+                    for (mesh, material_id) in self.pass_meshes(instance.material_override, true) {
+                        let double_sided = self.cpu_materials[material_id].double_sided;
+                        render_pass.set_pipeline(
+                            self.pipelines
+                                .transparent
+                                .get(instance.is_mirrored(), double_sided),
+                        );
+                        render_pass.set_bind_group(1, &self.materials[material_id].bind_group, &[]);
+                        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                        render_pass.set_index_buffer(
+                            mesh.index_buffer.slice(..),
+                            wgpu::IndexFormat::Uint32,
+                        );
+                        render_pass.draw_indexed(0..mesh.num_elements, 0, slot..slot + 1);
+                        render_stats.record_draw(mesh.num_elements, 1);
+                    }
+                }
+            }
+        }
+        self.render_stats = render_stats;
+
+        self.antialias.resolve(
+            &self.queue,
+            encoder,
+            &self.hdr_target,
+            self.camera_uniform.view_proj(),
+        );
+        if self.antialias.sample_count() == 1 {
+            self.ssao.render(
+                &self.queue,
+                encoder,
+                &self.hdr_target.view,
+                self.camera.build_projection_matrix(),
+            );
+            if self.dof_enabled {
+                self.dof.render(
+                    &self.queue,
+                    encoder,
+                    &self.hdr_target.view,
+                    self.camera.build_projection_matrix(),
+                );
+            }
+        }
+        // Works with MSAA: the velocity pass has its own depth buffer
+        self.motion_blur.render(
+            &self.queue,
+            encoder,
+            &self.camera_bind_group,
+            MotionBlurScene {
+                meshes: &self.meshes,
+                instance_buffer: &self.instance_buffer,
+                instance_count: self.visible_instances.len() as u32,
+            },
+            &self.hdr_target.view,
+        );
+        let output_view = self
+            .gamma_correction
+            .as_ref()
+            .map_or(&self.render_target.view, |gamma| gamma.linear_view());
+        let tonemap_view = self.color_grading.input_view();
+        let tonemapped = self.exposure.as_mut().is_some_and(|exposure| {
+            exposure.render(&self.queue, encoder, &self.hdr_target, tonemap_view)
+        });
+        if !tonemapped {
+            self.tonemapper.render(encoder, tonemap_view);
+        }
+        let graded_view = if self.chromatic_aberration.enabled() {
+            self.chromatic_aberration.input_view()
+        } else {
+            output_view
+        };
+        self.color_grading.render(encoder, graded_view);
+        if self.chromatic_aberration.enabled() {
+            self.chromatic_aberration.render(encoder, output_view);
+        }
+        if self.vignette_enabled {
+            self.vignette.render(encoder, output_view);
+        }
+
+        if self.antialias.sample_count() == 1 {
+            self.outline.render(
+                &self.queue,
+                encoder,
+                output_view,
+                !self.selected_instances.is_empty(),
+                self.camera.build_projection_matrix(),
+            );
+        }
+        if let Some(selection_buffer) = &self.selection_instance_buffer {
+            self.selection_outline.render(
+                &self.queue,
+                encoder,
+                output_view,
+                &self.camera_bind_group,
+                SelectionScene {
+                    meshes: &self.meshes,
+                    instance_buffer: selection_buffer,
+                    instance_count: self.selected_instances.len() as u32,
+                },
+                self.outline_color,
+            );
+        }
+        if let Some(frame) = self.gizmo_frame() {
+            self.gizmo.render(
+                &self.queue,
+                encoder,
+                output_view,
+                self.camera.build_view_projection_matrix(),
+                &frame,
+                self.gizmo_mode,
+                self.gizmo_drag
+                    .as_ref()
+                    .map(|drag| drag.handle)
+                    .or(self.gizmo_hovered),
+            );
+        }
+        if let Some(gamma_correction) = &self.gamma_correction {
+            gamma_correction.render(encoder, &self.render_target.view);
+        }
+
+        // Only one readback at a time, a newer click waits for the current one
+        match self.pick_request {
+            Some((ndc, extend)) if self.pending_pick.is_none() => {
+                self.pick_request = None;
+                let mut camera = CameraUniform::new();
+                camera.update_view_proj(&self.camera);
+                camera.apply_clip_transform(pick_matrix(
+                    ndc,
+                    self.config.width,
+                    self.config.height,
+                ));
+                let buffer = self.picker.encode(
+                    &self.device,
+                    &self.queue,
+                    encoder,
+                    camera,
+                    PickScene {
+                        meshes: &self.meshes,
+                        instance_buffer: &self.instance_buffer,
+                        instance_count: self.visible_instances.len() as u32,
+                    },
+                );
+                Some((buffer, extend))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn test_state_is_send() {
+        assert_send::<State>();
+    }
+
+    #[test]
+    fn test_headless_render_to_image() {
+        let mut state = State::new_headless(64, 48, "triangle.obj").unwrap();
+        let image = state.render_to_image().unwrap();
+        assert_eq!(image.dimensions(), (64, 48));
+        // The triangle stands out of the background
+        let background = image.get_pixel(0, 0);
+        assert!(image.pixels().any(|pixel| pixel != background));
+        assert!(state.render_stats().triangles_rendered >= 1);
+
+        // With the selection outline and the gizmo
+        let id = state.add_instance(Instance::default()).unwrap();
+        state.set_selection([id]);
+        let translate = state.render_to_image().unwrap();
+        assert_ne!(translate, image);
+        state.gizmo_mode = GizmoMode::Rotate;
+        let rotate = state.render_to_image().unwrap();
+        assert_ne!(rotate, translate);
+        state.gizmo_mode = GizmoMode::Scale;
+        assert_ne!(state.render_to_image().unwrap(), rotate);
+
+        // The grid lies under the camera
+        let with_grid = state.render_to_image().unwrap();
+        state.show_grid = false;
+        assert_ne!(state.render_to_image().unwrap(), with_grid);
+    }
+
+    #[test]
+    fn test_undo_redo_instance_commands() {
+        use crate::commands::{AddInstanceCommand, RenameInstanceCommand};
+
+        let mut state = State::new_headless(16, 16, "triangle.obj").unwrap();
+        let names = |state: &State| -> Vec<String> {
+            state.instances().iter().map(|i| i.name.clone()).collect()
+        };
+        for _ in 0..3 {
+            state
+                .execute(Box::new(AddInstanceCommand::new(Instance::default())))
+                .unwrap();
+        }
+        let initial = names(&state);
+        let count = initial.len();
+
+        let selection = HashSet::from([0, count - 2]);
+        state.set_selection(selection.clone());
+        state.delete_selection();
+        let deleted = names(&state);
+        assert_eq!(deleted.len(), count - 2);
+        assert!(state.selection().is_empty());
+        state.undo().unwrap();
+        assert_eq!(names(&state), initial);
+        assert_eq!(state.selection(), &selection);
+        state.redo().unwrap();
+        assert_eq!(names(&state), deleted);
+
+        state
+            .execute(Box::new(RenameInstanceCommand::new(0, "Renommé")))
+            .unwrap();
+        assert!(!state.can_redo());
+        state.undo().unwrap();
+        state.undo().unwrap();
+        assert_eq!(names(&state), initial);
+        state.undo().unwrap();
+        assert_eq!(state.instances().len(), count - 1);
+    }
+
+    #[test]
+    fn test_reload_material_texture() {
+        let mut state = State::new_headless(16, 16, "triangle.obj").unwrap();
+        let image = Path::new("assets/pizzaTxt.png");
+        let (width, height) = image::image_dimensions(image).unwrap();
+
+        state
+            .reload_material_texture(0, TextureSlot::Diffuse, image)
+            .unwrap();
+        let size = state.materials[0].textures.diffuse.texture.size();
+        assert_eq!((size.width, size.height), (width, height));
+        assert_eq!(state.materials[0].texture_path.as_deref(), Some(image));
+
+        assert_eq!(state.materials[0].properties.specular_map, 0);
+        state
+            .reload_material_texture(0, TextureSlot::MetallicRoughness, image)
+            .unwrap();
+        assert_eq!(state.materials[0].properties.specular_map, 1);
+
+        let missing = Path::new("assets/missing.png");
+        assert!(
+            state
+                .reload_material_texture(0, TextureSlot::Normal, missing)
+                .is_err()
+        );
+        let unknown = state.materials.len();
+        assert!(
+            state
+                .reload_material_texture(unknown, TextureSlot::Diffuse, image)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_select_by_material() {
+        use glam::{Quat, Vec3};
+
+        let mut state = State::new_headless(16, 16, "triangle.obj").unwrap();
+        let material = state.meshes[0].material_id;
+        let a = state
+            .add_instance(Instance::new(Vec3::ZERO, Quat::IDENTITY))
+            .unwrap();
+        let mut hidden = Instance::new(Vec3::X, Quat::IDENTITY);
+        hidden.visible = false;
+        state.add_instance(hidden).unwrap();
+        let mut overridden = Instance::new(Vec3::Y, Quat::IDENTITY);
+        // Past the last material, the model's own materials are used
+        overridden.material_override = Some(state.materials.len());
+        let b = state.add_instance(overridden).unwrap();
+
+        state.set_selection([a]);
+        let matching = state.instances_with_material(material);
+        assert_eq!(state.select_by_material(material), matching.len() - 1);
+        assert!(state.selection().contains(&b));
+        assert_eq!(state.select_by_material(material + 1), 0);
+
+        state.undo().unwrap();
+        assert!(state.selection().contains(&b));
+        state.undo().unwrap();
+        assert_eq!(state.selection().len(), 1);
+    }
+
+    #[test]
+    fn test_instance_parenting() {
+        use crate::commands::SetParentCommand;
+        use glam::{Quat, Vec3};
+
+        let mut state = State::new_headless(16, 16, "triangle.obj").unwrap();
+        let parent = state
+            .add_instance(Instance::new(Vec3::ZERO, Quat::IDENTITY))
+            .unwrap();
+        let other = state
+            .add_instance(Instance::new(Vec3::X, Quat::IDENTITY))
+            .unwrap();
+        let child = state
+            .add_instance(Instance::new(Vec3::Y, Quat::IDENTITY))
+            .unwrap();
+        state
+            .execute(Box::new(SetParentCommand::new([child], Some(parent))))
+            .unwrap();
+        state
+            .execute(Box::new(SetParentCommand::new([other], Some(child))))
+            .unwrap();
+        assert_eq!(state.descendants(parent), [child, other]);
+        assert!(state.set_parent(parent, Some(other)).is_err());
+        assert!(state.set_parent(parent, Some(parent)).is_err());
+        assert!(
+            state
+                .execute(Box::new(SetParentCommand::new(
+                    [other, parent],
+                    Some(other)
+                )))
+                .is_err()
+        );
+        assert_eq!(state.instances()[other].parent, Some(child));
+
+        // The last instance, `child`, takes the place of `parent` and becomes a root
+        state
+            .execute(Box::new(DeleteInstancesCommand::new(vec![parent])))
+            .unwrap();
+        assert_eq!(state.instances()[parent].parent, None);
+        assert_eq!(state.instances()[other].parent, Some(parent));
+        state.undo().unwrap();
+        assert_eq!(state.instances()[child].parent, Some(parent));
+        assert_eq!(state.instances()[other].parent, Some(child));
+
+        state.undo().unwrap();
+        state.undo().unwrap();
+        assert!(state.descendants(parent).is_empty());
+    }
+
+    #[test]
+    fn test_edit_instances_command() {
+        use crate::commands::EditInstancesCommand;
+        use glam::{Quat, Vec3};
+
+        let mut state = State::new_headless(16, 16, "triangle.obj").unwrap();
+        let a = state
+            .add_instance(Instance::new(Vec3::ZERO, Quat::IDENTITY))
+            .unwrap();
+        let b = state
+            .add_instance(Instance::new(Vec3::X, Quat::IDENTITY))
+            .unwrap();
+        let edits = [a, b].map(|id| {
+            let mut instance = state.instances()[id].clone();
+            instance.name = "Tour".to_string();
+            instance.position.y = 2.0;
+            (id, instance)
+        });
+        state
+            .execute(Box::new(EditInstancesCommand::new(edits.to_vec())))
+            .unwrap();
+        assert_eq!(state.instances()[b].name, "Tour");
+        assert_eq!(state.instances()[b].position, Vec3::new(1.0, 2.0, 0.0));
+
+        state.undo().unwrap();
+        assert_eq!(state.instances()[a].position, Vec3::ZERO);
+        assert_ne!(state.instances()[a].name, "Tour");
+        state.redo().unwrap();
+        assert_eq!(state.instances()[a], edits[0].1);
+        assert!(state.replace_instance(b + 1, edits[0].1.clone()).is_none());
+    }
+
+    #[test]
+    fn test_locked_instances_are_not_box_selected() {
+        use glam::{Quat, Vec3};
+
+        let mut state = State::new_headless(16, 16, "triangle.obj").unwrap();
+        let mut locked = Instance::new(Vec3::ZERO, Quat::IDENTITY);
+        locked.locked = true;
+        let id = state.add_instance(locked).unwrap();
+        let unlocked = state
+            .add_instance(Instance::new(Vec3::ZERO, Quat::IDENTITY))
+            .unwrap();
+        let viewport = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(16.0, 16.0));
+        state.perform_box_selection(viewport, viewport, false);
+        assert!(!state.selection().contains(&id));
+        assert!(state.selection().contains(&unlocked));
+        state.set_selection([id]);
+        assert!(state.selection().contains(&id));
+    }
+
+    #[test]
+    fn test_selection_commands() {
+        use glam::{Quat, Vec3};
+
+        let mut state = State::new_headless(16, 16, "triangle.obj").unwrap();
+        let a = state
+            .add_instance(Instance::new(Vec3::ZERO, Quat::IDENTITY))
+            .unwrap();
+        let mut hidden = Instance::new(Vec3::X, Quat::IDENTITY);
+        hidden.visible = false;
+        let hidden = state.add_instance(hidden).unwrap();
+        state.set_selection([a]);
+        let visible = state.instances().iter().filter(|i| i.visible).count();
+
+        state.select_all();
+        assert_eq!(state.selection().len(), visible);
+        assert!(!state.selection().contains(&hidden));
+        state.deselect_all();
+        assert!(state.selection().is_empty());
+        // Nothing to clear, nothing recorded
+        state.deselect_all();
+
+        state.undo().unwrap();
+        assert_eq!(state.selection().len(), visible);
+        state.undo().unwrap();
+        assert_eq!(state.selection().iter().collect::<Vec<_>>(), [&a]);
+        state.redo().unwrap();
+        assert_eq!(state.selection().len(), visible);
+
+        state.set_selection([a]);
+        state.invert_selection();
+        assert_eq!(state.selection().len(), visible - 1);
+        assert!(!state.selection().contains(&a) && !state.selection().contains(&hidden));
+        state.undo().unwrap();
+        assert_eq!(state.selection().iter().collect::<Vec<_>>(), [&a]);
+    }
+
+    #[test]
+    fn test_lasso_selection() {
+        use glam::{Quat, Vec3};
+
+        let mut state = State::new_headless(64, 64, "triangle.obj").unwrap();
+        state.camera = Camera::looking_at(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO);
+        let a = state
+            .add_instance(Instance::new(Vec3::new(-1.0, 0.0, 0.0), Quat::IDENTITY))
+            .unwrap();
+        let b = state
+            .add_instance(Instance::new(Vec3::new(1.0, 0.0, 0.0), Quat::IDENTITY))
+            .unwrap();
+
+        // The left half of the viewport
+        let viewport = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(64.0, 64.0));
+        let left =
+            [(0.0, 0.0), (32.0, 0.0), (32.0, 64.0), (0.0, 64.0)].map(|(x, y)| egui::pos2(x, y));
+        state.perform_lasso_selection(&left, viewport, false);
+        assert!(state.selection().contains(&a));
+        assert!(!state.selection().contains(&b));
+
+        let right = left.map(|p| p + egui::vec2(32.0, 0.0));
+        state.perform_lasso_selection(&right, viewport, true);
+        assert!(state.selection().contains(&a) && state.selection().contains(&b));
+        state.perform_lasso_selection(&right, viewport, false);
+        assert!(!state.selection().contains(&a));
+    }
+
+    #[test]
+    fn test_rotate_gizmo_drag() {
+        use glam::{Quat, Vec3};
+
+        let mut state = State::new_headless(64, 64, "triangle.obj").unwrap();
+        state.camera = Camera::looking_at(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO);
+        let a = state
+            .add_instance(Instance::new(Vec3::new(-1.0, 0.0, 0.0), Quat::IDENTITY))
+            .unwrap();
+        let b = state
+            .add_instance(Instance::new(Vec3::new(1.0, 0.0, 0.0), Quat::IDENTITY))
+            .unwrap();
+        state.set_selection([a, b]);
+        state.gizmo_mode = GizmoMode::Rotate;
+
+        // Along the Z ring, which faces the camera, a quarter turn at a time
+        let frame = state.gizmo_frame().unwrap();
+        let view_proj = state.camera.build_view_projection_matrix();
+        let on_ring = |angle: f32| {
+            let point = Quat::from_rotation_z(angle) * Vec3::X * frame.scale;
+            Some(view_proj.project_point3(point).truncate())
+        };
+        let viewport = glam::Vec2::splat(64.0);
+        let none = egui::Modifiers::NONE;
+        assert!(state.update_gizmo(on_ring(0.0), viewport, true, false, none));
+        for step in 1..=3 {
+            let angle = step as f32 * std::f32::consts::FRAC_PI_2;
+            state.update_gizmo(on_ring(angle), viewport, false, false, none);
+        }
+        state.update_gizmo(
+            on_ring(std::f32::consts::PI * 1.5),
+            viewport,
+            false,
+            true,
+            none,
+        );
+
+        // Three quarters of a turn around the center of the selection
+        let expected = Quat::from_rotation_z(std::f32::consts::PI * 1.5);
+        let instance = &state.instances()[a];
+        assert!(instance.rotation.angle_between(expected) < 1e-3);
+        assert!(instance.position.distance(Vec3::new(0.0, 1.0, 0.0)) < 1e-3);
+
+        // Undone at once
+        state.undo().unwrap();
+        assert_eq!(state.instances()[a].rotation, Quat::IDENTITY);
+        assert_eq!(state.instances()[b].position, Vec3::new(1.0, 0.0, 0.0));
+
+        // 50° snapped to 45°
+        state.snap_enabled = true;
+        state.snap_rotation_degrees = 45.0;
+        state.update_gizmo(on_ring(0.0), viewport, true, false, none);
+        state.update_gizmo(on_ring(50f32.to_radians()), viewport, false, true, none);
+        let expected = Quat::from_rotation_z(45f32.to_radians());
+        assert!(state.instances()[a].rotation.angle_between(expected) < 1e-3);
+    }
+
+    #[test]
+    fn test_translate_gizmo_snaps() {
+        use glam::{Quat, Vec3};
+
+        let mut state = State::new_headless(64, 64, "triangle.obj").unwrap();
+        state.camera = Camera::looking_at(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO);
+        let start = Vec3::new(0.1, 0.2, 0.0);
+        let id = state
+            .add_instance(Instance::new(start, Quat::IDENTITY))
+            .unwrap();
+        state.set_selection([id]);
+        state.snap_enabled = true;
+        state.snap_grid_size = 0.5;
+
+        let frame = state.gizmo_frame().unwrap();
+        let view_proj = state.camera.build_view_projection_matrix();
+        let at = |point: Vec3| Some(view_proj.project_point3(point).truncate());
+        let viewport = glam::Vec2::splat(64.0);
+        let drag = |state: &mut State, distance: f32, modifiers| {
+            let grab = start + Vec3::X * frame.scale * 0.5;
+            assert!(state.update_gizmo(at(grab), viewport, true, false, modifiers));
+            let to = grab + Vec3::X * distance;
+            state.update_gizmo(at(to), viewport, false, true, modifiers);
+        };
+
+        drag(&mut state, 0.7, egui::Modifiers::NONE);
+        let position = state.instances()[id].position;
+        assert!(position.distance(Vec3::new(1.0, 0.0, 0.0)) < 1e-5);
+        // Back before the snap
+        state.undo().unwrap();
+        assert_eq!(state.instances()[id].position, start);
+
+        // Ctrl drags freely
+        drag(&mut state, 0.7, egui::Modifiers::CTRL);
+        let position = state.instances()[id].position;
+        assert!(position.distance(Vec3::new(0.8, 0.2, 0.0)) < 1e-3);
+    }
+
+    #[test]
+    fn test_camera_bookmarks() {
+        use glam::Vec3;
+
+        let mut state = State::new_headless(16, 16, "triangle.obj").unwrap();
+        state.camera = Camera::looking_at(Vec3::new(0.0, 2.0, 5.0), Vec3::ZERO);
+        // Past the end, stored after the last one
+        assert_eq!(state.save_camera_bookmark(4), 0);
+        state.camera_bookmarks[0].name = "Face".to_string();
+
+        state.camera = Camera::looking_at(Vec3::new(5.0, 0.0, 0.0), Vec3::X);
+        assert_eq!(state.save_camera_bookmark(0), 0);
+        assert_eq!(state.camera_bookmarks.len(), 1);
+        assert_eq!(state.camera_bookmarks[0].name, "Face");
+        assert_eq!(state.camera_bookmarks[0].eye, Vec3::new(5.0, 0.0, 0.0));
+
+        state.camera = Camera::looking_at(Vec3::new(0.0, 0.0, 9.0), Vec3::ZERO);
+        assert!(!state.restore_camera_bookmark(1));
+        assert!(state.restore_camera_bookmark(0));
+        std::thread::sleep(crate::camera::CAMERA_TRANSITION_DURATION);
+        state.input_handler.update_camera(&mut state.camera);
+        assert!(state.camera.eye.distance(Vec3::new(5.0, 0.0, 0.0)) < 1e-4);
+        assert!(state.camera.target.distance(Vec3::X) < 1e-4);
+        assert!(!state.input_handler.camera_controller.is_transitioning());
+    }
+
+    #[test]
+    fn test_play_camera_path() {
+        use glam::Vec3;
+        use std::time::Duration;
+
+        let mut state = State::new_headless(16, 16, "triangle.obj").unwrap();
+        state.camera = Camera::looking_at(Vec3::new(0.0, 1.0, 5.0), Vec3::ZERO);
+        state.add_camera_waypoint();
+        state.camera = Camera::looking_at(Vec3::new(5.0, 1.0, 0.0), Vec3::Y);
+        state.add_camera_waypoint();
+        assert_eq!(state.camera_path.end_time(), CAMERA_WAYPOINT_INTERVAL);
+
+        let path = state.camera_path.clone();
+        state.play_camera_path(&path);
+        assert!(state.is_playing_camera_path());
+        state.update();
+        assert!(state.camera.eye.distance(Vec3::new(0.0, 1.0, 5.0)) < 0.1);
+
+        // Past the end, the camera stays on the last waypoint
+        state.camera_path_playback.as_mut().unwrap().start -= Duration::from_secs(10);
+        state.update();
+        assert!(!state.is_playing_camera_path());
+        assert_eq!(state.camera.eye, Vec3::new(5.0, 1.0, 0.0));
+        assert_eq!(state.camera.target, Vec3::Y);
+        state.update();
+        assert!(state.camera.eye.distance(Vec3::new(5.0, 1.0, 0.0)) < 1e-4);
+    }
+
+    #[test]
+    fn test_gizmo_frame_follows_space() {
+        use crate::gizmo::GizmoAxis;
+        use glam::{Quat, Vec3};
+
+        let mut state = State::new_headless(16, 16, "triangle.obj").unwrap();
+        let rotation = Quat::from_rotation_y(0.7);
+        let id = state
+            .add_instance(Instance::new(Vec3::new(2.0, 0.0, 0.0), rotation))
+            .unwrap();
+        state.set_selection([id]);
+
+        let world = state.gizmo_frame().unwrap();
+        assert_eq!(world.rotation, Quat::IDENTITY);
+        assert_eq!(world.direction(GizmoAxis::X), Vec3::X);
+
+        state.gizmo_space = state.gizmo_space.toggled();
+        let local = state.gizmo_frame().unwrap();
+        assert_eq!(local.center, world.center);
+        assert!(local.direction(GizmoAxis::X).distance(rotation * Vec3::X) < 1e-6);
+    }
+
+    #[test]
+    fn test_scale_gizmo_drag() {
+        use glam::{Quat, Vec3};
+
+        let mut state = State::new_headless(64, 64, "triangle.obj").unwrap();
+        state.camera = Camera::looking_at(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO);
+        let a = state
+            .add_instance(Instance::new(Vec3::new(-1.0, 1.0, 0.0), Quat::IDENTITY))
+            .unwrap();
+        let b = state
+            .add_instance(Instance::new(Vec3::new(1.0, -1.0, 0.0), Quat::IDENTITY))
+            .unwrap();
+        state.set_selection([a, b]);
+        state.gizmo_mode = GizmoMode::Scale;
+
+        let frame = state.gizmo_frame().unwrap();
+        let view_proj = state.camera.build_view_projection_matrix();
+        let at = |point: Vec3| Some(view_proj.project_point3(point).truncate());
+        let viewport = glam::Vec2::splat(64.0);
+        let drag_x_handle = |state: &mut State, uniform| {
+            let handle = Vec3::X * frame.scale;
+            let modifiers = if uniform {
+                egui::Modifiers::SHIFT
+            } else {
+                egui::Modifiers::NONE
+            };
+            assert!(state.update_gizmo(at(handle), viewport, true, false, modifiers));
+            state.update_gizmo(at(handle * 2.0), viewport, false, true, modifiers);
+        };
+
+        // One gizmo length further doubles the selection along X
+        drag_x_handle(&mut state, false);
+        let instance = &state.instances()[a];
+        assert!(instance.scale.distance(Vec3::new(2.0, 1.0, 1.0)) < 1e-3);
+        assert!(instance.position.distance(Vec3::new(-2.0, 1.0, 0.0)) < 1e-3);
+        state.undo().unwrap();
+        assert_eq!(state.instances()[a].scale, Vec3::ONE);
+        assert_eq!(state.instances()[b].position, Vec3::new(1.0, -1.0, 0.0));
+
+        // Evenly with Shift held
+        drag_x_handle(&mut state, true);
+        let instance = &state.instances()[b];
+        assert!(instance.scale.distance(Vec3::splat(2.0)) < 1e-3);
+        assert!(instance.position.distance(Vec3::new(2.0, -2.0, 0.0)) < 1e-3);
+    }
+
+    #[test]
+    fn test_gpu_memory_follows_resize() {
+        let mut state = State::new_headless(64, 48, "triangle.obj").unwrap();
+        let initial = state.gpu_memory();
+        assert!(initial.vertex_bytes > 0 && initial.index_bytes > 0);
+
+        state.resize(PhysicalSize::new(128, 96));
+        assert!(state.gpu_memory().texture_bytes > initial.texture_bytes);
+        // The previous targets are no longer counted
+        state.resize(PhysicalSize::new(64, 48));
+        assert_eq!(state.gpu_memory(), initial);
+    }
+
+    #[test]
+    fn test_instance_id_view_follows_scene_index() {
+        let mut state = State::new_headless(64, 48, "triangle.obj").unwrap();
+        state.debug_instance_id_view = true;
+        // A single instance in front of the camera, always first in the culled buffer
+        let mut render_visible = |visible: usize| {
+            state.instances = (0..10)
+                .map(|i| {
+                    let z = if i == visible { 0.0 } else { 1000.0 };
+                    Instance::new(glam::Vec3::new(0.0, 0.0, z), glam::Quat::IDENTITY)
+                })
+                .collect();
+            state.cull_instances();
+            assert_eq!(state.visible_instances, [visible]);
+            state.render_to_image().unwrap()
+        };
+        assert_ne!(render_visible(5), render_visible(9));
+    }
+
+    #[test]
+    fn test_load_scene_archive_uses_bundled_assets() {
+        let dir = std::env::temp_dir().join(format!("orengine-bundle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Only in the archive, not in the assets directory
+        std::fs::write(
+            dir.join("quad.obj"),
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf 1 3 4\n",
+        )
+        .unwrap();
+        let archive = dir.join("scene.esc");
+
+        let mut state = State::new_headless(64, 48, "triangle.obj").unwrap();
+        let mut scene = state.scene_data();
+        scene.model = "quad.obj".to_string();
+        let json = scene.to_json().unwrap();
+        crate::scene_archive::write_scene_archive(&archive, &json, &dir, &["quad.obj".into()])
+            .unwrap();
+
+        state.load_scene(&archive).unwrap();
+        assert_eq!(state.model_path, "quad.obj");
+        assert_eq!(state.cpu_meshes[0].indices.len(), 6);
+        assert!(state.render_to_image().is_ok());
+
+        // Saving again bundles the loaded model, not a local one
+        let resaved = dir.join("resaved.esc");
+        state.save_scene(&resaved).unwrap();
+        let extracted = crate::scene_archive::extract_scene_archive(&resaved).unwrap();
+        assert!(extracted.assets_dir().join("quad.obj").is_file());
+
+        drop((state, extracted));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_scene_archive_without_its_model() {
+        let archive =
+            std::env::temp_dir().join(format!("orengine-unbundled-{}.esc", std::process::id()));
+        let mut state = State::new_headless(64, 48, "triangle.obj").unwrap();
+        let json = state.scene_data().to_json().unwrap();
+        // triangle.obj is in the assets directory, but not in the archive
+        crate::scene_archive::write_scene_archive(&archive, &json, Path::new(ASSETS_DIR), &[])
+            .unwrap();
+
+        let err = state.load_scene(&archive).unwrap_err();
+        assert!(err.to_string().contains("does not bundle triangle.obj"));
+        assert!(state.scene_assets.is_none());
+
+        std::fs::remove_file(archive).unwrap();
+    }
+
+    #[test]
+    fn test_load_scene_archive_over_instance_limit() {
+        let archive =
+            std::env::temp_dir().join(format!("orengine-too-many-{}.esc", std::process::id()));
+        let mut state = State::new_headless(64, 48, "triangle.obj").unwrap();
+        let mut scene = state.scene_data();
+        scene.model = "pizza.obj".to_string();
+        scene.instances = vec![scene.instances[0].clone(); crate::instance::MAX_INSTANCES + 1];
+        let assets =
+            crate::scene_archive::model_asset_files(Path::new(ASSETS_DIR), "pizza.obj").unwrap();
+        crate::scene_archive::write_scene_archive(
+            &archive,
+            &scene.to_json().unwrap(),
+            Path::new(ASSETS_DIR),
+            &assets,
+        )
+        .unwrap();
+
+        let err = state.load_scene(&archive).unwrap_err();
+        assert!(matches!(err, OrengineError::InstanceLimitExceeded { .. }));
+        // The archive's model is not swapped in for the current instances
+        assert_eq!(state.model_path, "triangle.obj");
+        assert!(state.scene_assets.is_none());
+
+        std::fs::remove_file(archive).unwrap();
+    }
+
+    #[test]
+    fn test_load_scene_restores_material_properties() {
+        let path =
+            std::env::temp_dir().join(format!("orengine-materials-{}.json", std::process::id()));
+        let mut state = State::new_headless(16, 16, "triangle.obj").unwrap();
+        let edited = MaterialPropertiesUniform {
+            roughness: 0.1,
+            metallic: 1.0,
+            emissive: [0.0, 0.5, 1.0],
+            diffuse_color: [1.0, 0.0, 0.0],
+            ..Default::default()
+        };
+        state.update_material_properties(0, edited);
+        state.save_scene(&path).unwrap();
+
+        state.update_material_properties(0, MaterialPropertiesUniform::default());
+        state.load_scene(&path).unwrap();
+        assert_eq!(state.materials[0].properties, edited);
+
+        std::fs::remove_file(path).unwrap();
+    }
 }
@@ -0,0 +1,254 @@
+// Pipelines drawing into the 3D scene pass. They depend on its sample count, so they are
+// rebuilt whenever the anti-aliasing mode switches between MSAA levels.
+
+use crate::{instance::InstanceRaw, vertex::Vertex};
+
+/// The lit scene pipeline for each winding and culling, only four variants so that
+/// materials never need a pipeline of their own.
+pub(super) struct SurfacePipelines {
+    pub front: wgpu::RenderPipeline,
+    /// Clockwise front faces, for mirrored instances
+    pub mirrored: wgpu::RenderPipeline,
+    /// No culling, for `Material::double_sided`. The winding still tells the shader which
+    /// side is the front one.
+    pub double_sided: wgpu::RenderPipeline,
+    pub double_sided_mirrored: wgpu::RenderPipeline,
+}
+
+impl SurfacePipelines {
+    pub fn get(&self, mirrored: bool, double_sided: bool) -> &wgpu::RenderPipeline {
+        match (mirrored, double_sided) {
+            (false, false) => &self.front,
+            (true, false) => &self.mirrored,
+            (false, true) => &self.double_sided,
+            (true, true) => &self.double_sided_mirrored,
+        }
+    }
+}
+
+pub(super) struct ScenePipelines {
+    pub render: SurfacePipelines,
+    /// `render` blended over the opaque geometry, without depth writes
+    pub transparent: SurfacePipelines,
+    pub instance_id: wgpu::RenderPipeline,
+    /// `instance_id` with clockwise front faces, for mirrored instances
+    pub instance_id_mirrored: wgpu::RenderPipeline,
+    /// Cube map background, drawn first on the far plane
+    pub skybox: wgpu::RenderPipeline,
+    /// Reference grid, blended over the opaque geometry
+    pub grid: wgpu::RenderPipeline,
+}
+
+impl ScenePipelines {
+    pub fn instance_id(&self, mirrored: bool) -> &wgpu::RenderPipeline {
+        if mirrored {
+            &self.instance_id_mirrored
+        } else {
+            &self.instance_id
+        }
+    }
+}
+
+/// Everything the scene pipelines are built from, except the sample count.
+pub(super) struct ScenePipelineBuilder {
+    pub shader: wgpu::ShaderModule,
+    /// Camera, material and light bind groups
+    pub render_layout: wgpu::PipelineLayout,
+    /// Camera bind group only, for the instance ID view
+    pub overlay_layout: wgpu::PipelineLayout,
+    pub skybox_shader: wgpu::ShaderModule,
+    /// Camera and cube map bind groups
+    pub skybox_layout: wgpu::PipelineLayout,
+    pub grid_shader: wgpu::ShaderModule,
+    /// Camera and grid bind groups
+    pub grid_layout: wgpu::PipelineLayout,
+    pub color_format: wgpu::TextureFormat,
+    pub depth_format: wgpu::TextureFormat,
+}
+
+impl ScenePipelineBuilder {
+    pub fn build(&self, device: &wgpu::Device, sample_count: u32) -> ScenePipelines {
+        let multisample = wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        };
+        let primitive = wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        };
+        let depth_stencil = wgpu::DepthStencilState {
+            format: self.depth_format,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        };
+
+        let mirrored_primitive = wgpu::PrimitiveState {
+            front_face: wgpu::FrontFace::Cw,
+            ..primitive
+        };
+
+        // Transparent surfaces are sorted per instance, they must not hide what is drawn
+        // after them
+        let transparent_depth_stencil = wgpu::DepthStencilState {
+            depth_write_enabled: false,
+            ..depth_stencil.clone()
+        };
+
+        let pipeline = |label: &str, primitive, blend, depth_stencil: &wgpu::DepthStencilState| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&self.render_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.shader,
+                    entry_point: "vs_main",
+                    buffers: &[Vertex::desc(), InstanceRaw::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: self.color_format,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive,
+                depth_stencil: Some(depth_stencil.clone()),
+                multisample,
+                multiview: None,
+            })
+        };
+
+        let instance_id = |label, primitive| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&self.overlay_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.shader,
+                    entry_point: "vs_instance_id",
+                    buffers: &[Vertex::desc(), InstanceRaw::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.shader,
+                    entry_point: "fs_instance_id",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: self.color_format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive,
+                depth_stencil: Some(depth_stencil.clone()),
+                multisample,
+                multiview: None,
+            })
+        };
+
+        let skybox = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Skybox Pipeline"),
+            layout: Some(&self.skybox_layout),
+            vertex: wgpu::VertexState {
+                module: &self.skybox_shader,
+                entry_point: "vs_skybox",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.skybox_shader,
+                entry_point: "fs_skybox",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: self.color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                ..primitive
+            },
+            // On the far plane, which the cleared depth buffer is at too
+            depth_stencil: Some(wgpu::DepthStencilState {
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                ..depth_stencil.clone()
+            }),
+            multisample,
+            multiview: None,
+        });
+
+        let grid = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Grid Pipeline"),
+            layout: Some(&self.grid_layout),
+            vertex: wgpu::VertexState {
+                module: &self.grid_shader,
+                entry_point: "vs_grid",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.grid_shader,
+                entry_point: "fs_grid",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: self.color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                ..primitive
+            },
+            // Tested against the geometry with the depth of the plane, like the
+            // transparent surfaces
+            depth_stencil: Some(transparent_depth_stencil.clone()),
+            multisample,
+            multiview: None,
+        });
+
+        let surfaces = |label: &str, blend, depth_stencil: &wgpu::DepthStencilState| {
+            let double_sided = |primitive: wgpu::PrimitiveState| wgpu::PrimitiveState {
+                cull_mode: None,
+                ..primitive
+            };
+            SurfacePipelines {
+                front: pipeline(label, primitive, blend, depth_stencil),
+                mirrored: pipeline(
+                    &format!("Mirrored {label}"),
+                    mirrored_primitive,
+                    blend,
+                    depth_stencil,
+                ),
+                double_sided: pipeline(
+                    &format!("Double-Sided {label}"),
+                    double_sided(primitive),
+                    blend,
+                    depth_stencil,
+                ),
+                double_sided_mirrored: pipeline(
+                    &format!("Mirrored Double-Sided {label}"),
+                    double_sided(mirrored_primitive),
+                    blend,
+                    depth_stencil,
+                ),
+            }
+        };
+
+        ScenePipelines {
+            render: surfaces("Render Pipeline", wgpu::BlendState::REPLACE, &depth_stencil),
+            transparent: surfaces(
+                "Transparent Render Pipeline",
+                wgpu::BlendState::ALPHA_BLENDING,
+                &transparent_depth_stencil,
+            ),
+            instance_id: instance_id("Instance ID Pipeline", primitive),
+            instance_id_mirrored: instance_id("Mirrored Instance ID Pipeline", mirrored_primitive),
+            skybox,
+            grid,
+        }
+    }
+}
@@ -0,0 +1,297 @@
+// Saving and loading the editable part of the scene, see scene.rs for the format
+
+use super::{
+    ASSETS_DIR, State, create_file_watcher, create_instance_buffer, create_materials, is_gltf_path,
+    load_model_file, model_bounds, partition_meshes, upload_meshes,
+};
+use crate::{
+    bvh::Bvh,
+    camera_path::CameraPath,
+    console::LogLevel,
+    engine_log,
+    error::{OrengineError, Result},
+    instance::{Instance, check_instance_limit},
+    light::LightArray,
+    scene::{SceneData, SceneLights, SerializedLight, SerializedMaterial},
+    scene_archive::{
+        ExtractedScene, extract_scene_archive, is_scene_archive, model_asset_files,
+        write_scene_archive,
+    },
+};
+use std::path::{Path, PathBuf};
+
+/// Checks that `archive`, extracted to `assets_dir`, bundles `model` and every file it
+/// needs. Loading without one would not give the scene that was saved.
+fn check_bundled_assets(archive: &Path, assets_dir: &Path, model: &str) -> Result<()> {
+    let missing =
+        |file: &str| OrengineError::Generic(format!("{archive:?} does not bundle {file}"));
+    if !assets_dir.join(model).is_file() {
+        return Err(missing(model));
+    }
+    // glTF files carry their textures, OBJ ones list them in their .mtl
+    if is_gltf_path(model) {
+        return Ok(());
+    }
+    for file in model_asset_files(assets_dir, model)? {
+        if !assets_dir.join(&file).is_file() {
+            return Err(missing(&file));
+        }
+    }
+    Ok(())
+}
+
+/// The instances of `scene`, as long as there are not more than the engine can draw.
+fn scene_instances(scene: &SceneData) -> Result<Vec<Instance>> {
+    let instances: Vec<Instance> = scene.instances.iter().cloned().map(Into::into).collect();
+    check_instance_limit(instances.len())?;
+    Ok(instances)
+}
+
+impl State {
+    /// Snapshot of everything `save_scene` writes.
+    pub fn scene_data(&self) -> SceneData {
+        let lights = self
+            .lights
+            .lights()
+            .iter()
+            .map(SerializedLight::from)
+            .chain(std::iter::once((&self.directional_light).into()))
+            .chain(self.spot_light.as_ref().map(SerializedLight::from))
+            .collect();
+
+        SceneData {
+            model: self.model_path.clone(),
+            instances: self.instances.iter().map(Into::into).collect(),
+            lights,
+            camera: (&self.camera).into(),
+            fog: Some(self.fog_uniform.into()),
+            materials: self
+                .cpu_materials
+                .iter()
+                .zip(&self.materials)
+                .map(|(material, render)| SerializedMaterial::new(material, &render.properties))
+                .collect(),
+            camera_path: self.camera_path.waypoints.iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Writes the scene as JSON, or as a .esc archive bundling the model and its textures
+    /// when `path` has that extension.
+    pub fn save_scene(&self, path: &Path) -> Result<()> {
+        let json = self.scene_data().to_json()?;
+        if !is_scene_archive(path) {
+            std::fs::write(path, json)?;
+            return Ok(());
+        }
+
+        let assets_dir = self.assets_dir();
+        // glTF files carry their textures, OBJ ones list them in their .mtl
+        let assets = if is_gltf_path(&self.model_path) {
+            vec![self.model_path.clone()]
+        } else {
+            model_asset_files(&assets_dir, &self.model_path)?
+        };
+        write_scene_archive(path, &json, &assets_dir, &assets)
+    }
+
+    /// Where `model_path` and its textures were read from.
+    fn assets_dir(&self) -> PathBuf {
+        self.scene_assets
+            .as_ref()
+            .map_or_else(|| PathBuf::from(ASSETS_DIR), ExtractedScene::assets_dir)
+    }
+
+    /// Writes the scene in RON, never bundling the assets.
+    pub fn save_scene_ron(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.scene_data().to_ron()?)?;
+        Ok(())
+    }
+
+    /// Restores a scene saved by `save_scene_ron`, like `load_scene`.
+    pub fn load_scene_ron(&mut self, path: &Path) -> Result<()> {
+        let scene = SceneData::from_ron(&std::fs::read_to_string(path)?)?;
+        let instances = scene_instances(&scene)?;
+        self.check_scene_model(path, &scene);
+        self.apply_scene(scene, instances);
+        Ok(())
+    }
+
+    /// Restores a scene saved by `save_scene`. A .esc archive brings its own model and
+    /// textures, which replace the current ones. A JSON scene made for another model is
+    /// loaded onto the current one, with a warning. Nothing changes when loading fails.
+    pub fn load_scene(&mut self, path: &Path) -> Result<()> {
+        if !is_scene_archive(path) {
+            let scene = SceneData::from_json(&std::fs::read_to_string(path)?)?;
+            let instances = scene_instances(&scene)?;
+            self.check_scene_model(path, &scene);
+            self.apply_scene(scene, instances);
+            return Ok(());
+        }
+
+        let extracted = extract_scene_archive(path)?;
+        let scene = SceneData::from_json(&extracted.scene_json)?;
+        let instances = scene_instances(&scene)?;
+        check_bundled_assets(path, &extracted.assets_dir(), &scene.model)?;
+        self.load_bundled_model(extracted, &scene.model)?;
+        self.apply_scene(scene, instances);
+        Ok(())
+    }
+
+    /// Replaces the model, its meshes and its materials by `model_path` from `extracted`,
+    /// which is kept until the next archive is loaded.
+    fn load_bundled_model(&mut self, extracted: ExtractedScene, model_path: &str) -> Result<()> {
+        let assets_dir = extracted.assets_dir();
+        let model = load_model_file(&assets_dir, model_path)?;
+        let meshes = upload_meshes(&self.device, &model.meshes, None);
+        let materials = create_materials(
+            &self.device,
+            &self.queue,
+            &self.texture_bind_group_layout,
+            &model.materials,
+            &assets_dir,
+            &mut self.console,
+        );
+
+        for mesh in &self.meshes {
+            self.gpu_memory.untrack_buffer(&mesh.vertex_buffer);
+            self.gpu_memory.untrack_buffer(&mesh.index_buffer);
+        }
+        for material in &self.materials {
+            for texture in material.textures.all() {
+                self.gpu_memory.untrack_texture(&texture.texture);
+            }
+            self.gpu_memory.untrack_buffer(&material.properties_buffer);
+        }
+        for mesh in &meshes {
+            self.gpu_memory.track_buffer(&mesh.vertex_buffer);
+            self.gpu_memory.track_buffer(&mesh.index_buffer);
+        }
+        for material in &materials {
+            for texture in material.textures.all() {
+                self.gpu_memory.track_texture(&texture.texture);
+            }
+            self.gpu_memory.track_buffer(&material.properties_buffer);
+        }
+
+        (self.opaque_meshes, self.transparent_meshes) =
+            partition_meshes(&model.meshes, &model.materials);
+        self.model_aabb = model_bounds(&model.meshes);
+        self.bvh = Bvh::from_meshes(&model.meshes);
+        self.meshes = meshes;
+        self.materials = materials;
+        self.cpu_meshes = model.meshes;
+        self.cpu_materials = model.materials;
+        self.file_watcher = create_file_watcher(&self.materials, &mut self.console);
+        self.model_path = model_path.to_string();
+        // Drops the previous archive's files, now that nothing reads them
+        self.scene_assets = Some(extracted);
+        Ok(())
+    }
+
+    fn check_scene_model(&mut self, path: &Path, scene: &SceneData) {
+        if scene.model != self.model_path {
+            engine_log!(
+                self,
+                LogLevel::Warn,
+                "{:?} was saved with {}, applying it to {}",
+                path,
+                scene.model,
+                self.model_path
+            );
+        }
+    }
+
+    /// Replaces the instances by `instances`, from `scene_instances`, and restores the
+    /// rest of `scene`.
+    fn apply_scene(&mut self, scene: SceneData, instances: Vec<Instance>) {
+        if instances.len() > self.engine_config.instance_capacity {
+            self.engine_config.instance_capacity = instances.len();
+            self.gpu_memory.untrack_buffer(&self.instance_buffer);
+            self.gpu_memory
+                .untrack_buffer(&self.transparent_instance_buffer);
+            self.instance_buffer = create_instance_buffer(&self.device, instances.len());
+            self.transparent_instance_buffer =
+                create_instance_buffer(&self.device, instances.len());
+            self.gpu_memory.track_buffer(&self.instance_buffer);
+            self.gpu_memory
+                .track_buffer(&self.transparent_instance_buffer);
+        }
+        self.instances = instances;
+        self.selected_instances.clear();
+        self.hovered_instance = None;
+        self.pending_pick = None;
+        self.renaming_instance = None;
+        self.collapsed_instances.clear();
+        self.hierarchy_anchor = None;
+        self.inspector_edit_start = None;
+        // The recorded commands address the previous instances
+        self.commands.clear();
+        self.update_selection_buffer();
+
+        let lights = SceneLights::from_serialized(&scene.lights);
+        self.lights = LightArray::new();
+        for light in lights.points {
+            if let Err(e) = self.lights.push(light) {
+                engine_log!(self, LogLevel::Error, "{}", e);
+                break;
+            }
+        }
+        if let Some(directional) = lights.directional {
+            self.directional_light = directional;
+        }
+        self.spot_light = lights.spot;
+
+        self.camera_path_playback = None;
+        self.camera_path = CameraPath::from_serialized(&scene.camera_path);
+        scene.camera.apply(&mut self.camera);
+        self.inactive_projection = None;
+        self.input_handler.sync_with_camera(&self.camera);
+
+        self.set_fog(scene.fog.map(Into::into).unwrap_or_default());
+
+        for id in 0..self.cpu_materials.len() {
+            let material = &mut self.cpu_materials[id];
+            let Some(saved) = scene.materials.iter().find(|m| m.name == material.name) else {
+                continue;
+            };
+            material.display_name = saved.display_name().to_string();
+            if let Some(properties) = saved.properties
+                && let Some(render) = self.materials.get(id)
+            {
+                self.update_material_properties(id, properties.apply(render.properties));
+            }
+        }
+
+        self.cull_instances();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_bundled_assets() {
+        let archive =
+            std::env::temp_dir().join(format!("orengine-bundled-{}.esc", std::process::id()));
+        let assets_dir = Path::new(ASSETS_DIR);
+        // The model and its .mtl, without the texture the .mtl lists
+        write_scene_archive(
+            &archive,
+            "{}",
+            assets_dir,
+            &["pizza.obj".into(), "pizza.mtl".into()],
+        )
+        .unwrap();
+        let extracted = extract_scene_archive(&archive).unwrap();
+
+        let err = check_bundled_assets(&archive, &extracted.assets_dir(), "pizza.obj").unwrap_err();
+        assert!(err.to_string().contains("does not bundle pizzaTxt.png"));
+        let err = check_bundled_assets(&archive, &extracted.assets_dir(), "cube.obj").unwrap_err();
+        assert!(err.to_string().contains("does not bundle cube.obj"));
+        assert!(check_bundled_assets(&archive, assets_dir, "pizza.obj").is_ok());
+
+        drop(extracted);
+        std::fs::remove_file(archive).unwrap();
+    }
+}
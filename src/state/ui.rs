@@ -0,0 +1,1549 @@
+// Editor interface drawn with egui on top of the 3D viewport
+
+use super::{State, TextureSlot};
+use crate::{
+    commands::{
+        AddInstanceCommand, Command, DeleteInstancesCommand, DuplicateCommand,
+        EditInstancesCommand, RenameInstanceCommand, SelectCommand, SetParentCommand,
+    },
+    console::LogLevel,
+    engine_log,
+    fog::FogMode,
+    frame_stats::FRAME_STATS_CAPACITY,
+    gizmo::{GizmoMode, GizmoSpace, SNAP_GRID_PRESETS, SNAP_ROTATION_PRESETS},
+    gpu_memory::bytes_to_megabytes,
+    instance::{HierarchyRow, Instance, hierarchy_rows},
+    keybindings::{KEY_BINDINGS_FILE, KeyAction, KeyBindings, key_name},
+    light::{MAX_LIGHTS, PointLight, SpotLight},
+    models::MaterialPropertiesUniform,
+    motion_blur::MAX_MOTION_BLUR_TAPS,
+    scene_archive::{SCENE_ARCHIVE_EXTENSION, is_scene_archive},
+    ssao::MAX_SSAO_KERNEL_SIZE,
+    tonemap::TonemapMode,
+    utils::{linear_to_srgb, srgb_to_linear},
+};
+
+/// Height of the texture thumbnails, in points
+const THUMBNAIL_SIZE: f32 = 64.0;
+
+impl State {
+    pub(super) fn draw_ui(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("Fichier", |ui| self.file_menu_ui(ui));
+                ui.menu_button("Édition", |ui| self.edit_menu_ui(ui));
+                ui.menu_button("Affichage", |ui| self.view_menu_ui(ui));
+                ui.separator();
+                self.antialiasing_ui(ui);
+                ui.separator();
+                let label = if self.camera.projection.is_orthographic() {
+                    "Ortho"
+                } else {
+                    "Persp"
+                };
+                if ui
+                    .button(label)
+                    .on_hover_text("Basculer entre perspective et orthographique")
+                    .clicked()
+                {
+                    self.toggle_projection();
+                }
+                ui.separator();
+                for mode in GizmoMode::ALL {
+                    ui.selectable_value(&mut self.gizmo_mode, mode, mode.label());
+                }
+                ui.separator();
+                for space in GizmoSpace::ALL {
+                    ui.selectable_value(&mut self.gizmo_space, space, space.label())
+                        .on_hover_text("Axes du gizmo (G)");
+                }
+                ui.separator();
+                self.snap_ui(ui);
+                ui.separator();
+                self.camera_path_ui(ui);
+                ui.separator();
+                ui.toggle_value(&mut self.show_frame_stats, "Performances");
+                ui.toggle_value(&mut self.show_console, "Console");
+            });
+        });
+
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| self.status_bar_ui(ui));
+        if self.show_frame_stats {
+            egui::TopBottomPanel::bottom("frame_stats").show(ctx, |ui| self.frame_stats_ui(ui));
+        }
+        if self.show_console {
+            egui::TopBottomPanel::bottom("console")
+                .resizable(true)
+                .default_height(120.0)
+                .show(ctx, |ui| self.console_ui(ui));
+        }
+        let mut show_key_bindings = self.show_key_bindings;
+        egui::Window::new("Raccourcis")
+            .open(&mut show_key_bindings)
+            .resizable(false)
+            .show(ctx, |ui| self.key_bindings_ui(ui));
+        self.show_key_bindings = show_key_bindings;
+
+        egui::SidePanel::left("hierarchy").show(ctx, |ui| {
+            ui.label("Scène 3D");
+            ui.separator();
+            ui.label(format!("Objets ({})", self.instances.len()));
+            ui.label(format!("{} sélectionné(s)", self.selected_instances.len()));
+            ui.horizontal(|ui| {
+                if ui.button("Ajouter").clicked() {
+                    // Where the camera looks, upright
+                    let instance = Instance::new(self.camera.target, glam::Quat::IDENTITY);
+                    if let Err(e) = self.execute(Box::new(AddInstanceCommand::new(instance))) {
+                        engine_log!(self, LogLevel::Error, "{}", e);
+                    }
+                }
+                if ui
+                    .add_enabled(
+                        !self.selected_instances.is_empty(),
+                        egui::Button::new("Supprimer"),
+                    )
+                    .clicked()
+                {
+                    self.delete_selection();
+                }
+            });
+            ui.separator();
+            self.camera_bookmarks_ui(ui);
+            ui.separator();
+            self.instance_list_ui(ui);
+        });
+
+        egui::SidePanel::right("inspector").show(ctx, |ui| {
+            self.render_stats_ui(ui);
+
+            ui.separator();
+            self.gpu_memory_ui(ui);
+
+            ui.separator();
+            self.lights_ui(ui);
+
+            ui.separator();
+            self.directional_light_ui(ui);
+
+            ui.separator();
+            self.spot_light_ui(ui);
+
+            if !self.selected_instances.is_empty() {
+                ui.separator();
+                self.object_properties_ui(ui);
+
+                ui.separator();
+                self.instance_ui(ui);
+
+                ui.separator();
+                self.material_properties_ui(ui);
+            }
+
+            ui.separator();
+            self.outline_ui(ui);
+
+            ui.separator();
+            self.ssao_ui(ui);
+
+            ui.separator();
+            self.dof_ui(ui);
+
+            ui.separator();
+            self.motion_blur_ui(ui);
+
+            ui.separator();
+            self.chromatic_aberration_ui(ui);
+
+            ui.separator();
+            self.vignette_ui(ui);
+
+            ui.separator();
+            self.tonemap_ui(ui);
+
+            ui.separator();
+            self.exposure_ui(ui);
+
+            ui.separator();
+            self.fog_ui(ui);
+
+            ui.separator();
+            let has_skybox = self.has_skybox();
+            ui.add_enabled(
+                has_skybox,
+                egui::Checkbox::new(&mut self.skybox_enabled, "Ciel (skybox)"),
+            )
+            .on_disabled_hover_text("Aucune skybox chargée");
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(id) = self.gui.as_ref().and_then(|gui| gui.viewport_texture_id) {
+                let response = ui.add(
+                    egui::Image::new(egui::load::SizedTexture::new(id, ui.available_size()))
+                        .sense(egui::Sense::click_and_drag()),
+                );
+                self.is_scene_hovered = response.hovered();
+                self.handle_viewport_selection(ui, &response);
+
+                self.update_hover(
+                    response
+                        .hover_pos()
+                        .map(|pos| viewport_ndc(response.rect, pos)),
+                );
+                if let Some(i) = self.hovered_instance
+                    && self.box_selection_start.is_none()
+                    && self.lasso_points.is_none()
+                {
+                    egui::show_tooltip_at_pointer(ctx, egui::Id::new("hover_tooltip"), |ui| {
+                        ui.label(&self.instances[i].name);
+                    });
+                }
+            } else {
+                ui.label("Chargement de la texture...");
+            }
+        });
+    }
+
+    fn edit_menu_ui(&mut self, ui: &mut egui::Ui) {
+        let undo = ui.add_enabled(
+            self.can_undo(),
+            egui::Button::new("Annuler").shortcut_text("Ctrl+Z"),
+        );
+        if undo.clicked() {
+            if let Err(e) = self.undo() {
+                engine_log!(self, LogLevel::Error, "{}", e);
+            }
+            ui.close_menu();
+        }
+        let redo = ui.add_enabled(
+            self.can_redo(),
+            egui::Button::new("Rétablir").shortcut_text("Ctrl+Y"),
+        );
+        if redo.clicked() {
+            if let Err(e) = self.redo() {
+                engine_log!(self, LogLevel::Error, "{}", e);
+            }
+            ui.close_menu();
+        }
+        ui.separator();
+        let duplicate = ui.add_enabled(
+            !self.selected_instances.is_empty(),
+            egui::Button::new("Dupliquer").shortcut_text("Ctrl+D"),
+        );
+        if duplicate.clicked() {
+            self.duplicate_selection();
+            ui.close_menu();
+        }
+        ui.separator();
+        if ui
+            .add(egui::Button::new("Tout sélectionner").shortcut_text("Ctrl+A"))
+            .clicked()
+        {
+            self.select_all();
+            ui.close_menu();
+        }
+        let deselect = ui.add_enabled(
+            !self.selected_instances.is_empty(),
+            egui::Button::new("Tout désélectionner").shortcut_text("Échap"),
+        );
+        if deselect.clicked() {
+            self.deselect_all();
+            ui.close_menu();
+        }
+        if ui
+            .add(egui::Button::new("Inverser la sélection").shortcut_text("Ctrl+I"))
+            .clicked()
+        {
+            self.invert_selection();
+            ui.close_menu();
+        }
+        ui.menu_button("Sélectionner par matériau", |ui| {
+            let mut selected = None;
+            for (id, material) in self.cpu_materials.iter().enumerate() {
+                if ui.button(&material.display_name).clicked() {
+                    selected = Some(id);
+                }
+            }
+            if let Some(id) = selected {
+                let count = self.select_by_material(id);
+                engine_log!(self, LogLevel::Info, "{} instance(s) selected", count);
+                ui.close_menu();
+            }
+        });
+        ui.separator();
+        if ui.button("Raccourcis…").clicked() {
+            self.show_key_bindings = true;
+            ui.close_menu();
+        }
+    }
+
+    /// One row per remappable action: click its key, then press the new one.
+    fn key_bindings_ui(&mut self, ui: &mut egui::Ui) {
+        let input = &mut self.input_handler;
+        egui::Grid::new("key_bindings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for action in KeyAction::ALL {
+                    ui.label(action.label());
+                    let text = if input.remapping() == Some(action) {
+                        "Appuyez sur une touche…".to_string()
+                    } else {
+                        key_name(input.key_bindings.get(action))
+                    };
+                    if ui
+                        .button(text)
+                        .on_hover_text("Cliquer pour changer, Échap pour annuler")
+                        .clicked()
+                    {
+                        input.start_remap(action);
+                    }
+                    ui.end_row();
+                }
+            });
+        ui.label("Les flèches déplacent aussi la caméra.");
+        if ui.button("Réinitialiser").clicked() {
+            input.key_bindings = KeyBindings::default();
+            if let Err(e) = input
+                .key_bindings
+                .save(std::path::Path::new(KEY_BINDINGS_FILE))
+            {
+                engine_log!(
+                    self,
+                    LogLevel::Warn,
+                    "Cannot save {}: {}",
+                    KEY_BINDINGS_FILE,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Play/Stop of the camera fly-through, and its waypoints.
+    fn camera_path_ui(&mut self, ui: &mut egui::Ui) {
+        if self.is_playing_camera_path() {
+            if ui.button("⏹ Stop").clicked() {
+                self.stop_camera_path();
+            }
+        } else if ui
+            .add_enabled(
+                self.camera_path.is_playable(),
+                egui::Button::new("▶ Lecture"),
+            )
+            .on_disabled_hover_text("Ajoutez au moins deux points au chemin")
+            .clicked()
+        {
+            let path = self.camera_path.clone();
+            self.play_camera_path(&path);
+        }
+        ui.menu_button("Chemin caméra", |ui| {
+            ui.label(format!(
+                "{} point(s), {:.1} s",
+                self.camera_path.waypoints.len(),
+                self.camera_path.end_time()
+            ));
+            if ui
+                .button("Ajouter la vue")
+                .on_hover_text("Point suivant du chemin, 2 s après le dernier")
+                .clicked()
+            {
+                self.add_camera_waypoint();
+                ui.close_menu();
+            }
+            if ui
+                .add_enabled(
+                    !self.camera_path.waypoints.is_empty(),
+                    egui::Button::new("Effacer"),
+                )
+                .clicked()
+            {
+                self.stop_camera_path();
+                self.camera_path = Default::default();
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn view_menu_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.show_grid, "Grille");
+        ui.add_enabled(
+            self.show_grid,
+            egui::Slider::new(&mut self.grid_spacing, 0.1..=10.0)
+                .logarithmic(true)
+                .text("Espacement"),
+        );
+    }
+
+    fn file_menu_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Scène");
+            ui.text_edit_singleline(&mut self.scene_path);
+        });
+        let path = std::path::PathBuf::from(&self.scene_path);
+        let mut embed = is_scene_archive(&path);
+        if ui
+            .checkbox(&mut embed, "Intégrer les ressources (.esc)")
+            .on_hover_text("Enregistre aussi le modèle et ses textures dans un seul fichier")
+            .changed()
+        {
+            let extension = if embed {
+                SCENE_ARCHIVE_EXTENSION
+            } else {
+                "json"
+            };
+            self.scene_path = path
+                .with_extension(extension)
+                .to_string_lossy()
+                .into_owned();
+        }
+
+        if ui.button("Enregistrer la scène").clicked() {
+            let path = std::path::PathBuf::from(&self.scene_path);
+            if let Err(e) = self.save_scene(&path) {
+                engine_log!(self, LogLevel::Error, "Cannot save {:?}: {}", path, e);
+            }
+            ui.close_menu();
+        }
+        if ui.button("Charger la scène").clicked() {
+            let path = std::path::PathBuf::from(&self.scene_path);
+            if let Err(e) = self.load_scene(&path) {
+                engine_log!(self, LogLevel::Error, "Cannot load {:?}: {}", path, e);
+            }
+            ui.close_menu();
+        }
+
+        // Next to the scene path, with a .ron extension
+        ui.separator();
+        let ron_path = std::path::Path::new(&self.scene_path).with_extension("ron");
+        if ui
+            .button("Enregistrer en RON")
+            .on_hover_text(ron_path.to_string_lossy())
+            .clicked()
+        {
+            if let Err(e) = self.save_scene_ron(&ron_path) {
+                engine_log!(self, LogLevel::Error, "Cannot save {:?}: {}", ron_path, e);
+            }
+            ui.close_menu();
+        }
+        if ui
+            .button("Charger un RON")
+            .on_hover_text(ron_path.to_string_lossy())
+            .clicked()
+        {
+            if let Err(e) = self.load_scene_ron(&ron_path) {
+                engine_log!(self, LogLevel::Error, "Cannot load {:?}: {}", ron_path, e);
+            }
+            ui.close_menu();
+        }
+
+        ui.separator();
+        self.input_recording_ui(ui);
+    }
+
+    /// Records the inputs to `recording_path`, or replays them from it.
+    fn input_recording_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Entrées");
+            ui.text_edit_singleline(&mut self.recording_path);
+        });
+        let path = std::path::PathBuf::from(&self.recording_path);
+        if self.is_recording() {
+            if ui.button("Arrêter l'enregistrement").clicked() {
+                if let Err(e) = self.save_recording(&path) {
+                    engine_log!(self, LogLevel::Error, "Cannot save {:?}: {}", path, e);
+                }
+                ui.close_menu();
+            }
+        } else if ui
+            .button("Enregistrer les entrées")
+            .on_hover_text("Clavier et souris sur la scène, jusqu'à l'arrêt")
+            .clicked()
+        {
+            self.start_recording();
+            ui.close_menu();
+        }
+        if self.is_playing_recording() {
+            if ui.button("Arrêter la relecture").clicked() {
+                self.stop_playback();
+                ui.close_menu();
+            }
+        } else if ui
+            .add_enabled(
+                !self.is_recording(),
+                egui::Button::new("Rejouer les entrées"),
+            )
+            .clicked()
+        {
+            if let Err(e) = self.play_recording(&path) {
+                engine_log!(self, LogLevel::Error, "Cannot load {:?}: {}", path, e);
+            }
+            ui.close_menu();
+        }
+    }
+
+    /// Saved views, numbered like their shortcuts (1 to 9 restore, Ctrl to save).
+    fn camera_bookmarks_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Vues enregistrées").show(ui, |ui| {
+            let mut removed = None;
+            let mut restored = None;
+            for (i, bookmark) in self.camera_bookmarks.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}", i + 1));
+                    ui.add(egui::TextEdit::singleline(&mut bookmark.name).desired_width(90.0));
+                    if ui.button("Aller").clicked() {
+                        restored = Some(i);
+                    }
+                    if ui.button("Supprimer").clicked() {
+                        removed = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = restored {
+                self.restore_camera_bookmark(i);
+            }
+            if let Some(i) = removed {
+                self.camera_bookmarks.remove(i);
+            }
+            if ui
+                .button("Enregistrer la vue")
+                .on_hover_text("Ctrl+1 à Ctrl+9 remplacent une vue")
+                .clicked()
+            {
+                self.save_camera_bookmark(self.camera_bookmarks.len());
+            }
+        });
+    }
+
+    /// Snapping toggle, grid increment among `SNAP_GRID_PRESETS` and angle increment.
+    fn snap_ui(&mut self, ui: &mut egui::Ui) {
+        ui.toggle_value(&mut self.snap_enabled, "Aimanter")
+            .on_hover_text("Arrondir les déplacements et rotations (Ctrl inverse)");
+        let mut preset = SNAP_GRID_PRESETS
+            .iter()
+            .enumerate()
+            .min_by(|a, b| {
+                let distance = |size: f32| (size - self.snap_grid_size).abs();
+                distance(*a.1).total_cmp(&distance(*b.1))
+            })
+            .map_or(0, |(i, _)| i);
+        let slider = egui::Slider::new(&mut preset, 0..=SNAP_GRID_PRESETS.len() - 1)
+            .custom_formatter(|i, _| SNAP_GRID_PRESETS[i as usize].to_string())
+            .custom_parser(|text| {
+                let size = text.parse::<f32>().ok()?;
+                let preset = SNAP_GRID_PRESETS.iter().position(|&p| p == size)?;
+                Some(preset as f64)
+            });
+        if ui.add_enabled(self.snap_enabled, slider).changed() {
+            self.snap_grid_size = SNAP_GRID_PRESETS[preset];
+        }
+
+        ui.add_enabled_ui(self.snap_enabled, |ui| {
+            egui::ComboBox::from_id_source("snap_rotation")
+                .selected_text(format!("{}°", self.snap_rotation_degrees))
+                .width(48.0)
+                .show_ui(ui, |ui| {
+                    for degrees in SNAP_ROTATION_PRESETS {
+                        ui.selectable_value(
+                            &mut self.snap_rotation_degrees,
+                            degrees,
+                            format!("{degrees}°"),
+                        );
+                    }
+                });
+            // Any other increment
+            ui.add(
+                egui::DragValue::new(&mut self.snap_rotation_degrees)
+                    .clamp_range(1.0..=180.0)
+                    .suffix("°"),
+            );
+        });
+    }
+
+    fn status_bar_ui(&self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(format!("Gizmo : {}", self.gizmo_mode.label()));
+            ui.separator();
+            ui.label(format!("Axes : {}", self.gizmo_space.label()));
+            ui.separator();
+            ui.label(format!(
+                "{} objet(s) sélectionné(s)",
+                self.selected_instances.len()
+            ));
+        });
+    }
+
+    /// FPS and the frame times of the last frames, as a line graph.
+    fn frame_stats_ui(&self, ui: &mut egui::Ui) {
+        let stats = &self.frame_stats;
+        ui.horizontal(|ui| {
+            ui.label(format!("FPS: {:.0}", stats.fps()));
+            ui.label(format!("{:.1} ms", stats.average_frame_time()));
+
+            let (response, painter) = ui.allocate_painter(
+                egui::vec2(FRAME_STATS_CAPACITY as f32 * 2.0, 32.0),
+                egui::Sense::hover(),
+            );
+            let rect = response.rect;
+            painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+            // Scaled to the slowest frame, no lower than 30 FPS
+            let max_frame_time = stats
+                .frame_times
+                .iter()
+                .copied()
+                .fold(1000.0 / 30.0, f32::max);
+            let step = rect.width() / (FRAME_STATS_CAPACITY - 1) as f32;
+            let points = stats
+                .frame_times
+                .iter()
+                .enumerate()
+                .map(|(i, &frame_time)| {
+                    egui::pos2(
+                        rect.left() + i as f32 * step,
+                        rect.bottom() - rect.height() * frame_time / max_frame_time,
+                    )
+                })
+                .collect();
+            painter.add(egui::Shape::line(
+                points,
+                egui::Stroke::new(1.0, ui.visuals().text_color()),
+            ));
+            response.on_hover_text(format!("Échelle : {max_frame_time:.1} ms"));
+        });
+    }
+
+    /// Messages of `console`, colored by level and newest at the bottom. Double clicking a
+    /// line copies it.
+    fn console_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(format!("Console ({})", self.console.entries.len()));
+            if ui.button("Effacer").clicked() {
+                self.console.clear();
+            }
+        });
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let rows = self.console.entries.len();
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, rows, |ui, range| {
+                for entry in self.console.entries.range(range) {
+                    let line = entry.to_string();
+                    let text = egui::RichText::new(&line)
+                        .monospace()
+                        .color(entry.level.color());
+                    let label = ui.add(
+                        egui::Label::new(text)
+                            .wrap(false)
+                            .sense(egui::Sense::click()),
+                    );
+                    if label.double_clicked() {
+                        ui.output_mut(|o| o.copied_text = line);
+                    }
+                    label.on_hover_text("Double-clic pour copier");
+                }
+            });
+    }
+
+    fn render_stats_ui(&self, ui: &mut egui::Ui) {
+        let stats = self.render_stats();
+        egui::CollapsingHeader::new("Statistiques de rendu")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.label(format!("Appels de dessin : {}", stats.draw_calls));
+                ui.label(format!("Sommets : {}", stats.vertices_rendered));
+                ui.label(format!("Triangles : {}", stats.triangles_rendered));
+            });
+    }
+
+    fn gpu_memory_ui(&self, ui: &mut egui::Ui) {
+        let memory = self.gpu_memory();
+        egui::CollapsingHeader::new("Mémoire GPU").show(ui, |ui| {
+            for (label, bytes) in [
+                ("Sommets", memory.vertex_bytes),
+                ("Indices", memory.index_bytes),
+                ("Textures", memory.texture_bytes),
+                ("Autres tampons", memory.buffer_bytes),
+                ("Total", memory.total_bytes()),
+            ] {
+                ui.label(format!("{label} : {:.2} Mo", bytes_to_megabytes(bytes)));
+            }
+        });
+    }
+
+    /// The instances as a tree, one row per instance: visibility and lock toggles, then a
+    /// label selecting it. Ctrl-click toggles an instance in the selection, Shift-click
+    /// selects the rows from the last one clicked. Double clicking a name edits it, Enter or
+    /// clicking elsewhere ends the edit, and right-clicking opens `hierarchy_menu_ui`.
+    fn instance_list_ui(&mut self, ui: &mut egui::Ui) {
+        let modifiers = ui.input(|i| i.modifiers);
+        let rows = hierarchy_rows(&self.instances, &self.collapsed_instances);
+        // Fixed, so that only the rows in view are laid out
+        let row_height = ui.spacing().interact_size.y;
+        let indent = ui.spacing().indent;
+        // Run once the rows are drawn, as they name the current indices
+        let mut command = None;
+        egui::ScrollArea::vertical().show_rows(ui, row_height, rows.len(), |ui, range| {
+            egui::Grid::new("hierarchy_rows")
+                .num_columns(3)
+                .min_row_height(row_height)
+                .show(ui, |ui| {
+                    for position in range {
+                        let row = rows[position];
+                        let i = row.id;
+                        let visible = self.instances[i].visible;
+                        let eye = if visible { "👁" } else { "—" };
+                        if ui
+                            .button(eye)
+                            .on_hover_text(if visible { "Masquer" } else { "Afficher" })
+                            .clicked()
+                        {
+                            self.set_visibility(i, !visible);
+                        }
+                        let locked = self.instances[i].locked;
+                        let lock = if locked { "🔒" } else { "🔓" };
+                        if ui
+                            .button(lock)
+                            .on_hover_text(if locked {
+                                "Déverrouiller"
+                            } else {
+                                "Verrouiller (sélection depuis la hiérarchie uniquement)"
+                            })
+                            .clicked()
+                        {
+                            let mut edited = self.instances[i].clone();
+                            edited.locked = !locked;
+                            command = Some(Box::new(EditInstancesCommand::new(vec![(i, edited)]))
+                                as Box<dyn Command>);
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.add_space(indent * row.depth as f32);
+                            if row.has_children {
+                                let collapsed = self.collapsed_instances.contains(&i);
+                                let arrow =
+                                    egui::Button::new(if collapsed { "▶" } else { "▼" })
+                                        .frame(false);
+                                if ui.add_sized([row_height, row_height], arrow).clicked()
+                                    && !self.collapsed_instances.remove(&i)
+                                {
+                                    self.collapsed_instances.insert(i);
+                                }
+                            } else {
+                                ui.add_space(row_height + ui.spacing().item_spacing.x);
+                            }
+
+                            if self.renaming_instance == Some(i) {
+                                let edit = ui.text_edit_singleline(&mut self.rename_text);
+                                edit.request_focus();
+                                if edit.lost_focus() {
+                                    self.renaming_instance = None;
+                                    if self.rename_text != self.instances[i].name {
+                                        let name = std::mem::take(&mut self.rename_text);
+                                        command =
+                                            Some(Box::new(RenameInstanceCommand::new(i, name))
+                                                as Box<dyn Command>);
+                                    }
+                                }
+                                return;
+                            }
+
+                            let selected = self.selected_instances.contains(&i);
+                            let label = ui.add_enabled(
+                                visible,
+                                egui::SelectableLabel::new(selected, &self.instances[i].name),
+                            );
+                            if label.double_clicked() {
+                                self.renaming_instance = Some(i);
+                                self.rename_text = self.instances[i].name.clone();
+                            } else if label.clicked() {
+                                self.hierarchy_click(&rows, position, modifiers);
+                            }
+                            label.context_menu(|ui| {
+                                if let Some(menu_command) = self.hierarchy_menu_ui(ui, i) {
+                                    command = Some(menu_command);
+                                }
+                            });
+                        });
+                        ui.end_row();
+                    }
+                });
+        });
+        if let Some(command) = command
+            && let Err(e) = self.execute(command)
+        {
+            engine_log!(self, LogLevel::Error, "{}", e);
+        }
+    }
+
+    /// Selects the instance of `rows[position]`, see `instance_list_ui` for the modifiers.
+    fn hierarchy_click(
+        &mut self,
+        rows: &[HierarchyRow],
+        position: usize,
+        modifiers: egui::Modifiers,
+    ) {
+        let id = rows[position].id;
+        let anchor = self
+            .hierarchy_anchor
+            .and_then(|anchor| rows.iter().position(|row| row.id == anchor));
+        if let Some(anchor) = anchor
+            && modifiers.shift
+        {
+            // The anchor stays, for the next range
+            let range = anchor.min(position)..=anchor.max(position);
+            self.set_selection(rows[range].iter().map(|row| row.id));
+            return;
+        }
+        self.apply_pick(Some(id), modifiers.command);
+        self.hierarchy_anchor = Some(id);
+    }
+
+    /// Right-click menu of instance `id` in the hierarchy. Duplicating and deleting act on
+    /// the whole selection when `id` is part of it. Returns the command to run.
+    fn hierarchy_menu_ui(&mut self, ui: &mut egui::Ui, id: usize) -> Option<Box<dyn Command>> {
+        let targets: Vec<_> = if self.selected_instances.contains(&id) {
+            self.selected_instances.iter().copied().collect()
+        } else {
+            vec![id]
+        };
+        let mut command: Option<Box<dyn Command>> = None;
+        if ui.button("Renommer").clicked() {
+            self.renaming_instance = Some(id);
+            self.rename_text = self.instances[id].name.clone();
+            ui.close_menu();
+        }
+        if ui.button("Dupliquer").clicked() {
+            command = Some(Box::new(DuplicateCommand::new(targets.clone())));
+        }
+        if ui.button("Supprimer").clicked() {
+            command = Some(Box::new(DeleteInstancesCommand::new(targets)));
+        }
+        ui.separator();
+
+        let descendants = self.descendants(id);
+        if ui
+            .add_enabled(
+                !descendants.is_empty(),
+                egui::Button::new("Sélectionner les enfants"),
+            )
+            .clicked()
+        {
+            command = Some(Box::new(SelectCommand::new(descendants)));
+        }
+        // The selected instances that can go under `id`, which excludes its ancestors
+        let mut attachable: Vec<_> = self
+            .selected_instances
+            .iter()
+            .copied()
+            .filter(|&i| {
+                i != id
+                    && self.instances[i].parent != Some(id)
+                    && !self.descendants(i).contains(&id)
+            })
+            .collect();
+        attachable.sort_unstable();
+        if ui
+            .add_enabled(
+                !attachable.is_empty(),
+                egui::Button::new("Rattacher la sélection ici"),
+            )
+            .clicked()
+        {
+            command = Some(Box::new(SetParentCommand::new(attachable, Some(id))));
+        }
+        if ui
+            .add_enabled(
+                self.instances[id].parent.is_some(),
+                egui::Button::new("Détacher"),
+            )
+            .clicked()
+        {
+            command = Some(Box::new(SetParentCommand::new([id], None)));
+        }
+
+        if command.is_some() {
+            ui.close_menu();
+        }
+        command
+    }
+
+    fn directional_light_ui(&mut self, ui: &mut egui::Ui) {
+        let light = &mut self.directional_light;
+        ui.heading("Lumière directionnelle");
+        ui.label("Direction");
+        ui.add(egui::Slider::new(&mut light.direction[0], -1.0..=1.0).text("X"));
+        ui.add(egui::Slider::new(&mut light.direction[1], -1.0..=1.0).text("Y"));
+        ui.add(egui::Slider::new(&mut light.direction[2], -1.0..=1.0).text("Z"));
+        ui.horizontal(|ui| {
+            ui.label("Couleur");
+            linear_color_edit(ui, &mut light.color);
+        });
+        ui.add(egui::Slider::new(&mut light.intensity, 0.0..=10.0).text("Intensité"));
+    }
+
+    fn spot_light_ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Projecteur");
+        let mut enabled = self.spot_light.is_some();
+        if ui.checkbox(&mut enabled, "Activé").changed() {
+            self.spot_light = enabled.then(SpotLight::default);
+        }
+        let Some(light) = &mut self.spot_light else {
+            return;
+        };
+
+        ui.label("Position");
+        ui.add(egui::Slider::new(&mut light.position[0], -10.0..=10.0).text("X"));
+        ui.add(egui::Slider::new(&mut light.position[1], -10.0..=10.0).text("Y"));
+        ui.add(egui::Slider::new(&mut light.position[2], -10.0..=10.0).text("Z"));
+        ui.label("Direction");
+        ui.add(egui::Slider::new(&mut light.direction[0], -1.0..=1.0).text("X"));
+        ui.add(egui::Slider::new(&mut light.direction[1], -1.0..=1.0).text("Y"));
+        ui.add(egui::Slider::new(&mut light.direction[2], -1.0..=1.0).text("Z"));
+        ui.horizontal(|ui| {
+            ui.label("Angle intérieur");
+            ui.drag_angle(&mut light.inner_angle);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Angle extérieur");
+            ui.drag_angle(&mut light.outer_angle);
+        });
+        light.clamp_angles();
+        ui.horizontal(|ui| {
+            ui.label("Couleur");
+            linear_color_edit(ui, &mut light.color);
+        });
+        ui.add(egui::Slider::new(&mut light.intensity, 0.0..=10.0).text("Intensité"));
+    }
+
+    fn lights_ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Lumières");
+
+        let mut removed = None;
+        for (i, light) in self.lights.lights_mut().iter_mut().enumerate() {
+            egui::CollapsingHeader::new(format!("Lumière {}", i + 1))
+                .id_source(("light", i))
+                .default_open(i == 0)
+                .show(ui, |ui| {
+                    let position = &mut light.position;
+                    ui.add(egui::Slider::new(&mut position[0], -10.0..=10.0).text("X"));
+                    ui.add(egui::Slider::new(&mut position[1], -10.0..=10.0).text("Y"));
+                    ui.add(egui::Slider::new(&mut position[2], -10.0..=10.0).text("Z"));
+                    ui.horizontal(|ui| {
+                        ui.label("Couleur");
+                        linear_color_edit(ui, &mut light.color);
+                    });
+                    ui.add(egui::Slider::new(&mut light.intensity, 0.0..=10.0).text("Intensité"));
+                    ui.add(egui::Slider::new(&mut light.radius, 0.1..=50.0).text("Portée"));
+                    if ui.button("Supprimer").clicked() {
+                        removed = Some(i);
+                    }
+                });
+        }
+        if let Some(i) = removed {
+            self.remove_light(i);
+        }
+
+        let can_add = self.lights().len() < MAX_LIGHTS;
+        if ui
+            .add_enabled(can_add, egui::Button::new("Ajouter une lumière"))
+            .clicked()
+        {
+            // Cannot fail, the button is disabled once the array is full
+            let _ = self.add_light(PointLight::default());
+        }
+    }
+
+    /// Name and transform of the selection. A field whose value differs between the selected
+    /// instances shows "—", editing it gives them all the new value. Edits show at once and
+    /// are recorded as one command when the field is let go.
+    fn object_properties_ui(&mut self, ui: &mut egui::Ui) {
+        let mut ids: Vec<_> = self.selected_instances.iter().copied().collect();
+        ids.sort_unstable();
+        let mut edited: Vec<_> = ids.iter().map(|&i| self.instances[i].clone()).collect();
+        // Whether a field is still being dragged or typed in
+        let mut editing = false;
+
+        ui.heading("Propriétés de l'objet");
+        egui::Grid::new("object_properties")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Nom");
+                let mixed = edited.iter().any(|i| i.name != edited[0].name);
+                let mut name = if mixed {
+                    String::new()
+                } else {
+                    edited[0].name.clone()
+                };
+                let response = ui.add(egui::TextEdit::singleline(&mut name).hint_text("—"));
+                editing |= response.has_focus();
+                if response.changed() {
+                    for instance in &mut edited {
+                        instance.name.clone_from(&name);
+                    }
+                }
+                ui.end_row();
+
+                ui.label("Position");
+                ui.horizontal(|ui| {
+                    for axis in 0..3 {
+                        let values = edited.iter().map(|i| i.position[axis]);
+                        if let Some(value) = multi_drag_value(ui, values, 0.05, "", &mut editing) {
+                            edited.iter_mut().for_each(|i| i.position[axis] = value);
+                        }
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Rotation");
+                ui.horizontal(|ui| {
+                    let angles: Vec<_> = edited
+                        .iter()
+                        .map(|i| {
+                            let (x, y, z) = i.rotation.to_euler(glam::EulerRot::XYZ);
+                            glam::Vec3::new(x, y, z)
+                        })
+                        .collect();
+                    for axis in 0..3 {
+                        let degrees = angles.iter().map(|a| a[axis].to_degrees());
+                        if let Some(value) = multi_drag_value(ui, degrees, 1.0, "°", &mut editing)
+                        {
+                            for (instance, mut angles) in edited.iter_mut().zip(angles.clone()) {
+                                angles[axis] = value.to_radians();
+                                instance.rotation = glam::Quat::from_euler(
+                                    glam::EulerRot::XYZ,
+                                    angles.x,
+                                    angles.y,
+                                    angles.z,
+                                );
+                            }
+                        }
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Échelle");
+                ui.horizontal(|ui| {
+                    for axis in 0..3 {
+                        let values = edited.iter().map(|i| i.scale[axis]);
+                        if let Some(value) = multi_drag_value(ui, values, 0.01, "", &mut editing) {
+                            edited.iter_mut().for_each(|i| i.scale[axis] = value);
+                        }
+                    }
+                });
+                ui.end_row();
+            });
+
+        let changed: Vec<_> = ids
+            .iter()
+            .copied()
+            .zip(edited)
+            .filter(|(id, instance)| *instance != self.instances[*id])
+            .collect();
+        if !changed.is_empty() && self.inspector_edit_start.is_none() {
+            self.inspector_edit_start = Some(
+                ids.iter()
+                    .map(|&i| (i, self.instances[i].clone()))
+                    .collect(),
+            );
+        }
+        for (id, instance) in changed {
+            self.replace_instance(id, instance);
+        }
+        if !editing && let Some(start) = self.inspector_edit_start.take() {
+            // Put back as they were, then edited again through the command
+            let edits: Vec<_> = start
+                .into_iter()
+                .filter_map(|(id, before)| {
+                    let after = self.replace_instance(id, before.clone())?;
+                    (after != before).then_some((id, after))
+                })
+                .collect();
+            if !edits.is_empty() {
+                // Cannot fail, the command skips missing instances
+                let _ = self.execute(Box::new(EditInstancesCommand::new(edits)));
+            }
+        }
+    }
+
+    /// Shows the first selected instance, edits apply to the whole selection.
+    fn instance_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(&first) = self.selected_instances.iter().min() else {
+            return;
+        };
+        let mut tint = self.instances[first].tint.to_array();
+        let mut material_override = self.instances[first].material_override;
+
+        ui.heading("Instance");
+        ui.horizontal(|ui| {
+            ui.label("Teinte");
+            let mut changed = ui.color_edit_button_rgba_unmultiplied(&mut tint).changed();
+            if ui.button("Réinitialiser").clicked() {
+                tint = [1.0; 4];
+                changed = true;
+            }
+            if changed {
+                // Uploaded by the next `update`
+                for &i in &self.selected_instances {
+                    self.instances[i].tint = tint.into();
+                }
+            }
+        });
+
+        let material_name = |id: Option<usize>| match id.and_then(|id| self.cpu_materials.get(id)) {
+            Some(material) => material.display_name.clone(),
+            None => "Celui du modèle".to_string(),
+        };
+        let mut changed = false;
+        egui::ComboBox::from_label("Matériau")
+            .selected_text(material_name(material_override))
+            .show_ui(ui, |ui| {
+                for id in std::iter::once(None).chain((0..self.materials.len()).map(Some)) {
+                    changed |= ui
+                        .selectable_value(&mut material_override, id, material_name(id))
+                        .changed();
+                }
+            });
+        if changed {
+            // Regrouped by the next `update`
+            for &i in &self.selected_instances {
+                self.instances[i].material_override = material_override;
+            }
+        }
+    }
+
+    /// Material of the first selected instance: its override, or else the material of the
+    /// model's primary mesh. Shows the diffuse texture, edits the shading parameters and
+    /// replaces textures, see `State::reload_material_texture`.
+    fn material_properties_ui(&mut self, ui: &mut egui::Ui) {
+        let first = self.selected_instances.iter().min().copied();
+        let material_override = first
+            .and_then(|i| self.instances[i].material_override)
+            .filter(|&id| id < self.materials.len());
+        let Some(material_id) =
+            material_override.or_else(|| self.meshes.first().map(|m| m.material_id))
+        else {
+            return;
+        };
+
+        egui::CollapsingHeader::new("Matériau")
+            .default_open(true)
+            .show(ui, |ui| {
+                if let Some(material) = self.cpu_materials.get_mut(material_id) {
+                    ui.horizontal(|ui| {
+                        ui.label("Nom");
+                        ui.text_edit_singleline(&mut material.display_name);
+                    })
+                    .response
+                    .on_hover_text(format!("Nom d'origine : {}", material.name));
+                }
+                let diffuse = &self.materials[material_id].textures.diffuse;
+                if let Some(gui) = &mut self.gui {
+                    let size = diffuse.texture.size();
+                    let height = THUMBNAIL_SIZE;
+                    let width = (height * size.width as f32 / size.height as f32).min(height * 4.0);
+                    let id = gui.thumbnail_texture(&self.device, &diffuse.view);
+                    ui.image(egui::load::SizedTexture::new(id, [width, height]))
+                        .on_hover_text(format!("{} x {}", size.width, size.height));
+                }
+                self.material_parameters_ui(ui, material_id);
+                ui.separator();
+                self.material_texture_ui(ui, material_id);
+            });
+    }
+
+    fn material_parameters_ui(&mut self, ui: &mut egui::Ui, material_id: usize) {
+        let mut properties = self.materials[material_id].properties;
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Couleur diffuse");
+            let previous = properties.diffuse_color;
+            linear_color_edit(ui, &mut properties.diffuse_color);
+            changed |= properties.diffuse_color != previous;
+        });
+        // A specular map overrides both values
+        let editable = properties.specular_map == 0;
+        changed |= ui
+            .add_enabled(
+                editable,
+                egui::Slider::new(&mut properties.roughness, 0.0..=1.0).text("Rugosité"),
+            )
+            .on_disabled_hover_text("Fournie par la carte spéculaire")
+            .changed();
+        changed |= ui
+            .add_enabled(
+                editable,
+                egui::Slider::new(&mut properties.metallic, 0.0..=1.0).text("Métallique"),
+            )
+            .on_disabled_hover_text("Fournie par la carte spéculaire")
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut properties.ao, 0.0..=1.0).text("Occlusion ambiante"))
+            .changed();
+        ui.horizontal(|ui| {
+            ui.label("Émission");
+            changed |= ui.color_edit_button_rgb(&mut properties.emissive).changed();
+        });
+        if ui.button("Réinitialiser").clicked() {
+            properties = MaterialPropertiesUniform::default();
+            changed = true;
+        }
+
+        if changed {
+            self.update_material_properties(material_id, properties);
+        }
+    }
+
+    /// Image path typed in, loaded into the chosen texture slot of the material.
+    fn material_texture_ui(&mut self, ui: &mut egui::Ui, material_id: usize) {
+        egui::ComboBox::from_label("Emplacement")
+            .selected_text(self.material_texture_slot.label())
+            .show_ui(ui, |ui| {
+                for slot in TextureSlot::ALL {
+                    ui.selectable_value(&mut self.material_texture_slot, slot, slot.label());
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.label("Image");
+            ui.text_edit_singleline(&mut self.material_texture_path);
+        });
+        let path = self.material_texture_path.trim();
+        if ui
+            .add_enabled(!path.is_empty(), egui::Button::new("Charger la texture…"))
+            .clicked()
+        {
+            let path = std::path::PathBuf::from(path);
+            let slot = self.material_texture_slot;
+            if let Err(e) = self.reload_material_texture(material_id, slot, &path) {
+                engine_log!(self, LogLevel::Warn, "Cannot load {:?}: {}", path, e);
+            }
+        }
+    }
+
+    fn antialiasing_ui(&mut self, ui: &mut egui::Ui) {
+        let mut mode = self.engine_config.antialiasing;
+        egui::ComboBox::from_label("Anticrénelage")
+            .selected_text(mode.label())
+            .show_ui(ui, |ui| {
+                for &supported in self.antialias.supported_modes() {
+                    ui.selectable_value(&mut mode, supported, supported.label());
+                }
+            });
+        self.set_antialiasing(mode);
+    }
+
+    fn outline_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Contours").show(ui, |ui| {
+            ui.checkbox(&mut self.outline.enabled, "Activer");
+            if self.antialias.sample_count() > 1 {
+                ui.label("Indisponible avec le MSAA");
+            }
+
+            let states = [
+                ("Normal", &mut self.outline.settings),
+                ("Sélection", &mut self.outline.selected_settings),
+            ];
+            for (label, settings) in states {
+                ui.label(label);
+                ui.push_id(label, |ui| {
+                    ui.color_edit_button_rgba_unmultiplied(&mut settings.color);
+                    ui.add(egui::Slider::new(&mut settings.thickness, 1.0..=5.0).text("Épaisseur"));
+                    ui.add(egui::Slider::new(&mut settings.threshold, 0.01..=1.0).text("Seuil"));
+                });
+            }
+        });
+        egui::CollapsingHeader::new("Contour de sélection").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Couleur");
+                ui.color_edit_button_rgba_unmultiplied(&mut self.outline_color);
+            });
+            ui.add(
+                egui::Slider::new(&mut self.selection_outline.thickness, 1.0..=10.0)
+                    .text("Épaisseur"),
+            );
+        });
+    }
+
+    fn ssao_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Occlusion ambiante (SSAO)").show(ui, |ui| {
+            ui.checkbox(&mut self.ssao.enabled, "Activer");
+            if self.antialias.sample_count() > 1 {
+                ui.label("Indisponible avec le MSAA");
+            }
+            let settings = &mut self.ssao.settings;
+            ui.add(egui::Slider::new(&mut settings.radius, 0.05..=5.0).text("Rayon"));
+            ui.add(
+                egui::Slider::new(&mut settings.kernel_size, 1..=MAX_SSAO_KERNEL_SIZE)
+                    .text("Échantillons"),
+            );
+            ui.add(egui::Slider::new(&mut settings.bias, 0.0..=0.1).text("Biais"));
+        });
+    }
+
+    fn dof_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Profondeur de champ").show(ui, |ui| {
+            ui.checkbox(&mut self.dof_enabled, "Activer");
+            if self.antialias.sample_count() > 1 {
+                ui.label("Indisponible avec le MSAA");
+            }
+            let mut dof = self.dof();
+            ui.add(
+                egui::Slider::new(&mut dof.focal_distance, 0.1..=100.0)
+                    .logarithmic(true)
+                    .text("Mise au point"),
+            );
+            ui.add(egui::Slider::new(&mut dof.focal_length, 10.0..=200.0).text("Focale (mm)"));
+            ui.add(
+                egui::Slider::new(&mut dof.aperture, 1.0..=22.0)
+                    .logarithmic(true)
+                    .text("Ouverture (f/)"),
+            );
+            if dof != self.dof() {
+                self.set_dof(dof);
+            }
+        });
+    }
+
+    fn motion_blur_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Flou de mouvement").show(ui, |ui| {
+            ui.checkbox(&mut self.motion_blur.enabled, "Activer");
+            let settings = &mut self.motion_blur.settings;
+            ui.add(
+                egui::Slider::new(&mut settings.taps, 2..=MAX_MOTION_BLUR_TAPS)
+                    .text("Échantillons"),
+            );
+            ui.add(egui::Slider::new(&mut settings.strength, 0.0..=1.0).text("Intensité"));
+        });
+    }
+
+    fn chromatic_aberration_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Aberration chromatique").show(ui, |ui| {
+            let mut strength = self.chromatic_aberration();
+            ui.add(egui::Slider::new(&mut strength, 0.0..=0.02).text("Intensité"));
+            if strength != self.chromatic_aberration() {
+                self.set_chromatic_aberration(strength);
+            }
+        });
+    }
+
+    fn vignette_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Vignettage").show(ui, |ui| {
+            ui.checkbox(&mut self.vignette_enabled, "Activer");
+            let mut vignette = self.vignette();
+            ui.add(egui::Slider::new(&mut vignette.strength, 0.0..=1.0).text("Intensité"));
+            ui.add(egui::Slider::new(&mut vignette.radius, 0.5..=2.0).text("Rayon"));
+            ui.add(egui::Slider::new(&mut vignette.softness, 0.01..=2.0).text("Douceur"));
+            ui.horizontal(|ui| {
+                ui.label("Couleur");
+                ui.color_edit_button_rgba_unmultiplied(&mut vignette.color);
+            });
+            if vignette != self.vignette() {
+                self.set_vignette(vignette);
+            }
+        });
+    }
+
+    fn tonemap_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Mappage tonal").show(ui, |ui| {
+            let mut mode = self.tonemap_mode();
+            let mut exposure = self.tonemap_exposure();
+            egui::ComboBox::from_label("Courbe")
+                .selected_text(mode.label())
+                .show_ui(ui, |ui| {
+                    for candidate in TonemapMode::ALL {
+                        ui.selectable_value(&mut mode, candidate, candidate.label());
+                    }
+                });
+            ui.add(
+                egui::Slider::new(&mut exposure, 0.1..=10.0)
+                    .logarithmic(true)
+                    .text("Exposition"),
+            );
+            if mode != self.tonemap_mode() || exposure != self.tonemap_exposure() {
+                self.set_tonemap(mode, exposure);
+            }
+        });
+    }
+
+    fn exposure_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Exposition automatique").show(ui, |ui| {
+            let Some(exposure) = &mut self.exposure else {
+                ui.label("Indisponible sur ce GPU");
+                return;
+            };
+            ui.checkbox(&mut exposure.enabled, "Activer");
+            let settings = &mut exposure.settings;
+            ui.add(egui::Slider::new(&mut settings.lag, 0.0..=5.0).text("Adaptation (s)"));
+            ui.add(egui::Slider::new(&mut settings.key, 0.05..=0.5).text("Gris moyen"));
+        });
+    }
+
+    fn fog_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Brouillard").show(ui, |ui| {
+            let mut fog = self.fog();
+            let mut color = [fog.color[0], fog.color[1], fog.color[2]];
+            let mut changed = ui
+                .add(egui::Slider::new(&mut fog.color[3], 0.0..=1.0).text("Opacité"))
+                .changed();
+            ui.horizontal(|ui| {
+                ui.label("Couleur");
+                let before = color;
+                linear_color_edit(ui, &mut color);
+                changed |= color != before;
+            });
+            fog.color[..3].copy_from_slice(&color);
+
+            let mut mode = fog.mode();
+            egui::ComboBox::from_label("Mode")
+                .selected_text(mode.label())
+                .show_ui(ui, |ui| {
+                    for candidate in FogMode::ALL {
+                        ui.selectable_value(&mut mode, candidate, candidate.label());
+                    }
+                });
+            changed |= mode as u32 != fog.mode;
+            fog.mode = mode as u32;
+
+            if mode == FogMode::Linear {
+                changed |= ui
+                    .add(egui::Slider::new(&mut fog.start, 0.0..=100.0).text("Début"))
+                    .changed();
+                changed |= ui
+                    .add(egui::Slider::new(&mut fog.end, 0.0..=200.0).text("Fin"))
+                    .changed();
+            } else {
+                changed |= ui
+                    .add(
+                        egui::Slider::new(&mut fog.density, 0.0..=0.5)
+                            .logarithmic(true)
+                            .text("Densité"),
+                    )
+                    .changed();
+            }
+
+            if changed {
+                self.set_fog(fog);
+            }
+        });
+    }
+
+    /// Left click selects the instance under the cursor (or clears the selection on the
+    /// background), left drag draws a selection box (it orbits in orbit mode), or a lasso
+    /// when L is held as the drag starts. Holding Shift adds to the current selection
+    /// instead of replacing it.
+    fn handle_viewport_selection(&mut self, ui: &egui::Ui, response: &egui::Response) {
+        let modifiers = ui.input(|i| i.modifiers);
+        let extend = modifiers.shift;
+
+        let pointer = response
+            .interact_pointer_pos()
+            .or_else(|| response.hover_pos())
+            .map(|pos| viewport_ndc(response.rect, pos));
+        let viewport = glam::Vec2::new(response.rect.width(), response.rect.height());
+        if self.update_gizmo(
+            pointer,
+            viewport,
+            response.drag_started_by(egui::PointerButton::Primary),
+            response.drag_stopped_by(egui::PointerButton::Primary),
+            modifiers,
+        ) {
+            return;
+        }
+
+        if response.drag_started_by(egui::PointerButton::Primary)
+            && !self.input_handler.is_orbit_mode()
+        {
+            if ui.input(|i| i.key_down(egui::Key::L)) {
+                self.lasso_points = response.interact_pointer_pos().map(|pos| vec![pos]);
+            } else {
+                self.box_selection_start = response.interact_pointer_pos();
+            }
+        }
+
+        let current = response
+            .interact_pointer_pos()
+            .or_else(|| ui.input(|i| i.pointer.latest_pos()));
+
+        if let Some(points) = &mut self.lasso_points {
+            // Skips the points too close to the last one, the path stays light
+            if let Some(current) = current
+                && points
+                    .last()
+                    .is_none_or(|last| last.distance(current) >= 2.0)
+            {
+                points.push(current);
+            }
+            if response.drag_stopped_by(egui::PointerButton::Primary) {
+                let points = self.lasso_points.take().unwrap_or_default();
+                self.perform_lasso_selection(&points, response.rect, extend);
+            } else {
+                // Only convex paths can be filled
+                ui.painter().add(egui::Shape::Path(egui::epaint::PathShape {
+                    points: points.clone(),
+                    closed: true,
+                    fill: egui::Color32::TRANSPARENT,
+                    stroke: egui::Stroke::new(1.0, egui::Color32::from_rgb(255, 160, 0)),
+                }));
+            }
+        }
+
+        if let (Some(start), Some(current)) = (self.box_selection_start, current) {
+            let selection_rect = egui::Rect::from_two_pos(start, current);
+
+            if response.drag_stopped_by(egui::PointerButton::Primary) {
+                self.box_selection_start = None;
+                self.perform_box_selection(selection_rect, response.rect, extend);
+            } else {
+                ui.painter().rect(
+                    selection_rect,
+                    0.0,
+                    egui::Color32::from_rgba_unmultiplied(255, 160, 0, 30),
+                    egui::Stroke::new(1.0, egui::Color32::from_rgb(255, 160, 0)),
+                );
+            }
+        }
+
+        if response.clicked_by(egui::PointerButton::Primary)
+            && let Some(pos) = response.interact_pointer_pos()
+        {
+            self.request_pick(viewport_ndc(response.rect, pos), extend);
+        }
+    }
+}
+
+/// Position inside the viewport in NDC: (-1, -1) bottom-left, (1, 1) top-right.
+fn viewport_ndc(viewport: egui::Rect, pos: egui::Pos2) -> glam::Vec2 {
+    glam::Vec2::new(
+        (pos.x - viewport.min.x) / viewport.width() * 2.0 - 1.0,
+        1.0 - (pos.y - viewport.min.y) / viewport.height() * 2.0,
+    )
+}
+
+/// Drag value for a number shared by several instances, showing "—" when their `values`
+/// differ. Returns the new value once edited, and sets `editing` while it is dragged or typed
+/// in.
+fn multi_drag_value(
+    ui: &mut egui::Ui,
+    mut values: impl Iterator<Item = f32>,
+    speed: f64,
+    suffix: &str,
+    editing: &mut bool,
+) -> Option<f32> {
+    let mut value = values.next()?;
+    let mixed = values.any(|v| v != value);
+    let mut drag = egui::DragValue::new(&mut value)
+        .speed(speed)
+        .suffix(suffix)
+        .max_decimals(3);
+    if mixed {
+        drag = drag.custom_formatter(|_, _| "—".to_string());
+    }
+    let response = ui.add(drag);
+    *editing |= response.dragged() || response.has_focus();
+    response.changed().then_some(value)
+}
+
+/// The picker shows sRGB values while the shader expects linear light.
+fn linear_color_edit(ui: &mut egui::Ui, linear: &mut [f32; 3]) {
+    let mut srgb = linear.map(|c| (linear_to_srgb(c.clamp(0.0, 1.0)) * 255.0).round() as u8);
+    if ui.color_edit_button_srgb(&mut srgb).changed() {
+        *linear = srgb.map(|c| srgb_to_linear(c as f32 / 255.0));
+    }
+}
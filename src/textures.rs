@@ -1,8 +1,23 @@
-use crate::error::Result;
-use image::GenericImageView;
+use crate::error::{OrengineError, Result};
 use std::path::Path;
 
-pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+/// Format of the scene before tone mapping, holds values above 1.0 (highlights, emission).
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Cells per axis of `Texture::identity_lut`.
+pub const IDENTITY_LUT_SIZE: u32 = 16;
+
+/// Depth32Float when the adapter can render to and sample it, Depth24Plus otherwise
+/// (some mobile and web backends).
+pub fn best_depth_format(adapter: &wgpu::Adapter) -> wgpu::TextureFormat {
+    let usages = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+    let depth32 = adapter.get_texture_format_features(wgpu::TextureFormat::Depth32Float);
+    if depth32.allowed_usages.contains(usages) {
+        wgpu::TextureFormat::Depth32Float
+    } else {
+        wgpu::TextureFormat::Depth24Plus
+    }
+}
 
 #[derive(Debug)]
 pub struct Texture {
@@ -12,9 +27,12 @@ pub struct Texture {
 }
 
 impl Texture {
+    /// `sample_count` must match the color attachment it is used with (MSAA).
     pub fn create_depth_texture(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
         label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
@@ -27,9 +45,9 @@ impl Texture {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
-            format: DEPTH_FORMAT,
+            format,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         };
@@ -63,8 +81,52 @@ impl Texture {
         label: Option<&str>,
     ) -> Result<Self> {
         let img = image::open(path)?;
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+        Ok(Self::from_rgba(device, queue, &img.to_rgba8(), label))
+    }
+
+    /// Like `from_image`, for textures holding data rather than colors (normal maps...):
+    /// the values are sampled as stored, without sRGB decoding.
+    pub fn from_image_linear(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let img = image::open(path)?;
+        Ok(Self::upload_rgba(
+            device,
+            queue,
+            &img.to_rgba8(),
+            wgpu::TextureFormat::Rgba8Unorm,
+            label,
+        ))
+    }
+
+    /// Uploads an image already decoded in memory, such as one embedded in a glTF file.
+    pub fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &image::RgbaImage,
+        label: Option<&str>,
+    ) -> Self {
+        // Standard format PNG/JPG
+        Self::upload_rgba(
+            device,
+            queue,
+            rgba,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            label,
+        )
+    }
+
+    fn upload_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &image::RgbaImage,
+        format: wgpu::TextureFormat,
+        label: Option<&str>,
+    ) -> Self {
+        let dimensions = rgba.dimensions();
 
         let size = wgpu::Extent3d {
             width: dimensions.0,
@@ -78,7 +140,7 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb, // Standard format PNG/JPG
+            format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
@@ -90,7 +152,7 @@ impl Texture {
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
             },
-            &rgba,
+            rgba,
             wgpu::ImageDataLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * dimensions.0),
@@ -110,11 +172,11 @@ impl Texture {
             ..Default::default()
         });
 
-        Ok(Self {
+        Self {
             texture,
             view,
             sampler,
-        })
+        }
     }
 
     pub fn from_color(
@@ -122,6 +184,32 @@ impl Texture {
         queue: &wgpu::Queue,
         color: [u8; 4],
         label: Option<&str>,
+    ) -> Self {
+        Self::color_with_format(
+            device,
+            queue,
+            color,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            label,
+        )
+    }
+
+    /// Single texel of data, see `from_image_linear`.
+    pub fn from_color_linear(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color: [u8; 4],
+        label: Option<&str>,
+    ) -> Self {
+        Self::color_with_format(device, queue, color, wgpu::TextureFormat::Rgba8Unorm, label)
+    }
+
+    fn color_with_format(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color: [u8; 4],
+        format: wgpu::TextureFormat,
+        label: Option<&str>,
     ) -> Self {
         let size = wgpu::Extent3d {
             width: 1,
@@ -134,7 +222,7 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
@@ -173,10 +261,196 @@ impl Texture {
         }
     }
 
+    /// Cube map from six square images of the same size, in the `+X, -X, +Y, -Y, +Z, -Z`
+    /// order of the cube faces. Sampled as sRGB colors.
+    pub fn from_cubemap_files(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        paths: [&str; 6],
+    ) -> Result<Self> {
+        let faces = paths
+            .iter()
+            .map(|path| Ok(image::open(path)?.to_rgba8()))
+            .collect::<Result<Vec<_>>>()?;
+        let (width, height) = faces[0].dimensions();
+        if width != height
+            || faces
+                .iter()
+                .any(|face| face.dimensions() != (width, height))
+        {
+            return Err(OrengineError::Generic(format!(
+                "Cube map faces must be square and of the same size: {:?}",
+                faces
+                    .iter()
+                    .map(|face| face.dimensions())
+                    .collect::<Vec<_>>()
+            )));
+        }
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 6,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Cube Map"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, face) in faces.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                face,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers: 1,
+                    ..size
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    /// Color grading LUT that leaves colors unchanged.
+    pub fn identity_lut(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let size = IDENTITY_LUT_SIZE;
+        let level = |i: u32| (i * 255 / (size - 1)) as u8;
+        let texels = (0..size.pow(3))
+            .flat_map(|i| {
+                [
+                    level(i % size),
+                    level(i / size % size),
+                    level(i / size / size),
+                    255,
+                ]
+            })
+            .collect::<Vec<_>>();
+        Self::upload_lut(device, queue, size, &texels, Some("Identity LUT"))
+    }
+
+    /// Color grading LUT from an image, see `lut_texels` for the supported layouts.
+    pub fn from_lut_image(device: &wgpu::Device, queue: &wgpu::Queue, path: &Path) -> Result<Self> {
+        let img = image::open(path)?.to_rgba8();
+        let (size, texels) = lut_texels(&img)?;
+        let label = path.to_string_lossy();
+        Ok(Self::upload_lut(device, queue, size, &texels, Some(&label)))
+    }
+
+    /// `size`³ texels, red varying fastest then green then blue. Stored as is (not sRGB):
+    /// LUTs map display encoded colors to display encoded colors.
+    fn upload_lut(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: u32,
+        texels: &[u8],
+        label: Option<&str>,
+    ) -> Self {
+        let extent = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: size,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            texture.as_image_copy(),
+            texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * size),
+                rows_per_image: Some(size),
+            },
+            extent,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D3),
+            ..Default::default()
+        });
+        // Trilinear between the cells
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("LUT Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// Scene color target in `HDR_FORMAT`, tone mapped into the render target.
+    pub fn create_hdr_target(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        Self::create_color_target(device, config, HDR_FORMAT, &[], "HDR Target")
+    }
+
     pub fn create_render_target(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
         label: &str,
+    ) -> Self {
+        Self::create_color_target(device, config, config.format, &[], label)
+    }
+
+    pub(crate) fn create_color_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        view_formats: &[wgpu::TextureFormat],
+        label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
             width: config.width,
@@ -190,9 +464,12 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: config.format,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING, // IMPORTANT
-            view_formats: &[],
+            format,
+            // COPY_SRC: TAA keeps a copy of the previous frame
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats,
         };
 
         let texture = device.create_texture(&desc);
@@ -213,6 +490,126 @@ impl Texture {
             sampler,
         }
     }
+
+    /// Reads an 8-bit RGBA or BGRA 2D texture back to the CPU, waiting for the GPU. The
+    /// texture needs `TextureUsages::COPY_SRC`.
+    pub fn read_rgba(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<image::RgbaImage> {
+        use wgpu::TextureFormat::*;
+        let format = self.texture.format();
+        let bgra = match format {
+            Rgba8Unorm | Rgba8UnormSrgb => false,
+            Bgra8Unorm | Bgra8UnormSrgb => true,
+            _ => {
+                return Err(OrengineError::Generic(format!(
+                    "Cannot read back a {format:?} texture"
+                )));
+            }
+        };
+
+        let size = self.texture.size();
+        // Rows of the copy must be aligned, the padding is dropped below
+        let row_bytes = 4 * size.width;
+        let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_row_bytes * size.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            wgpu::Extent3d {
+                depth_or_array_layers: 1,
+                ..size
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let (sender, mapped) = std::sync::mpsc::channel();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        device.poll(wgpu::Maintain::Wait);
+        mapped
+            .recv()
+            .map_err(|e| OrengineError::Generic(e.to_string()))?
+            .map_err(|e| OrengineError::Generic(e.to_string()))?;
+
+        let mut pixels = Vec::with_capacity((row_bytes * size.height) as usize);
+        {
+            let data = buffer.slice(..).get_mapped_range();
+            for row in data.chunks(padded_row_bytes as usize) {
+                pixels.extend_from_slice(&row[..row_bytes as usize]);
+            }
+        }
+        buffer.unmap();
+        if bgra {
+            pixels.chunks_mut(4).for_each(|texel| texel.swap(0, 2));
+        }
+
+        Ok(image::RgbaImage::from_raw(size.width, size.height, pixels)
+            .expect("the buffer holds width * height texels"))
+    }
+
+    /// View of a render target for egui, which expects samples in linear light: decodes the
+    /// gamma encoded content of `GammaCorrection::create_render_target` targets.
+    pub fn viewport_view(&self) -> wgpu::TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.texture.format().add_srgb_suffix()),
+            ..Default::default()
+        })
+    }
+}
+
+/// Cell count per axis and RGBA texels (red fastest, blue slowest) of a color grading LUT
+/// image, in either layout:
+/// - a horizontal strip of `size` square slices along blue, `size`² x `size` (256 x 16),
+/// - a HALD CLUT of level `level`, a `level`³ square holding the `level`² cube row by row
+///   (512 x 512 for level 8, a 64³ LUT).
+pub fn lut_texels(img: &image::RgbaImage) -> Result<(u32, Vec<u8>)> {
+    let (width, height) = img.dimensions();
+    let texel = |x: u32, y: u32| img.get_pixel(x, y).0;
+
+    if height > 1 && width == height * height {
+        let size = height;
+        let texels = (0..size.pow(3))
+            .flat_map(|i| {
+                let (r, g, b) = (i % size, i / size % size, i / size / size);
+                texel(b * size + r, g)
+            })
+            .collect();
+        return Ok((size, texels));
+    }
+
+    let level = (2..=16u32).find(|level| level.pow(3) == width);
+    if let Some(level) = level.filter(|_| width == height) {
+        // The cube is already stored red fastest, in reading order
+        let texels = img.as_raw().clone();
+        return Ok((level * level, texels));
+    }
+
+    Err(OrengineError::Generic(format!(
+        "Unsupported LUT image size {width}x{height}, expected a size² x size strip or a \
+         level³ x level³ HALD CLUT"
+    )))
 }
 
 #[cfg(test)]
@@ -221,6 +618,27 @@ mod tests {
     use crate::error::OrengineError;
     use std::path::Path;
 
+    #[test]
+    fn test_best_depth_format() {
+        let adapter = pollster::block_on(
+            wgpu::Instance::new(wgpu::InstanceDescriptor::default())
+                .request_adapter(&wgpu::RequestAdapterOptions::default()),
+        )
+        .unwrap();
+
+        let format = best_depth_format(&adapter);
+        assert!(matches!(
+            format,
+            wgpu::TextureFormat::Depth32Float | wgpu::TextureFormat::Depth24Plus
+        ));
+        assert!(
+            adapter
+                .get_texture_format_features(format)
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        );
+    }
+
     #[test]
     fn test_texture_load_not_found() {
         let (device, queue) = pollster::block_on(async {
@@ -241,4 +659,113 @@ mod tests {
         let err = result.unwrap_err();
         assert!(matches!(err, OrengineError::Image(_)));
     }
+
+    #[test]
+    fn test_cubemap_faces_must_match() {
+        let (device, queue) = pollster::block_on(async {
+            let adapter = wgpu::Instance::new(wgpu::InstanceDescriptor::default())
+                .request_adapter(&wgpu::RequestAdapterOptions::default())
+                .await
+                .unwrap();
+            adapter
+                .request_device(&wgpu::DeviceDescriptor::default(), None)
+                .await
+                .unwrap()
+        });
+
+        let directory = std::env::temp_dir().join("orengine_cubemap_test");
+        std::fs::create_dir_all(&directory).unwrap();
+        let face = |name: &str, size: u32| {
+            let path = directory.join(name);
+            image::RgbaImage::new(size, size).save(&path).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let faces = ["px", "nx", "py", "ny", "pz"].map(|name| face(&format!("{name}.png"), 4));
+        let small = face("nz_small.png", 2);
+        let nz = face("nz.png", 4);
+
+        let [px, nx, py, ny, pz] = faces.each_ref().map(String::as_str);
+        let cubemap =
+            Texture::from_cubemap_files(&device, &queue, [px, nx, py, ny, pz, &nz]).unwrap();
+        assert_eq!(cubemap.texture.depth_or_array_layers(), 6);
+
+        let result = Texture::from_cubemap_files(&device, &queue, [px, nx, py, ny, pz, &small]);
+        assert!(matches!(result, Err(OrengineError::Generic(_))));
+    }
+
+    #[test]
+    fn test_read_rgba_drops_row_padding() {
+        let (device, queue) = pollster::block_on(async {
+            let adapter = wgpu::Instance::new(wgpu::InstanceDescriptor::default())
+                .request_adapter(&wgpu::RequestAdapterOptions::default())
+                .await
+                .unwrap();
+            adapter
+                .request_device(&wgpu::DeviceDescriptor::default(), None)
+                .await
+                .unwrap()
+        });
+
+        // 3 texels per row, far from the 256 bytes copy alignment
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Bgra8Unorm,
+            width: 3,
+            height: 2,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        let target = Texture::create_render_target(&device, &config, "Render Target");
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::RED),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        queue.submit(Some(encoder.finish()));
+
+        let image = target.read_rgba(&device, &queue).unwrap();
+        assert_eq!(image.dimensions(), (3, 2));
+        assert!(image.pixels().all(|p| p.0 == [255, 0, 0, 255]));
+
+        let hdr = Texture::create_hdr_target(&device, &config);
+        assert!(matches!(
+            hdr.read_rgba(&device, &queue),
+            Err(OrengineError::Generic(_))
+        ));
+    }
+
+    #[test]
+    fn test_lut_texels_layouts() {
+        // Each texel holds its own coordinates
+        let strip = image::RgbaImage::from_fn(16, 4, |x, y| {
+            image::Rgba([x % 4, y, x / 4, 0].map(|c| c as u8))
+        });
+        let (size, texels) = lut_texels(&strip).unwrap();
+        assert_eq!(size, 4);
+        for (i, texel) in texels.chunks(4).enumerate() {
+            let i = i as u8;
+            assert_eq!(texel[..3], [i % 4, i / 4 % 4, i / 16]);
+        }
+
+        // Level 2: a 4³ cube in 8 x 8 pixels, read row by row
+        let hald = image::RgbaImage::from_fn(8, 8, |x, y| image::Rgba([(y * 8 + x) as u8; 4]));
+        let (size, texels) = lut_texels(&hald).unwrap();
+        assert_eq!(size, 4);
+        assert_eq!(texels[4 * 37], 37);
+
+        let result = lut_texels(&image::RgbaImage::new(100, 30));
+        assert!(matches!(result, Err(OrengineError::Generic(_))));
+    }
 }
@@ -0,0 +1,199 @@
+use crate::post_process::{
+    create_fullscreen_pipeline, create_fullscreen_shader, run_fullscreen_pass,
+    texture_layout_entry, uniform_layout_entry,
+};
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+/// WGSL of the curves and of `TonemapUniform`, to prepend to the shaders tone mapping the
+/// HDR scene.
+pub(crate) const TONEMAP_CURVES_WGSL: &str = include_str!("../tonemap_curves.wgsl");
+
+/// Curve compressing the HDR scene into the displayable range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TonemapMode {
+    Reinhard,
+    #[default]
+    Aces,
+    Uncharted2,
+}
+
+impl TonemapMode {
+    pub const ALL: [TonemapMode; 3] = [
+        TonemapMode::Reinhard,
+        TonemapMode::Aces,
+        TonemapMode::Uncharted2,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TonemapMode::Reinhard => "Reinhard",
+            TonemapMode::Aces => "ACES filmique",
+            TonemapMode::Uncharted2 => "Uncharted 2",
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct TonemapUniform {
+    /// `TonemapMode` as the index the shader switches on
+    pub mode: u32,
+    /// Linear scale applied before the curve
+    pub exposure: f32,
+    pub _pad: [u32; 2],
+}
+
+impl TonemapUniform {
+    pub fn new(mode: TonemapMode, exposure: f32) -> Self {
+        Self {
+            mode: mode as u32,
+            exposure,
+            _pad: [0; 2],
+        }
+    }
+}
+
+impl Default for TonemapUniform {
+    fn default() -> Self {
+        Self::new(TonemapMode::default(), 1.0)
+    }
+}
+
+/// Tone mapping of the HDR scene into the render target. Runs as is while auto exposure is
+/// disabled or unsupported, otherwise `AutoExposure` reads the same settings.
+pub struct Tonemapper {
+    mode: TonemapMode,
+    exposure: f32,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+}
+
+impl Tonemapper {
+    pub fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        hdr_view: &wgpu::TextureView,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("hdr_blit_bind_group_layout"),
+            entries: &[
+                texture_layout_entry(0, wgpu::TextureSampleType::Float { filterable: false }),
+                uniform_layout_entry(1),
+            ],
+        });
+        let shader = create_fullscreen_shader(
+            device,
+            "HDR Blit Shader",
+            &format!(
+                "{TONEMAP_CURVES_WGSL}\n{}",
+                include_str!("../hdr_blit.wgsl")
+            ),
+        );
+        let pipeline = create_fullscreen_pipeline(
+            device,
+            "HDR Blit Pipeline",
+            &[&bind_group_layout],
+            &shader,
+            "fs_blit",
+            target_format,
+            None,
+        );
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tonemap Buffer"),
+            contents: bytemuck::cast_slice(&[TonemapUniform::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, hdr_view, &uniform_buffer);
+
+        Self {
+            mode: TonemapMode::default(),
+            exposure: 1.0,
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            uniform_buffer,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        hdr_view: &wgpu::TextureView,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("hdr_blit_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(hdr_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// The HDR target is recreated on resize, so is the bind group reading it.
+    pub fn resize(&mut self, device: &wgpu::Device, hdr_view: &wgpu::TextureView) {
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            hdr_view,
+            &self.uniform_buffer,
+        );
+    }
+
+    pub fn mode(&self) -> TonemapMode {
+        self.mode
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// Holds the `TonemapUniform`, also bound by the auto exposure pass.
+    pub fn uniform_buffer(&self) -> &wgpu::Buffer {
+        &self.uniform_buffer
+    }
+
+    pub fn set(&mut self, queue: &wgpu::Queue, mode: TonemapMode, exposure: f32) {
+        self.mode = mode;
+        self.exposure = exposure;
+        let uniform = TonemapUniform::new(mode, exposure);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        run_fullscreen_pass(
+            encoder,
+            "HDR Blit Pass",
+            target,
+            None,
+            &self.pipeline,
+            &[&self.bind_group],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tonemap_uniform_layout() {
+        // Matches `TonemapUniform` in tonemap_curves.wgsl
+        assert_eq!(std::mem::size_of::<TonemapUniform>(), 16);
+        let uniform = TonemapUniform::new(TonemapMode::Uncharted2, 2.5);
+        assert_eq!(uniform.mode, 2);
+        assert_eq!(uniform.exposure, 2.5);
+        assert_eq!(TonemapUniform::default().mode, 1);
+    }
+}
@@ -0,0 +1,72 @@
+// Draw order of the transparent instances. Blending is order dependent, so they are drawn
+// back to front after the opaque geometry, one whole instance at a time.
+
+use crate::{
+    error::{OrengineError, Result},
+    models::Aabb,
+};
+use glam::Vec3;
+
+/// Sorts `(instance, world bounds)` pairs from the farthest to the closest bounds center.
+pub fn sort_back_to_front(eye: Vec3, instances: &mut [(usize, Aabb)]) {
+    instances.sort_by(|(_, a), (_, b)| {
+        let distance_a = a.center().distance_squared(eye);
+        let distance_b = b.center().distance_squared(eye);
+        distance_b.total_cmp(&distance_a)
+    });
+}
+
+/// Fails with `OrengineError::TransparentDepthSort` when two neighbours of a sorted list
+/// have overlapping bounds. Each may then be partly in front of the other (a cycle), which
+/// no order of whole instances draws correctly.
+pub fn check_depth_order(sorted: &[(usize, Aabb)]) -> Result<()> {
+    match sorted
+        .windows(2)
+        .find(|pair| pair[0].1.intersects(&pair[1].1))
+    {
+        Some(pair) => Err(OrengineError::TransparentDepthSort {
+            first: pair[0].0,
+            second: pair[1].0,
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box_at(center: Vec3) -> Aabb {
+        Aabb::new(center - Vec3::splat(0.5), center + Vec3::splat(0.5))
+    }
+
+    #[test]
+    fn test_sort_back_to_front() {
+        let mut instances = [
+            (0, unit_box_at(Vec3::new(0.0, 0.0, -2.0))),
+            (1, unit_box_at(Vec3::new(0.0, 0.0, -10.0))),
+            (2, unit_box_at(Vec3::new(3.0, 0.0, -5.0))),
+        ];
+        sort_back_to_front(Vec3::ZERO, &mut instances);
+        let order: Vec<_> = instances.iter().map(|&(i, _)| i).collect();
+        assert_eq!(order, [1, 2, 0]);
+        assert!(check_depth_order(&instances).is_ok());
+    }
+
+    #[test]
+    fn test_check_depth_order_overlap() {
+        let mut instances = [
+            (4, unit_box_at(Vec3::new(0.0, 0.0, -3.0))),
+            (7, unit_box_at(Vec3::new(0.2, 0.0, -3.5))),
+        ];
+        sort_back_to_front(Vec3::ZERO, &mut instances);
+        let err = check_depth_order(&instances).unwrap_err();
+        assert!(matches!(
+            err,
+            OrengineError::TransparentDepthSort {
+                first: 7,
+                second: 4
+            }
+        ));
+    }
+}
@@ -11,6 +11,27 @@ pub struct Vertex {
     pub tex_coords: [f32; 2],
     /// Normal, the orientation of the vertex
     pub normal: [f32; 3],
+    /// Direction of increasing U for normal mapping, `w` is the handedness (±1) of the
+    /// bitangent `cross(normal, tangent) * w`. See `compute_tangents`.
+    pub tangent: [f32; 4],
+    /// Second UV set, for lightmaps: must not overlap, unlike `tex_coords`
+    pub tex_coords2: [f32; 2],
+}
+
+// Compared bit for bit, which is what deduplication needs: 0.0 and -0.0 differ,
+// identical NaNs are equal. Pod guarantees there are no padding bytes to worry about.
+impl PartialEq for Vertex {
+    fn eq(&self, other: &Self) -> bool {
+        bytemuck::bytes_of(self) == bytemuck::bytes_of(other)
+    }
+}
+
+impl Eq for Vertex {}
+
+impl std::hash::Hash for Vertex {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        bytemuck::bytes_of(self).hash(state);
+    }
 }
 
 impl Vertex {
@@ -47,7 +68,60 @@ impl Vertex {
                     shader_location: 3,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                // 4. Tangent
+                wgpu::VertexAttribute {
+                    offset: (std::mem::size_of::<[f32; 3]>()
+                        + std::mem::size_of::<[f32; 3]>()
+                        + std::mem::size_of::<[f32; 2]>()
+                        + std::mem::size_of::<[f32; 3]>())
+                        as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                // 11. Lightmap Coordinates, after the instance attributes (5 to 10)
+                wgpu::VertexAttribute {
+                    offset: std::mem::offset_of!(Vertex, tex_coords2) as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
             ],
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::{BuildHasher, RandomState};
+
+    fn vertex(x: f32) -> Vertex {
+        Vertex {
+            position: [x, 1.0, 2.0],
+            color: [1.0, 1.0, 1.0],
+            tex_coords: [0.5, 0.25],
+            normal: [0.0, 1.0, 0.0],
+            tangent: [1.0, 0.0, 0.0, 1.0],
+            tex_coords2: [0.5, 0.25],
+        }
+    }
+
+    #[test]
+    fn test_vertex_hash_eq() {
+        let hasher = RandomState::new();
+        let (a, b) = (vertex(0.0), vertex(0.0));
+        assert_eq!(a, b);
+        assert_eq!(hasher.hash_one(a), hasher.hash_one(b));
+
+        assert_ne!(a, vertex(3.0));
+        assert_ne!(a, vertex(-0.0));
+    }
+
+    #[test]
+    fn test_vertex_layout() {
+        // The lightmap UVs come last, the attributes before them keep their offsets
+        assert_eq!(std::mem::size_of::<Vertex>(), 68);
+        assert_eq!(std::mem::offset_of!(Vertex, tex_coords2), 60);
+        let layout = Vertex::desc();
+        assert_eq!(layout.attributes.last().unwrap().offset, 60);
+    }
+}
@@ -0,0 +1,136 @@
+// Vignette: a full-screen pass darkening the edges of the tone mapped image (vignette.wgsl).
+
+use crate::post_process::{
+    create_fullscreen_pipeline, create_fullscreen_shader, run_fullscreen_pass, uniform_layout_entry,
+};
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VignetteUniform {
+    /// 0 keeps the image untouched, 1 reaches `color` in the corners
+    pub strength: f32,
+    /// Distance from the screen center in NDC (1 at the middle of the edges, √2 in the
+    /// corners) where the darkening is complete
+    pub radius: f32,
+    /// Width of the transition, inward from `radius`
+    pub softness: f32,
+    /// Linear RGBA the edges fade to, the alpha scaling the effect
+    pub color: [f32; 4],
+}
+
+impl Default for VignetteUniform {
+    fn default() -> Self {
+        Self {
+            strength: 0.5,
+            radius: 1.4,
+            softness: 0.8,
+            color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct VignettePassUniform {
+    color: [f32; 4],
+    strength: f32,
+    radius: f32,
+    softness: f32,
+    _padding: f32,
+}
+
+impl From<VignetteUniform> for VignettePassUniform {
+    fn from(settings: VignetteUniform) -> Self {
+        Self {
+            color: settings.color,
+            strength: settings.strength,
+            radius: settings.radius,
+            softness: settings.softness.max(f32::EPSILON),
+            _padding: 0.0,
+        }
+    }
+}
+
+/// Post-process pass blended over the render target after tone mapping.
+pub struct VignetteRenderer {
+    settings: VignetteUniform,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+}
+
+impl VignetteRenderer {
+    pub fn new(device: &wgpu::Device, target_format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("vignette_bind_group_layout"),
+            entries: &[uniform_layout_entry(0)],
+        });
+        let shader =
+            create_fullscreen_shader(device, "Vignette Shader", include_str!("../vignette.wgsl"));
+        let pipeline = create_fullscreen_pipeline(
+            device,
+            "Vignette Pipeline",
+            &[&bind_group_layout],
+            &shader,
+            "fs_vignette",
+            target_format,
+            Some(wgpu::BlendState::ALPHA_BLENDING),
+        );
+
+        let settings = VignetteUniform::default();
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vignette Buffer"),
+            contents: bytemuck::cast_slice(&[VignettePassUniform::from(settings)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vignette_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            settings,
+            pipeline,
+            bind_group,
+            uniform_buffer,
+        }
+    }
+
+    pub fn settings(&self) -> VignetteUniform {
+        self.settings
+    }
+
+    pub fn set(&mut self, queue: &wgpu::Queue, settings: VignetteUniform) {
+        self.settings = settings;
+        let uniform = VignettePassUniform::from(settings);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        run_fullscreen_pass(
+            encoder,
+            "Vignette Pass",
+            target,
+            None,
+            &self.pipeline,
+            &[&self.bind_group],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vignette_uniform_layout() {
+        // Matches `VignetteUniform` in vignette.wgsl
+        assert_eq!(std::mem::size_of::<VignettePassUniform>(), 32);
+        assert_eq!(std::mem::offset_of!(VignettePassUniform, strength), 16);
+    }
+}
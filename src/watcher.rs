@@ -0,0 +1,132 @@
+// File system change notifications for hot reloading. notify runs the OS watcher
+// (inotify, FSEvents, ...) on its own thread and events are handed over through a channel,
+// to be drained from the render loop.
+
+use crate::error::Result;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchEvent {
+    /// Canonical path of the changed file
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+}
+
+/// Watches individual files. Their parent directory is what is actually watched, because
+/// many editors save by writing a new file and renaming it over the old one, which a watch
+/// on the file itself would lose track of.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    receiver: Receiver<WatchEvent>,
+    /// Shared with the notify thread, which drops events for other files
+    files: Arc<Mutex<HashSet<PathBuf>>>,
+    directories: HashSet<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new() -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let files = Arc::new(Mutex::new(HashSet::<PathBuf>::new()));
+
+        let watched = files.clone();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("File watcher error: {}", e);
+                    return;
+                }
+            };
+            let kind = match event.kind {
+                EventKind::Create(_) => ChangeKind::Created,
+                EventKind::Modify(_) => ChangeKind::Modified,
+                EventKind::Remove(_) => ChangeKind::Removed,
+                _ => return,
+            };
+            let Ok(watched) = watched.lock() else {
+                return;
+            };
+            for path in event.paths {
+                if watched.contains(&path) {
+                    let _ = sender.send(WatchEvent { path, kind });
+                }
+            }
+        })?;
+
+        Ok(Self {
+            watcher,
+            receiver,
+            files,
+            directories: HashSet::new(),
+        })
+    }
+
+    /// Starts reporting changes to `path`, which must exist.
+    pub fn watch(&mut self, path: &Path) -> Result<()> {
+        let path = path.canonicalize()?;
+        if let Some(directory) = path.parent()
+            && !self.directories.contains(directory)
+        {
+            self.watcher.watch(directory, RecursiveMode::NonRecursive)?;
+            self.directories.insert(directory.to_path_buf());
+        }
+        if let Ok(mut files) = self.files.lock() {
+            files.insert(path);
+        }
+        Ok(())
+    }
+
+    /// Changes received since the last call. A single save often produces several events.
+    pub fn poll(&self) -> Vec<WatchEvent> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_file_watcher_reports_watched_file_only() {
+        let dir = std::env::temp_dir().join(format!("orengine-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let watched = dir.join("watched.wgsl");
+        let other = dir.join("other.wgsl");
+        std::fs::write(&watched, "a").unwrap();
+        std::fs::write(&other, "a").unwrap();
+
+        let mut watcher = FileWatcher::new().unwrap();
+        watcher.watch(&watched).unwrap();
+        std::fs::write(&other, "b").unwrap();
+        std::fs::write(&watched, "b").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut events = Vec::new();
+        while events.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+            events.extend(watcher.poll());
+        }
+
+        let watched = watched.canonicalize().unwrap();
+        assert!(!events.is_empty());
+        assert!(events.iter().all(|e| e.path == watched));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
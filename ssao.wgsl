@@ -0,0 +1,89 @@
+// Screen-space ambient occlusion: hemisphere samples around the view-space position of each
+// pixel, tested against the depth buffer. There is no normal buffer, normals are rebuilt
+// from the depth of the neighbouring pixels.
+
+const MAX_KERNEL_SIZE: u32 = 64u;
+const TAU: f32 = 6.28318531;
+
+struct SsaoUniform {
+    projection: mat4x4<f32>,
+    inverse_projection: mat4x4<f32>,
+    // View-space radius of the sampled hemisphere
+    radius: f32,
+    // Depth difference under which a sample does not count as occluded (self-shadowing)
+    bias: f32,
+    kernel_size: u32,
+    _padding: u32,
+    // Tangent space offsets in the +Z hemisphere, within the unit sphere
+    kernel: array<vec4<f32>, MAX_KERNEL_SIZE>,
+};
+
+// Bound as an unfilterable float texture: loading from `texture_depth_2d` is not portable to GL
+@group(0) @binding(0)
+var t_depth: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> ssao: SsaoUniform;
+
+fn view_position(pixel: vec2<i32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(t_depth));
+    let p = clamp(pixel, vec2<i32>(0), size - 1);
+    let depth = textureLoad(t_depth, p, 0).r;
+    let uv = (vec2<f32>(p) + 0.5) / vec2<f32>(size);
+    // Texture rows go down, NDC y goes up
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let view = ssao.inverse_projection * ndc;
+    return view.xyz / view.w;
+}
+
+// PCG hash, also used as the random number generator step
+fn pcg(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+@fragment
+fn fs_ssao(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_depth));
+    let pixel = vec2<i32>(in.uv * size);
+    // Nothing to occlude on the background
+    if (textureLoad(t_depth, pixel, 0).r >= 1.0) {
+        return vec4<f32>(1.0);
+    }
+
+    // Differences with the neighbour closest in depth, so that silhouettes do not bend
+    // the normal towards the background
+    let center = view_position(pixel);
+    let left = center - view_position(pixel - vec2<i32>(1, 0));
+    let right = view_position(pixel + vec2<i32>(1, 0)) - center;
+    let up = center - view_position(pixel - vec2<i32>(0, 1));
+    let down = view_position(pixel + vec2<i32>(0, 1)) - center;
+    let dx = select(left, right, abs(right.z) < abs(left.z));
+    let dy = select(up, down, abs(down.z) < abs(up.z));
+    // Pixel rows go down the screen, so this faces the camera (+Z in view space)
+    let normal = normalize(cross(dy, dx));
+
+    // Random rotation around the normal per pixel, the blur hides the resulting noise
+    let angle = f32(pcg(u32(pixel.x) + pcg(u32(pixel.y)))) / 4294967295.0 * TAU;
+    let random = vec3<f32>(cos(angle), sin(angle), 0.0);
+    let tangent = normalize(random - normal * dot(random, normal));
+    let tbn = mat3x3<f32>(tangent, cross(normal, tangent), normal);
+
+    let count = min(ssao.kernel_size, MAX_KERNEL_SIZE);
+    var occlusion = 0.0;
+    for (var i = 0u; i < count; i += 1u) {
+        let sample_position = center + tbn * ssao.kernel[i].xyz * ssao.radius;
+        let clip = ssao.projection * vec4<f32>(sample_position, 1.0);
+        let ndc = clip.xy / clip.w;
+        let sample_pixel = vec2<i32>(vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5) * size);
+        let scene_z = view_position(sample_pixel).z;
+
+        // An occluder much farther than the radius is another object in front, not a crease
+        let range = smoothstep(0.0, 1.0, ssao.radius / abs(center.z - scene_z));
+        // The camera looks down -Z: closer surfaces have a larger z
+        occlusion += select(0.0, 1.0, scene_z >= sample_position.z + ssao.bias) * range;
+    }
+
+    let visibility = 1.0 - occlusion / f32(max(count, 1u));
+    return vec4<f32>(visibility, 0.0, 0.0, 1.0);
+}
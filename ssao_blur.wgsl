@@ -0,0 +1,30 @@
+// SSAO denoising and composition: a 4x4 box blur of the raw occlusion, then the blurred
+// result multiplied into the lit image (through the blend state).
+
+@group(0) @binding(0)
+var t_occlusion: texture_2d<f32>;
+
+fn occlusion_at(pixel: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(t_occlusion));
+    return textureLoad(t_occlusion, clamp(pixel, vec2<i32>(0), size - 1), 0).r;
+}
+
+// Averages a 4x4 block, smoothing out the noise of the per-pixel random rotations
+@fragment
+fn fs_blur(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.uv * vec2<f32>(textureDimensions(t_occlusion)));
+    var sum = 0.0;
+    for (var y = -2; y < 2; y++) {
+        for (var x = -2; x < 2; x++) {
+            sum += occlusion_at(pixel + vec2<i32>(x, y));
+        }
+    }
+    return vec4<f32>(sum / 16.0, 0.0, 0.0, 1.0);
+}
+
+// Multiplied into the target by the blend state, alpha left untouched
+@fragment
+fn fs_composite(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.uv * vec2<f32>(textureDimensions(t_occlusion)));
+    return vec4<f32>(vec3<f32>(occlusion_at(pixel)), 1.0);
+}
@@ -0,0 +1,40 @@
+// SSAO sample kernel: offsets in the +Z hemisphere of tangent space, denser near the origin
+// so that close occluders weigh more. Generated once at startup.
+
+@group(0) @binding(0)
+var<storage, read_write> kernel: array<vec4<f32>>;
+
+const PI: f32 = 3.14159265;
+
+// PCG hash, also used as the random number generator step
+fn pcg(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Uniform in [0, 1]
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = pcg(*state);
+    return f32(*state) / 4294967295.0;
+}
+
+@compute @workgroup_size(64)
+fn cs_ssao_kernel(@builtin(global_invocation_id) id: vec3<u32>) {
+    let count = arrayLength(&kernel);
+    let i = id.x;
+    if i >= count {
+        return;
+    }
+
+    var state = pcg(i);
+    // A uniform cos(theta) spreads the directions uniformly over the hemisphere
+    let cos_theta = random(&state);
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    let phi = 2.0 * PI * random(&state);
+    let direction = vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    let t = f32(i) / f32(count);
+    let scale = mix(0.1, 1.0, t * t);
+    kernel[i] = vec4<f32>(direction * random(&state) * scale, 0.0);
+}
@@ -0,0 +1,62 @@
+// Temporal anti-aliasing: the projection is jittered by a different sub-pixel offset every
+// frame and the result accumulated over time. The history is reprojected with the depth buffer
+// and clamped to the current 3x3 neighborhood, which rejects most stale samples.
+
+struct TaaUniform {
+    // Clip space of the current frame -> world
+    inv_view_proj: mat4x4<f32>,
+    // World -> clip space of the previous frame
+    prev_view_proj: mat4x4<f32>,
+    // Weight of the current frame in the accumulation
+    blend: f32,
+    // 0 when the history holds nothing usable yet
+    history_valid: f32,
+    _padding: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var t_color: texture_2d<f32>;
+@group(0) @binding(1)
+var t_history: texture_2d<f32>;
+@group(0) @binding(2)
+var t_depth: texture_2d<f32>;
+@group(0) @binding(3)
+var s_linear: sampler;
+@group(0) @binding(4)
+var<uniform> taa: TaaUniform;
+
+@fragment
+fn fs_taa(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t_color));
+    let p = vec2<i32>(in.clip_position.xy);
+    let current = textureLoad(t_color, p, 0);
+
+    if taa.history_valid == 0.0 {
+        return current;
+    }
+
+    var lo = current.rgb;
+    var hi = current.rgb;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let c = textureLoad(t_color, clamp(p + vec2<i32>(x, y), vec2<i32>(0), size - 1), 0).rgb;
+            lo = min(lo, c);
+            hi = max(hi, c);
+        }
+    }
+
+    let depth = textureLoad(t_depth, p, 0).r;
+    let ndc = vec4<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0, depth, 1.0);
+    let world = taa.inv_view_proj * ndc;
+    let prev_clip = taa.prev_view_proj * vec4<f32>(world.xyz / world.w, 1.0);
+    let prev_ndc = prev_clip.xy / prev_clip.w;
+    let prev_uv = vec2<f32>(prev_ndc.x * 0.5 + 0.5, 0.5 - prev_ndc.y * 0.5);
+
+    // Disoccluded from off-screen: nothing to accumulate with
+    if prev_clip.w <= 0.0 || any(prev_uv < vec2<f32>(0.0)) || any(prev_uv > vec2<f32>(1.0)) {
+        return current;
+    }
+
+    let history = clamp(textureSampleLevel(t_history, s_linear, prev_uv, 0.0).rgb, lo, hi);
+    return vec4<f32>(mix(history, current.rgb, taa.blend), current.a);
+}
@@ -0,0 +1,31 @@
+// Tone mapping: the image is scaled so that the adapted average luminance (from
+// exposure.wgsl) lands on the key value, then compressed with the curve picked in the
+// editor (tonemap_curves.wgsl).
+
+struct ExposureUniform {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    adaptation: f32,
+    pixel_count: f32,
+    key: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+};
+
+@group(0) @binding(0)
+var t_color: texture_2d<f32>;
+@group(0) @binding(1)
+var<storage, read> adapted_luminance: f32;
+@group(0) @binding(2)
+var<uniform> exposure: ExposureUniform;
+// Its exposure multiplies the adapted one, as a compensation
+@group(0) @binding(3)
+var<uniform> settings: TonemapUniform;
+
+@fragment
+fn fs_tonemap(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(t_color, vec2<i32>(in.clip_position.xy), 0);
+    let scale = exposure.key / max(adapted_luminance, 0.0001);
+    return vec4<f32>(tonemap(color.rgb * scale, settings), color.a);
+}
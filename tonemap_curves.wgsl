@@ -0,0 +1,58 @@
+// Tone mapping curves, prepended to hdr_blit.wgsl and tonemap.wgsl which bind a
+// `TonemapUniform` of their own.
+
+struct TonemapUniform {
+    // 0 = Reinhard, 1 = ACES, 2 = Uncharted 2
+    mode: u32,
+    // Linear scale applied before the curve
+    exposure: f32,
+    _padding0: u32,
+    _padding1: u32,
+};
+
+fn reinhard(x: vec3<f32>) -> vec3<f32> {
+    return x / (1.0 + x);
+}
+
+// Krzysztof Narkowicz's fit of the ACES reference rendering transform
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// John Hable's filmic curve from Uncharted 2
+fn uncharted2_partial(x: vec3<f32>) -> vec3<f32> {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+}
+
+fn uncharted2(x: vec3<f32>) -> vec3<f32> {
+    // The curve is dark at 1.0, Hable's exposure bias and white point bring it back
+    let exposure_bias = 2.0;
+    let white = vec3<f32>(11.2);
+    return uncharted2_partial(x * exposure_bias) / uncharted2_partial(white);
+}
+
+fn tonemap(color: vec3<f32>, settings: TonemapUniform) -> vec3<f32> {
+    let x = color * settings.exposure;
+    switch settings.mode {
+        case 0u: {
+            return reinhard(x);
+        }
+        case 2u: {
+            return uncharted2(x);
+        }
+        default: {
+            return aces(x);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+// Vignette: darkens the edges of the screen, blended over the tone mapped image so that
+// the scene color never has to be read back.
+
+struct VignetteUniform {
+    // Color the edges fade to, its alpha scaling the effect
+    color: vec4<f32>,
+    // 0 keeps the image untouched, 1 reaches `color` in the corners
+    strength: f32,
+    // Distance from the center in NDC where the darkening is complete
+    radius: f32,
+    // Width of the transition, inward from `radius`
+    softness: f32,
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> vignette: VignetteUniform;
+
+@fragment
+fn fs_vignette(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let dist = length(in.uv * 2.0 - 1.0);
+    let factor = 1.0 - smoothstep(vignette.radius - vignette.softness, vignette.radius, dist)
+        * vignette.strength;
+    // Blended as color * (1 - factor) + scene * factor
+    return vec4<f32>(vignette.color.rgb, (1.0 - factor) * vignette.color.a);
+}